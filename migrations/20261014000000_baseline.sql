@@ -0,0 +1,188 @@
+-- Schema the backend was deployed with before it ran migrations. The statements are
+-- idempotent, so databases created by hand from it adopt it without being recreated.
+
+-- Create the user table
+CREATE TABLE IF NOT EXISTS app_user (
+    id serial primary key not null,
+    name varchar(64) not null,
+    email varchar(128) unique not null,
+    hashed_password varchar(128) not null,
+    role varchar(32) not null,
+    totp_secret varchar(128),
+    totp_enabled boolean default false not null,
+    created_at timestamp with time zone default current_timestamp not null,
+    updated_at timestamp with time zone default current_timestamp not null
+);
+
+-- Create the entity table
+CREATE TABLE IF NOT EXISTS entity (
+    id serial primary key not null,
+    name varchar(128) not null,
+    created_at timestamp with time zone default current_timestamp not null,
+    updated_at timestamp with time zone default current_timestamp not null
+);
+
+-- Create the account table, with a foreign key to entity
+CREATE TABLE IF NOT EXISTS account (
+    id serial primary key not null,
+    address varchar(64) unique not null,
+    network varchar(32) default 'aptos-mainnet' not null,
+    entity_id integer references entity(id) on delete cascade,
+    created_at timestamp with time zone default current_timestamp not null,
+    updated_at timestamp with time zone default current_timestamp not null
+
+);
+
+-- Create the watched_account table, flagging the accounts whose balance is snapshotted daily
+CREATE TABLE IF NOT EXISTS watched_account (
+    account_id integer primary key not null references account(id) on delete cascade,
+    created_at timestamp with time zone default current_timestamp not null
+);
+
+-- Create the account_balance_history table, keeping the snapshots of watched accounts
+CREATE TABLE IF NOT EXISTS account_balance_history (
+    id serial primary key not null,
+    account_id integer not null references account(id) on delete cascade,
+    total_usd float not null,
+    recorded_at timestamp with time zone default current_timestamp not null
+);
+CREATE INDEX IF NOT EXISTS account_balance_history_account ON account_balance_history (account_id, recorded_at);
+
+-- Create the project table, with a foreign key to account
+CREATE TABLE IF NOT EXISTS project (
+    id serial primary key not null,
+    name varchar(128) unique,
+    token varchar(64) not null,
+    category varchar(128) not null,
+    contract_address varchar(64) references account(address) on delete cascade,
+    num_chains integer,
+    core_developers integer,
+    code_commits integer,
+    total_value_locked float,
+    trading_volume float,
+    token_max_supply bigint,
+    defi_llama_slug varchar(128),
+    cmc_id bigint,
+    github_repo varchar(128),
+    coingecko_id varchar(128),
+    price_usd_cg float,
+    market_cap_cg float,
+    volume_24h_cg float,
+    -- USD price of the token, snapshotted daily
+    price_usd float,
+    token_terminal_slug varchar(128),
+    -- USD value that came into and left the treasury accounts over the last 7 days
+    treasury_inflow_7d float,
+    treasury_outflow_7d float,
+    treasury_net_flow_7d float,
+    -- Entry function whose swaps are summed into the all time volume
+    volume_entry_function varchar(256),
+    -- USD volume of every swap through the volume entry function, refreshed weekly
+    all_time_volume_usd float,
+    -- Fee taken on the input of each swap of the pools of the contract, in basis points
+    swap_fee_bps integer,
+    -- USD fees of every swap up to the transaction last_processed_version, summed weekly
+    all_time_fees_usd float,
+    last_processed_version bigint,
+    -- Total value locked integrated over the last 7 days, in USD-hours, refreshed daily
+    twick_7d float,
+    -- Treasury, vesting and team accounts whose balances don't count as circulating supply
+    excluded_supply_addresses varchar(66)[] not null default '{}',
+    cloned_from integer references project(id) on delete set null,
+    created_at timestamp with time zone default current_timestamp not null,
+    updated_at timestamp with time zone default current_timestamp not null
+);
+
+-- Create the project_metric_formula table, with a foreign key to project
+CREATE TABLE IF NOT EXISTS project_metric_formula (
+    id serial primary key not null,
+    project_id integer not null references project(id) on delete cascade,
+    name varchar(64) not null,
+    formula text not null,
+    created_at timestamp with time zone default current_timestamp not null,
+    unique (project_id, name)
+);
+
+-- Create the treasury_account table, flagging the accounts holding the treasury of a project
+CREATE TABLE IF NOT EXISTS treasury_account (
+    project_id integer not null references project(id) on delete cascade,
+    account_id integer not null references account(id) on delete cascade,
+    created_at timestamp with time zone default current_timestamp not null,
+    primary key (project_id, account_id)
+);
+
+-- Create the project_attribute_history table, keeping every change of a project metric
+CREATE TABLE IF NOT EXISTS project_attribute_history (
+    id serial primary key not null,
+    project_id integer not null references project(id) on delete cascade,
+    key varchar(64) not null,
+    old_value float,
+    new_value float,
+    changed_by varchar(64) not null,
+    changed_at timestamp with time zone default current_timestamp not null
+);
+CREATE INDEX IF NOT EXISTS project_attribute_history_key ON project_attribute_history (project_id, key, changed_at);
+
+-- Create the alert_rule table, posting swaps of a project's token above min_usd to a webhook
+CREATE TABLE IF NOT EXISTS alert_rule (
+    id serial primary key not null,
+    project_id integer not null references project(id) on delete cascade,
+    min_usd float not null,
+    webhook_url varchar(512) not null,
+    created_by varchar(64) not null,
+    created_at timestamp with time zone default current_timestamp not null,
+    updated_at timestamp with time zone default current_timestamp not null
+);
+
+-- Create the alert_delivery table, keeping every swap an alert rule fired for
+CREATE TABLE IF NOT EXISTS alert_delivery (
+    id serial primary key not null,
+    rule_id integer not null references alert_rule(id) on delete cascade,
+    transaction_version bigint not null,
+    value_usd float not null,
+    attempts integer not null,
+    delivered boolean not null,
+    -- Status of the last attempt, missing when the webhook could not be reached
+    status_code integer,
+    error text,
+    created_at timestamp with time zone default current_timestamp not null,
+    unique (rule_id, transaction_version)
+);
+
+-- Create the anomaly_event table, keeping the metric values far out of a project's usual range
+CREATE TABLE IF NOT EXISTS anomaly_event (
+    id serial primary key not null,
+    project_id integer not null references project(id) on delete cascade,
+    metric varchar(64) not null,
+    value float not null,
+    -- Mean and standard deviation of the daily values the value was compared with
+    mean float not null,
+    std_dev float not null,
+    detected_at timestamp with time zone default current_timestamp not null
+);
+CREATE INDEX IF NOT EXISTS anomaly_event_project ON anomaly_event (project_id, detected_at);
+
+-- Create the coin_info table, caching coin metadata fetched from the indexer
+CREATE TABLE IF NOT EXISTS coin_info (
+    coin_type varchar(256) primary key not null,
+    decimals smallint not null,
+    symbol varchar(64) not null,
+    name varchar(128) not null,
+    last_refreshed timestamp with time zone default current_timestamp not null
+);
+
+-- Create the known_address table, labeling well-known contracts and wallets
+CREATE TABLE IF NOT EXISTS known_address (
+    address varchar(66) primary key not null,
+    label varchar(128) not null,
+    category varchar(64) not null,
+    created_at timestamp with time zone default current_timestamp not null,
+    updated_at timestamp with time zone default current_timestamp not null
+);
+
+INSERT INTO known_address (address, label, category) VALUES
+    ('0x0000000000000000000000000000000000000000000000000000000000000001', 'Aptos Framework', 'Framework'),
+    ('0xc7efb4076dbe143cbcd98cfaaa929ecfc8f299203dfff63b95ccb6bfe19850fa', 'PancakeSwap Router', 'DEX'),
+    ('0x190d44266241744264b964a37b8f09863167a12d3e70cda39376cfb4e3561e12', 'Liquidswap', 'DEX'),
+    ('0x48271d39d0b05bd6efca2278f22277d6fcc375504f9839fd73f74ace240861af', 'Thala', 'DEX')
+ON CONFLICT (address) DO NOTHING;
@@ -0,0 +1,9 @@
+-- Create the known_pair table, listing the token pairs seen in the swap events of a DEX
+CREATE TABLE IF NOT EXISTS known_pair (
+    project_id integer not null references project(id) on delete cascade,
+    token_a varchar(256) not null,
+    token_b varchar(256) not null,
+    first_seen_at timestamp with time zone default current_timestamp not null,
+    last_seen_at timestamp with time zone default current_timestamp not null,
+    primary key (project_id, token_a, token_b)
+);
@@ -0,0 +1,6 @@
+-- Create the coin_filter table, listing the coins priced regardless of their liquidity or never priced
+CREATE TABLE IF NOT EXISTS coin_filter (
+    coin_type varchar(256) primary key not null,
+    status varchar(16) not null check (status in ('WHITELIST', 'BLACKLIST')),
+    created_at timestamp with time zone default current_timestamp not null
+);
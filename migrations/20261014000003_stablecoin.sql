@@ -0,0 +1,5 @@
+-- Create the stablecoin table, listing the coins priced at one dollar besides USDT and USDC
+CREATE TABLE IF NOT EXISTS stablecoin (
+    coin_type varchar(256) primary key not null,
+    created_at timestamp with time zone default current_timestamp not null
+);
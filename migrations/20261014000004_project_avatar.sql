@@ -0,0 +1,2 @@
+-- Add the avatar_url column to project, the logo uploaded to the avatar store or hosted elsewhere
+ALTER TABLE project ADD COLUMN IF NOT EXISTS avatar_url varchar(2048);
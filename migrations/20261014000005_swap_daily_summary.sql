@@ -0,0 +1,11 @@
+-- Create the swap_daily_summary table, the swaps made through the volume entry function of a
+-- project summed per UTC day
+CREATE TABLE IF NOT EXISTS swap_daily_summary (
+    project_id integer not null references project(id) on delete cascade,
+    date date not null,
+    tx_count integer not null,
+    volume_usd double precision not null,
+    unique_traders integer not null,
+    avg_trade_usd double precision not null,
+    primary key (project_id, date)
+);
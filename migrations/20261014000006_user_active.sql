@@ -0,0 +1,3 @@
+-- Add the active column to app_user, false once an admin deactivated the user, who then can't
+-- log in but keeps their data
+ALTER TABLE app_user ADD COLUMN IF NOT EXISTS active boolean default true not null;
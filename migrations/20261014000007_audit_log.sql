@@ -0,0 +1,14 @@
+-- Create the audit_log table, who created, updated or deleted which user, account, entity or
+-- project, with the resource as it was before and after
+CREATE TABLE IF NOT EXISTS audit_log (
+    id serial primary key not null,
+    user_id integer references app_user(id) on delete set null,
+    action varchar(16) not null,
+    resource_type varchar(16) not null,
+    resource_id integer not null,
+    before jsonb,
+    after jsonb,
+    created_at timestamp with time zone default current_timestamp not null
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_resource ON audit_log (resource_type, resource_id, created_at);
@@ -0,0 +1,21 @@
+-- Create the project_attribute_schema table, the type each attribute of the projects of a
+-- category must have, checked whenever a project is written
+CREATE TABLE IF NOT EXISTS project_attribute_schema (
+    category varchar(64) not null,
+    key varchar(64) not null,
+    expected_type varchar(16) not null check (expected_type in ('float', 'integer', 'string')),
+    required boolean default false not null,
+    description text,
+    primary key (category, key)
+);
+
+INSERT INTO project_attribute_schema (category, key, expected_type, required, description) VALUES
+    ('DEX', 'total_value_locked', 'float', false, 'USD value locked in the pools'),
+    ('DEX', 'trading_volume', 'float', false, 'USD volume traded over the last 7 days'),
+    ('DEX', 'num_chains', 'integer', false, 'Chains the DEX is deployed on'),
+    ('DEX', 'core_developers', 'integer', false, 'Contributors to the GitHub repository'),
+    ('DEX', 'code_commits', 'integer', false, 'Commits to the GitHub repository'),
+    ('DEX', 'token_max_supply', 'integer', false, 'Most tokens that can ever exist'),
+    ('DEX', 'swap_fee_bps', 'integer', false, 'Fee taken on the input of each swap, in basis points'),
+    ('DEX', 'contract_address', 'string', false, 'Account the pools are published at')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,3 @@
+-- Add the active column to project, false once the health check found no pair reserves left
+-- at its contract address
+ALTER TABLE project ADD COLUMN IF NOT EXISTS active boolean default true not null;
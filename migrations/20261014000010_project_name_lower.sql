@@ -0,0 +1,23 @@
+-- Make project names unique ignoring case, so looking a project up by name is never ambiguous.
+-- Projects whose name already differs from an older project's only by case get their id
+-- appended to it, each of them is reported.
+DO $$
+DECLARE
+    duplicate record;
+BEGIN
+    FOR duplicate IN
+        SELECT id, name
+        FROM (
+            SELECT id, name, row_number() OVER (PARTITION BY lower(name) ORDER BY id) AS position
+            FROM project
+            WHERE name IS NOT NULL
+        ) named
+        WHERE position > 1
+    LOOP
+        UPDATE project SET name = duplicate.name || ' (' || duplicate.id || ')' WHERE id = duplicate.id;
+        RAISE WARNING 'Renamed project % from "%" to "% (%)", another project has the same name',
+            duplicate.id, duplicate.name, duplicate.name, duplicate.id;
+    END LOOP;
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS project_name_lower ON project (lower(name));
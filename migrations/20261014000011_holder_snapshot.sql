@@ -0,0 +1,13 @@
+-- Create the holder_snapshot table, the holders of a token as they were on a UTC day. The
+-- full set is only kept as a hash, along with a deterministic sample of the addresses that
+-- the next snapshots are compared against.
+CREATE TABLE IF NOT EXISTS holder_snapshot (
+    token varchar(256) not null,
+    date date not null,
+    holder_count bigint not null,
+    holders_hash varchar(64) not null,
+    sample varchar(66)[] not null,
+    sample_rate integer not null check (sample_rate > 0),
+    created_at timestamp with time zone default current_timestamp not null,
+    primary key (token, date)
+);
@@ -0,0 +1,8 @@
+-- Create the user_project_watchlist table, the projects each user starred for their dashboard.
+-- Stars go along with the user or the project.
+CREATE TABLE IF NOT EXISTS user_project_watchlist (
+    user_id integer not null references app_user(id) on delete cascade,
+    project_id integer not null references project(id) on delete cascade,
+    created_at timestamp with time zone default current_timestamp not null,
+    primary key (user_id, project_id)
+);
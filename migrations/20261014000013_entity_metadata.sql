@@ -0,0 +1,9 @@
+-- Describe entities beyond their name, with what kind of entity they are, their website, their
+-- Twitter handle and a description
+ALTER TABLE entity ADD COLUMN IF NOT EXISTS entity_type varchar(16)
+    check (entity_type in ('CEX', 'PROTOCOL', 'FUND', 'INDIVIDUAL'));
+ALTER TABLE entity ADD COLUMN IF NOT EXISTS website varchar(2048);
+ALTER TABLE entity ADD COLUMN IF NOT EXISTS twitter varchar(15);
+ALTER TABLE entity ADD COLUMN IF NOT EXISTS description text;
+
+CREATE INDEX IF NOT EXISTS entity_type ON entity (entity_type);
@@ -0,0 +1,5 @@
+-- Index the entity names by trigram so searching them for a part of a name, and the accounts
+-- by the name of their entity, doesn't scan the whole table
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS entity_name_trgm ON entity USING gin (name gin_trgm_ops);
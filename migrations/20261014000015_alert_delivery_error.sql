@@ -0,0 +1,8 @@
+-- Replace the errors recorded for webhook calls, which quoted the HTTP client and could tell
+-- about the network of the server, with the general message now recorded
+UPDATE alert_delivery
+SET error = CASE
+    WHEN status_code IS NOT NULL THEN 'The webhook answered ' || status_code
+    ELSE 'The webhook call failed'
+END
+WHERE error IS NOT NULL AND error NOT LIKE 'The webhook %';
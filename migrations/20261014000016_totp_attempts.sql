@@ -0,0 +1,8 @@
+-- Add the columns guarding the TOTP challenge of a user: the wrong codes in a row, which lock
+-- the challenge for a while once too many, the step of the last code that logged in, so a code
+-- only logs in once, and when, so a partial token issued before only logs in once too
+ALTER TABLE app_user
+    ADD COLUMN IF NOT EXISTS totp_failed_attempts integer default 0 not null,
+    ADD COLUMN IF NOT EXISTS totp_locked_until timestamp with time zone,
+    ADD COLUMN IF NOT EXISTS totp_last_step bigint,
+    ADD COLUMN IF NOT EXISTS totp_last_login_at timestamp with time zone;
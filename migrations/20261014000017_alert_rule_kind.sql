@@ -0,0 +1,4 @@
+-- Add the kind of an alert rule: 'swap' rules fire on the large swaps of the project's token,
+-- 'anomaly' rules on the spikes of its trading volume
+ALTER TABLE alert_rule
+    ADD COLUMN IF NOT EXISTS kind varchar(16) default 'swap' not null;
@@ -0,0 +1,4 @@
+-- Add the version of the last transaction summed into the all time volume of a project, the
+-- volume task carries on from there instead of paging through the whole history again
+ALTER TABLE project
+    ADD COLUMN IF NOT EXISTS volume_processed_version bigint;
@@ -0,0 +1,7 @@
+-- Create the task_cursor table, the version of the last transaction a background task went
+-- through, so it carries on from there after a restart instead of skipping what came between
+CREATE TABLE IF NOT EXISTS task_cursor (
+    task varchar(100) primary key,
+    version bigint not null,
+    updated_at timestamp with time zone default current_timestamp not null
+);
@@ -5,51 +5,25 @@ CREATE DATABASE testdb;
 -- Connect to the newly created database
 \c testdb;
 
--- Drop tables if they exist, then create them
-DROP TABLE IF EXISTS project;
-DROP TABLE IF EXISTS account;
-DROP TABLE IF EXISTS entity;
-DROP TABLE IF EXISTS app_user;
-
--- Create the user table
-CREATE TABLE app_user (
-    id serial primary key not null,
-    name varchar(64) not null,
-    email varchar(128) unique not null,
-    hashed_password varchar(128) not null,
-    role varchar(32) not null,
-    created_at timestamp with time zone default current_timestamp not null,
-    updated_at timestamp with time zone default current_timestamp not null
-);
-
--- Create the entity table
-CREATE TABLE entity (
-    id serial primary key not null,
-    name varchar(128) not null,
-    created_at timestamp with time zone default current_timestamp not null,
-    updated_at timestamp with time zone default current_timestamp not null
-);
-
--- Create the account table, with a foreign key to entity
-CREATE TABLE account (
-    id serial primary key not null,
-    address varchar(64) unique not null,
-    entity_id integer references entity(id) on delete cascade,
-    created_at timestamp with time zone default current_timestamp not null,
-    updated_at timestamp with time zone default current_timestamp not null
-
-);
--- Create the project table, with a foreign key to account
-CREATE TABLE project (
-    id serial primary key not null,
-    token varchar(64) not null,
-    category varchar(128) not null,
-    contract_address varchar(64) references account(address) on delete cascade,
-    num_chains integer,
-    core_developers integer,
-    code_commits integer,
-    total_value_locked float,
-    token_max_supply bigint,
-    created_at timestamp with time zone default current_timestamp not null,
-    updated_at timestamp with time zone default current_timestamp not null
-);
+-- Apply the migrations, so the query macros can be checked before the backend first runs.
+-- The backend then records them as applied, each migration has to be idempotent for that.
+\ir ../migrations/20261014000000_baseline.sql
+\ir ../migrations/20261014000001_known_pair.sql
+\ir ../migrations/20261014000002_coin_filter.sql
+\ir ../migrations/20261014000003_stablecoin.sql
+\ir ../migrations/20261014000004_project_avatar.sql
+\ir ../migrations/20261014000005_swap_daily_summary.sql
+\ir ../migrations/20261014000006_user_active.sql
+\ir ../migrations/20261014000007_audit_log.sql
+\ir ../migrations/20261014000008_project_attribute_schema.sql
+\ir ../migrations/20261014000009_project_active.sql
+\ir ../migrations/20261014000010_project_name_lower.sql
+\ir ../migrations/20261014000011_holder_snapshot.sql
+\ir ../migrations/20261014000012_user_project_watchlist.sql
+\ir ../migrations/20261014000013_entity_metadata.sql
+\ir ../migrations/20261014000014_entity_name_search.sql
+\ir ../migrations/20261014000015_alert_delivery_error.sql
+\ir ../migrations/20261014000016_totp_attempts.sql
+\ir ../migrations/20261014000017_alert_rule_kind.sql
+\ir ../migrations/20261014000018_volume_processed_version.sql
+\ir ../migrations/20261014000019_task_cursor.sql
@@ -1,7 +1,52 @@
 use crate::config::Config;
 use crate::database::PostgreDatabase;
+use crate::external::External;
+use crate::storage::AvatarStore;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
 pub struct AppState {
     pub db: PostgreDatabase,
+    pub external: External,
     pub config: Config,
+    pub avatars: AvatarStore,
+    /// Cancelled when the app starts shutting down, ending background tasks and streams
+    pub shutdown: CancellationToken,
+    /// Projects whose total value locked is being backfilled, so the same days aren't written twice
+    pub tvl_backfills: Mutex<HashSet<i32>>,
+    /// One-shot background tasks, e.g. the first all time volume of a new project, which the
+    /// scheduler waits for on shutdown along with its own
+    pub one_shot_tasks: TaskTracker,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State over `db` with the default config, no upstream keys and avatars in a temporary
+    /// directory, for tests calling handlers directly
+    pub fn for_test(db: PostgreDatabase) -> std::sync::Arc<Self> {
+        let vars = [
+            ("POSTGRES_USER", "postgres"),
+            ("POSTGRES_PASSWORD", "postgres"),
+            ("DATABASE_URL", "postgres://localhost/warehouse"),
+            ("JWT_SECRET", "0123456789abcdef0123456789abcdef"),
+            ("JWT_EXPIRED_IN", "60m"),
+            ("JWT_MAXAGE", "60"),
+            ("LOG_FORMAT", ""),
+            ("RUN_SCHEDULER", "false"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+        let config = Config::from_vars(&vars).expect("The test config must be valid");
+        std::sync::Arc::new(AppState {
+            db,
+            external: External::new(),
+            avatars: AvatarStore::new(std::env::temp_dir().join("avatars"), "http://localhost"),
+            config,
+            shutdown: CancellationToken::new(),
+            tvl_backfills: Default::default(),
+            one_shot_tasks: Default::default(),
+        })
+    }
 }
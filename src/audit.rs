@@ -0,0 +1,84 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    database::PostgreTransaction,
+    models::{AuditEntry, User},
+};
+
+/// Writes the changes of one user to the audit log, in the transaction of the change so the
+/// change and its entry are committed together, and a failed one leaves no entry. Resources
+/// are recorded as the API returns them, pass the response DTOs rather than the rows, which
+/// may hold secrets.
+pub struct AuditLogger<'a, 'db> {
+    tx: &'a mut PostgreTransaction<'db>,
+    user_id: i32,
+}
+
+impl<'a, 'db> AuditLogger<'a, 'db> {
+    pub fn new(tx: &'a mut PostgreTransaction<'db>, user: &User) -> Self {
+        AuditLogger {
+            tx,
+            user_id: user.id,
+        }
+    }
+
+    pub async fn created(
+        &mut self,
+        resource_type: &str,
+        resource_id: i32,
+        after: &impl Serialize,
+    ) -> Result<(), sqlx::Error> {
+        self.record(
+            AuditEntry::CREATE,
+            resource_type,
+            resource_id,
+            None,
+            Some(after),
+        )
+        .await
+    }
+
+    pub async fn updated<T: Serialize>(
+        &mut self,
+        resource_type: &str,
+        resource_id: i32,
+        before: &T,
+        after: &T,
+    ) -> Result<(), sqlx::Error> {
+        self.record(
+            AuditEntry::UPDATE,
+            resource_type,
+            resource_id,
+            Some(before),
+            Some(after),
+        )
+        .await
+    }
+
+    /// A failed write fails the request, rolling the change back along with the transaction
+    async fn record<T: Serialize>(
+        &mut self,
+        action: &str,
+        resource_type: &str,
+        resource_id: i32,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Result<(), sqlx::Error> {
+        let entry = AuditEntry {
+            user_id: Some(self.user_id),
+            action: action.to_string(),
+            resource_type: resource_type.to_string(),
+            resource_id,
+            before: before.map(to_json),
+            after: after.map(to_json),
+            ..Default::default()
+        };
+        self.tx.create_audit_entry(&entry).await?;
+        Ok(())
+    }
+}
+
+fn to_json(resource: &impl Serialize) -> Value {
+    serde_json::to_value(resource).unwrap_or(Value::Null)
+}
@@ -0,0 +1,264 @@
+use std::error::Error;
+
+use tracing::info;
+
+use crate::{
+    database,
+    routes::{api_docs, make_state},
+    scheduler::{self, MAX_BACKFILL_DAYS, RUNNABLE_TASKS},
+    Config,
+};
+
+pub const USAGE: &str = "Usage: axum-jwt [COMMAND]
+
+Commands:
+  serve                                   Serve the API and run the background tasks (default)
+  migrate                                 Apply the pending migrations
+  run-task --type TASK [--project-id ID]  Run one pass of a background task
+  backfill-metrics --project-id ID --days DAYS
+                                          Write the past daily total value locked of a project
+  export-openapi --output FILE            Write the OpenAPI document of the API as JSON
+  help                                    Print this message";
+
+/// What the binary was asked to do, parsed from its arguments
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Serve,
+    Migrate,
+    RunTask {
+        task: String,
+        project_id: Option<i32>,
+    },
+    BackfillMetrics {
+        project_id: i32,
+        days: i64,
+    },
+    ExportOpenapi {
+        output: String,
+    },
+    Help,
+}
+
+impl Command {
+    /// Parses the arguments following the binary name, [`Command::Serve`] without any. Options
+    /// are given as `--name value` or `--name=value`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+        let mut args = args.into_iter();
+        let Some(command) = args.next() else {
+            return Ok(Command::Serve);
+        };
+        let mut options = Options::parse(args)?;
+        let command = match command.as_str() {
+            "serve" => Command::Serve,
+            "migrate" => Command::Migrate,
+            "run-task" => Command::RunTask {
+                task: options.required("type")?.to_string(),
+                project_id: options.parsed("project-id")?,
+            },
+            "backfill-metrics" => Command::BackfillMetrics {
+                project_id: options
+                    .parsed("project-id")?
+                    .ok_or("--project-id is required")?,
+                days: options.parsed("days")?.ok_or("--days is required")?,
+            },
+            "export-openapi" => Command::ExportOpenapi {
+                output: options.required("output")?.to_string(),
+            },
+            "help" | "--help" | "-h" => Command::Help,
+            _ => return Err(format!("Unknown command {command}")),
+        };
+        options.check_used()?;
+        Ok(command)
+    }
+}
+
+/// `--name value` options of a command, each taken at most once
+struct Options {
+    values: Vec<(String, String)>,
+    used: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        let mut values: Vec<(String, String)> = Vec::new();
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.strip_prefix("--") else {
+                return Err(format!("Unexpected argument {arg}"));
+            };
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => {
+                    let value = args
+                        .next_if(|value| !value.starts_with("--"))
+                        .ok_or_else(|| format!("--{option} needs a value"))?;
+                    (option.to_string(), value)
+                }
+            };
+            if values.iter().any(|(known, _)| *known == name) {
+                return Err(format!("--{name} is given twice"));
+            }
+            values.push((name, value));
+        }
+        Ok(Self {
+            values,
+            used: Vec::new(),
+        })
+    }
+
+    fn get(&mut self, name: &str) -> Option<&str> {
+        self.used.push(name.to_string());
+        self.values
+            .iter()
+            .find(|(known, _)| known == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn required(&mut self, name: &str) -> Result<&str, String> {
+        self.get(name)
+            .ok_or_else(|| format!("--{name} is required"))
+    }
+
+    fn parsed<T: std::str::FromStr>(&mut self, name: &str) -> Result<Option<T>, String> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("--{name} must be a number, got {value}"))
+            })
+            .transpose()
+    }
+
+    /// Fails on the first option the command doesn't take
+    fn check_used(&self) -> Result<(), String> {
+        match self
+            .values
+            .iter()
+            .find(|(name, _)| !self.used.contains(name))
+        {
+            Some((name, _)) => Err(format!("Unknown option --{name}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Applies the pending migrations, so deployments can run them before the rollout
+pub async fn migrate() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    let config = Config::init();
+    let pool = database::connect_sqlx(&config.db_url).await;
+    database::run_migrations(&pool).await?;
+    println!("Applied the pending migrations");
+    Ok(())
+}
+
+/// Writes the OpenAPI document served at `/api-docs/openapi.json` to `output`, so clients can
+/// be generated without running the server
+pub fn export_openapi(output: &str) -> Result<(), Box<dyn Error>> {
+    std::fs::write(output, api_docs().to_pretty_json()?)?;
+    println!("Wrote the OpenAPI document to {output}");
+    Ok(())
+}
+
+/// Runs one pass of the background task `task`, on the project `project_id` alone when set
+pub async fn run_task(task: &str, project_id: Option<i32>) -> Result<(), Box<dyn Error>> {
+    if !RUNNABLE_TASKS.contains(&task) {
+        return Err(format!(
+            "Unknown task {task}, expected one of {}",
+            RUNNABLE_TASKS.join(", ")
+        )
+        .into());
+    }
+    let state = make_state().await?;
+    scheduler::run_task(&state, task, project_id)
+        .await
+        .map_err(|failure| format!("Task {task} failed: {}", failure.error))?;
+    info!(task, "Ran the task");
+    Ok(())
+}
+
+/// Writes the daily total value locked of the last `days` days of a project into its
+/// attribute history
+pub async fn backfill_metrics(project_id: i32, days: i64) -> Result<(), Box<dyn Error>> {
+    if !(1..=MAX_BACKFILL_DAYS).contains(&days) {
+        return Err(format!("--days must be between 1 and {MAX_BACKFILL_DAYS}").into());
+    }
+    let state = make_state().await?;
+    let project = state
+        .db
+        .get_project_by_id(project_id)
+        .await?
+        .ok_or_else(|| format!("Project {project_id} not found"))?;
+    let written = scheduler::backfill_total_value_locked(&state, &project, days)
+        .await
+        .map_err(|failure| format!("Backfill failed: {}", failure.error))?;
+    info!(
+        project = project_id,
+        days, written, "Backfilled the metrics"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_usage_descriptions_line_up() {
+    let columns: Vec<usize> = USAGE
+        .lines()
+        .skip_while(|line| *line != "Commands:")
+        .skip(1)
+        .filter_map(|line| {
+            let indent = line.len() - line.trim_start().len();
+            // Description wrapped under a long command
+            if indent > 2 {
+                return Some(indent);
+            }
+            let gap = indent + line[indent..].find("  ")?;
+            Some(gap + line[gap..].len() - line[gap..].trim_start().len())
+        })
+        .collect();
+    assert!(columns.windows(2).all(|pair| pair[0] == pair[1]), "{USAGE}");
+}
+
+#[test]
+fn test_parse_command() {
+    let parse = |args: &[&str]| Command::parse(args.iter().map(|arg| arg.to_string()));
+
+    assert_eq!(parse(&[]), Ok(Command::Serve));
+    assert_eq!(parse(&["migrate"]), Ok(Command::Migrate));
+    assert_eq!(
+        parse(&["run-task", "--type", "twick", "--project-id=4"]),
+        Ok(Command::RunTask {
+            task: "twick".to_string(),
+            project_id: Some(4)
+        })
+    );
+    assert_eq!(
+        parse(&["run-task", "--type", "pair_discovery"]),
+        Ok(Command::RunTask {
+            task: "pair_discovery".to_string(),
+            project_id: None
+        })
+    );
+    assert_eq!(
+        parse(&["backfill-metrics", "--days", "30", "--project-id", "4"]),
+        Ok(Command::BackfillMetrics {
+            project_id: 4,
+            days: 30
+        })
+    );
+
+    assert_eq!(
+        parse(&["export-openapi", "--output", "openapi.json"]),
+        Ok(Command::ExportOpenapi {
+            output: "openapi.json".to_string()
+        })
+    );
+
+    assert!(parse(&["deploy"]).is_err());
+    assert!(parse(&["run-task"]).is_err());
+    assert!(parse(&["run-task", "--type"]).is_err());
+    assert!(parse(&["run-task", "--type", "twick", "--type", "twick"]).is_err());
+    assert!(parse(&["migrate", "--days", "3"]).is_err());
+    assert!(parse(&["backfill-metrics", "--project-id", "four", "--days", "3"]).is_err());
+    assert!(parse(&["backfill-metrics", "--project-id", "4"]).is_err());
+    assert!(parse(&["export-openapi"]).is_err());
+}
@@ -1,36 +1,442 @@
-use std::env::var;
+use std::{collections::HashMap, env, process, str::FromStr, time::Duration};
+
+use reqwest::Url;
+
+use crate::{
+    external::{FULLNODE_API, INDEXER_API},
+    storage::{s3::S3Config, StorageBackend, AVATAR_ROUTE},
+};
+
+/// Shortest accepted `JWT_SECRET`, HS256 keys shouldn't be shorter than its 256 bit hash
+const MIN_JWT_SECRET_LENGTH: usize = 32;
+
+/// Output format of the log lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Json,
+    Pretty,
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
-    //pub cors_url: String,
+    /// Port the HTTP server listens on
+    pub port: u16,
+    pub cors_origins: Vec<String>,
     pub db_user: String,
     pub db_password: String,
     pub db_url: String,
+    /// Whether the pending migrations are applied at startup
+    pub run_migrations: bool,
     pub jwt_secret: String,
     pub jwt_expires_in: String,
     pub jwt_maxage: i32,
+    pub health_check_fullnode: bool,
+    pub log_format: LogFormat,
+    pub slack_webhook_url: Option<String>,
+    pub telegram_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub max_request_body_bytes: usize,
+    /// REST API of the Aptos fullnode
+    pub fullnode_url: String,
+    /// GraphQL endpoint of the Aptos indexer
+    pub indexer_url: String,
+    pub cmc_api_key: Option<String>,
+    /// Whether only the whitelisted coins are priced when valuing reserves
+    pub strict_token_whitelist: bool,
+    /// Coin types priced at one dollar besides USDT and USDC
+    pub stablecoin_addresses: Vec<String>,
+    /// Paid TokenTerminal API key, the TokenTerminal pages are scraped without it
+    pub tokenterminal_api_key: Option<String>,
+    /// Quota of the CoinGecko API, the calls are spaced out to stay under it
+    pub coingecko_calls_per_minute: u32,
+    pub swap_cache_ttl: Duration,
+    pub upstream_timeout: Duration,
+    pub external_max_concurrency: usize,
+    pub external_connect_timeout: Duration,
+    pub external_max_idle_connections: usize,
+    /// HTTP date after which `/api/v1` may be removed, v1 is deprecated once set
+    pub api_v1_sunset: Option<String>,
+    /// Most accounts whose balance may be snapshotted, each costs indexer calls every day
+    pub watchlist_max_accounts: i64,
+    /// Whether this instance runs the background tasks, only one replica should
+    pub run_scheduler: bool,
+    /// Whether alert webhooks may be on loopback or private addresses, for local development
+    pub allow_private_webhooks: bool,
+    /// Longest in-flight requests and background tasks are waited for on shutdown
+    pub shutdown_timeout: Duration,
+    /// Key the TOTP secrets of users are encrypted under, two-factor auth is off without it
+    pub totp_encryption_key: Option<[u8; 32]>,
+    /// Where the uploaded project avatars are written to
+    pub storage_backend: StorageBackend,
+    /// URL the avatars are served at, by the API itself by default
+    pub avatar_base_url: String,
 }
 
 impl Config {
+    /// Reads the configuration from the environment, exiting with every invalid variable
+    /// listed when there are any
     pub fn init() -> Config {
-        let db_user = var("POSTGRES_USER").expect("POSTGRES_USER must be set");
-        let db_password = var("POSTGRES_PASSWORD").expect("POSTGRES_PASSWORD must be set");
-        let db_url = var("DATABASE_URL").expect("DATABASE_URL must be set");
-        // let cors_url = var("ALLOW_ORIGIN").unwrap_or(String::from("http://localhost:3000"));
-        let jwt_secret = var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let jwt_expires_in = var("JWT_EXPIRED_IN").expect("JWT_EXPIRED_IN must be set");
-        let jwt_maxage = var("JWT_MAXAGE")
-            .map(|age| age.parse::<i32>())
-            .expect("JWT_MAXAGE must be set")
-            .expect("JWT_MAXAGE must be a number");
-        Config {
-            //cors_url,
+        let vars: HashMap<String, String> = env::vars().collect();
+        Config::from_vars(&vars).unwrap_or_else(|problems| {
+            eprintln!("Invalid configuration:");
+            for problem in problems {
+                eprintln!("  {problem}");
+            }
+            process::exit(1);
+        })
+    }
+
+    /// Parses and validates the configuration in `vars`, blank variables count as unset.
+    /// Gives one line per invalid variable.
+    pub fn from_vars(vars: &HashMap<String, String>) -> Result<Config, Vec<String>> {
+        let mut vars = Vars {
+            vars,
+            problems: Vec::new(),
+        };
+
+        let port = vars.parsed("PORT", 8080, "a port between 1 and 65535");
+        if port == 0 {
+            vars.invalid("PORT", "must be a port between 1 and 65535");
+        }
+        let cors_origins: Vec<String> = vars
+            .get("CORS_ORIGINS")
+            .unwrap_or("http://localhost:3000")
+            .split(',')
+            .map(|origin| origin.trim().to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        for origin in &cors_origins {
+            if origin != "*" && !is_url(origin, &["http", "https"]) {
+                vars.invalid(
+                    "CORS_ORIGINS",
+                    &format!("{origin} is not an http(s) origin"),
+                );
+            }
+        }
+        let db_user = vars.required("POSTGRES_USER");
+        let db_password = vars.required("POSTGRES_PASSWORD");
+        let db_url = vars.required("DATABASE_URL");
+        if !db_url.is_empty() && !is_url(&db_url, &["postgres", "postgresql"]) {
+            vars.invalid("DATABASE_URL", "must be a postgres:// URL");
+        }
+        let run_migrations = vars.parsed("RUN_MIGRATIONS", true, "true or false");
+        let jwt_secret = vars.required("JWT_SECRET");
+        if !jwt_secret.is_empty() && jwt_secret.len() < MIN_JWT_SECRET_LENGTH {
+            vars.invalid(
+                "JWT_SECRET",
+                &format!("must be at least {MIN_JWT_SECRET_LENGTH} characters"),
+            );
+        }
+        let jwt_expires_in = vars.required("JWT_EXPIRED_IN");
+        let jwt_maxage = match vars.get("JWT_MAXAGE") {
+            Some(_) => vars.parsed("JWT_MAXAGE", 0, "a number of minutes"),
+            None => {
+                vars.invalid("JWT_MAXAGE", "must be set");
+                0
+            }
+        };
+        let health_check_fullnode = vars.parsed("HEALTH_CHECK_FULLNODE", false, "true or false");
+        let log_format = match vars.get("LOG_FORMAT") {
+            Some("json") => LogFormat::Json,
+            Some("pretty") | None => LogFormat::Pretty,
+            Some(_) => {
+                vars.invalid("LOG_FORMAT", "must be json or pretty");
+                LogFormat::Pretty
+            }
+        };
+        let slack_webhook_url = vars.url("SLACK_WEBHOOK_URL", &["https"]);
+        let telegram_token = vars.optional("TELEGRAM_TOKEN");
+        let telegram_chat_id = vars.optional("TELEGRAM_CHAT_ID");
+        let otlp_endpoint = vars.url("OTLP_ENDPOINT", &["http", "https"]);
+        let max_request_body_bytes = vars.positive("MAX_REQUEST_BODY_BYTES", 1024 * 1024);
+        let fullnode_url = vars
+            .url("FULLNODE_URL", &["http", "https"])
+            .unwrap_or_else(|| FULLNODE_API.to_string());
+        let indexer_url = vars
+            .url("INDEXER_URL", &["http", "https"])
+            .unwrap_or_else(|| INDEXER_API.to_string());
+        let cmc_api_key = vars.optional("CMC_API_KEY");
+        let tokenterminal_api_key = vars.optional("TOKENTERMINAL_API_KEY");
+        let coingecko_calls_per_minute = vars.positive(
+            "COINGECKO_CALLS_PER_MINUTE",
+            crate::external::coingecko::FREE_TIER_CALLS_PER_MINUTE,
+        );
+        let swap_cache_ttl = vars.seconds("SWAP_CACHE_TTL_SECS", 30);
+        let upstream_timeout = vars.seconds("UPSTREAM_TIMEOUT_SECS", 20);
+        let external_max_concurrency = vars.positive("EXTERNAL_MAX_CONCURRENCY", 50);
+        let external_connect_timeout = vars.seconds("EXTERNAL_CONNECT_TIMEOUT_SECS", 5);
+        let external_max_idle_connections =
+            vars.parsed("EXTERNAL_MAX_IDLE_CONNECTIONS", 32, "a number");
+        let api_v1_sunset = vars.optional("API_V1_SUNSET");
+        if let Some(date) = &api_v1_sunset {
+            if chrono::DateTime::parse_from_rfc2822(date).is_err() {
+                vars.invalid(
+                    "API_V1_SUNSET",
+                    "must be an HTTP date, e.g. Sat, 31 Oct 2026 23:59:59 GMT",
+                );
+            }
+        }
+        let watchlist_max_accounts = vars.parsed("WATCHLIST_MAX_ACCOUNTS", 100, "a number");
+        if watchlist_max_accounts < 0 {
+            vars.invalid("WATCHLIST_MAX_ACCOUNTS", "can't be negative");
+        }
+        let run_scheduler = vars.parsed("RUN_SCHEDULER", true, "true or false");
+        let allow_private_webhooks = vars.parsed("ALLOW_PRIVATE_WEBHOOKS", false, "true or false");
+        let strict_token_whitelist = vars.parsed("STRICT_TOKEN_WHITELIST", false, "true or false");
+        let stablecoin_addresses: Vec<String> = vars
+            .get("STABLECOIN_ADDRESSES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|coin_type| coin_type.trim().to_string())
+            .filter(|coin_type| !coin_type.is_empty())
+            .collect();
+        for coin_type in &stablecoin_addresses {
+            if coin_type.split("::").count() != 3 {
+                vars.invalid(
+                    "STABLECOIN_ADDRESSES",
+                    &format!("{coin_type} is not a coin type"),
+                );
+            }
+        }
+        let shutdown_timeout = vars.seconds("SHUTDOWN_TIMEOUT_SECS", 30);
+        let storage_backend = match vars.get("STORAGE_BACKEND") {
+            Some("local") | None => StorageBackend::Local {
+                dir: vars
+                    .optional("AVATAR_DIR")
+                    .unwrap_or_else(|| "uploads/avatars".to_string()),
+            },
+            Some("s3") => {
+                let endpoint = vars.required("S3_ENDPOINT");
+                if !endpoint.is_empty() && !is_url(&endpoint, &["http", "https"]) {
+                    vars.invalid("S3_ENDPOINT", "must be a http or https URL");
+                }
+                StorageBackend::S3(S3Config {
+                    endpoint,
+                    bucket: vars.required("S3_BUCKET"),
+                    region: vars
+                        .optional("S3_REGION")
+                        .unwrap_or_else(|| "us-east-1".to_string()),
+                    access_key_id: vars.required("S3_ACCESS_KEY_ID"),
+                    secret_access_key: vars.required("S3_SECRET_ACCESS_KEY"),
+                })
+            }
+            Some(_) => {
+                vars.invalid("STORAGE_BACKEND", "must be local or s3");
+                StorageBackend::Local { dir: String::new() }
+            }
+        };
+        let avatar_base_url = vars
+            .url("AVATAR_BASE_URL", &["http", "https"])
+            .unwrap_or_else(|| AVATAR_ROUTE.to_string());
+        let totp_encryption_key = vars.optional("TOTP_ENCRYPTION_KEY").and_then(|key| {
+            let key = crate::models::totp::parse_key(&key);
+            if key.is_none() {
+                vars.invalid("TOTP_ENCRYPTION_KEY", "must be 64 hex digits");
+            }
+            key
+        });
+
+        if !vars.problems.is_empty() {
+            return Err(vars.problems);
+        }
+        Ok(Config {
+            port,
+            cors_origins,
             db_user,
             db_password,
             db_url,
+            run_migrations,
             jwt_secret,
             jwt_expires_in,
             jwt_maxage,
+            health_check_fullnode,
+            log_format,
+            slack_webhook_url,
+            telegram_token,
+            telegram_chat_id,
+            otlp_endpoint,
+            max_request_body_bytes,
+            fullnode_url,
+            indexer_url,
+            cmc_api_key,
+            strict_token_whitelist,
+            stablecoin_addresses,
+            tokenterminal_api_key,
+            coingecko_calls_per_minute,
+            swap_cache_ttl,
+            upstream_timeout,
+            external_max_concurrency,
+            external_connect_timeout,
+            external_max_idle_connections,
+            api_v1_sunset,
+            watchlist_max_accounts,
+            run_scheduler,
+            allow_private_webhooks,
+            shutdown_timeout,
+            totp_encryption_key,
+            storage_backend,
+            avatar_base_url,
+        })
+    }
+}
+
+/// Environment variables being read, with the problems found in them so far
+struct Vars<'a> {
+    vars: &'a HashMap<String, String>,
+    problems: Vec<String>,
+}
+
+impl Vars<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .get(name)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn invalid(&mut self, name: &str, problem: &str) {
+        self.problems.push(format!("{name}: {problem}"));
+    }
+
+    fn optional(&self, name: &str) -> Option<String> {
+        self.get(name).map(str::to_string)
+    }
+
+    /// Blank when unset, which is reported
+    fn required(&mut self, name: &str) -> String {
+        let value = self.optional(name);
+        if value.is_none() {
+            self.invalid(name, "must be set");
+        }
+        value.unwrap_or_default()
+    }
+
+    /// `default` when unset, and when invalid after reporting it must be `expected`
+    fn parsed<T: FromStr>(&mut self, name: &str, default: T, expected: &str) -> T {
+        match self.get(name).map(str::parse) {
+            None => default,
+            Some(Ok(value)) => value,
+            Some(Err(_)) => {
+                let value = self.get(name).unwrap_or_default().to_string();
+                self.invalid(name, &format!("must be {expected}, got {value:?}"));
+                default
+            }
+        }
+    }
+
+    fn positive<T: FromStr + Default + PartialEq + Copy>(&mut self, name: &str, default: T) -> T {
+        let value = self.parsed(name, default, "a positive number");
+        if value == T::default() {
+            self.invalid(name, "must be at least 1");
+            return default;
+        }
+        value
+    }
+
+    fn seconds(&mut self, name: &str, default: u64) -> Duration {
+        Duration::from_secs(self.positive(name, default))
+    }
+
+    /// `None` when unset or when it isn't a URL of one of the `schemes`, which is reported
+    fn url(&mut self, name: &str, schemes: &[&str]) -> Option<String> {
+        let url = self.optional(name)?;
+        if !is_url(&url, schemes) {
+            self.invalid(name, &format!("must be a {} URL", schemes.join(" or ")));
+            return None;
+        }
+        Some(url)
+    }
+}
+
+fn is_url(value: &str, schemes: &[&str]) -> bool {
+    Url::parse(value).is_ok_and(|url| schemes.contains(&url.scheme()) && url.has_host())
+}
+
+#[test]
+fn test_config_from_vars() {
+    let mut vars: HashMap<String, String> = [
+        ("POSTGRES_USER", "postgres"),
+        ("POSTGRES_PASSWORD", "postgres"),
+        ("DATABASE_URL", "postgres://localhost/warehouse"),
+        ("JWT_SECRET", "0123456789abcdef0123456789abcdef"),
+        ("JWT_EXPIRED_IN", "60m"),
+        ("JWT_MAXAGE", "60"),
+        ("LOG_FORMAT", ""),
+        ("RUN_SCHEDULER", "false"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+
+    let config = Config::from_vars(&vars).unwrap();
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.cors_origins, ["http://localhost:3000"]);
+    assert_eq!(config.log_format, LogFormat::Pretty);
+    assert_eq!(config.fullnode_url, FULLNODE_API);
+    assert_eq!(config.upstream_timeout, Duration::from_secs(20));
+    assert!(config.run_migrations);
+    assert!(!config.run_scheduler);
+    assert!(!config.strict_token_whitelist);
+    assert!(config.stablecoin_addresses.is_empty());
+    assert!(config.totp_encryption_key.is_none());
+    assert_eq!(
+        config.storage_backend,
+        StorageBackend::Local {
+            dir: "uploads/avatars".to_string()
         }
+    );
+
+    let s3 = [
+        ("STORAGE_BACKEND", "s3"),
+        ("S3_ENDPOINT", "https://s3.eu-west-3.amazonaws.com"),
+        ("S3_BUCKET", "avatars"),
+        ("S3_ACCESS_KEY_ID", "key"),
+        ("S3_SECRET_ACCESS_KEY", "secret"),
+    ];
+    let mut s3_vars = vars.clone();
+    s3_vars.extend(s3.map(|(name, value)| (name.to_string(), value.to_string())));
+    let config = Config::from_vars(&s3_vars).unwrap();
+    assert_eq!(
+        config.storage_backend,
+        StorageBackend::S3(S3Config {
+            endpoint: "https://s3.eu-west-3.amazonaws.com".to_string(),
+            bucket: "avatars".to_string(),
+            region: "us-east-1".to_string(),
+            access_key_id: "key".to_string(),
+            secret_access_key: "secret".to_string(),
+        })
+    );
+    s3_vars.remove("S3_BUCKET");
+    s3_vars.remove("S3_ENDPOINT");
+    assert_eq!(
+        Config::from_vars(&s3_vars).unwrap_err(),
+        ["S3_ENDPOINT: must be set", "S3_BUCKET: must be set"]
+    );
+
+    // Every bad variable is reported at once
+    for (name, value) in [
+        ("PORT", "70000"),
+        ("CORS_ORIGINS", "https://app.example.com, localhost"),
+        ("DATABASE_URL", "mysql://localhost/warehouse"),
+        ("JWT_SECRET", "short"),
+        ("UPSTREAM_TIMEOUT_SECS", "0"),
+        ("INDEXER_URL", "indexer"),
+    ] {
+        vars.insert(name.to_string(), value.to_string());
     }
+    vars.remove("JWT_MAXAGE");
+    let problems = Config::from_vars(&vars).unwrap_err();
+    assert_eq!(
+        problems,
+        [
+            "PORT: must be a port between 1 and 65535, got \"70000\"",
+            "CORS_ORIGINS: localhost is not an http(s) origin",
+            "DATABASE_URL: must be a postgres:// URL",
+            "JWT_SECRET: must be at least 32 characters",
+            "JWT_MAXAGE: must be set",
+            "INDEXER_URL: must be a http or https URL",
+            "UPSTREAM_TIMEOUT_SECS: must be at least 1",
+        ]
+    );
 }
@@ -1,52 +1,95 @@
-use crate::models::{Account, Entity, Project, User};
-use sqlx::{postgres::PgPoolOptions, PgPool, Result};
+#[cfg(test)]
+pub mod test_db;
+mod transaction;
+
+use crate::models::{
+    Account, AccountBalanceSnapshot, AlertDelivery, AlertRule, AnomalyAlert, AuditEntry,
+    CoinFilter, CoinInfo, DexTotals, Entity, HolderSnapshot, KnownAddress, KnownPair, Project,
+    ProjectAttributeChange, ProjectAttributeSchema, ProjectMetricFormula, SwapDailySummary, User,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    postgres::PgPoolOptions,
+    PgPool, Result,
+};
+use std::collections::BTreeMap;
+use tokio::sync::broadcast;
+
+pub use transaction::PostgreTransaction;
+
+/// Connections the pool keeps open even when idle
+pub const MIN_CONNECTIONS: u32 = 4;
+
+/// Migrations of the `migrations` directory, embedded in the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Applies the migrations the database hasn't run yet, in order
+pub async fn run_migrations(pool: &PgPool) -> std::result::Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Whether `error` is a write refused by a unique index, SQLSTATE `23505`
+pub fn is_unique_violation(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .is_some_and(|error| error.is_unique_violation())
+}
+
+/// Whether `error` is a write referencing a row that doesn't exist, SQLSTATE `23503`
+pub fn is_foreign_key_violation(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .is_some_and(|error| error.is_foreign_key_violation())
+}
 
 /// Connects to a PostgreSQL database with the given `db_url`, returning a connection pool for accessing it
 pub async fn connect_sqlx(db_url: &str) -> sqlx::PgPool {
     PgPoolOptions::new()
         .max_connections(32)
-        .min_connections(4)
+        .min_connections(MIN_CONNECTIONS)
         .connect(db_url)
         .await
         .expect("Could not connect to the database")
 }
 
+/// Attribute changes buffered for each subscriber before the slowest ones miss some
+const ATTRIBUTE_CHANGES_CAPACITY: usize = 256;
+
+#[derive(Clone)]
 pub struct PostgreDatabase {
     sqlx_db: PgPool,
+    attribute_changes: broadcast::Sender<ProjectAttributeChange>,
 }
 
 impl PostgreDatabase {
     pub fn new(sqlx_db: PgPool) -> Self {
-        PostgreDatabase { sqlx_db }
+        let (attribute_changes, _) = broadcast::channel(ATTRIBUTE_CHANGES_CAPACITY);
+        PostgreDatabase {
+            sqlx_db,
+            attribute_changes,
+        }
+    }
+    /// Receives every change of a numeric project attribute recorded from now on
+    pub fn subscribe_attribute_changes(&self) -> broadcast::Receiver<ProjectAttributeChange> {
+        self.attribute_changes.subscribe()
+    }
+    /// Check that the database answers a trivial query
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.sqlx_db).await?;
+        Ok(())
+    }
+    /// Opens the [`MIN_CONNECTIONS`] of the pool by pinging on that many connections at once
+    pub async fn warm_up(&self) -> Result<()> {
+        futures::future::try_join_all((0..MIN_CONNECTIONS).map(|_| self.ping())).await?;
+        Ok(())
     }
     /// Create a new user using a reference to a `User` struct
     pub async fn create_user(&self, user: &User) -> Result<User> {
-        let result = sqlx::query!(
-            r#"
-            INSERT INTO app_user (name, email, hashed_password, role)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, name, email, hashed_password, role, created_at, updated_at
-            "#,
-            user.name,
-            user.email,
-            user.hashed_password,
-            user.role
-        )
-        .fetch_one(&self.sqlx_db)
-        .await;
-
-        match result {
-            Ok(row) => Ok(User {
-                id: row.id,
-                name: row.name,
-                email: row.email,
-                hashed_password: row.hashed_password,
-                role: row.role,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-            }),
-            Err(e) => Err(e),
-        }
+        let mut tx = self.begin().await?;
+        let result = tx.create_user(user).await?;
+        tx.commit().await?;
+        Ok(result)
     }
 
     /// Get a user by ID
@@ -54,7 +97,7 @@ impl PostgreDatabase {
         let row = sqlx::query_as!(
             User,
             r#"
-            SELECT id, name, email, hashed_password, role, created_at, updated_at
+            SELECT id, name, email, hashed_password, role, totp_secret, totp_enabled, active, created_at, updated_at
             FROM app_user
             WHERE id = $1
             "#,
@@ -70,7 +113,7 @@ impl PostgreDatabase {
         let row = sqlx::query_as!(
             User,
             r#"
-            SELECT id, name, email, hashed_password, role, created_at, updated_at
+            SELECT id, name, email, hashed_password, role, totp_secret, totp_enabled, active, created_at, updated_at
             FROM app_user
             WHERE email = $1
             "#,
@@ -81,75 +124,267 @@ impl PostgreDatabase {
         Ok(row)
     }
 
-    // Create a new entity using a reference to a `Entity` struct
-    pub async fn create_entity(&self, new_entity: &Entity) -> Result<Entity> {
-        let result = sqlx::query!(
+    /// Store a new encrypted TOTP secret for a user, to be confirmed before login asks for codes
+    pub async fn set_totp_secret(&self, user_id: i32, totp_secret: &str) -> Result<()> {
+        sqlx::query!(
             r#"
-            INSERT INTO entity (name)
-            VALUES ($1)
-            RETURNING id, name, created_at, updated_at
+            UPDATE app_user
+            SET totp_secret = $2, totp_enabled = false, updated_at = current_timestamp
+            WHERE id = $1
             "#,
-            new_entity.name,
+            user_id,
+            totp_secret
         )
-        .fetch_one(&self.sqlx_db)
-        .await;
-
-        match result {
-            Ok(row) => Ok(Entity {
-                id: row.id,
-                name: row.name,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-            }),
-            Err(e) => Err(e),
-        }
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(())
     }
-    /// Get an entity by ID
-    pub async fn get_entity_by_id(&self, id: i32) -> Result<Option<Entity>> {
-        let row = sqlx::query_as!(
-            Entity,
+
+    /// Make login ask for a TOTP code, once the user proved their app has the secret
+    pub async fn enable_totp(&self, user_id: i32) -> Result<User> {
+        let mut tx = self.begin().await?;
+        let result = tx.enable_totp(user_id).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    /// End of the lockout of the TOTP challenge of a user, `None` when codes can be tried
+    pub async fn get_totp_locked_until(&self, user_id: i32) -> Result<Option<DateTime<Utc>>> {
+        let locked_until = sqlx::query_scalar!(
+            r#"
+            SELECT totp_locked_until FROM app_user
+            WHERE id = $1 AND totp_locked_until > current_timestamp
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+        Ok(locked_until.flatten())
+    }
+
+    /// Count a wrong TOTP code of a user. Once `max_attempts` were wrong in a row the challenge
+    /// is locked until `locked_until` and the count starts over. Returns whether it was locked.
+    pub async fn record_totp_failure(
+        &self,
+        user_id: i32,
+        max_attempts: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<bool> {
+        let locked = sqlx::query_scalar!(
             r#"
-            SELECT id, name, created_at, updated_at
-            FROM entity
+            UPDATE app_user
+            SET totp_failed_attempts = CASE
+                    WHEN totp_failed_attempts + 1 >= $2 THEN 0
+                    ELSE totp_failed_attempts + 1
+                END,
+                totp_locked_until = CASE
+                    WHEN totp_failed_attempts + 1 >= $2 THEN $3
+                    ELSE totp_locked_until
+                END
             WHERE id = $1
+            RETURNING totp_locked_until IS NOT DISTINCT FROM $3 AS "locked!"
             "#,
-            id
+            user_id,
+            max_attempts,
+            locked_until
         )
         .fetch_optional(&self.sqlx_db)
         .await?;
-        Ok(row)
+        Ok(locked.unwrap_or(false))
     }
-    /// Create a new account
-    pub async fn create_account(&self, new_account: &Account) -> Result<Account> {
+
+    /// Record the TOTP login of a user with a code of `step` and a partial token issued at
+    /// `issued_at`, unless a code of that step or a later one already logged in, a TOTP login
+    /// happened since the partial token was issued, or the challenge is locked. Returns whether
+    /// the login goes through, checked and recorded at once so concurrent logins can't both.
+    pub async fn record_totp_login(
+        &self,
+        user_id: i32,
+        step: i64,
+        issued_at: DateTime<Utc>,
+    ) -> Result<bool> {
         let result = sqlx::query!(
             r#"
-            INSERT INTO account (address, entity_id)
-            VALUES ($1, $2)
-            RETURNING id, address, entity_id, created_at, updated_at
+            UPDATE app_user
+            SET totp_last_step = $2,
+                totp_last_login_at = current_timestamp,
+                totp_failed_attempts = 0
+            WHERE id = $1
+                AND (totp_last_step IS NULL OR totp_last_step < $2)
+                AND (totp_last_login_at IS NULL OR totp_last_login_at < $3)
+                AND (totp_locked_until IS NULL OR totp_locked_until <= current_timestamp)
+            "#,
+            user_id,
+            step,
+            issued_at
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// List the users, ordered by ID
+    pub async fn list_users(&self, limit: i64, offset: i64) -> Result<Vec<User>> {
+        let rows = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, name, email, hashed_password, role, totp_secret, totp_enabled, active, created_at, updated_at
+            FROM app_user
+            ORDER BY id
+            LIMIT $1 OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Count the users
+    pub async fn count_users(&self) -> Result<i64> {
+        let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM app_user"#)
+            .fetch_one(&self.sqlx_db)
+            .await?;
+        Ok(count)
+    }
+    /// Keep a user from logging in, leaving their data in place. Returns whether there is a
+    /// user with that ID.
+    pub async fn deactivate_user(&self, user_id: i32) -> Result<bool> {
+        let mut tx = self.begin().await?;
+        let result = tx.deactivate_user(user_id).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    /// Record a change made by `entry.user_id`, the ID and timestamp of `entry` are set by the
+    /// database
+    pub async fn create_audit_entry(&self, entry: &AuditEntry) -> Result<AuditEntry> {
+        let mut tx = self.begin().await?;
+        let result = tx.create_audit_entry(entry).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    /// List the `limit` latest audit entries, only those of `resource_type` and of
+    /// `resource_id` when set, newest first
+    pub async fn list_audit_entries(
+        &self,
+        resource_type: Option<&str>,
+        resource_id: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>> {
+        let rows = sqlx::query_as!(
+            AuditEntry,
+            r#"
+            SELECT * FROM audit_log
+            WHERE ($1::text IS NULL OR resource_type = $1) AND ($2::integer IS NULL OR resource_id = $2)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3
+            "#,
+            resource_type,
+            resource_id,
+            limit
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+
+    // Create a new entity using a reference to a `Entity` struct
+    pub async fn create_entity(&self, new_entity: &Entity) -> Result<Entity> {
+        let mut tx = self.begin().await?;
+        let result = tx.create_entity(new_entity).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+    /// Get an entity by ID
+    pub async fn get_entity_by_id(&self, id: i32) -> Result<Option<Entity>> {
+        let row = sqlx::query_as!(Entity, "SELECT * FROM entity WHERE id = $1", id)
+            .fetch_optional(&self.sqlx_db)
+            .await?;
+        Ok(row)
+    }
+    /// Get the entities of `ids` that exist, ordered by ID
+    pub async fn get_entities_by_ids(&self, ids: &[i32]) -> Result<Vec<Entity>> {
+        let rows = sqlx::query_as!(
+            Entity,
+            "SELECT * FROM entity WHERE id = ANY($1) ORDER BY id",
+            ids
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Replace the name and the metadata of an entity, `None` when there is none by its ID
+    pub async fn update_entity(&self, entity: &Entity) -> Result<Option<Entity>> {
+        let mut tx = self.begin().await?;
+        let result = tx.update_entity(entity).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+    /// List the entities ordered by ID, only those of `entity_type` when set
+    pub async fn list_entities(
+        &self,
+        entity_type: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Entity>> {
+        let rows = sqlx::query_as!(
+            Entity,
+            r#"
+            SELECT * FROM entity
+            WHERE $1::text IS NULL OR entity_type = $1
+            ORDER BY id
+            LIMIT $2 OFFSET $3
             "#,
-            new_account.address,
-            new_account.entity_id
+            entity_type,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Count the entities, only those of `entity_type` when set
+    pub async fn count_entities(&self, entity_type: Option<&str>) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM entity WHERE $1::text IS NULL OR entity_type = $1"#,
+            entity_type
         )
         .fetch_one(&self.sqlx_db)
-        .await;
-
-        match result {
-            Ok(row) => Ok(Account {
-                id: row.id,
-                address: row.address,
-                entity_id: row.entity_id,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-            }),
-            Err(e) => Err(e),
-        }
+        .await?;
+        Ok(count)
+    }
+    /// Entities whose name contains `query`, ignoring case, by name
+    pub async fn search_entities_by_name(&self, query: &str, limit: i64) -> Result<Vec<Entity>> {
+        let rows = sqlx::query_as!(
+            Entity,
+            r#"
+            SELECT * FROM entity
+            WHERE name ILIKE '%' || $1 || '%'
+            ORDER BY name, id
+            LIMIT $2
+            "#,
+            escape_like(query),
+            limit
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Create a new account
+    pub async fn create_account(&self, new_account: &Account) -> Result<Account> {
+        let mut tx = self.begin().await?;
+        let result = tx.create_account(new_account).await?;
+        tx.commit().await?;
+        Ok(result)
     }
     /// Get an account by ID
     pub async fn get_account_by_id(&self, id: i32) -> Result<Option<Account>> {
         let row = sqlx::query_as!(
             Account,
             r#"
-            SELECT id, address, entity_id, created_at, updated_at
+            SELECT id, address, network, entity_id, created_at, updated_at
             FROM account
             WHERE id = $1
             "#,
@@ -163,7 +398,7 @@ impl PostgreDatabase {
         let row = sqlx::query_as!(
             Account,
             r#"
-            SELECT id, address, entity_id, created_at, updated_at
+            SELECT id, address, network, entity_id, created_at, updated_at
             FROM account
             WHERE address = $1
             "#,
@@ -173,17 +408,274 @@ impl PostgreDatabase {
         .await?;
         Ok(row)
     }
+    /// List accounts ordered by ID
+    pub async fn list_accounts(&self, limit: i64, offset: i64) -> Result<Vec<Account>> {
+        let rows = sqlx::query_as!(
+            Account,
+            r#"
+            SELECT id, address, network, entity_id, created_at, updated_at
+            FROM account
+            ORDER BY id
+            LIMIT $1 OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Count all accounts
+    pub async fn count_accounts(&self) -> Result<i64> {
+        let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM account"#)
+            .fetch_one(&self.sqlx_db)
+            .await?;
+        Ok(count)
+    }
+    /// List the accounts of an entity ordered by ID
+    pub async fn get_accounts_by_entity(
+        &self,
+        entity_id: i32,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Account>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            Account,
+            r#"
+            SELECT id, address, network, entity_id, created_at, updated_at
+            FROM account
+            WHERE entity_id = $1
+            ORDER BY id
+            LIMIT $2 OFFSET $3
+            "#,
+            entity_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Count the accounts of an entity
+    pub async fn count_accounts_by_entity(&self, entity_id: i32) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM account WHERE entity_id = $1"#,
+            entity_id
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(count)
+    }
+    /// Accounts of the entities whose name contains `query`, ignoring case, by entity name.
+    /// Accounts have no name of their own, they go by the name of their entity.
+    pub async fn search_accounts_by_name(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<Account>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            Account,
+            r#"
+            SELECT account.id, address, network, entity_id, account.created_at, account.updated_at
+            FROM account
+            JOIN entity ON entity.id = account.entity_id
+            WHERE entity.name ILIKE '%' || $1 || '%'
+            ORDER BY entity.name, account.id
+            LIMIT $2
+            "#,
+            escape_like(query),
+            limit
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
     pub async fn update_account(&self, account: &Account) -> Result<Account, sqlx::Error> {
-        let query = sqlx::query_as!(
+        self.update_account_if_unchanged(account, None)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+    /// Like [`PostgreDatabase::update_account`], but only when the account wasn't updated
+    /// since `expected_updated_at`. Returns `None` when it was, nothing is written then.
+    pub async fn update_account_if_unchanged(
+        &self,
+        account: &Account,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Account>, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let result = tx
+            .update_account_if_unchanged(account, expected_updated_at)
+            .await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+    /// Whether the balance of an account is snapshotted
+    pub async fn is_account_watched(&self, account_id: i32) -> Result<bool> {
+        let watched = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM watched_account WHERE account_id = $1) AS "watched!""#,
+            account_id
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(watched)
+    }
+    /// Start snapshotting the balance of an account unless `max_accounts` are watched already,
+    /// returning whether it is watched. The table is locked while counting, so concurrent
+    /// requests can't watch more accounts than that between them.
+    pub async fn watch_account(&self, account_id: i32, max_accounts: i64) -> Result<bool> {
+        let mut tx = self.sqlx_db.begin().await?;
+        sqlx::query!("LOCK TABLE watched_account IN SHARE ROW EXCLUSIVE MODE")
+            .execute(&mut *tx)
+            .await?;
+        let watched = sqlx::query_scalar!(
+            r#"
+            WITH inserted AS (
+                INSERT INTO watched_account (account_id)
+                SELECT $1 WHERE (SELECT COUNT(*) FROM watched_account) < $2
+                ON CONFLICT DO NOTHING
+                RETURNING account_id
+            )
+            SELECT EXISTS(SELECT 1 FROM inserted)
+                OR EXISTS(SELECT 1 FROM watched_account WHERE account_id = $1) AS "watched!"
+            "#,
+            account_id,
+            max_accounts
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(watched)
+    }
+    /// Stop snapshotting the balance of an account, returning whether it was watched
+    pub async fn unwatch_account(&self, account_id: i32) -> Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM watched_account WHERE account_id = $1",
+            account_id
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// Add a project to the watchlist of a user, doing nothing if it is already on it
+    pub async fn watch_project(&self, user_id: i32, project_id: i32) -> Result<()> {
+        sqlx::query!(
+            "INSERT INTO user_project_watchlist (user_id, project_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            user_id,
+            project_id
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(())
+    }
+    /// Remove a project from the watchlist of a user, returning whether it was on it
+    pub async fn unwatch_project(&self, user_id: i32, project_id: i32) -> Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM user_project_watchlist WHERE user_id = $1 AND project_id = $2",
+            user_id,
+            project_id
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// List a page of the projects on the watchlist of a user, the latest added first
+    pub async fn list_watched_projects(
+        &self,
+        user_id: i32,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT p.* FROM project p
+            JOIN user_project_watchlist w ON w.project_id = p.id
+            WHERE w.user_id = $1
+            ORDER BY w.created_at DESC, p.id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Count the projects on the watchlist of a user
+    pub async fn count_watched_projects(&self, user_id: i32) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM user_project_watchlist WHERE user_id = $1"#,
+            user_id
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(count)
+    }
+    /// List the watched accounts without a snapshot taken after `since`, ordered by ID
+    pub async fn get_watched_accounts_without_snapshot(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Account>> {
+        let rows = sqlx::query_as!(
             Account,
-            "UPDATE account SET entity_id = $1, updated_at = now() WHERE id = $2 RETURNING *",
-            account.entity_id,
-            account.id
+            r#"
+            SELECT a.id, a.address, a.network, a.entity_id, a.created_at, a.updated_at
+            FROM account a
+            JOIN watched_account w ON w.account_id = a.id
+            WHERE NOT EXISTS (
+                SELECT 1 FROM account_balance_history h
+                WHERE h.account_id = a.id AND h.recorded_at > $1
+            )
+            ORDER BY a.id
+            "#,
+            since
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Record the USD value of an account
+    pub async fn create_balance_snapshot(
+        &self,
+        account_id: i32,
+        total_usd: f64,
+    ) -> Result<AccountBalanceSnapshot> {
+        let row = sqlx::query_as!(
+            AccountBalanceSnapshot,
+            r#"
+            INSERT INTO account_balance_history (account_id, total_usd)
+            VALUES ($1, $2)
+            RETURNING *
+            "#,
+            account_id,
+            total_usd
         )
         .fetch_one(&self.sqlx_db)
         .await?;
-
-        Ok(query)
+        Ok(row)
+    }
+    /// Fetch the latest `limit` balance snapshots of an account, oldest first
+    pub async fn get_balance_history(
+        &self,
+        account_id: i32,
+        limit: i64,
+    ) -> Result<Vec<AccountBalanceSnapshot>> {
+        let mut rows = sqlx::query_as!(
+            AccountBalanceSnapshot,
+            r#"
+            SELECT * FROM account_balance_history
+            WHERE account_id = $1
+            ORDER BY recorded_at DESC, id DESC
+            LIMIT $2
+            "#,
+            account_id,
+            limit
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        rows.reverse();
+        Ok(rows)
     }
 
     /// Fetch a project by its ID
@@ -219,55 +711,2295 @@ impl PostgreDatabase {
 
         Ok(result)
     }
-    /// Create a new project
-    pub async fn create_project(&self, project: &Project) -> Result<Project, sqlx::Error> {
+    /// Fetch a project by its name
+    pub async fn get_project_by_name(&self, name: &str) -> Result<Option<Project>, sqlx::Error> {
         let result = sqlx::query_as!(
             Project,
             r#"
-            INSERT INTO project (token, category, contract_address)
-            VALUES ($1, $2, $3)
-            RETURNING *
+            SELECT * FROM project
+            WHERE lower(name) = lower($1)
             "#,
-            project.token,
-            project.category,
-            project.contract_address,
+            name
         )
-        .fetch_one(&self.sqlx_db)
+        .fetch_optional(&self.sqlx_db)
         .await?;
 
         Ok(result)
     }
-    /// Update an existing project
-    pub async fn update_project(&self, project: &Project) -> Result<Project, sqlx::Error> {
-        let result = sqlx::query_as!(
+    /// List projects ordered by ID
+    pub async fn list_projects(&self, limit: i64, offset: i64) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
             Project,
             r#"
-            UPDATE project
-            SET token = $1,
-                category = $2,
-                contract_address = $3,
-                num_chains = $4,
-                core_developers = $5,
-                code_commits = $6,
-                total_value_locked = $7,
-                token_max_supply = $8,
-                updated_at = CURRENT_TIMESTAMP
-            WHERE id = $9
-            RETURNING *
+            SELECT * FROM project
+            ORDER BY id
+            LIMIT $1 OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// A page of the projects whose numeric attribute `key` is set and within `min_value` and
+    /// `max_value`, both inclusive, by id, along with the number of such projects. `key` names
+    /// a column of `project`, so anything but letters, digits and underscores is refused before
+    /// it reaches the query.
+    pub async fn list_projects_by_attribute(
+        &self,
+        key: &str,
+        min_value: Option<f64>,
+        max_value: Option<f64>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<Project>, i64)> {
+        if !is_column_name(key) {
+            return Err(sqlx::Error::ColumnNotFound(key.to_string()));
+        }
+        let condition = format!(
+            "CAST({key} AS double precision) BETWEEN COALESCE($1, '-Infinity') AND COALESCE($2, 'Infinity')"
+        );
+        let list =
+            format!("SELECT * FROM project WHERE {condition} ORDER BY id LIMIT $3 OFFSET $4");
+        let count = format!("SELECT COUNT(*) FROM project WHERE {condition}");
+        let page = sqlx::query_as::<_, Project>(&list)
+            .bind(min_value)
+            .bind(max_value)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.sqlx_db);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(min_value)
+            .bind(max_value)
+            .fetch_one(&self.sqlx_db);
+        tokio::try_join!(page, total)
+    }
+    /// Count all projects
+    pub async fn count_projects(&self) -> Result<i64> {
+        let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM project"#)
+            .fetch_one(&self.sqlx_db)
+            .await?;
+        Ok(count)
+    }
+    /// Share of the project in the trading volume of all DEX projects, in percent.
+    /// `None` when the project has no volume or no DEX has traded at all.
+    pub async fn calculate_market_share(&self, project_id: i32) -> Result<Option<f64>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                (SELECT trading_volume FROM project WHERE id = $1) AS volume,
+                SUM(trading_volume) AS total
+            FROM project
+            WHERE lower(category) = lower($2)
             "#,
-            project.token,
-            project.category,
-            project.contract_address,
-            project.num_chains,
-            project.core_developers,
-            project.code_commits,
-            project.total_value_locked,
-            project.token_max_supply,
-            project.id
+            project_id,
+            Project::DEX_CATEGORY
         )
         .fetch_one(&self.sqlx_db)
         .await?;
 
-        Ok(result)
+        Ok(match (row.volume, row.total) {
+            (Some(volume), Some(total)) if total > 0.0 => Some(volume / total * 100.0),
+            _ => None,
+        })
+    }
+    /// Sum the value locked and volume of every DEX project, skipping those not reporting them.
+    /// A value set at creation and never changed since dates from the creation of its project.
+    pub async fn get_dex_totals(&self) -> Result<DexTotals> {
+        let totals = sqlx::query_as!(
+            DexTotals,
+            r#"
+            SELECT
+                COUNT(*) AS "projects!",
+                SUM(p.total_value_locked) AS total_value_locked,
+                COUNT(p.total_value_locked) AS "total_value_locked_projects!",
+                SUM(p.trading_volume) AS trading_volume,
+                COUNT(p.trading_volume) AS "trading_volume_projects!",
+                MIN(LEAST(
+                    CASE WHEN p.total_value_locked IS NOT NULL
+                        THEN COALESCE(h.total_value_locked_at, p.created_at) END,
+                    CASE WHEN p.trading_volume IS NOT NULL
+                        THEN COALESCE(h.trading_volume_at, p.created_at) END
+                )) AS updated_at
+            FROM project p
+            LEFT JOIN LATERAL (
+                SELECT
+                    MAX(changed_at) FILTER (WHERE key = 'total_value_locked') AS total_value_locked_at,
+                    MAX(changed_at) FILTER (WHERE key = 'trading_volume') AS trading_volume_at
+                FROM project_attribute_history
+                WHERE project_id = p.id
+            ) h ON true
+            WHERE lower(p.category) = lower($1)
+            "#,
+            Project::DEX_CATEGORY
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(totals)
+    }
+    /// List the DEX projects with the most value locked, leaving out those not reporting it
+    pub async fn list_top_dex_projects(&self, limit: i64) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT * FROM project
+            WHERE lower(category) = lower($1) AND total_value_locked IS NOT NULL
+            ORDER BY total_value_locked DESC, id
+            LIMIT $2
+            "#,
+            Project::DEX_CATEGORY,
+            limit
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List the projects with a CoinGecko coin id, ordered by ID
+    pub async fn list_coingecko_projects(&self) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT * FROM project
+            WHERE coingecko_id IS NOT NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List the projects with a contract address and a volume entry function, ordered by ID
+    pub async fn list_volume_projects(&self) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT * FROM project
+            WHERE contract_address IS NOT NULL AND volume_entry_function IS NOT NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List the projects [`Self::list_volume_projects`] returns that have no swap summary of
+    /// `date` yet, ordered by ID
+    pub async fn list_volume_projects_without_summary(
+        &self,
+        date: NaiveDate,
+    ) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT * FROM project p
+            WHERE contract_address IS NOT NULL AND volume_entry_function IS NOT NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM swap_daily_summary s
+                WHERE s.project_id = p.id AND s.date = $1
+            )
+            ORDER BY id
+            "#,
+            date
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Get the day of the latest swap summary of a project, none when it has none
+    pub async fn get_latest_swap_summary_date(&self, project_id: i32) -> Result<Option<NaiveDate>> {
+        let date = sqlx::query_scalar!(
+            "SELECT max(date) FROM swap_daily_summary WHERE project_id = $1",
+            project_id
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(date)
     }
+    /// Store the swap summary of a project and day, replacing the one already stored
+    pub async fn upsert_swap_daily_summary(&self, summary: &SwapDailySummary) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO swap_daily_summary (project_id, date, tx_count, volume_usd, unique_traders, avg_trade_usd)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (project_id, date) DO UPDATE SET
+                tx_count = EXCLUDED.tx_count,
+                volume_usd = EXCLUDED.volume_usd,
+                unique_traders = EXCLUDED.unique_traders,
+                avg_trade_usd = EXCLUDED.avg_trade_usd
+            "#,
+            summary.project_id,
+            summary.date,
+            summary.tx_count,
+            summary.volume_usd,
+            summary.unique_traders,
+            summary.avg_trade_usd
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(())
+    }
+    /// List the swap summaries of a project from `start` to `end` included, oldest first
+    pub async fn list_swap_daily_summaries(
+        &self,
+        project_id: i32,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<SwapDailySummary>> {
+        let rows = sqlx::query_as!(
+            SwapDailySummary,
+            r#"
+            SELECT * FROM swap_daily_summary
+            WHERE project_id = $1 AND date BETWEEN $2 AND $3
+            ORDER BY date
+            "#,
+            project_id,
+            start,
+            end
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Store the holder snapshot of a token and day, replacing the one already stored
+    pub async fn upsert_holder_snapshot(&self, snapshot: &HolderSnapshot) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO holder_snapshot (token, date, holder_count, holders_hash, sample, sample_rate)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (token, date) DO UPDATE SET
+                holder_count = EXCLUDED.holder_count,
+                holders_hash = EXCLUDED.holders_hash,
+                sample = EXCLUDED.sample,
+                sample_rate = EXCLUDED.sample_rate,
+                created_at = current_timestamp
+            "#,
+            snapshot.token,
+            snapshot.date,
+            snapshot.holder_count,
+            snapshot.holders_hash,
+            &snapshot.sample,
+            snapshot.sample_rate
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(())
+    }
+    /// List the `limit` latest holder snapshots of a token taken on `until` or before, newest
+    /// first
+    pub async fn list_holder_snapshots(
+        &self,
+        token: &str,
+        until: NaiveDate,
+        limit: i64,
+    ) -> Result<Vec<HolderSnapshot>> {
+        let rows = sqlx::query_as!(
+            HolderSnapshot,
+            r#"
+            SELECT * FROM holder_snapshot
+            WHERE token = $1 AND date <= $2
+            ORDER BY date DESC
+            LIMIT $3
+            "#,
+            token,
+            until,
+            limit
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List the projects with a contract address and a swap fee, ordered by ID
+    pub async fn list_fee_projects(&self) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT * FROM project
+            WHERE contract_address IS NOT NULL AND swap_fee_bps IS NOT NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List the projects with a contract address and a total value locked, ordered by ID
+    pub async fn list_tvl_projects(&self) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT * FROM project
+            WHERE contract_address IS NOT NULL AND total_value_locked IS NOT NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List the projects with at least one treasury account, ordered by ID
+    pub async fn list_treasury_projects(&self) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT * FROM project p
+            WHERE EXISTS (SELECT 1 FROM treasury_account t WHERE t.project_id = p.id)
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List the treasury accounts of a project, ordered by ID
+    pub async fn get_treasury_accounts(&self, project_id: i32) -> Result<Vec<Account>> {
+        let rows = sqlx::query_as!(
+            Account,
+            r#"
+            SELECT a.id, a.address, a.network, a.entity_id, a.created_at, a.updated_at
+            FROM account a
+            JOIN treasury_account t ON t.account_id = a.id
+            WHERE t.project_id = $1
+            ORDER BY a.id
+            "#,
+            project_id
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Flag an account as holding the treasury of a project, doing nothing if it already is
+    pub async fn add_treasury_account(&self, project_id: i32, account_id: i32) -> Result<()> {
+        sqlx::query!(
+            "INSERT INTO treasury_account (project_id, account_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            project_id,
+            account_id
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(())
+    }
+    /// Stop counting an account in the treasury of a project, returning whether it was
+    pub async fn remove_treasury_account(&self, project_id: i32, account_id: i32) -> Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM treasury_account WHERE project_id = $1 AND account_id = $2",
+            project_id,
+            account_id
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// List the DEX projects with a contract address, ordered by ID
+    pub async fn list_dex_projects(&self) -> Result<Vec<Project>> {
+        let rows = sqlx::query_as!(
+            Project,
+            r#"
+            SELECT * FROM project
+            WHERE lower(category) = lower($1) AND contract_address IS NOT NULL
+            ORDER BY id
+            "#,
+            Project::DEX_CATEGORY
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Mark a project active or not, returning whether that changed anything
+    pub async fn set_project_active(&self, id: i32, active: bool) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE project SET active = $2, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND active <> $2
+            "#,
+            id,
+            active
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// List the token pairs seen trading on a project, ordered by token
+    pub async fn list_known_pairs(&self, project_id: i32) -> Result<Vec<KnownPair>> {
+        let rows = sqlx::query_as!(
+            KnownPair,
+            r#"
+            SELECT * FROM known_pair
+            WHERE project_id = $1
+            ORDER BY token_a, token_b
+            "#,
+            project_id
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Record the pairs seen trading on a project at `seen_at`, moving the last sighting of
+    /// those already known. Returns how many were new.
+    pub async fn upsert_known_pairs(
+        &self,
+        project_id: i32,
+        pairs: &[(String, String)],
+        seen_at: DateTime<Utc>,
+    ) -> Result<u64> {
+        let (tokens_a, tokens_b): (Vec<String>, Vec<String>) = pairs.iter().cloned().unzip();
+        // xmax is only 0 on the rows the statement inserted
+        let inserted = sqlx::query_scalar!(
+            r#"
+            INSERT INTO known_pair (project_id, token_a, token_b, first_seen_at, last_seen_at)
+            SELECT $1, token_a, token_b, $4, $4 FROM UNNEST($2::text[], $3::text[]) AS t(token_a, token_b)
+            ON CONFLICT (project_id, token_a, token_b) DO UPDATE SET last_seen_at = EXCLUDED.last_seen_at
+            RETURNING xmax = 0 AS "inserted!"
+            "#,
+            project_id,
+            &tokens_a,
+            &tokens_b,
+            seen_at
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(inserted.into_iter().filter(|inserted| *inserted).count() as u64)
+    }
+    /// CoinGecko coin id of the lowest numbered project with `token`, if it has one
+    pub async fn get_coingecko_id_by_token(&self, token: &str) -> Result<Option<String>> {
+        let coingecko_id = sqlx::query_scalar!(
+            r#"
+            SELECT coingecko_id AS "coingecko_id!" FROM project
+            WHERE token = $1 AND coingecko_id IS NOT NULL
+            ORDER BY id
+            LIMIT 1
+            "#,
+            token
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+        Ok(coingecko_id)
+    }
+    /// Create a new project
+    pub async fn create_project(&self, project: &Project) -> Result<Project, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let result = tx.create_project(project).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    /// Checks `attributes`, fields of a project by name, against the attribute schema of
+    /// `category`. Returns what is wrong with each attribute breaking it, empty when none does.
+    /// Schema keys missing from `attributes` are passed over, so a key naming no field of a
+    /// project can't make every write fail, and a body can be checked on the fields it sends.
+    pub async fn validate_attributes(
+        &self,
+        category: &str,
+        attributes: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<BTreeMap<String, String>> {
+        let schemas = sqlx::query_as!(
+            ProjectAttributeSchema,
+            r#"
+            SELECT * FROM project_attribute_schema
+            WHERE lower(category) = lower($1)
+            "#,
+            category
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+
+        Ok(schemas
+            .iter()
+            .filter_map(|schema| {
+                let value = attributes.get(&schema.key)?;
+                Some((schema.key.clone(), schema.violation(value)?))
+            })
+            .collect())
+    }
+
+    /// Update an existing project, recording each change of its numeric attributes made by
+    /// `changed_by` in the attribute history
+    pub async fn update_project(
+        &self,
+        project: &Project,
+        changed_by: &str,
+    ) -> Result<Project, sqlx::Error> {
+        self.update_project_if_unchanged(project, changed_by, None)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    /// Like [`PostgreDatabase::update_project`], but only when the project wasn't updated
+    /// since `expected_updated_at`. Returns `None` when it was, nothing is written then.
+    pub async fn update_project_if_unchanged(
+        &self,
+        project: &Project,
+        changed_by: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Project>, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let result = tx
+            .update_project_if_unchanged(project, changed_by, expected_updated_at)
+            .await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+    /// Latest changes of the attribute `key` of a project, newest first, only the ones made
+    /// after `since` when set
+    pub async fn get_attribute_history(
+        &self,
+        project_id: i32,
+        key: &str,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<ProjectAttributeChange>> {
+        let rows = sqlx::query_as!(
+            ProjectAttributeChange,
+            r#"
+            SELECT * FROM project_attribute_history
+            WHERE project_id = $1 AND key = $2 AND ($3::timestamptz IS NULL OR changed_at >= $3)
+            ORDER BY changed_at DESC, id DESC
+            LIMIT $4
+            "#,
+            project_id,
+            key,
+            since,
+            limit
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// When the attribute `key` of a project was first changed, `None` if it never was
+    pub async fn get_first_attribute_change_at(
+        &self,
+        project_id: i32,
+        key: &str,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let first = sqlx::query_scalar!(
+            r#"
+            SELECT MIN(changed_at) FROM project_attribute_history
+            WHERE project_id = $1 AND key = $2
+            "#,
+            project_id,
+            key
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(first)
+    }
+    /// Last value the attribute `key` of a project was set to on each UTC day from `since` to
+    /// before `until`, newest first. Days without a change are left out.
+    pub async fn get_daily_attribute_values(
+        &self,
+        project_id: i32,
+        key: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(NaiveDate, f64)>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT DISTINCT ON ((changed_at AT TIME ZONE 'UTC')::date)
+                (changed_at AT TIME ZONE 'UTC')::date AS "day!",
+                new_value AS "new_value!"
+            FROM project_attribute_history
+            WHERE project_id = $1 AND key = $2 AND new_value IS NOT NULL
+                AND changed_at >= $3 AND changed_at < $4
+            ORDER BY (changed_at AT TIME ZONE 'UTC')::date DESC, changed_at DESC, id DESC
+            "#,
+            project_id,
+            key,
+            since,
+            until
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.day, row.new_value))
+            .collect())
+    }
+    /// Value the attribute `key` of a project had at each of the times from `since` to
+    /// `until` spaced `step` apart, oldest first. `None` at the times before its first change.
+    pub async fn get_attribute_samples(
+        &self,
+        project_id: i32,
+        key: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        step: Duration,
+    ) -> Result<Vec<(DateTime<Utc>, Option<f64>)>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT s.at AS "at!", h.new_value
+            FROM generate_series($3::timestamptz, $4::timestamptz, make_interval(secs => $5)) AS s(at)
+            LEFT JOIN LATERAL (
+                SELECT new_value FROM project_attribute_history
+                WHERE project_id = $1 AND key = $2 AND changed_at <= s.at
+                ORDER BY changed_at DESC, id DESC
+                LIMIT 1
+            ) h ON true
+            ORDER BY s.at
+            "#,
+            project_id,
+            key,
+            since,
+            until,
+            step.num_milliseconds() as f64 / 1000.0
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.at, row.new_value))
+            .collect())
+    }
+    /// Highest daily price snapshot of the token `coin_type` across the projects holding it,
+    /// with when it was taken, the earliest one on a tie
+    pub async fn get_price_ath(
+        &self,
+        coin_type: &str,
+    ) -> Result<Option<(f64, DateTime<Utc>)>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT h.new_value AS "price!", h.changed_at
+            FROM project_attribute_history h
+            JOIN project p ON p.id = h.project_id
+            WHERE p.token = $1 AND h.key = $2 AND h.new_value IS NOT NULL
+            ORDER BY h.new_value DESC, h.changed_at
+            LIMIT 1
+            "#,
+            coin_type,
+            Project::PRICE_ATTRIBUTE
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+        Ok(row.map(|row| (row.price, row.changed_at)))
+    }
+    /// Lowest daily price snapshot of the token `coin_type` across the projects holding it,
+    /// with when it was taken, the earliest one on a tie
+    pub async fn get_price_atl(
+        &self,
+        coin_type: &str,
+    ) -> Result<Option<(f64, DateTime<Utc>)>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT h.new_value AS "price!", h.changed_at
+            FROM project_attribute_history h
+            JOIN project p ON p.id = h.project_id
+            WHERE p.token = $1 AND h.key = $2 AND h.new_value IS NOT NULL
+            ORDER BY h.new_value, h.changed_at
+            LIMIT 1
+            "#,
+            coin_type,
+            Project::PRICE_ATTRIBUTE
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+        Ok(row.map(|row| (row.price, row.changed_at)))
+    }
+    /// Record past changes of project attributes, e.g. values computed after the fact, all or
+    /// none of them
+    pub async fn create_attribute_changes(&self, changes: &[ProjectAttributeChange]) -> Result<()> {
+        let mut tx = self.sqlx_db.begin().await?;
+        for change in changes {
+            sqlx::query!(
+                r#"
+                INSERT INTO project_attribute_history (project_id, key, old_value, new_value, changed_by, changed_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+                change.project_id,
+                change.key,
+                change.old_value,
+                change.new_value,
+                change.changed_by,
+                change.changed_at
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+    /// Fetch a saved formula of a project by its name
+    pub async fn get_project_formula_by_name(
+        &self,
+        project_id: i32,
+        name: &str,
+    ) -> Result<Option<ProjectMetricFormula>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            ProjectMetricFormula,
+            r#"
+            SELECT * FROM project_metric_formula
+            WHERE project_id = $1 AND name = $2
+            "#,
+            project_id,
+            name
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+
+        Ok(result)
+    }
+    /// Save a named formula for a project
+    pub async fn create_project_formula(
+        &self,
+        formula: &ProjectMetricFormula,
+    ) -> Result<ProjectMetricFormula, sqlx::Error> {
+        let result = sqlx::query_as!(
+            ProjectMetricFormula,
+            r#"
+            INSERT INTO project_metric_formula (project_id, name, formula)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+            formula.project_id,
+            formula.name,
+            formula.formula,
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+
+        Ok(result)
+    }
+    /// Fetch the cached metadata of a coin type
+    pub async fn get_coin_info(&self, coin_type: &str) -> Result<Option<CoinInfo>> {
+        let result = sqlx::query_as!(
+            CoinInfo,
+            r#"
+            SELECT * FROM coin_info
+            WHERE coin_type = $1
+            "#,
+            coin_type
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+
+        Ok(result)
+    }
+    /// Cache the metadata of a coin type, replacing what was cached before
+    pub async fn upsert_coin_info(&self, info: &CoinInfo) -> Result<CoinInfo> {
+        let result = sqlx::query_as!(
+            CoinInfo,
+            r#"
+            INSERT INTO coin_info (coin_type, decimals, symbol, name)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (coin_type) DO UPDATE
+            SET decimals = EXCLUDED.decimals,
+                symbol = EXCLUDED.symbol,
+                name = EXCLUDED.name,
+                last_refreshed = CURRENT_TIMESTAMP
+            RETURNING *
+            "#,
+            info.coin_type,
+            info.decimals,
+            info.symbol,
+            info.name,
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+
+        Ok(result)
+    }
+    /// Fetch the label of an address, which must be normalized
+    pub async fn get_known_address(&self, address: &str) -> Result<Option<KnownAddress>> {
+        let result = sqlx::query_as!(
+            KnownAddress,
+            r#"
+            SELECT * FROM known_address
+            WHERE address = $1
+            "#,
+            address
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+
+        Ok(result)
+    }
+    /// Fetch the labels of those of `addresses` that are known, which must be normalized
+    pub async fn get_known_addresses(&self, addresses: &[String]) -> Result<Vec<KnownAddress>> {
+        let rows = sqlx::query_as!(
+            KnownAddress,
+            r#"
+            SELECT * FROM known_address
+            WHERE address = ANY($1)
+            "#,
+            addresses
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List labeled addresses ordered by address
+    pub async fn list_known_addresses(&self, limit: i64, offset: i64) -> Result<Vec<KnownAddress>> {
+        let rows = sqlx::query_as!(
+            KnownAddress,
+            r#"
+            SELECT * FROM known_address
+            ORDER BY address
+            LIMIT $1 OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Count all labeled addresses
+    pub async fn count_known_addresses(&self) -> Result<i64> {
+        let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM known_address"#)
+            .fetch_one(&self.sqlx_db)
+            .await?;
+        Ok(count)
+    }
+    /// Label a new address
+    pub async fn create_known_address(&self, known: &KnownAddress) -> Result<KnownAddress> {
+        let result = sqlx::query_as!(
+            KnownAddress,
+            r#"
+            INSERT INTO known_address (address, label, category)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+            known.address,
+            known.label,
+            known.category,
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+
+        Ok(result)
+    }
+    /// Replace the label and category of an address
+    pub async fn update_known_address(&self, known: &KnownAddress) -> Result<KnownAddress> {
+        let result = sqlx::query_as!(
+            KnownAddress,
+            r#"
+            UPDATE known_address
+            SET label = $1,
+                category = $2,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE address = $3
+            RETURNING *
+            "#,
+            known.label,
+            known.category,
+            known.address,
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+
+        Ok(result)
+    }
+    /// Remove the label of an address, `false` when it had none
+    pub async fn delete_known_address(&self, address: &str) -> Result<bool> {
+        let result = sqlx::query!("DELETE FROM known_address WHERE address = $1", address)
+            .execute(&self.sqlx_db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// List the whitelisted and blacklisted coins ordered by coin type
+    pub async fn list_coin_filters(&self) -> Result<Vec<CoinFilter>> {
+        let rows = sqlx::query_as!(
+            CoinFilter,
+            r#"
+            SELECT * FROM coin_filter
+            ORDER BY coin_type
+            "#
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Put a coin on the whitelist or the blacklist, moving it when it is on the other one
+    pub async fn upsert_coin_filter(&self, coin_type: &str, status: &str) -> Result<CoinFilter> {
+        let result = sqlx::query_as!(
+            CoinFilter,
+            r#"
+            INSERT INTO coin_filter (coin_type, status)
+            VALUES ($1, $2)
+            ON CONFLICT (coin_type) DO UPDATE
+            SET status = EXCLUDED.status
+            RETURNING *
+            "#,
+            coin_type,
+            status,
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+
+        Ok(result)
+    }
+    /// Take a coin off the whitelist or the blacklist, `false` when it was on neither
+    pub async fn delete_coin_filter(&self, coin_type: &str) -> Result<bool> {
+        let result = sqlx::query!("DELETE FROM coin_filter WHERE coin_type = $1", coin_type)
+            .execute(&self.sqlx_db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// List the coin types of the stablecoins added besides USDT and USDC
+    pub async fn get_stablecoins(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query_scalar!("SELECT coin_type FROM stablecoin ORDER BY coin_type")
+            .fetch_all(&self.sqlx_db)
+            .await?;
+        Ok(rows)
+    }
+    /// List the alert rules of a project of the given kind
+    pub async fn list_alert_rules_of_kind(
+        &self,
+        project_id: i32,
+        kind: &str,
+    ) -> Result<Vec<AlertRule>> {
+        let rows = sqlx::query_as!(
+            AlertRule,
+            "SELECT * FROM alert_rule WHERE project_id = $1 AND kind = $2 ORDER BY id",
+            project_id,
+            kind
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// List the alert rules of a project ordered by ID, only those of `created_by` when set
+    pub async fn list_alert_rules(
+        &self,
+        project_id: i32,
+        created_by: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AlertRule>> {
+        let rows = sqlx::query_as!(
+            AlertRule,
+            r#"
+            SELECT * FROM alert_rule
+            WHERE project_id = $1 AND ($2::text IS NULL OR created_by = $2)
+            ORDER BY id
+            LIMIT $3 OFFSET $4
+            "#,
+            project_id,
+            created_by,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Count the alert rules of a project, only those of `created_by` when set
+    pub async fn count_alert_rules(
+        &self,
+        project_id: i32,
+        created_by: Option<&str>,
+    ) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM alert_rule
+            WHERE project_id = $1 AND ($2::text IS NULL OR created_by = $2)
+            "#,
+            project_id,
+            created_by
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(count)
+    }
+    /// Record an anomaly found in the metrics of a project
+    pub async fn create_anomaly_event(&self, alert: &AnomalyAlert) -> Result<AnomalyAlert> {
+        let result = sqlx::query_as!(
+            AnomalyAlert,
+            r#"
+            INSERT INTO anomaly_event (project_id, metric, value, mean, std_dev, detected_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+            alert.project_id,
+            alert.metric,
+            alert.value,
+            alert.mean,
+            alert.std_dev,
+            alert.detected_at
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(result)
+    }
+    /// List the anomalies found in the metrics of a project, newest first
+    pub async fn list_anomaly_events(
+        &self,
+        project_id: i32,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AnomalyAlert>> {
+        let rows = sqlx::query_as!(
+            AnomalyAlert,
+            r#"
+            SELECT * FROM anomaly_event
+            WHERE project_id = $1
+            ORDER BY detected_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            project_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Fetch the latest anomaly found in a metric of a project
+    pub async fn get_latest_anomaly_event(
+        &self,
+        project_id: i32,
+        metric: &str,
+    ) -> Result<Option<AnomalyAlert>> {
+        let row = sqlx::query_as!(
+            AnomalyAlert,
+            r#"
+            SELECT * FROM anomaly_event
+            WHERE project_id = $1 AND metric = $2
+            ORDER BY detected_at DESC, id DESC
+            LIMIT 1
+            "#,
+            project_id,
+            metric
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+        Ok(row)
+    }
+    /// Count the anomalies found in the metrics of a project
+    pub async fn count_anomaly_events(&self, project_id: i32) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM anomaly_event WHERE project_id = $1"#,
+            project_id
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(count)
+    }
+    /// Fetch every swap alert rule along with the token of its project
+    pub async fn get_alert_rules_with_token(&self) -> Result<Vec<(AlertRule, String)>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT r.id, r.project_id, r.kind, r.min_usd, r.webhook_url, r.created_by,
+                r.created_at, r.updated_at, p.token
+            FROM alert_rule r
+            JOIN project p ON p.id = r.project_id
+            WHERE r.kind = $1
+            ORDER BY r.id
+            "#,
+            AlertRule::SWAP_KIND
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let rule = AlertRule {
+                    id: row.id,
+                    project_id: row.project_id,
+                    kind: row.kind,
+                    min_usd: row.min_usd,
+                    webhook_url: row.webhook_url,
+                    created_by: row.created_by,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                };
+                (rule, row.token)
+            })
+            .collect())
+    }
+    /// Get the version of the last transaction the background task `task` went through
+    pub async fn get_task_cursor(&self, task: &str) -> Result<Option<i64>> {
+        let version = sqlx::query_scalar!("SELECT version FROM task_cursor WHERE task = $1", task)
+            .fetch_optional(&self.sqlx_db)
+            .await?;
+        Ok(version)
+    }
+    /// Record that the background task `task` went through the transactions up to `version`
+    pub async fn set_task_cursor(&self, task: &str, version: i64) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO task_cursor (task, version) VALUES ($1, $2)
+            ON CONFLICT (task) DO UPDATE SET version = $2, updated_at = NOW()
+            "#,
+            task,
+            version
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(())
+    }
+    /// Fetch an alert rule of a project created by `created_by`
+    pub async fn get_alert_rule(
+        &self,
+        project_id: i32,
+        id: i32,
+        created_by: &str,
+    ) -> Result<Option<AlertRule>> {
+        let row = sqlx::query_as!(
+            AlertRule,
+            "SELECT * FROM alert_rule WHERE project_id = $1 AND id = $2 AND created_by = $3",
+            project_id,
+            id,
+            created_by
+        )
+        .fetch_optional(&self.sqlx_db)
+        .await?;
+        Ok(row)
+    }
+    /// Create an alert rule
+    pub async fn create_alert_rule(&self, rule: &AlertRule) -> Result<AlertRule> {
+        let row = sqlx::query_as!(
+            AlertRule,
+            r#"
+            INSERT INTO alert_rule (project_id, kind, min_usd, webhook_url, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+            rule.project_id,
+            rule.kind,
+            rule.min_usd,
+            rule.webhook_url,
+            rule.created_by,
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(row)
+    }
+    /// Replace the threshold and webhook of an alert rule
+    pub async fn update_alert_rule(&self, rule: &AlertRule) -> Result<AlertRule> {
+        let row = sqlx::query_as!(
+            AlertRule,
+            r#"
+            UPDATE alert_rule
+            SET min_usd = $1,
+                webhook_url = $2,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $3
+            RETURNING *
+            "#,
+            rule.min_usd,
+            rule.webhook_url,
+            rule.id,
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(row)
+    }
+    /// Delete an alert rule of a project created by `created_by` along with its deliveries,
+    /// `false` when there was none
+    pub async fn delete_alert_rule(
+        &self,
+        project_id: i32,
+        id: i32,
+        created_by: &str,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM alert_rule WHERE project_id = $1 AND id = $2 AND created_by = $3",
+            project_id,
+            id,
+            created_by
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// Record the delivery of a swap to the webhook of a rule, once per swap and rule
+    pub async fn create_alert_delivery(&self, delivery: &AlertDelivery) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO alert_delivery
+                (rule_id, transaction_version, value_usd, attempts, delivered, status_code, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (rule_id, transaction_version) DO NOTHING
+            "#,
+            delivery.rule_id,
+            delivery.transaction_version,
+            delivery.value_usd,
+            delivery.attempts,
+            delivery.delivered,
+            delivery.status_code,
+            delivery.error,
+        )
+        .execute(&self.sqlx_db)
+        .await?;
+        Ok(())
+    }
+    /// List the deliveries of an alert rule, newest first
+    pub async fn list_alert_deliveries(
+        &self,
+        rule_id: i32,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AlertDelivery>> {
+        let rows = sqlx::query_as!(
+            AlertDelivery,
+            r#"
+            SELECT * FROM alert_delivery
+            WHERE rule_id = $1
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            rule_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.sqlx_db)
+        .await?;
+        Ok(rows)
+    }
+    /// Count the deliveries of an alert rule
+    pub async fn count_alert_deliveries(&self, rule_id: i32) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM alert_delivery WHERE rule_id = $1"#,
+            rule_id
+        )
+        .fetch_one(&self.sqlx_db)
+        .await?;
+        Ok(count)
+    }
+}
+
+/// Whether `name` can be spliced into a query as a column name, being only ASCII letters,
+/// digits and underscores
+fn is_column_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[test]
+fn test_is_column_name() {
+    assert!(is_column_name("total_value_locked"));
+    assert!(is_column_name("twick_7d"));
+    assert!(!is_column_name(""));
+    assert!(!is_column_name("id) OR (1=1"));
+    assert!(!is_column_name("name; DROP TABLE project"));
+    assert!(!is_column_name("\"price\""));
+}
+
+/// `text` matched literally by `LIKE`, its wildcards and escape character escaped
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[test]
+fn test_escape_like() {
+    assert_eq!(escape_like("pancake"), "pancake");
+    assert_eq!(escape_like("100%_sure\\"), "100\\%\\_sure\\\\");
+}
+
+#[tokio::test]
+async fn test_migrations_adopt_an_existing_schema() {
+    use sqlx::Executor;
+
+    let test_db = test_db::TestDatabase::empty().await;
+    let pool = &test_db.pool;
+    // Created by hand like before the migrations, then adopted by them and applied again
+    pool.execute(include_str!("../../migrations/20261014000000_baseline.sql"))
+        .await
+        .unwrap();
+    run_migrations(pool).await.unwrap();
+    run_migrations(pool).await.unwrap();
+    let known_addresses: i64 = sqlx::query_scalar("SELECT count(*) FROM known_address")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    let applied: i64 = sqlx::query_scalar("SELECT count(*) FROM _sqlx_migrations")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+    assert_eq!(known_addresses, 4);
+    assert_eq!(applied, MIGRATOR.iter().count() as i64);
+}
+
+#[tokio::test]
+async fn test_project_name_lower_renames_case_duplicates() {
+    use sqlx::Executor;
+
+    let test_db = test_db::TestDatabase::empty().await;
+    let pool = &test_db.pool;
+    let (before, from): (Vec<_>, Vec<_>) = MIGRATOR
+        .iter()
+        .partition(|migration| migration.version < 20261014000010);
+    for migration in before {
+        pool.execute(&*migration.sql).await.unwrap();
+    }
+    for name in ["Pancake", "pancake", "PANCAKE", "Liquidswap"] {
+        sqlx::query("INSERT INTO project (name, token, category) VALUES ($1, '0x1::a::A', 'DEX')")
+            .bind(name)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+    pool.execute(&*from[0].sql).await.unwrap();
+
+    let names: Vec<String> = sqlx::query_scalar("SELECT name FROM project ORDER BY id")
+        .fetch_all(pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        names,
+        ["Pancake", "pancake (2)", "PANCAKE (3)", "Liquidswap"]
+    );
+}
+
+#[tokio::test]
+async fn test_create_and_get_user() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let user = User {
+        name: "Ada".to_string(),
+        email: "ada@example.com".to_string(),
+        hashed_password: "hash".to_string(),
+        role: "user".to_string(),
+        ..Default::default()
+    };
+
+    let created = db.create_user(&user).await.unwrap();
+    assert!(!created.totp_enabled);
+    let by_id = db.get_user_by_id(created.id).await.unwrap().unwrap();
+    assert_eq!(by_id.email, "ada@example.com");
+    let by_email = db.get_user_by_email(&user.email).await.unwrap().unwrap();
+    assert_eq!(by_email.id, created.id);
+    assert!(db
+        .get_user_by_email("bob@example.com")
+        .await
+        .unwrap()
+        .is_none());
+
+    // Emails are unique
+    assert!(db.create_user(&user).await.is_err());
+
+    db.set_totp_secret(created.id, "secret").await.unwrap();
+    let enabled = db.enable_totp(created.id).await.unwrap();
+    assert_eq!(enabled.totp_secret.as_deref(), Some("secret"));
+    assert!(enabled.totp_enabled);
+
+    let other = db
+        .create_user(&User {
+            email: "bob@example.com".to_string(),
+            ..user.clone()
+        })
+        .await
+        .unwrap();
+    assert!(other.active);
+    let users = db.list_users(10, 0).await.unwrap();
+    assert_eq!(
+        users.iter().map(|user| user.id).collect::<Vec<_>>(),
+        [created.id, other.id]
+    );
+    assert_eq!(db.list_users(10, 1).await.unwrap().len(), 1);
+    assert_eq!(db.count_users().await.unwrap(), 2);
+
+    // Deactivated users are kept
+    assert!(db.deactivate_user(other.id).await.unwrap());
+    assert!(!db.get_user_by_id(other.id).await.unwrap().unwrap().active);
+    assert!(!db.deactivate_user(other.id + 1).await.unwrap());
+    assert_eq!(db.count_users().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_totp_attempts() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let user = db
+        .create_user(&User {
+            name: "Ada".to_string(),
+            email: "ada@example.com".to_string(),
+            hashed_password: "hash".to_string(),
+            role: "user".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let now = Utc::now();
+    let issued_at = now - chrono::Duration::minutes(1);
+    let locked_until = now + chrono::Duration::minutes(15);
+
+    // Locked on the third wrong code in a row
+    assert!(!db
+        .record_totp_failure(user.id, 3, locked_until)
+        .await
+        .unwrap());
+    assert!(!db
+        .record_totp_failure(user.id, 3, locked_until)
+        .await
+        .unwrap());
+    assert_eq!(db.get_totp_locked_until(user.id).await.unwrap(), None);
+    assert!(db
+        .record_totp_failure(user.id, 3, locked_until)
+        .await
+        .unwrap());
+    let locked = db.get_totp_locked_until(user.id).await.unwrap().unwrap();
+    assert!((locked - locked_until).num_milliseconds().abs() < 1);
+    assert!(!db.record_totp_login(user.id, 10, issued_at).await.unwrap());
+
+    sqlx::query!(
+        "UPDATE app_user SET totp_locked_until = NULL WHERE id = $1",
+        user.id
+    )
+    .execute(&db.sqlx_db)
+    .await
+    .unwrap();
+    assert!(db.record_totp_login(user.id, 10, issued_at).await.unwrap());
+    // Neither the code of a step nor a partial token logs in twice
+    let issued_after = Utc::now() + chrono::Duration::seconds(1);
+    assert!(!db
+        .record_totp_login(user.id, 10, issued_after)
+        .await
+        .unwrap());
+    assert!(!db.record_totp_login(user.id, 11, issued_at).await.unwrap());
+    assert!(db
+        .record_totp_login(user.id, 11, issued_after)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn test_create_get_and_update_account() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let entity = db
+        .create_entity(&Entity {
+            name: "Treasury".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        db.get_entity_by_id(entity.id).await.unwrap().unwrap().name,
+        "Treasury"
+    );
+    assert!(db.get_entity_by_id(entity.id + 1).await.unwrap().is_none());
+
+    let address = format!("0x{}", "a".repeat(60));
+    let account = db
+        .create_account(&Account {
+            address: address.clone(),
+            network: Account::DEFAULT_NETWORK.to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(account.entity_id, None);
+    let duplicate = db
+        .create_account(&Account {
+            address: address.clone(),
+            network: Account::DEFAULT_NETWORK.to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(is_unique_violation(&duplicate));
+    assert_eq!(
+        db.get_account_by_address(&address)
+            .await
+            .unwrap()
+            .unwrap()
+            .id,
+        account.id
+    );
+
+    let updated = db
+        .update_account(&Account {
+            entity_id: Some(entity.id),
+            ..account.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(updated.entity_id, Some(entity.id));
+    let by_id = db.get_account_by_id(account.id).await.unwrap().unwrap();
+    assert_eq!(by_id.entity_id, Some(entity.id));
+    assert_eq!(db.count_accounts_by_entity(entity.id).await.unwrap(), 1);
+    assert_eq!(db.count_accounts().await.unwrap(), 1);
+
+    // Updates made against the current version go through, stale ones are refused
+    let detached = Account {
+        entity_id: None,
+        ..updated.clone()
+    };
+    let stale = db
+        .update_account_if_unchanged(&detached, Some(account.updated_at))
+        .await
+        .unwrap();
+    assert!(stale.is_none());
+    let current = db.get_account_by_id(account.id).await.unwrap().unwrap();
+    assert_eq!(current.entity_id, Some(entity.id));
+    let detached = db
+        .update_account_if_unchanged(&detached, Some(updated.updated_at))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(detached.entity_id, None);
+    assert!(detached.updated_at > updated.updated_at);
+}
+
+#[tokio::test]
+async fn test_update_project_records_attribute_changes() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let project = |name: &str| Project {
+        name: Some(name.to_string()),
+        token: "CAKE".to_string(),
+        category: Project::DEX_CATEGORY.to_string(),
+        total_value_locked: Some(100.0),
+        ..Default::default()
+    };
+    let created = db.create_project(&project("PancakeSwap")).await.unwrap();
+    let other = db.create_project(&project("LiquidSwap")).await.unwrap();
+    assert_eq!(
+        db.get_project_by_name("PancakeSwap")
+            .await
+            .unwrap()
+            .unwrap()
+            .id,
+        created.id
+    );
+    let mut changes = db.subscribe_attribute_changes();
+
+    let updated = db
+        .update_project(
+            &Project {
+                total_value_locked: Some(250.0),
+                num_chains: Some(2),
+                ..created.clone()
+            },
+            "test",
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.total_value_locked, Some(250.0));
+    let history = db
+        .get_attribute_history(created.id, "total_value_locked", None, 10)
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(
+        (history[0].old_value, history[0].new_value),
+        (Some(100.0), Some(250.0))
+    );
+    assert_eq!(history[0].changed_by, "test");
+    let mut published = vec![
+        changes.try_recv().unwrap().key,
+        changes.try_recv().unwrap().key,
+    ];
+    published.sort();
+    assert_eq!(published, ["num_chains", "total_value_locked"]);
+
+    // A failed update leaves neither the project nor its history changed
+    let renamed = Project {
+        name: other.name.clone(),
+        total_value_locked: Some(999.0),
+        ..updated.clone()
+    };
+    let error = db.update_project(&renamed, "test").await.unwrap_err();
+    assert!(is_unique_violation(&error));
+    let current = db.get_project_by_id(created.id).await.unwrap().unwrap();
+    assert_eq!(current.total_value_locked, Some(250.0));
+    let history = db
+        .get_attribute_history(created.id, "total_value_locked", None, 10)
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(changes.try_recv().is_err());
+
+    // Names are unique and looked up ignoring case
+    let error = db.create_project(&project("liquidswap")).await.unwrap_err();
+    assert!(is_unique_violation(&error));
+    assert_eq!(
+        db.get_project_by_name("LIQUIDSWAP")
+            .await
+            .unwrap()
+            .unwrap()
+            .id,
+        other.id
+    );
+}
+
+#[tokio::test]
+async fn test_upserts_update_on_conflict() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+
+    let info = CoinInfo {
+        coin_type: "0xcafe::meme::MEME".to_string(),
+        decimals: 6,
+        symbol: "MEME".to_string(),
+        name: "Meme".to_string(),
+        last_refreshed: Utc::now(),
+    };
+    db.upsert_coin_info(&info).await.unwrap();
+    let renamed = db
+        .upsert_coin_info(&CoinInfo {
+            symbol: "MEMEV2".to_string(),
+            ..info.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(renamed.symbol, "MEMEV2");
+    let cached = db.get_coin_info(&info.coin_type).await.unwrap().unwrap();
+    assert_eq!(cached.symbol, "MEMEV2");
+
+    let project = db
+        .create_project(&Project {
+            token: "CAKE".to_string(),
+            category: Project::DEX_CATEGORY.to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let pairs = [
+        ("0x1::a::A".to_string(), "0x1::b::B".to_string()),
+        ("0x1::a::A".to_string(), "0x1::c::C".to_string()),
+    ];
+    let first_seen = Utc::now() - Duration::days(1);
+    assert_eq!(
+        db.upsert_known_pairs(project.id, &pairs, first_seen)
+            .await
+            .unwrap(),
+        2
+    );
+    let seen_again = Utc::now();
+    assert_eq!(
+        db.upsert_known_pairs(project.id, &pairs[..1], seen_again)
+            .await
+            .unwrap(),
+        0
+    );
+    let known = db.list_known_pairs(project.id).await.unwrap();
+    assert_eq!(known.len(), 2);
+    let again = known
+        .iter()
+        .find(|pair| pair.token_b == "0x1::b::B")
+        .unwrap();
+    assert!(again.last_seen_at > again.first_seen_at);
+
+    db.upsert_coin_filter(&info.coin_type, CoinFilter::WHITELIST)
+        .await
+        .unwrap();
+    db.upsert_coin_filter(&info.coin_type, CoinFilter::BLACKLIST)
+        .await
+        .unwrap();
+    let filters = db.list_coin_filters().await.unwrap();
+    assert_eq!(filters.len(), 1);
+    assert_eq!(filters[0].status, CoinFilter::BLACKLIST);
+}
+
+#[tokio::test]
+async fn test_watch_account_up_to_the_cap() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let mut ids = Vec::new();
+    for address in ["0xa", "0xb", "0xc"] {
+        let account = db
+            .create_account(&Account {
+                address: address.to_string(),
+                network: Account::DEFAULT_NETWORK.to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        ids.push(account.id);
+    }
+
+    assert!(db.watch_account(ids[0], 2).await.unwrap());
+    assert!(db.watch_account(ids[1], 2).await.unwrap());
+    assert!(!db.watch_account(ids[2], 2).await.unwrap());
+    // Watching an account again doesn't count against the cap
+    assert!(db.watch_account(ids[0], 2).await.unwrap());
+    assert!(!db.is_account_watched(ids[2]).await.unwrap());
+
+    assert!(db.unwatch_account(ids[1]).await.unwrap());
+    assert!(db.watch_account(ids[2], 2).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_swap_daily_summaries() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let address = format!("0x{}", "b".repeat(60));
+    db.create_account(&Account {
+        address: address.clone(),
+        network: Account::DEFAULT_NETWORK.to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let project = db
+        .create_project(&Project {
+            token: "CAKE".to_string(),
+            category: Project::DEX_CATEGORY.to_string(),
+            contract_address: Some(address.clone()),
+            volume_entry_function: Some(format!("{address}::router::swap_exact_input")),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let day = |day| NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+    let summary = |date, tx_count| SwapDailySummary {
+        project_id: project.id,
+        date,
+        tx_count,
+        volume_usd: 10.0 * tx_count as f64,
+        unique_traders: 1,
+        avg_trade_usd: 10.0,
+    };
+
+    let missing = db
+        .list_volume_projects_without_summary(day(12))
+        .await
+        .unwrap();
+    assert_eq!(missing.len(), 1);
+    let latest = db.get_latest_swap_summary_date(project.id).await.unwrap();
+    assert_eq!(latest, None);
+    db.upsert_swap_daily_summary(&summary(day(12), 3))
+        .await
+        .unwrap();
+    db.upsert_swap_daily_summary(&summary(day(12), 4))
+        .await
+        .unwrap();
+    db.upsert_swap_daily_summary(&summary(day(10), 1))
+        .await
+        .unwrap();
+    let missing = db
+        .list_volume_projects_without_summary(day(12))
+        .await
+        .unwrap();
+    assert!(missing.is_empty());
+    let latest = db.get_latest_swap_summary_date(project.id).await.unwrap();
+    assert_eq!(latest, Some(day(12)));
+
+    let summaries = db
+        .list_swap_daily_summaries(project.id, day(10), day(12))
+        .await
+        .unwrap();
+    assert_eq!(summaries, [summary(day(10), 1), summary(day(12), 4)]);
+    let summaries = db
+        .list_swap_daily_summaries(project.id, day(11), day(13))
+        .await
+        .unwrap();
+    assert_eq!(summaries.len(), 1);
+}
+
+#[tokio::test]
+async fn test_audit_entries() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let user = User {
+        name: "Ada".to_string(),
+        email: "ada@example.com".to_string(),
+        hashed_password: "hash".to_string(),
+        role: "admin".to_string(),
+        ..Default::default()
+    };
+    let user = db.create_user(&user).await.unwrap();
+
+    let created = AuditEntry {
+        user_id: Some(user.id),
+        action: AuditEntry::CREATE.to_string(),
+        resource_type: AuditEntry::PROJECT.to_string(),
+        resource_id: 1,
+        after: Some(serde_json::json!({ "name": "Liquidswap" })),
+        ..Default::default()
+    };
+    let created = db.create_audit_entry(&created).await.unwrap();
+    assert!(created.id > 0);
+    assert_eq!(created.before, None);
+    let updated = AuditEntry {
+        action: AuditEntry::UPDATE.to_string(),
+        before: created.after.clone(),
+        after: Some(serde_json::json!({ "name": "Pontem" })),
+        ..created.clone()
+    };
+    let updated = db.create_audit_entry(&updated).await.unwrap();
+    let other = AuditEntry {
+        resource_id: 2,
+        ..created.clone()
+    };
+    let other = db.create_audit_entry(&other).await.unwrap();
+    let account = AuditEntry {
+        resource_type: AuditEntry::ACCOUNT.to_string(),
+        ..created.clone()
+    };
+    let account = db.create_audit_entry(&account).await.unwrap();
+
+    let ids = |entries: Vec<AuditEntry>| entries.iter().map(|e| e.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(db.list_audit_entries(None, None, 10).await.unwrap()),
+        vec![account.id, other.id, updated.id, created.id]
+    );
+    assert_eq!(
+        ids(db.list_audit_entries(None, None, 2).await.unwrap()),
+        vec![account.id, other.id]
+    );
+    assert_eq!(
+        ids(db
+            .list_audit_entries(Some(AuditEntry::PROJECT), Some(1), 10)
+            .await
+            .unwrap()),
+        vec![updated.id, created.id]
+    );
+    assert_eq!(
+        db.list_audit_entries(Some(AuditEntry::PROJECT), Some(1), 10)
+            .await
+            .unwrap()[0],
+        updated
+    );
+
+    // Entries outlive the user who made the change
+    sqlx::query!("DELETE FROM app_user WHERE id = $1", user.id)
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+    let entries = db
+        .list_audit_entries(Some(AuditEntry::ACCOUNT), None, 10)
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].user_id, None);
+}
+
+#[tokio::test]
+async fn test_validate_attributes() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let dex = |project: Project| match serde_json::to_value(project).unwrap() {
+        serde_json::Value::Object(attributes) => attributes,
+        _ => unreachable!(),
+    };
+
+    // The seeded DEX schema passes projects without any metric
+    let project = Project {
+        category: "dex".to_string(),
+        ..Default::default()
+    };
+    assert!(db
+        .validate_attributes("dex", &dex(project.clone()))
+        .await
+        .unwrap()
+        .is_empty());
+
+    sqlx::query!(
+        "INSERT INTO project_attribute_schema (category, key, expected_type, required) VALUES ('DEX', 'token', 'integer', true), ('DEX', 'defi_llama_slug', 'string', true), ('DEX', 'not_a_field', 'string', true)"
+    )
+    .execute(&test_db.pool)
+    .await
+    .unwrap();
+    let violations = db
+        .validate_attributes("DEX", &dex(project.clone()))
+        .await
+        .unwrap();
+    assert_eq!(
+        violations,
+        BTreeMap::from([
+            (
+                "defi_llama_slug".to_string(),
+                "is required for DEX projects".to_string()
+            ),
+            ("token".to_string(), "must be of type integer".to_string()),
+        ])
+    );
+    // Only the fields of a body are checked, the attributes it leaves out aren't missing
+    let body = serde_json::json!({"num_chains": "three", "defi_llama_slug": "pancakeswap"});
+    let violations = db
+        .validate_attributes("DEX", body.as_object().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        violations,
+        BTreeMap::from([(
+            "num_chains".to_string(),
+            "must be of type integer".to_string()
+        )])
+    );
+    // Schemas of other categories don't apply
+    assert!(db
+        .validate_attributes("Lending", &dex(project))
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_list_projects_by_attribute() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let mut ids = Vec::new();
+    for total_value_locked in [Some(10.0), Some(20.0), None, Some(30.0)] {
+        let project = db
+            .create_project(&Project {
+                token: "TKN".to_string(),
+                category: Project::DEX_CATEGORY.to_string(),
+                total_value_locked,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        ids.push(project.id);
+    }
+
+    let (page, total) = db
+        .list_projects_by_attribute("total_value_locked", Some(15.0), None, 1, 1)
+        .await
+        .unwrap();
+    assert_eq!(total, 2);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].id, ids[3]);
+    // The count doesn't depend on the page
+    let (page, total) = db
+        .list_projects_by_attribute("total_value_locked", None, Some(20.0), 10, 5)
+        .await
+        .unwrap();
+    assert!(page.is_empty());
+    assert_eq!(total, 2);
+    assert!(db
+        .list_projects_by_attribute("id; DROP TABLE project", None, None, 10, 0)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_update_project_if_unchanged() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let created = db
+        .create_project(&Project {
+            name: Some("PancakeSwap".to_string()),
+            token: "CAKE".to_string(),
+            category: Project::DEX_CATEGORY.to_string(),
+            total_value_locked: Some(100.0),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let first = db
+        .update_project_if_unchanged(
+            &Project {
+                total_value_locked: Some(250.0),
+                ..created.clone()
+            },
+            "first",
+            Some(created.updated_at),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.total_value_locked, Some(250.0));
+
+    // A second editor who read the project before the first update is refused, and leaves
+    // neither the project nor its history changed
+    let second = db
+        .update_project_if_unchanged(
+            &Project {
+                total_value_locked: Some(300.0),
+                ..created.clone()
+            },
+            "second",
+            Some(created.updated_at),
+        )
+        .await
+        .unwrap();
+    assert!(second.is_none());
+    let current = db.get_project_by_id(created.id).await.unwrap().unwrap();
+    assert_eq!(current.total_value_locked, Some(250.0));
+    let history = db
+        .get_attribute_history(created.id, "total_value_locked", None, 10)
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].changed_by, "first");
+
+    // Without an expected version the update always goes through
+    let forced = db
+        .update_project_if_unchanged(
+            &Project {
+                total_value_locked: Some(300.0),
+                ..created
+            },
+            "second",
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(forced.total_value_locked, Some(300.0));
+}
+
+#[tokio::test]
+async fn test_set_project_active() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let project = db
+        .create_project(&Project {
+            token: "CAKE".to_string(),
+            category: Project::DEX_CATEGORY.to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(project.active);
+
+    assert!(!db.set_project_active(project.id, true).await.unwrap());
+    assert!(db.set_project_active(project.id, false).await.unwrap());
+    let inactive = db.get_project_by_id(project.id).await.unwrap().unwrap();
+    assert!(!inactive.active);
+    assert!(inactive.updated_at > project.updated_at);
+    assert!(!db.set_project_active(project.id, false).await.unwrap());
+    assert!(db.set_project_active(project.id, true).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_holder_snapshots() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let day = |d: u32| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+    let snapshot = |d: u32, holders: &[&str]| {
+        HolderSnapshot::take(
+            "0x1::coin::T",
+            day(d),
+            holders.iter().map(|a| a.to_string()).collect(),
+        )
+    };
+    for snapshot in [
+        snapshot(1, &["0xa"]),
+        snapshot(7, &["0xa", "0xb"]),
+        snapshot(14, &["0xc"]),
+        snapshot(14, &["0xa", "0xc"]),
+    ] {
+        db.upsert_holder_snapshot(&snapshot).await.unwrap();
+    }
+    let other = HolderSnapshot {
+        token: "0x2::coin::U".to_string(),
+        ..snapshot(7, &["0xd"])
+    };
+    db.upsert_holder_snapshot(&other).await.unwrap();
+
+    let snapshots = db
+        .list_holder_snapshots("0x1::coin::T", day(14), 10)
+        .await
+        .unwrap();
+    let dates: Vec<_> = snapshots.iter().map(|s| s.date).collect();
+    assert_eq!(dates, [day(14), day(7), day(1)]);
+    // The second snapshot of a day replaced the first
+    assert_eq!(snapshots[0].sample, ["0xa", "0xc"]);
+    assert_eq!(snapshots[0].holder_count, 2);
+    assert_eq!(
+        snapshots[1].holders_hash,
+        snapshot(7, &["0xb", "0xa"]).holders_hash
+    );
+    let before = db
+        .list_holder_snapshots("0x1::coin::T", day(13), 1)
+        .await
+        .unwrap();
+    assert_eq!(before.len(), 1);
+    assert_eq!(before[0].date, day(7));
+}
+
+#[tokio::test]
+async fn test_project_watchlist() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let user = |name: &str| User {
+        name: name.to_string(),
+        email: format!("{name}@example.com"),
+        hashed_password: "hash".to_string(),
+        role: "user".to_string(),
+        ..Default::default()
+    };
+    let ada = db.create_user(&user("ada")).await.unwrap();
+    let bob = db.create_user(&user("bob")).await.unwrap();
+    let project = |name: &str| Project {
+        name: Some(name.to_string()),
+        token: name.to_uppercase(),
+        category: Project::DEX_CATEGORY.to_string(),
+        ..Default::default()
+    };
+    let cake = db.create_project(&project("cake")).await.unwrap();
+    let meme = db.create_project(&project("meme")).await.unwrap();
+
+    db.watch_project(ada.id, cake.id).await.unwrap();
+    db.watch_project(ada.id, meme.id).await.unwrap();
+    db.watch_project(ada.id, cake.id).await.unwrap();
+    db.watch_project(bob.id, meme.id).await.unwrap();
+    let ids = |projects: Vec<Project>| projects.iter().map(|p| p.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(db.list_watched_projects(ada.id, 10, 0).await.unwrap()),
+        [meme.id, cake.id]
+    );
+    assert_eq!(
+        ids(db.list_watched_projects(ada.id, 1, 1).await.unwrap()),
+        [cake.id]
+    );
+    assert_eq!(db.count_watched_projects(ada.id).await.unwrap(), 2);
+    // Watching a project that doesn't exist is refused by its foreign key
+    let missing = db.watch_project(ada.id, meme.id + 100).await.unwrap_err();
+    assert!(is_foreign_key_violation(&missing));
+
+    assert!(db.unwatch_project(ada.id, meme.id).await.unwrap());
+    assert!(!db.unwatch_project(ada.id, meme.id).await.unwrap());
+    assert_eq!(
+        ids(db.list_watched_projects(ada.id, 10, 0).await.unwrap()),
+        [cake.id]
+    );
+
+    // Deleted projects leave the watchlists
+    sqlx::query!("DELETE FROM project WHERE id = $1", meme.id)
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+    assert!(db
+        .list_watched_projects(bob.id, 10, 0)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(db.count_watched_projects(bob.id).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_entity_metadata() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let binance = db
+        .create_entity(&Entity {
+            name: "Binance".to_string(),
+            entity_type: Some(Entity::CEX.to_string()),
+            website: Some("https://www.binance.com".to_string()),
+            twitter: Some("binance".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(binance.twitter.as_deref(), Some("binance"));
+    let labs = db
+        .create_entity(&Entity {
+            name: "PancakeSwap Labs".to_string(),
+            entity_type: Some(Entity::PROTOCOL.to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let unclassified = db
+        .create_entity(&Entity {
+            name: "Whale".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    // The type is one of the known ones
+    assert!(db
+        .create_entity(&Entity {
+            name: "Bank".to_string(),
+            entity_type: Some("BANK".to_string()),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+    let ids = |entities: Vec<Entity>| entities.iter().map(|e| e.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(db.list_entities(None, 10, 0).await.unwrap()),
+        [binance.id, labs.id, unclassified.id]
+    );
+    assert_eq!(
+        ids(db.list_entities(Some(Entity::CEX), 10, 0).await.unwrap()),
+        [binance.id]
+    );
+    assert_eq!(db.count_entities(None).await.unwrap(), 3);
+    assert_eq!(db.count_entities(Some(Entity::FUND)).await.unwrap(), 0);
+    assert_eq!(
+        ids(db
+            .get_entities_by_ids(&[unclassified.id, binance.id, 999])
+            .await
+            .unwrap()),
+        [binance.id, unclassified.id]
+    );
+
+    let updated = db
+        .update_entity(&Entity {
+            entity_type: Some(Entity::INDIVIDUAL.to_string()),
+            description: Some("Holds a lot of APT".to_string()),
+            ..unclassified.clone()
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.entity_type.as_deref(), Some(Entity::INDIVIDUAL));
+    assert_eq!(updated.description.as_deref(), Some("Holds a lot of APT"));
+    assert!(updated.updated_at > unclassified.updated_at);
+    let missing = Entity { id: 999, ..updated };
+    assert!(db.update_entity(&missing).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_search_by_name() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+
+    let entity = |name: &str| Entity {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    let pancake = db
+        .create_entity(&entity("PancakeSwap Treasury"))
+        .await
+        .unwrap();
+    let percent = db.create_entity(&entity("100% Fund")).await.unwrap();
+    db.create_entity(&entity("Binance")).await.unwrap();
+    let account = |address: &str, entity_id: Option<i32>| Account {
+        address: address.to_string(),
+        network: Account::DEFAULT_NETWORK.to_string(),
+        entity_id,
+        ..Default::default()
+    };
+    let treasury = db
+        .create_account(&account("0xa", Some(pancake.id)))
+        .await
+        .unwrap();
+    db.create_account(&account("0xb", Some(percent.id)))
+        .await
+        .unwrap();
+    db.create_account(&account("0xc", None)).await.unwrap();
+
+    let names = |entities: Vec<Entity>| entities.into_iter().map(|e| e.name).collect::<Vec<_>>();
+    assert_eq!(
+        names(db.search_entities_by_name("pancake", 10).await.unwrap()),
+        ["PancakeSwap Treasury"]
+    );
+    assert_eq!(
+        names(db.search_entities_by_name("a", 1).await.unwrap()),
+        ["Binance"]
+    );
+    // Wildcards are matched literally
+    assert_eq!(
+        names(db.search_entities_by_name("%", 10).await.unwrap()),
+        ["100% Fund"]
+    );
+    assert!(db
+        .search_entities_by_name("kraken", 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let accounts = db.search_accounts_by_name("TREASURY", 10).await.unwrap();
+    assert_eq!(
+        accounts.iter().map(|a| a.id).collect::<Vec<_>>(),
+        [treasury.id]
+    );
+    assert!(db
+        .search_accounts_by_name("binance", 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_task_cursor() {
+    let test_db = test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    assert_eq!(db.get_task_cursor("swap_alerts").await.unwrap(), None);
+
+    db.set_task_cursor("swap_alerts", 42).await.unwrap();
+    db.set_task_cursor("other", 7).await.unwrap();
+    db.set_task_cursor("swap_alerts", 58).await.unwrap();
+    assert_eq!(db.get_task_cursor("swap_alerts").await.unwrap(), Some(58));
+    assert_eq!(db.get_task_cursor("other").await.unwrap(), Some(7));
 }
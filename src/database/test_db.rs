@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sqlx::{postgres::PgPoolOptions, Connection, Executor, PgConnection, PgPool};
+
+use super::{run_migrations, PostgreDatabase};
+
+/// Databases created by this test process so far, numbering the next one
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Database of a single test, created on the server of `DATABASE_URL` and dropped along with
+/// the fixture, even when the test panics. Tests can't see each other's rows, so they can run
+/// in parallel and assert on exact counts.
+pub struct TestDatabase {
+    pub db: PostgreDatabase,
+    pub pool: PgPool,
+    name: String,
+    server_url: String,
+}
+
+impl TestDatabase {
+    /// Database without any table
+    pub async fn empty() -> Self {
+        dotenv::dotenv().ok();
+        let server_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        let name = format!(
+            "test_{}_{}",
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut server = PgConnection::connect(&server_url)
+            .await
+            .expect("Could not connect to the database server");
+        server
+            .execute(format!("CREATE DATABASE {name}").as_str())
+            .await
+            .expect("Could not create the test database");
+        server.close().await.ok();
+
+        let mut url = reqwest::Url::parse(&server_url).expect("DATABASE_URL must be a URL");
+        url.set_path(&name);
+        let pool = PgPoolOptions::new()
+            .max_connections(4)
+            .connect(url.as_str())
+            .await
+            .expect("Could not connect to the test database");
+        TestDatabase {
+            db: PostgreDatabase::new(pool.clone()),
+            pool,
+            name,
+            server_url,
+        }
+    }
+
+    /// Database with every migration applied
+    pub async fn migrated() -> Self {
+        let test_db = Self::empty().await;
+        run_migrations(&test_db.pool)
+            .await
+            .expect("Could not apply the migrations");
+        test_db
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        let name = self.name.clone();
+        let server_url = self.server_url.clone();
+        // The runtime of the test may be gone already, the database is dropped from a new one.
+        // FORCE closes the connections the pool still has open.
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Could not start a runtime");
+            runtime.block_on(async {
+                if let Ok(mut server) = PgConnection::connect(&server_url).await {
+                    let drop = format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)");
+                    server.execute(drop.as_str()).await.ok();
+                }
+            });
+        })
+        .join()
+        .ok();
+    }
+}
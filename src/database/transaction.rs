@@ -0,0 +1,477 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Acquire, Postgres, Result, Transaction};
+
+use crate::models::{Account, AuditEntry, Entity, Project, ProjectAttributeChange, User};
+
+use super::PostgreDatabase;
+
+/// Transaction of several writes, e.g. a change and its audit entry, which are all kept or
+/// all rolled back. The attribute changes of the projects it updated are only broadcast once
+/// it is committed, dropping it rolls everything back.
+pub struct PostgreTransaction<'a> {
+    db: &'a PostgreDatabase,
+    tx: Transaction<'static, Postgres>,
+    changes: Vec<ProjectAttributeChange>,
+}
+
+impl PostgreDatabase {
+    /// Start a transaction, see [`PostgreTransaction`]
+    pub async fn begin(&self) -> Result<PostgreTransaction<'_>> {
+        Ok(PostgreTransaction {
+            db: self,
+            tx: self.sqlx_db.begin().await?,
+            changes: Vec::new(),
+        })
+    }
+}
+
+impl PostgreTransaction<'_> {
+    /// Keep the writes of the transaction, then broadcast the attribute changes it made
+    pub async fn commit(self) -> Result<()> {
+        self.tx.commit().await?;
+        for change in self.changes {
+            // Only fails when nobody is subscribed
+            let _ = self.db.attribute_changes.send(change);
+        }
+        Ok(())
+    }
+
+    /// Create a new user using a reference to a `User` struct
+    pub async fn create_user(&mut self, user: &User) -> Result<User> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO app_user (name, email, hashed_password, role)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, name, email, hashed_password, role, totp_secret, totp_enabled, active, created_at, updated_at
+            "#,
+            user.name,
+            user.email,
+            user.hashed_password,
+            user.role
+        )
+        .fetch_one(&mut *self.tx)
+        .await;
+
+        match result {
+            Ok(row) => Ok(User {
+                id: row.id,
+                name: row.name,
+                email: row.email,
+                hashed_password: row.hashed_password,
+                role: row.role,
+                totp_secret: row.totp_secret,
+                totp_enabled: row.totp_enabled,
+                active: row.active,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Make login ask for a TOTP code, once the user proved their app has the secret
+    pub async fn enable_totp(&mut self, user_id: i32) -> Result<User> {
+        sqlx::query_as!(
+            User,
+            r#"
+            UPDATE app_user
+            SET totp_enabled = true, updated_at = current_timestamp
+            WHERE id = $1
+            RETURNING id, name, email, hashed_password, role, totp_secret, totp_enabled, active, created_at, updated_at
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *self.tx)
+        .await
+    }
+
+    /// Keep a user from logging in, leaving their data in place. Returns whether there is a
+    /// user with that ID.
+    pub async fn deactivate_user(&mut self, user_id: i32) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE app_user
+            SET active = false, updated_at = current_timestamp
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *self.tx)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record a change made by `entry.user_id`, the ID and timestamp of `entry` are set by the
+    /// database
+    pub async fn create_audit_entry(&mut self, entry: &AuditEntry) -> Result<AuditEntry> {
+        sqlx::query_as!(
+            AuditEntry,
+            r#"
+            INSERT INTO audit_log (user_id, action, resource_type, resource_id, before, after)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+            entry.user_id,
+            entry.action,
+            entry.resource_type,
+            entry.resource_id,
+            entry.before,
+            entry.after
+        )
+        .fetch_one(&mut *self.tx)
+        .await
+    }
+
+    // Create a new entity using a reference to a `Entity` struct
+    pub async fn create_entity(&mut self, new_entity: &Entity) -> Result<Entity> {
+        sqlx::query_as!(
+            Entity,
+            r#"
+            INSERT INTO entity (name, entity_type, website, twitter, description)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+            new_entity.name,
+            new_entity.entity_type,
+            new_entity.website,
+            new_entity.twitter,
+            new_entity.description
+        )
+        .fetch_one(&mut *self.tx)
+        .await
+    }
+
+    /// Replace the name and the metadata of an entity, `None` when there is none by its ID
+    pub async fn update_entity(&mut self, entity: &Entity) -> Result<Option<Entity>> {
+        sqlx::query_as!(
+            Entity,
+            r#"
+            UPDATE entity
+            SET name = $2, entity_type = $3, website = $4, twitter = $5, description = $6,
+                updated_at = current_timestamp
+            WHERE id = $1
+            RETURNING *
+            "#,
+            entity.id,
+            entity.name,
+            entity.entity_type,
+            entity.website,
+            entity.twitter,
+            entity.description
+        )
+        .fetch_optional(&mut *self.tx)
+        .await
+    }
+
+    /// Create a new account
+    pub async fn create_account(&mut self, new_account: &Account) -> Result<Account> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO account (address, network, entity_id)
+            VALUES ($1, $2, $3)
+            RETURNING id, address, network, entity_id, created_at, updated_at
+            "#,
+            new_account.address,
+            new_account.network,
+            new_account.entity_id
+        )
+        .fetch_one(&mut *self.tx)
+        .await;
+
+        match result {
+            Ok(row) => Ok(Account {
+                id: row.id,
+                address: row.address,
+                network: row.network,
+                entity_id: row.entity_id,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn update_account(&mut self, account: &Account) -> Result<Account, sqlx::Error> {
+        self.update_account_if_unchanged(account, None)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    /// Like [`PostgreDatabase::update_account`], but only when the account wasn't updated
+    /// since `expected_updated_at`. Returns `None` when it was, nothing is written then.
+    pub async fn update_account_if_unchanged(
+        &mut self,
+        account: &Account,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Account>, sqlx::Error> {
+        sqlx::query_as!(
+            Account,
+            r#"
+            UPDATE account SET entity_id = $1, updated_at = now()
+            WHERE id = $2 AND ($3::timestamptz IS NULL OR updated_at = $3)
+            RETURNING *
+            "#,
+            account.entity_id,
+            account.id,
+            expected_updated_at
+        )
+        .fetch_optional(&mut *self.tx)
+        .await
+    }
+
+    /// Create a new project
+    pub async fn create_project(&mut self, project: &Project) -> Result<Project, sqlx::Error> {
+        let result = sqlx::query_as!(
+            Project,
+            r#"
+            INSERT INTO project (
+                name, token, category, contract_address, num_chains, core_developers,
+                code_commits, total_value_locked, trading_volume, token_max_supply,
+                defi_llama_slug, cmc_id, github_repo, cloned_from, coingecko_id,
+                token_terminal_slug, volume_entry_function, swap_fee_bps,
+                excluded_supply_addresses, avatar_url
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+                $19, $20
+            )
+            RETURNING *
+            "#,
+            project.name,
+            project.token,
+            project.category,
+            project.contract_address,
+            project.num_chains,
+            project.core_developers,
+            project.code_commits,
+            project.total_value_locked,
+            project.trading_volume,
+            project.token_max_supply,
+            project.defi_llama_slug,
+            project.cmc_id,
+            project.github_repo,
+            project.cloned_from,
+            project.coingecko_id,
+            project.token_terminal_slug,
+            project.volume_entry_function,
+            project.swap_fee_bps,
+            &project.excluded_supply_addresses,
+            project.avatar_url,
+        )
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Update an existing project, recording each change of its numeric attributes made by
+    /// `changed_by` in the attribute history
+    pub async fn update_project(
+        &mut self,
+        project: &Project,
+        changed_by: &str,
+    ) -> Result<Project, sqlx::Error> {
+        self.update_project_if_unchanged(project, changed_by, None)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    /// Like [`PostgreDatabase::update_project`], but only when the project wasn't updated
+    /// since `expected_updated_at`. Returns `None` when it was, nothing is written then.
+    pub async fn update_project_if_unchanged(
+        &mut self,
+        project: &Project,
+        changed_by: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Project>, sqlx::Error> {
+        // A savepoint, so a guard failing leaves the rest of the transaction in place
+        let mut tx = self.tx.begin().await?;
+        let current = sqlx::query_as!(
+            Project,
+            "SELECT * FROM project WHERE id = $1 FOR UPDATE",
+            project.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let mut changes = Vec::new();
+        for (key, old_value, new_value) in current.changed_attributes(project) {
+            let change = sqlx::query_as!(
+                ProjectAttributeChange,
+                r#"
+                INSERT INTO project_attribute_history (project_id, key, old_value, new_value, changed_by)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING *
+                "#,
+                project.id,
+                key,
+                old_value,
+                new_value,
+                changed_by
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            changes.push(change);
+        }
+
+        let result = sqlx::query_as!(
+            Project,
+            r#"
+            UPDATE project
+            SET token = $1,
+                category = $2,
+                contract_address = $3,
+                num_chains = $4,
+                core_developers = $5,
+                code_commits = $6,
+                total_value_locked = $7,
+                token_max_supply = $8,
+                defi_llama_slug = $9,
+                cmc_id = $10,
+                github_repo = $11,
+                trading_volume = $12,
+                name = $13,
+                coingecko_id = $14,
+                price_usd_cg = $15,
+                market_cap_cg = $16,
+                volume_24h_cg = $17,
+                price_usd = $18,
+                token_terminal_slug = $19,
+                treasury_inflow_7d = $20,
+                treasury_outflow_7d = $21,
+                treasury_net_flow_7d = $22,
+                volume_entry_function = $23,
+                all_time_volume_usd = $24,
+                swap_fee_bps = $25,
+                all_time_fees_usd = $26,
+                last_processed_version = $27,
+                twick_7d = $28,
+                excluded_supply_addresses = $29,
+                avatar_url = $30,
+                volume_processed_version = $31,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $32 AND ($33::timestamptz IS NULL OR updated_at = $33)
+            RETURNING *
+            "#,
+            project.token,
+            project.category,
+            project.contract_address,
+            project.num_chains,
+            project.core_developers,
+            project.code_commits,
+            project.total_value_locked,
+            project.token_max_supply,
+            project.defi_llama_slug,
+            project.cmc_id,
+            project.github_repo,
+            project.trading_volume,
+            project.name,
+            project.coingecko_id,
+            project.price_usd_cg,
+            project.market_cap_cg,
+            project.volume_24h_cg,
+            project.price_usd,
+            project.token_terminal_slug,
+            project.treasury_inflow_7d,
+            project.treasury_outflow_7d,
+            project.treasury_net_flow_7d,
+            project.volume_entry_function,
+            project.all_time_volume_usd,
+            project.swap_fee_bps,
+            project.all_time_fees_usd,
+            project.last_processed_version,
+            project.twick_7d,
+            &project.excluded_supply_addresses,
+            project.avatar_url,
+            project.volume_processed_version,
+            project.id,
+            expected_updated_at
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        // Dropping the savepoint rolls back the attribute history
+        let Some(result) = result else {
+            return Ok(None);
+        };
+        tx.commit().await?;
+
+        self.changes.extend(changes);
+        Ok(Some(result))
+    }
+}
+
+#[tokio::test]
+async fn test_transaction_commits_or_rolls_back_together() {
+    let test_db = super::test_db::TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let project = db
+        .create_project(&Project {
+            name: Some("PancakeSwap".to_string()),
+            token: "0x1::cake::Cake".to_string(),
+            category: "DEX".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let mut changes = db.subscribe_attribute_changes();
+
+    // A dropped transaction keeps none of its writes
+    let mut tx = db.begin().await.unwrap();
+    let entity = tx
+        .create_entity(&Entity {
+            name: "Pancake".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    drop(tx);
+    assert!(db.get_entity_by_id(entity.id).await.unwrap().is_none());
+
+    // A guard failing only rolls back the update, changes are broadcast on commit
+    let mut tx = db.begin().await.unwrap();
+    let stale = tx
+        .update_project_if_unchanged(
+            &Project {
+                total_value_locked: Some(100.0),
+                ..project.clone()
+            },
+            "test",
+            Some(project.updated_at - chrono::Duration::seconds(1)),
+        )
+        .await
+        .unwrap();
+    assert!(stale.is_none());
+    tx.update_project(
+        &Project {
+            trading_volume: Some(50.0),
+            ..project.clone()
+        },
+        "test",
+    )
+    .await
+    .unwrap();
+    let entry = tx
+        .create_audit_entry(&AuditEntry {
+            action: AuditEntry::UPDATE.to_string(),
+            resource_type: AuditEntry::PROJECT.to_string(),
+            resource_id: project.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(changes.try_recv().is_err());
+    tx.commit().await.unwrap();
+
+    assert_eq!(changes.try_recv().unwrap().key, "trading_volume");
+    assert!(changes.try_recv().is_err());
+    let updated = db.get_project_by_id(project.id).await.unwrap().unwrap();
+    assert_eq!(
+        (updated.trading_volume, updated.total_value_locked),
+        (Some(50.0), None)
+    );
+    let entries = db
+        .list_audit_entries(Some(AuditEntry::PROJECT), Some(project.id), 10)
+        .await
+        .unwrap();
+    assert_eq!(entries, [entry]);
+}
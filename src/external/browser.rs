@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use headless_chrome::{Browser, LaunchOptionsBuilder, Tab};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn};
+
+use super::ExternalError;
+
+/// Time without any message from Chrome after which the connection to it is dropped, longer
+/// than the pause between two scrapes so the browser outlives them
+const BROWSER_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// One headless Chrome shared by every scrape, launched on first use and again once it
+/// stops answering. Each scrape is lent a tab of its own, kept open afterwards for the next
+/// one, so concurrent scrapes never navigate the same tab. At most `max_tabs` tabs are lent at
+/// once, the other scrapes wait in [`BrowserPool::wait_for_tab`].
+pub struct BrowserPool {
+    browser: Mutex<Option<Browser>>,
+    idle_tabs: Mutex<Vec<Arc<Tab>>>,
+    max_tabs: usize,
+    permits: Arc<Semaphore>,
+}
+
+impl BrowserPool {
+    pub fn new(max_tabs: usize) -> Self {
+        Self {
+            browser: Mutex::new(None),
+            idle_tabs: Mutex::new(Vec::new()),
+            max_tabs,
+            permits: Arc::new(Semaphore::new(max_tabs)),
+        }
+    }
+
+    /// Waits until a tab can be lent, the permit is to be held until it's given back
+    pub async fn wait_for_tab(&self) -> OwnedSemaphorePermit {
+        self.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("The tab semaphore is never closed")
+    }
+
+    /// A tab no other scrape is using, to be given back with [`BrowserPool::release`]. The
+    /// `_permit` of [`BrowserPool::wait_for_tab`] caps the tabs open at once. Blocks while
+    /// Chrome is launched.
+    pub fn acquire(&self, _permit: &OwnedSemaphorePermit) -> Result<Arc<Tab>, ExternalError> {
+        if let Some(tab) = self.lock_idle_tabs().pop() {
+            return Ok(tab);
+        }
+
+        let mut browser = self
+            .browser
+            .lock()
+            .expect("Browser lock must not be poisoned");
+        if let Some(running) = browser.as_ref() {
+            match running.new_tab() {
+                Ok(tab) => return Ok(tab),
+                Err(error) => warn!(%error, "Headless Chrome stopped answering, relaunching it"),
+            }
+        }
+        // The tabs of a dead browser are dead too
+        self.lock_idle_tabs().clear();
+        let launched = Self::launch()?;
+        let tab = launched.new_tab().map_err(|e| scrape_error(&e))?;
+        *browser = Some(launched);
+        Ok(tab)
+    }
+
+    /// Gives back a tab lent by [`BrowserPool::acquire`]. Tabs a scrape failed with are
+    /// closed instead of being lent again, in case they are stuck.
+    pub fn release(&self, tab: Arc<Tab>, healthy: bool) {
+        let mut idle_tabs = self.lock_idle_tabs();
+        if healthy && idle_tabs.len() < self.max_tabs {
+            idle_tabs.push(tab);
+            return;
+        }
+        drop(idle_tabs);
+        if let Err(error) = tab.close(true) {
+            warn!(%error, "Could not close a headless Chrome tab");
+        }
+    }
+
+    fn launch() -> Result<Browser, ExternalError> {
+        let options = LaunchOptionsBuilder::default()
+            .headless(true)
+            .idle_browser_timeout(BROWSER_IDLE_TIMEOUT)
+            .build()
+            .map_err(|e| scrape_error(&e))?;
+        let browser = Browser::new(options).map_err(|e| scrape_error(&e))?;
+        info!("Launched headless Chrome");
+        Ok(browser)
+    }
+
+    fn lock_idle_tabs(&self) -> std::sync::MutexGuard<'_, Vec<Arc<Tab>>> {
+        self.idle_tabs
+            .lock()
+            .expect("Idle tabs lock must not be poisoned")
+    }
+}
+
+fn scrape_error(error: &dyn std::fmt::Display) -> ExternalError {
+    ExternalError::scrape("headless Chrome", error)
+}
+
+#[tokio::test]
+async fn test_wait_for_tab() {
+    let pool = BrowserPool::new(1);
+    let permit = pool.wait_for_tab().await;
+    let waiting = tokio::time::timeout(Duration::from_millis(50), pool.wait_for_tab());
+    assert!(waiting.await.is_err());
+    drop(permit);
+    let waiting = tokio::time::timeout(Duration::from_millis(50), pool.wait_for_tab());
+    assert!(waiting.await.is_ok());
+}
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use moka::future::Cache;
+
+/// Entries outlive their TTL by this factor so they can still be served while refreshing
+const STALE_FACTOR: u32 = 10;
+
+/// Where a cached lookup was answered from, sent back to clients in `X-Cache`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheStatus {
+    Hit,
+    Stale,
+    Miss,
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Stale => "STALE",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Bypass => "BYPASS",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Entry<V> {
+    fetched_at: Instant,
+    value: V,
+}
+
+/// In-memory cache serving entries older than `ttl` while they are refreshed in the background
+#[derive(Clone)]
+pub struct StaleWhileRevalidate<V: Clone + Send + Sync + 'static> {
+    entries: Cache<String, Entry<V>>,
+    refreshing: Arc<Mutex<HashSet<String>>>,
+    ttl: Duration,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl<V: Clone + Send + Sync + 'static> StaleWhileRevalidate<V> {
+    pub fn new(ttl: Duration, max_capacity: u64) -> Self {
+        Self {
+            entries: Cache::builder()
+                .max_capacity(max_capacity)
+                .time_to_live(ttl * STALE_FACTOR)
+                .build(),
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+            ttl,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Cached value of `key`, along with whether it is past its TTL
+    pub async fn get(&self, key: &str) -> Option<(V, CacheStatus)> {
+        match self.entries.get(key).await {
+            Some(entry) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                let status = if entry.fetched_at.elapsed() < self.ttl {
+                    CacheStatus::Hit
+                } else {
+                    CacheStatus::Stale
+                };
+                Some((entry.value, status))
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub async fn insert(&self, key: &str, value: V) {
+        let entry = Entry {
+            fetched_at: Instant::now(),
+            value,
+        };
+        self.entries.insert(key.to_string(), entry).await;
+    }
+
+    /// Claims the refresh of `key` until the guard is dropped, `None` when another task is
+    /// already refreshing it. A refresh that panics gives its claim up all the same.
+    pub fn start_refresh(&self, key: &str) -> Option<RefreshGuard> {
+        let claimed = self.refreshing.lock().unwrap().insert(key.to_string());
+        claimed.then(|| RefreshGuard {
+            refreshing: self.refreshing.clone(),
+            key: key.to_string(),
+        })
+    }
+
+    /// Number of lookups answered from the cache, and of those that weren't
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Claim on the refresh of a key of a [`StaleWhileRevalidate`], given up when dropped
+pub struct RefreshGuard {
+    refreshing: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        // Still given up when another claim panicked while holding the lock
+        let mut refreshing = self
+            .refreshing
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        refreshing.remove(&self.key);
+    }
+}
+
+#[tokio::test]
+async fn test_cache_hit_miss_and_stale() {
+    let cache = StaleWhileRevalidate::new(Duration::from_millis(50), 16);
+
+    assert!(cache.get("pancake").await.is_none());
+    cache.insert("pancake", 1).await;
+    assert_eq!(cache.get("pancake").await, Some((1, CacheStatus::Hit)));
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(cache.get("pancake").await, Some((1, CacheStatus::Stale)));
+    assert_eq!(cache.stats(), (2, 1));
+}
+
+#[test]
+fn test_cache_single_refresh() {
+    let cache = StaleWhileRevalidate::<i32>::new(Duration::from_secs(30), 16);
+
+    let refresh = cache.start_refresh("pancake");
+    assert!(refresh.is_some());
+    assert!(cache.start_refresh("pancake").is_none());
+    drop(refresh);
+    assert!(cache.start_refresh("pancake").is_some());
+}
+
+#[tokio::test]
+async fn test_cache_refresh_given_up_on_panic() {
+    let cache = StaleWhileRevalidate::<i32>::new(Duration::from_secs(30), 16);
+
+    let refresh = cache.start_refresh("pancake").unwrap();
+    let task = tokio::spawn(async move {
+        let _refresh = refresh;
+        panic!("refresh failed");
+    });
+    assert!(task.await.is_err());
+    assert!(cache.start_refresh("pancake").is_some());
+}
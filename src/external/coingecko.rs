@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use reqwest::Url;
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::{instrument, warn};
+
+use super::{External, ExternalError};
+use crate::models::{AppError, CoinGeckoMarketData, PricePoint, Project};
+
+const COINGECKO_API: &str = "https://api.coingecko.com/api/v3";
+const COINGECKO_HOST: &str = "api.coingecko.com";
+
+/// Calls per minute the public API accepts without a key before answering 429
+pub const FREE_TIER_CALLS_PER_MINUTE: u32 = 30;
+
+/// Longest a call waits for its slot, past that it fails as rate limited rather than holding
+/// the request of the client for minutes
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(10);
+
+/// Spaces calls out evenly so a burst can't exceed a per minute quota. Waiting callers are
+/// given the next free slots in the order they asked.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn per_minute(calls: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / calls.max(1),
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Waits until a call may be made, at most `max_wait`. Returns how long until a slot is
+    /// free when that is later, nothing is booked then.
+    pub async fn acquire(&self, max_wait: Duration) -> Result<(), Duration> {
+        let now = Instant::now();
+        let slot = self.reserve(now, now + max_wait)?;
+        tokio::time::sleep_until(slot).await;
+        Ok(())
+    }
+
+    /// Books the first free slot from `now` on, returning when it starts, or how long until
+    /// then when it starts after `deadline`
+    fn reserve(&self, now: Instant, deadline: Instant) -> Result<Instant, Duration> {
+        let mut next = self
+            .next
+            .lock()
+            .expect("Rate limiter lock must not be poisoned");
+        let slot = next.map_or(now, |next| next.max(now));
+        if slot > deadline {
+            return Err(slot - now);
+        }
+        *next = Some(slot + self.interval);
+        Ok(slot)
+    }
+}
+
+impl External {
+    /// Latest USD market data of the CoinGecko coin `coingecko_id`, e.g. `aptos`
+    #[instrument(skip(self))]
+    pub async fn get_token_market_data(
+        &self,
+        coingecko_id: &str,
+    ) -> Result<CoinGeckoMarketData, ExternalError> {
+        self.wait_for_coingecko().await?;
+        let request = self
+            .client
+            .get(format!("{COINGECKO_API}/coins/markets"))
+            .query(&[("vs_currency", "usd"), ("ids", coingecko_id)]);
+        let response = Self::correlate(request).send().await?;
+        let res: Value = ExternalError::check_status(response)?.json().await?;
+
+        Self::parse_coingecko_market(&res).ok_or_else(|| {
+            ExternalError::NotFound(format!(
+                "CoinGecko has no USD market data for {coingecko_id}"
+            ))
+        })
+    }
+
+    /// Waits for the rate limiter of the CoinGecko calls, failing as rate limited when the
+    /// next slot is more than [`MAX_RATE_LIMIT_WAIT`] away
+    async fn wait_for_coingecko(&self) -> Result<(), ExternalError> {
+        self.coingecko_limiter
+            .acquire(MAX_RATE_LIMIT_WAIT)
+            .await
+            .map_err(|wait| ExternalError::RateLimited {
+                host: COINGECKO_HOST.to_string(),
+                retry_after: Some(wait.as_secs_f64().ceil() as u64),
+            })
+    }
+
+    /// Rejects a `coingecko_id` that doesn't look like the id of a CoinGecko coin, lowercase
+    /// letters, digits and dashes as in `pancakeswap-token`
+    pub fn check_coingecko_id(coingecko_id: &str) -> Result<(), AppError> {
+        let valid = !coingecko_id.is_empty()
+            && coingecko_id.len() <= 100
+            && coingecko_id.chars().all(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.')
+            });
+        if valid {
+            Ok(())
+        } else {
+            Err(AppError::Validation(format!(
+                "{coingecko_id} is not a CoinGecko coin id"
+            )))
+        }
+    }
+
+    /// Market data of the only coin `/coins/markets` was asked for
+    fn parse_coingecko_market(markets: &Value) -> Option<CoinGeckoMarketData> {
+        let market = markets.as_array()?.first()?;
+        Some(CoinGeckoMarketData {
+            price: market["current_price"].as_f64()?,
+            market_cap: market["market_cap"].as_f64().unwrap_or_default(),
+            fully_diluted_valuation: market["fully_diluted_valuation"].as_f64(),
+            volume_24h: market["total_volume"].as_f64().unwrap_or_default(),
+            price_change_24h: market["price_change_percentage_24h"]
+                .as_f64()
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Daily USD prices of the CoinGecko coin `coingecko_id` over the last `days` days,
+    /// oldest first
+    #[instrument(skip(self))]
+    pub async fn get_market_chart(
+        &self,
+        coingecko_id: &str,
+        days: i64,
+    ) -> Result<Vec<(NaiveDate, f64)>, ExternalError> {
+        self.wait_for_coingecko().await?;
+        // The id is escaped as one segment of the path, it can't point at another endpoint
+        let mut url = Url::parse(COINGECKO_API).expect("CoinGecko API URL must parse");
+        url.path_segments_mut()
+            .expect("CoinGecko API URL must have a path")
+            .extend(["coins", coingecko_id, "market_chart"]);
+        let request = self.client.get(url).query(&[
+            ("vs_currency", "usd"),
+            ("days", &days.to_string()),
+            ("interval", "daily"),
+        ]);
+        let response = Self::correlate(request).send().await?;
+        let res: Value = ExternalError::check_status(response)?.json().await?;
+
+        Self::parse_market_chart(&res).ok_or_else(|| ExternalError::parse(COINGECKO_API, "prices"))
+    }
+
+    /// Prices of a market chart down to the last one of each UTC day
+    fn parse_market_chart(chart: &Value) -> Option<Vec<(NaiveDate, f64)>> {
+        let mut daily = BTreeMap::new();
+        for point in chart["prices"].as_array()? {
+            let timestamp = DateTime::from_timestamp_millis(point[0].as_f64()? as i64)?;
+            daily.insert(timestamp.date_naive(), point[1].as_f64()?);
+        }
+        Some(daily.into_iter().collect())
+    }
+
+    /// USD price of the token of `project` on each of the last `days` days, today included,
+    /// oldest first. Days without a daily snapshot are filled from the CoinGecko market chart
+    /// when the project has a `coingecko_id`, which can only fail the call when there are no
+    /// snapshots to fall back on.
+    pub async fn get_price_history(
+        &self,
+        project: &Project,
+        days: i64,
+    ) -> Result<Vec<PricePoint>, ExternalError> {
+        let today = Utc::now().date_naive().and_time(NaiveTime::MIN).and_utc();
+        let since = today - chrono::Duration::days(days - 1);
+        let local = match &self.db {
+            Some(db) => {
+                db.get_daily_attribute_values(
+                    project.id,
+                    Project::PRICE_ATTRIBUTE,
+                    since,
+                    today + chrono::Duration::days(1),
+                )
+                .await?
+            }
+            None => Vec::new(),
+        };
+
+        let mut external = Vec::new();
+        if let (Some(coingecko_id), true) = (&project.coingecko_id, (local.len() as i64) < days) {
+            match self.get_market_chart(coingecko_id, days).await {
+                Ok(prices) => external = prices,
+                Err(error) if !local.is_empty() => {
+                    warn!(project = project.id, %error, "Could not fill a price history from CoinGecko");
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        external.retain(|(date, _)| *date >= since.date_naive());
+        Ok(PricePoint::merge(&local, &external))
+    }
+}
+
+#[test]
+fn test_parse_coingecko_market() {
+    let markets = serde_json::json!([{
+        "id": "aptos",
+        "symbol": "apt",
+        "current_price": 8.42,
+        "market_cap": 4012345678.0,
+        "fully_diluted_valuation": null,
+        "total_volume": 98765432.1,
+        "price_change_percentage_24h": -3.25
+    }]);
+    let data = External::parse_coingecko_market(&markets).unwrap();
+    assert_eq!(data.price, 8.42);
+    assert_eq!(data.market_cap, 4012345678.0);
+    assert_eq!(data.fully_diluted_valuation, None);
+    assert_eq!(data.volume_24h, 98765432.1);
+    assert_eq!(data.price_change_24h, -3.25);
+
+    // Unknown ids give an empty list
+    assert!(External::parse_coingecko_market(&serde_json::json!([])).is_none());
+}
+
+#[test]
+fn test_rate_limiter_spaces_calls() {
+    let limiter = RateLimiter::per_minute(30);
+    let start = Instant::now();
+    let within = |now: Instant| now + Duration::from_secs(5);
+    assert_eq!(limiter.reserve(start, within(start)), Ok(start));
+    assert_eq!(
+        limiter.reserve(start, within(start)),
+        Ok(start + Duration::from_secs(2))
+    );
+    assert_eq!(
+        limiter.reserve(start, within(start)),
+        Ok(start + Duration::from_secs(4))
+    );
+    // A slot past the deadline isn't booked
+    assert_eq!(
+        limiter.reserve(start, within(start)),
+        Err(Duration::from_secs(6))
+    );
+    assert_eq!(
+        limiter.reserve(start, start + Duration::from_secs(6)),
+        Ok(start + Duration::from_secs(6))
+    );
+
+    // Slots left unused aren't saved up for a later burst
+    let later = start + Duration::from_secs(60);
+    assert_eq!(limiter.reserve(later, within(later)), Ok(later));
+    assert_eq!(
+        limiter.reserve(later, within(later)),
+        Ok(later + Duration::from_secs(2))
+    );
+}
+
+#[test]
+fn test_is_coingecko_id() {
+    for id in ["aptos", "pancakeswap-token", "usd-coin", "wrapped-bitcoin"] {
+        assert!(External::check_coingecko_id(id).is_ok(), "{id}");
+    }
+    for id in [
+        "",
+        "Aptos",
+        "aptos/../markets",
+        "aptos?x=1",
+        "aptos coin",
+        "aptos#",
+    ] {
+        assert!(External::check_coingecko_id(id).is_err(), "{id}");
+    }
+}
+
+#[test]
+fn test_parse_market_chart() {
+    // 2026-10-12 00:00, 2026-10-12 18:00 and 2026-10-13 00:00 UTC
+    let chart = serde_json::json!({
+        "prices": [[1791763200000u64, 8.0], [1791828000000u64, 8.4], [1791849600000u64, 8.6]],
+        "market_caps": [],
+        "total_volumes": []
+    });
+    let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+    assert_eq!(
+        External::parse_market_chart(&chart),
+        Some(vec![(day(12), 8.4), (day(13), 8.6)])
+    );
+    assert_eq!(External::parse_market_chart(&serde_json::json!({})), None);
+}
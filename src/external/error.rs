@@ -0,0 +1,231 @@
+use axum::http::{header::RETRY_AFTER, StatusCode};
+use reqwest::Response;
+use thiserror::Error;
+
+use crate::models::AppError;
+
+/// Every way a call to a third-party API can fail, keeping the host and status that caused it
+#[derive(Debug, Error)]
+pub enum ExternalError {
+    /// No answer within the client timeout
+    #[error("{host} did not answer in time")]
+    Timeout { host: String },
+    /// The connection couldn't be established or broke before an answer
+    #[error("Failed to reach {host}")]
+    Connect { host: String },
+    /// The API answered with an error status
+    #[error("{host} answered {status}")]
+    Status { host: String, status: StatusCode },
+    /// The API answered 429, `retry_after` is in seconds when it said how long to wait
+    #[error("{host} is rate limiting our requests")]
+    RateLimited {
+        host: String,
+        retry_after: Option<u64>,
+    },
+    /// The body of the answer couldn't be decoded
+    #[error("Invalid response from {host}: {message}")]
+    InvalidResponse { host: String, message: String },
+    /// The GraphQL endpoint rejected the query
+    #[error("{host} rejected the query: {}", messages.join("; "))]
+    GraphQl { host: String, messages: Vec<String> },
+    /// The answer was decoded but `field` is missing or doesn't have the expected shape
+    #[error("Unexpected `{field}` in the response of {host}")]
+    Parse { host: String, field: String },
+    #[error("{0}")]
+    NotFound(String),
+    /// The API is still computing the answer, the same call will succeed later
+    #[error("{0}")]
+    Pending(String),
+    /// The API can't be called with the current configuration
+    #[error("{0}")]
+    NotConfigured(String),
+    /// The data was fetched but can't be processed
+    #[error("{0}")]
+    Unprocessable(String),
+    /// The headless browser failed to load or read a page
+    #[error("Failed to scrape {page}: {message}")]
+    Scrape { page: String, message: String },
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    /// A spawned request task panicked or was cancelled
+    #[error("Request task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl ExternalError {
+    /// Passes successful responses through, turning error statuses into an `ExternalError`
+    pub fn check_status(response: Response) -> Result<Response, ExternalError> {
+        let status = response.status();
+        let host = host_of(response.url());
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok());
+            return Err(ExternalError::RateLimited { host, retry_after });
+        }
+        if !status.is_success() {
+            return Err(ExternalError::Status { host, status });
+        }
+        Ok(response)
+    }
+
+    /// `field` of the response of the API at `base_url` isn't what we expected
+    pub fn parse(base_url: &str, field: &str) -> Self {
+        let host = reqwest::Url::parse(base_url)
+            .map(|url| host_of(&url))
+            .unwrap_or_else(|_| base_url.to_string());
+        ExternalError::Parse {
+            host,
+            field: field.to_string(),
+        }
+    }
+
+    pub fn scrape(page: &str, error: &dyn std::fmt::Display) -> Self {
+        ExternalError::Scrape {
+            page: page.to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    /// Host of the API that failed, when the failure comes from a call to it
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            ExternalError::Timeout { host }
+            | ExternalError::Connect { host }
+            | ExternalError::Status { host, .. }
+            | ExternalError::RateLimited { host, .. }
+            | ExternalError::InvalidResponse { host, .. }
+            | ExternalError::GraphQl { host, .. }
+            | ExternalError::Parse { host, .. } => Some(host),
+            _ => None,
+        }
+    }
+
+    /// Name of the API the host belongs to, as shown to clients
+    pub fn service(&self) -> &'static str {
+        match self.host() {
+            Some("api.mainnet.aptoslabs.com") => "Aptos fullnode",
+            Some("indexer.mainnet.aptoslabs.com") => "Aptos indexer",
+            Some("api.github.com") => "GitHub",
+            Some("pro-api.coinmarketcap.com") => "CoinMarketCap",
+            Some("api.llama.fi") => "DeFiLlama",
+            Some("api.coingecko.com") => "CoinGecko",
+            _ if matches!(self, ExternalError::Scrape { .. }) => "TokenTerminal",
+            _ => "External API",
+        }
+    }
+}
+
+fn host_of(url: &reqwest::Url) -> String {
+    url.host_str().unwrap_or_default().to_string()
+}
+
+impl From<reqwest::Error> for ExternalError {
+    fn from(error: reqwest::Error) -> Self {
+        let host = error.url().map(host_of).unwrap_or_default();
+
+        if error.is_timeout() {
+            ExternalError::Timeout { host }
+        } else if let Some(status) = error.status() {
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                ExternalError::RateLimited {
+                    host,
+                    retry_after: None,
+                }
+            } else {
+                ExternalError::Status { host, status }
+            }
+        } else if error.is_decode() {
+            ExternalError::InvalidResponse {
+                host,
+                message: error.to_string(),
+            }
+        } else {
+            ExternalError::Connect { host }
+        }
+    }
+}
+
+impl From<ExternalError> for AppError {
+    fn from(error: ExternalError) -> Self {
+        let service = error.service();
+        let message = error.to_string();
+
+        match error {
+            ExternalError::Timeout { .. } => AppError::UpstreamTimeout { service, message },
+            ExternalError::RateLimited { retry_after, .. } => AppError::RateLimited {
+                service,
+                message,
+                retry_after,
+            },
+            ExternalError::NotFound(_) => AppError::NotFound(message),
+            ExternalError::Pending(_) | ExternalError::NotConfigured(_) => {
+                AppError::Unavailable(message)
+            }
+            ExternalError::Unprocessable(_) => AppError::Unprocessable(message),
+            ExternalError::Database(error) => AppError::Database(error),
+            ExternalError::Task(_) => AppError::Internal(message),
+            ExternalError::Connect { .. }
+            | ExternalError::Status { .. }
+            | ExternalError::InvalidResponse { .. }
+            | ExternalError::GraphQl { .. }
+            | ExternalError::Parse { .. }
+            | ExternalError::Scrape { .. } => AppError::Upstream { service, message },
+        }
+    }
+}
+
+#[test]
+fn test_external_error_to_app_error() {
+    let error = AppError::from(ExternalError::Timeout {
+        host: "indexer.mainnet.aptoslabs.com".to_string(),
+    });
+    assert_eq!(error.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(error.code(), "UPSTREAM_TIMEOUT");
+
+    let error = AppError::from(ExternalError::Status {
+        host: "api.mainnet.aptoslabs.com".to_string(),
+        status: StatusCode::SERVICE_UNAVAILABLE,
+    });
+    assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(
+        error.to_string(),
+        "api.mainnet.aptoslabs.com answered 503 Service Unavailable"
+    );
+
+    let error = AppError::from(ExternalError::RateLimited {
+        host: "api.github.com".to_string(),
+        retry_after: Some(30),
+    });
+    assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(matches!(
+        error,
+        AppError::RateLimited {
+            service: "GitHub",
+            retry_after: Some(30),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_external_error_messages() {
+    let error = ExternalError::GraphQl {
+        host: "api.mainnet.aptoslabs.com".to_string(),
+        messages: vec!["field not found".to_string(), "bad offset".to_string()],
+    };
+    assert_eq!(
+        error.to_string(),
+        "api.mainnet.aptoslabs.com rejected the query: field not found; bad offset"
+    );
+
+    let error = ExternalError::parse("https://api.llama.fi", "chainTvls");
+    assert_eq!(error.service(), "DeFiLlama");
+    assert_eq!(AppError::from(error).status(), StatusCode::BAD_GATEWAY);
+
+    let error = AppError::from(ExternalError::NotFound("No USD price".to_string()));
+    assert_eq!(error.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+
+use futures::future::join_all;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use tracing::{debug, info, instrument};
+
+use super::{
+    like_prefix, pairs::swap_event_pair, parse_raw_amount, to_f64, usd_value, External,
+    ExternalError, FULLNODE_API,
+};
+
+/// Swap events fetched per page
+const FEE_PAGE_SIZE: usize = 100;
+
+/// Swap events summed between two progress logs of [`External::get_all_time_fees`], also
+/// the size of the batches the fee task stores its progress after
+pub const FEE_PROGRESS_EVENTS: usize = 10_000;
+
+/// Fees of the swap events following a transaction version
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FeeBatch {
+    pub fees_usd: f64,
+    /// Version of the last transaction whose events were summed, `None` when there were none
+    pub last_processed_version: Option<i64>,
+    pub events: usize,
+}
+
+impl External {
+    /// USD fees earned on every swap of the pools published at `address`, with the fee taken
+    /// from the input amounts at `fee_numerator / fee_denominator`. Each coin is valued at its
+    /// current price, coins without one are left out.
+    ///
+    /// Pages through every swap event of the pools, which takes minutes on busy ones, so it's
+    /// meant for background tasks only.
+    #[instrument(skip(self))]
+    pub async fn get_all_time_fees(
+        &self,
+        address: &str,
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Result<f64, ExternalError> {
+        let mut fees_usd = 0.0;
+        let mut events = 0;
+        let mut after_version = None;
+        loop {
+            let batch = self
+                .get_fees_after_version(
+                    address,
+                    fee_numerator,
+                    fee_denominator,
+                    after_version,
+                    FEE_PROGRESS_EVENTS,
+                )
+                .await?;
+            if batch.events == 0 {
+                break;
+            }
+            fees_usd += batch.fees_usd;
+            events += batch.events;
+            after_version = batch.last_processed_version;
+            info!(events, fees_usd, "Still summing the all time fees");
+        }
+        Ok(fees_usd)
+    }
+
+    /// Fees of at least `max_events` swap events of the pools published at `address` after
+    /// the transaction `after_version`, oldest first, or of the remaining ones when there are
+    /// fewer. The events of a transaction are always summed together, so the batches can be
+    /// chained through their `last_processed_version`.
+    #[instrument(skip(self))]
+    pub async fn get_fees_after_version(
+        &self,
+        address: &str,
+        fee_numerator: u64,
+        fee_denominator: u64,
+        after_version: Option<i64>,
+        max_events: usize,
+    ) -> Result<FeeBatch, ExternalError> {
+        let event_type = format!("{address}::swap::SwapEvent");
+        let pattern = like_prefix(&event_type);
+        let mut amounts: BTreeMap<String, u128> = BTreeMap::new();
+        let mut batch = FeeBatch {
+            last_processed_version: after_version,
+            ..Default::default()
+        };
+        // Event index of the last event summed when a transaction had more events than a page
+        // holds, the batch doesn't end before the rest of them are summed
+        let mut partial_index = None;
+        while batch.events < max_events || partial_index.is_some() {
+            let cursor_filter = match (batch.last_processed_version, partial_index) {
+                (Some(version), Some(index)) => format!(
+                    ", _or: [{{transaction_version: {{_gt: {version}}}}}, \
+                     {{transaction_version: {{_eq: {version}}}, event_index: {{_gt: {index}}}}}]"
+                ),
+                (Some(version), None) => format!(", transaction_version: {{_gt: {version}}}"),
+                (None, _) => String::new(),
+            };
+            let query = format!(
+                r#"
+                query SwapEvents {{
+                    events(
+                        limit: {FEE_PAGE_SIZE}
+                        where: {{indexed_type: {{_like: {pattern}}}{cursor_filter}}}
+                        order_by: [{{transaction_version: asc}}, {{event_index: asc}}]
+                    ) {{
+                        data
+                        event_index
+                        indexed_type
+                        transaction_version
+                    }}
+                }}
+                "#
+            );
+            let response = Self::post_graphql(&self.client, &query).await?;
+            let page = Self::parse_swap_events_page(&response, &event_type, FEE_PAGE_SIZE)
+                .ok_or_else(|| ExternalError::parse(FULLNODE_API, "events"))?;
+            let Some(last_version) = page.last_version else {
+                break;
+            };
+            for (coin_type, amount) in page.amounts_in {
+                let total = amounts.entry(coin_type).or_default();
+                *total = total.saturating_add(amount);
+            }
+            batch.events += page.events;
+            batch.last_processed_version = Some(last_version);
+            partial_index = page.partial_index;
+        }
+
+        let stablecoins = self.stablecoins().await;
+        let prices = join_all(amounts.keys().map(|coin_type| {
+            Self::get_price_and_decimals(
+                self.client.clone(),
+                self.db.clone(),
+                stablecoins.clone(),
+                coin_type,
+            )
+        }))
+        .await;
+        let fee_share = Decimal::from(fee_numerator)
+            .checked_div(Decimal::from(fee_denominator))
+            .unwrap_or_default();
+        let mut fees_usd = Decimal::ZERO;
+        for ((coin_type, amount), price) in amounts.into_iter().zip(prices) {
+            let Some((price, decimals)) = price else {
+                debug!(coin_type, "Left a coin without a price out of the fees");
+                continue;
+            };
+            fees_usd = fees_usd
+                .saturating_add(usd_value(price, amount, decimals).saturating_mul(fee_share));
+        }
+        batch.fees_usd = to_f64(fees_usd);
+        Ok(batch)
+    }
+
+    /// Input amounts of each coin in a page of swap events of `event_type`. When the page is
+    /// full, the events of its last transaction are left to the next page, which may hold
+    /// more of them. When the page holds no other transaction, its `partial_index` tells
+    /// where the next page goes on within the transaction.
+    fn parse_swap_events_page(
+        response: &Value,
+        event_type: &str,
+        page_size: usize,
+    ) -> Option<SwapEventsPage> {
+        let integer = |value: &Value| value.as_i64().or_else(|| value.as_str()?.parse().ok());
+        let mut events: Vec<(i64, i64, &Value)> = response["data"]["events"]
+            .as_array()?
+            .iter()
+            .map(|event| {
+                let version = integer(&event["transaction_version"])?;
+                let index = integer(&event["event_index"])?;
+                Some((version, index, event))
+            })
+            .collect::<Option<_>>()?;
+        let mut partial_index = None;
+        if events.len() >= page_size {
+            let (last_version, last_index, _) = *events.last()?;
+            let whole = events
+                .iter()
+                .position(|(version, _, _)| *version == last_version)
+                .unwrap_or(0);
+            if whole > 0 {
+                events.truncate(whole);
+            } else {
+                partial_index = Some(last_index);
+            }
+        }
+
+        let mut page = SwapEventsPage {
+            last_version: events.last().map(|(version, _, _)| *version),
+            partial_index,
+            events: events.len(),
+            ..Default::default()
+        };
+        for (_, _, event) in events {
+            let Some((coin_x, coin_y)) = event["indexed_type"]
+                .as_str()
+                .and_then(|indexed_type| swap_event_pair(indexed_type, event_type))
+            else {
+                continue;
+            };
+            for (coin_type, amount) in [
+                (coin_x, &event["data"]["amount_x_in"]),
+                (coin_y, &event["data"]["amount_y_in"]),
+            ] {
+                match parse_raw_amount(amount) {
+                    Some(amount) if amount > 0 => {
+                        let total = page.amounts_in.entry(coin_type).or_default();
+                        *total = total.saturating_add(amount);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Some(page)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SwapEventsPage {
+    amounts_in: BTreeMap<String, u128>,
+    last_version: Option<i64>,
+    /// Event index of the last event when the page ended within the transaction `last_version`
+    partial_index: Option<i64>,
+    events: usize,
+}
+
+#[test]
+fn test_parse_swap_events_page() {
+    const EVENT: &str = "0xc7ef::swap::SwapEvent";
+    let response = serde_json::json!({"data": {"events": [
+        {
+            "transaction_version": 10,
+            "event_index": 0,
+            "indexed_type": "0xc7ef::swap::SwapEvent<0x1::aptos_coin::AptosCoin, 0x1::usdc::USDC>",
+            "data": {"amount_x_in": "150000000", "amount_y_in": "0"}
+        },
+        {
+            "transaction_version": "11",
+            "event_index": "3",
+            "indexed_type": "0xc7ef::swap::SwapEvent<0x1::aptos_coin::AptosCoin, 0x1::usdc::USDC>",
+            "data": {"amount_x_in": "0", "amount_y_in": "2000000"}
+        },
+        {
+            "transaction_version": 12,
+            "event_index": 1,
+            "indexed_type": "0xc7ef::swap::SwapEvent<0x1::aptos_coin::AptosCoin,0x1::usdc::USDC>",
+            "data": {"amount_x_in": "50000000", "amount_y_in": "0"}
+        },
+    ]}});
+
+    let page = External::parse_swap_events_page(&response, EVENT, 100).unwrap();
+    assert_eq!(page.events, 3);
+    assert_eq!(page.last_version, Some(12));
+    assert_eq!(page.amounts_in["0x1::aptos_coin::AptosCoin"], 200000000);
+    assert_eq!(page.amounts_in["0x1::usdc::USDC"], 2000000);
+
+    // A full page leaves its last transaction to the next page
+    let page = External::parse_swap_events_page(&response, EVENT, 3).unwrap();
+    assert_eq!(page.events, 2);
+    assert_eq!(page.last_version, Some(11));
+    assert_eq!(page.amounts_in["0x1::aptos_coin::AptosCoin"], 150000000);
+    assert_eq!(page.partial_index, None);
+
+    // A full page of one transaction goes on within it
+    let event = serde_json::json!({
+        "transaction_version": 20,
+        "event_index": 4,
+        "indexed_type": "0xc7ef::swap::SwapEvent<0x1::aptos_coin::AptosCoin, 0x1::usdc::USDC>",
+        "data": {"amount_x_in": "1", "amount_y_in": "0"}
+    });
+    let mut next = event.clone();
+    next["event_index"] = 7.into();
+    let single = serde_json::json!({"data": {"events": [event, next]}});
+    let page = External::parse_swap_events_page(&single, EVENT, 2).unwrap();
+    assert_eq!(page.events, 2);
+    assert_eq!((page.last_version, page.partial_index), (Some(20), Some(7)));
+
+    let empty = serde_json::json!({"data": {"events": []}});
+    let page = External::parse_swap_events_page(&empty, EVENT, 100).unwrap();
+    assert_eq!((page.events, page.last_version), (0, None));
+
+    assert!(
+        External::parse_swap_events_page(&serde_json::json!({"errors": []}), EVENT, 100).is_none()
+    );
+}
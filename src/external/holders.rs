@@ -0,0 +1,209 @@
+use chrono::{Duration, Utc};
+use futures::future::join_all;
+use serde_json::Value;
+use tracing::{info, instrument};
+
+use super::{AptosClient, External, ExternalError, FULLNODE_API};
+use crate::{
+    database::PostgreDatabase,
+    models::{HolderDiff, HolderSnapshot},
+};
+
+/// Holders fetched per call, the most the indexer returns
+const HOLDER_PAGE_SIZE: u64 = 100;
+
+/// Snapshots before the compared one that returning holders are looked up in
+const RETURNING_HOLDER_SNAPSHOTS: i64 = 30;
+
+impl External {
+    /// Fetches every holder of `token` and stores today's snapshot of them, which keeps only a
+    /// sample of the addresses of tokens with many holders
+    #[instrument(skip(self))]
+    pub async fn snapshot_token_holders(&self, token: &str) -> Result<(), ExternalError> {
+        let db = self.holder_snapshots()?;
+        let holders = self.get_token_holders(token).await?;
+        let snapshot = HolderSnapshot::take(token, Utc::now().date_naive(), holders);
+        db.upsert_holder_snapshot(&snapshot).await?;
+        Ok(())
+    }
+
+    /// Holders `token` gained and lost between its latest snapshot and the one taken
+    /// `days_ago` days before it, at least one, or the latest before that. `None` when no
+    /// snapshot of the token was taken yet. The counts of tokens with many holders are
+    /// estimated from the samples of the snapshots.
+    #[instrument(skip(self))]
+    pub async fn get_holder_diff(
+        &self,
+        token: &str,
+        days_ago: u64,
+    ) -> Result<Option<HolderDiff>, ExternalError> {
+        let db = self.holder_snapshots()?;
+        let today = Utc::now().date_naive();
+        let Some(current) = db.list_holder_snapshots(token, today, 1).await?.pop() else {
+            return Ok(None);
+        };
+
+        let since = current.date - Duration::days(days_ago.max(1) as i64);
+        let mut snapshots = db
+            .list_holder_snapshots(token, since, RETURNING_HOLDER_SNAPSHOTS + 1)
+            .await?
+            .into_iter();
+        let previous = snapshots.next();
+        let older: Vec<_> = snapshots.collect();
+        Ok(Some(HolderDiff::between(
+            &current,
+            previous.as_ref(),
+            &older,
+        )))
+    }
+
+    fn holder_snapshots(&self) -> Result<&PostgreDatabase, ExternalError> {
+        self.db.as_ref().ok_or_else(|| {
+            ExternalError::NotConfigured("The holder snapshots need a database".to_string())
+        })
+    }
+
+    /// Addresses holding some `token`, fetched a page at a time from offset 0 through the
+    /// number of holders
+    #[instrument(skip(self))]
+    pub async fn get_token_holders(&self, token: &str) -> Result<Vec<String>, ExternalError> {
+        let count = self.get_number_of_token_holders(token).await?;
+        let pages: Vec<_> = (0..count.div_ceil(HOLDER_PAGE_SIZE))
+            .map(|page| {
+                let client = self.client.clone();
+                let token = token.to_string();
+                self.spawn_limited(async move {
+                    Self::query_holder_addresses(&client, &token, page * HOLDER_PAGE_SIZE).await
+                })
+            })
+            .collect();
+
+        let mut holders = Vec::with_capacity(count as usize);
+        for page in join_all(pages).await {
+            holders.extend(page??);
+        }
+        info!(holders = holders.len(), "Fetched the token holders");
+        Ok(holders)
+    }
+
+    /// Page of the holders of `token` starting at `offset`, ordered by address so the pages
+    /// don't overlap
+    async fn query_holder_addresses(
+        client: &AptosClient,
+        token: &str,
+        offset: u64,
+    ) -> Result<Vec<String>, ExternalError> {
+        let query = format!(
+            r#"
+            query HolderAddresses {{
+                current_coin_balances(
+                    offset: {offset}
+                    limit: {HOLDER_PAGE_SIZE}
+                    order_by: {{owner_address: asc}}
+                    where: {{coin_type: {{_eq: "{token}"}}, amount: {{_gt: "0"}}}}
+                ) {{
+                    owner_address
+                }}
+            }}
+            "#
+        );
+        let response = Self::post_graphql(client, &query).await?;
+        Self::parse_holder_addresses(&response)
+            .ok_or_else(|| ExternalError::parse(FULLNODE_API, "current_coin_balances"))
+    }
+
+    fn parse_holder_addresses(response: &Value) -> Option<Vec<String>> {
+        response["data"]["current_coin_balances"]
+            .as_array()?
+            .iter()
+            .map(|balance| balance["owner_address"].as_str().map(str::to_string))
+            .collect()
+    }
+}
+
+#[test]
+fn test_parse_holder_addresses() {
+    let response = serde_json::json!({"data": {"current_coin_balances": [
+        {"owner_address": "0xa"},
+        {"owner_address": "0xb"}
+    ]}});
+    assert_eq!(
+        External::parse_holder_addresses(&response),
+        Some(vec!["0xa".to_string(), "0xb".to_string()])
+    );
+    let malformed = serde_json::json!({"data": {"current_coin_balances": [{"amount": "1"}]}});
+    assert_eq!(External::parse_holder_addresses(&malformed), None);
+}
+
+#[tokio::test]
+async fn test_get_holder_diff() {
+    const MEME: &str = "0xcafe::meme::MEME";
+    let test_db = crate::database::test_db::TestDatabase::migrated().await;
+    let external = super::mock::MockAptos::new()
+        .graphql(
+            "HolderAddresses",
+            serde_json::json!({"data": {"current_coin_balances": [
+                {"owner_address": "0xa"},
+                {"owner_address": "0xb"},
+                {"owner_address": "0xe"}
+            ]}}),
+        )
+        // Counting the holders stops at the first page that isn't full
+        .graphql(
+            "offset: 1\n",
+            serde_json::json!({"data": {"current_coin_balances": [
+                {"amount": "5"},
+                {"amount": "7"}
+            ]}}),
+        )
+        .start()
+        .await;
+    let external = External {
+        db: Some(test_db.db.clone()),
+        ..external
+    };
+
+    let day = |days_ago: i64| Utc::now().date_naive() - Duration::days(days_ago);
+    let snapshot = |days_ago: i64, holders: &[&str]| {
+        HolderSnapshot::take(
+            MEME,
+            day(days_ago),
+            holders.iter().map(|a| a.to_string()).collect(),
+        )
+    };
+    let db = &test_db.db;
+    assert_eq!(external.get_holder_diff(MEME, 7).await.unwrap(), None);
+    db.upsert_holder_snapshot(&snapshot(20, &["0xb", "0xc"]))
+        .await
+        .unwrap();
+    db.upsert_holder_snapshot(&snapshot(8, &["0xa", "0xd"]))
+        .await
+        .unwrap();
+
+    // Compared from the latest snapshot, a week before it is the snapshot of day 20
+    let diff = external.get_holder_diff(MEME, 7).await.unwrap();
+    assert_eq!(diff.map(|diff| diff.compared_to), Some(Some(day(20))));
+
+    external.snapshot_token_holders(MEME).await.unwrap();
+    let stored = db.list_holder_snapshots(MEME, day(0), 1).await.unwrap();
+    assert_eq!(stored[0].sample, ["0xa", "0xb", "0xe"]);
+    let diff = external.get_holder_diff(MEME, 7).await.unwrap();
+    assert_eq!(
+        diff,
+        Some(HolderDiff {
+            new_holders: 2,
+            lost_holders: 1,
+            returning_holders: 1,
+            holders: 3,
+            date: day(0),
+            compared_to: Some(day(8)),
+            exact: true,
+        })
+    );
+
+    let without_db = super::mock::MockAptos::new().start().await;
+    assert!(matches!(
+        without_db.get_holder_diff(MEME, 7).await,
+        Err(ExternalError::NotConfigured(_))
+    ));
+}
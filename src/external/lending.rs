@@ -0,0 +1,136 @@
+use std::{sync::Arc, time::Duration};
+
+use serde_json::Value;
+use tracing::instrument;
+
+use super::{External, ExternalError, DEFI_LLAMA_API};
+use crate::models::LendingStats;
+
+const DEFI_LLAMA_YIELDS_API: &str = "https://yields.llama.fi";
+
+/// Time the yield pools are kept for. DeFiLlama lists the pools of every chain in one large
+/// response, updated about hourly, so it isn't fetched again for each lending project.
+pub(super) const YIELD_POOLS_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Pools of Aptos among the DeFiLlama yields, kept for [`YIELD_POOLS_TTL`]
+pub(super) fn yield_pools_cache() -> moka::future::Cache<(), Arc<Vec<Value>>> {
+    moka::future::Cache::builder()
+        .max_capacity(1)
+        .time_to_live(YIELD_POOLS_TTL)
+        .build()
+}
+
+impl External {
+    /// Supplied and borrowed value of the lending protocol `protocol_slug` on Aptos as
+    /// DeFiLlama reports it, along with the rates of its pools
+    #[instrument(skip(self))]
+    pub async fn get_lending_stats(
+        &self,
+        protocol_slug: &str,
+    ) -> Result<LendingStats, ExternalError> {
+        let protocol_url = format!("{DEFI_LLAMA_API}/protocol/{protocol_slug}");
+        let (protocol, pools) = tokio::try_join!(
+            Self::get_json(&self.client, &protocol_url),
+            self.aptos_yield_pools(),
+        )?;
+
+        let (total_supplied_usd, total_borrowed_usd) = Self::parse_lending_totals(&protocol)
+            .ok_or_else(|| {
+                ExternalError::NotFound(format!(
+                    "DeFiLlama has no Aptos lending market for {protocol_slug}"
+                ))
+            })?;
+        let (supply_apy, borrow_apy) = Self::parse_lending_apys(&pools, protocol_slug);
+        Ok(LendingStats {
+            total_supplied_usd,
+            total_borrowed_usd,
+            supply_apy,
+            borrow_apy,
+            bad_debt_usd: None,
+        })
+    }
+
+    /// Aptos pools of the DeFiLlama yields, fetched again once [`YIELD_POOLS_TTL`] is over.
+    /// Failures aren't kept.
+    async fn aptos_yield_pools(&self) -> Result<Arc<Vec<Value>>, ExternalError> {
+        if let Some(pools) = self.yield_pools_cache.get(&()).await {
+            return Ok(pools);
+        }
+        let pools_url = format!("{DEFI_LLAMA_YIELDS_API}/pools");
+        let response = Self::get_json(&self.client, &pools_url).await?;
+        let pools = Arc::new(Self::parse_aptos_pools(response));
+        self.yield_pools_cache.insert((), pools.clone()).await;
+        Ok(pools)
+    }
+
+    /// Pools on Aptos of a yields response
+    fn parse_aptos_pools(mut response: Value) -> Vec<Value> {
+        match response["data"].take() {
+            Value::Array(pools) => pools
+                .into_iter()
+                .filter(|pool| pool["chain"].as_str() == Some("Aptos"))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The TVL of a lending protocol leaves out what is borrowed, which DeFiLlama lists as
+    /// its own `Aptos-borrowed` chain
+    fn parse_lending_totals(protocol: &Value) -> Option<(f64, f64)> {
+        let chains = &protocol["currentChainTvls"];
+        let available = chains["Aptos"].as_f64()?;
+        let borrowed = chains["Aptos-borrowed"].as_f64().unwrap_or_default();
+        Some((available + borrowed, borrowed))
+    }
+
+    /// Supply rates of the Aptos `pools` of `protocol_slug` weighted by their TVL, and borrow
+    /// rates weighted by what is borrowed from them
+    fn parse_lending_apys(pools: &[Value], protocol_slug: &str) -> (Option<f64>, Option<f64>) {
+        let pools: Vec<&Value> = pools
+            .iter()
+            .filter(|pool| pool["project"].as_str() == Some(protocol_slug))
+            .collect();
+        let weighted = |rate: &str, weight: &str| {
+            let (sum, total) = pools
+                .iter()
+                .filter_map(|pool| Some((pool[rate].as_f64()?, pool[weight].as_f64()?)))
+                .fold((0.0, 0.0), |(sum, total), (rate, weight)| {
+                    (sum + rate * weight, total + weight)
+                });
+            (total > 0.0).then(|| sum / total)
+        };
+        (
+            weighted("apyBase", "tvlUsd"),
+            weighted("apyBaseBorrow", "totalBorrowUsd"),
+        )
+    }
+}
+
+#[test]
+fn test_parse_lending_totals() {
+    let protocol = serde_json::json!({
+        "currentChainTvls": {"Aptos": 6_000_000.0, "Aptos-borrowed": 2_000_000.0, "borrowed": 2_000_000.0}
+    });
+    assert_eq!(
+        External::parse_lending_totals(&protocol),
+        Some((8_000_000.0, 2_000_000.0))
+    );
+    assert_eq!(External::parse_lending_totals(&serde_json::json!({})), None);
+}
+
+#[test]
+fn test_parse_lending_apys() {
+    let pools = serde_json::json!({"status": "success", "data": [
+        {"project": "aptin-finance", "chain": "Aptos", "tvlUsd": 3_000_000.0, "apyBase": 2.0, "apyBaseBorrow": 6.0, "totalBorrowUsd": 1_000_000.0},
+        {"project": "aptin-finance", "chain": "Aptos", "tvlUsd": 1_000_000.0, "apyBase": 6.0, "apyBaseBorrow": 10.0, "totalBorrowUsd": 3_000_000.0},
+        {"project": "aptin-finance", "chain": "Ethereum", "tvlUsd": 9e9, "apyBase": 50.0},
+        {"project": "aries-markets", "chain": "Aptos", "tvlUsd": 9e9, "apyBase": 50.0},
+    ]});
+    let pools = External::parse_aptos_pools(pools);
+    assert_eq!(pools.len(), 3);
+    assert_eq!(
+        External::parse_lending_apys(&pools, "aptin-finance"),
+        (Some(3.0), Some(9.0))
+    );
+    assert_eq!(External::parse_lending_apys(&pools, "thala"), (None, None));
+}
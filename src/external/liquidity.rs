@@ -0,0 +1,42 @@
+use chrono::{Duration, Utc};
+use tracing::instrument;
+
+use super::{External, ExternalError};
+use crate::models::{time_weighted_liquidity, LIQUIDITY_INTERVALS};
+
+/// Attribute whose history the time-weighted liquidity is sampled from
+const LIQUIDITY_ATTRIBUTE: &str = "total_value_locked";
+
+impl External {
+    /// Total value locked of the project `project_id` integrated over the last
+    /// `window_hours`, in USD-hours. The value recorded at every tenth of the window is
+    /// sampled, so liquidity parked around a single snapshot weighs no more than its share of
+    /// the window.
+    #[instrument(skip(self))]
+    pub async fn get_time_weighted_liquidity(
+        &self,
+        project_id: i32,
+        window_hours: u64,
+    ) -> Result<f64, ExternalError> {
+        let db = self.db.as_ref().ok_or_else(|| {
+            ExternalError::NotConfigured("The TVL history needs a database".to_string())
+        })?;
+
+        let window = Duration::hours(window_hours as i64);
+        let until = Utc::now();
+        let samples = db
+            .get_attribute_samples(
+                project_id,
+                LIQUIDITY_ATTRIBUTE,
+                until - window,
+                until,
+                window / LIQUIDITY_INTERVALS,
+            )
+            .await?;
+        time_weighted_liquidity(&samples).ok_or_else(|| {
+            ExternalError::Unprocessable(format!(
+                "Project {project_id} has no total value locked recorded over the last {window_hours} hours"
+            ))
+        })
+    }
+}
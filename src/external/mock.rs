@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use reqwest::{Client, Url};
+use serde_json::Value;
+
+use super::{AptosClient, External};
+
+/// Aptos fullnode and indexer answering canned responses, so the fetchers can be tested
+/// without reaching mainnet. GraphQL queries are answered by the first response whose needle
+/// they contain, REST calls by the response of their path and query.
+#[derive(Default)]
+pub struct MockAptos {
+    graphql: Vec<(String, Value)>,
+    rest: Vec<(String, Value)>,
+}
+
+impl MockAptos {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers `response` to the GraphQL queries containing `needle`, e.g. the table they read
+    pub fn graphql(mut self, needle: &str, response: Value) -> Self {
+        self.graphql.push((needle.to_string(), response));
+        self
+    }
+
+    /// Answers `response` to the fullnode calls of `path`, e.g. `/accounts/0x1/resources`
+    pub fn rest(mut self, path: &str, response: Value) -> Self {
+        // Compared as the client sends it, with the type arguments percent-encoded
+        let url = Url::parse(&format!("http://mock{path}")).expect("Invalid mock path");
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+        self.rest.push((path, response));
+        self
+    }
+
+    /// Serves the responses on a free local port, for the returned [`External`] to call
+    pub async fn start(self) -> External {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Could not bind the mock");
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().fallback(answer).with_state(Arc::new(self));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        External {
+            client: AptosClient::new(Client::new(), &base_url, &format!("{base_url}/graphql")),
+            ..External::new()
+        }
+    }
+}
+
+async fn answer(
+    State(mock): State<Arc<MockAptos>>,
+    uri: Uri,
+    body: Option<Json<Value>>,
+) -> Response {
+    if uri.path() == "/graphql" {
+        let query = body
+            .as_ref()
+            .and_then(|Json(body)| body["query"].as_str())
+            .unwrap_or_default();
+        return match mock
+            .graphql
+            .iter()
+            .find(|(needle, _)| query.contains(needle.as_str()))
+        {
+            Some((_, response)) => Json(response.clone()).into_response(),
+            None => Json(serde_json::json!({
+                "data": null,
+                "errors": [{ "message": "No canned response for the query" }]
+            }))
+            .into_response(),
+        };
+    }
+
+    let path = uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or_default();
+    match mock.rest.iter().find(|(known, _)| known == path) {
+        Some((_, response)) => Json(response.clone()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
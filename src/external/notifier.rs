@@ -0,0 +1,126 @@
+use std::future::Future;
+
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+
+use crate::Config;
+
+/// Describes a background task that returned an error
+#[derive(Debug, Clone)]
+pub struct TaskFailure {
+    pub task: String,
+    pub error: String,
+    pub project_id: Option<i32>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl TaskFailure {
+    pub fn new(task: &str, error: &str, project_id: Option<i32>) -> Self {
+        Self {
+            task: task.to_string(),
+            error: error.to_string(),
+            project_id,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Something operators can be alerted through when a task fails
+pub trait Notifier {
+    fn notify_error(
+        &self,
+        failure: &TaskFailure,
+    ) -> impl Future<Output = Result<(), reqwest::Error>> + Send;
+}
+
+fn project_label(project_id: Option<i32>) -> String {
+    project_id.map_or(String::from("-"), |id| id.to_string())
+}
+
+/// Posts failures to a Slack incoming webhook
+pub struct SlackNotifier {
+    client: Client,
+    webhook_url: String,
+}
+
+impl SlackNotifier {
+    pub fn new(webhook_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            webhook_url: webhook_url.to_string(),
+        }
+    }
+
+    /// Returns `None` when no webhook is configured
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.slack_webhook_url.as_deref().map(Self::new)
+    }
+}
+
+impl Notifier for SlackNotifier {
+    async fn notify_error(&self, failure: &TaskFailure) -> Result<(), reqwest::Error> {
+        let text = format!(
+            ":rotating_light: Task *{}* failed\n*Project:* {}\n*Error:* {}\n*At:* {}",
+            failure.task,
+            project_label(failure.project_id),
+            failure.error,
+            failure.timestamp.to_rfc3339(),
+        );
+
+        self.client
+            .post(&self.webhook_url)
+            .json(&serde_json::json!({ "text": text }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Sends failures to a Telegram chat through a bot
+pub struct TelegramNotifier {
+    client: Client,
+    token: String,
+    chat_id: String,
+}
+
+impl TelegramNotifier {
+    pub fn new(token: &str, chat_id: &str) -> Self {
+        Self {
+            client: Client::new(),
+            token: token.to_string(),
+            chat_id: chat_id.to_string(),
+        }
+    }
+
+    /// Returns `None` unless both the bot token and the chat id are configured
+    pub fn from_config(config: &Config) -> Option<Self> {
+        match (&config.telegram_token, &config.telegram_chat_id) {
+            (Some(token), Some(chat_id)) => Some(Self::new(token, chat_id)),
+            _ => None,
+        }
+    }
+}
+
+impl Notifier for TelegramNotifier {
+    async fn notify_error(&self, failure: &TaskFailure) -> Result<(), reqwest::Error> {
+        let text = format!(
+            "🚨 Task {} failed\nProject: {}\nError: {}\nAt: {}",
+            failure.task,
+            project_label(failure.project_id),
+            failure.error,
+            failure.timestamp.to_rfc3339(),
+        );
+
+        self.client
+            .post(format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                self.token
+            ))
+            .json(&serde_json::json!({ "chat_id": self.chat_id, "text": text }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
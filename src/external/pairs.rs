@@ -0,0 +1,84 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+use tracing::{info, instrument};
+
+use super::{like_prefix, External, ExternalError, FULLNODE_API};
+
+impl External {
+    /// Token pairs traded in the latest `limit` swap events of the pools published at
+    /// `address`, as `(token_x, token_y)` in the order of the pool's type arguments
+    #[instrument(skip(self))]
+    pub async fn discover_pairs_from_events(
+        &self,
+        address: &str,
+        limit: u64,
+    ) -> Result<HashSet<(String, String)>, ExternalError> {
+        let event_type = format!("{address}::swap::SwapEvent");
+        let pattern = like_prefix(&event_type);
+        let query = format!(
+            r#"
+            query SwapEventTypes {{
+                events(
+                    limit: {limit}
+                    where: {{indexed_type: {{_like: {pattern}}}}}
+                    order_by: {{transaction_version: desc}}
+                ) {{
+                    indexed_type
+                }}
+            }}
+            "#
+        );
+        let response = Self::post_graphql(&self.client, &query).await?;
+        let pairs = Self::parse_event_pairs(&response, &event_type)
+            .ok_or_else(|| ExternalError::parse(FULLNODE_API, "events"))?;
+        info!(pairs = pairs.len(), "Discovered the traded pairs");
+        Ok(pairs)
+    }
+
+    fn parse_event_pairs(response: &Value, event_type: &str) -> Option<HashSet<(String, String)>> {
+        let pairs = response["data"]["events"]
+            .as_array()?
+            .iter()
+            .filter_map(|event| swap_event_pair(event["indexed_type"].as_str()?, event_type))
+            .collect();
+        Some(pairs)
+    }
+}
+
+/// Coin types of the pool a swap event of `event_type` was emitted by, e.g.
+/// `0xc7ef::swap::SwapEvent<0x1::aptos_coin::AptosCoin, 0x1::usdc::USDC>`. `None` for the
+/// events of other types.
+pub(super) fn swap_event_pair(indexed_type: &str, event_type: &str) -> Option<(String, String)> {
+    let indexed_type = indexed_type.replace(' ', "");
+    let pair = indexed_type
+        .strip_prefix(event_type)?
+        .strip_prefix('<')?
+        .strip_suffix('>')?;
+    Some(External::get_token_name_from_pair(pair))
+}
+
+#[test]
+fn test_parse_event_pairs() {
+    const EVENT: &str = "0xc7ef::swap::SwapEvent";
+    let response = serde_json::json!({"data": {"events": [
+        {"indexed_type": "0xc7ef::swap::SwapEvent<0x1::aptos_coin::AptosCoin, 0x1::usdc::USDC>"},
+        {"indexed_type": "0xc7ef::swap::SwapEvent<0x1::aptos_coin::AptosCoin,0x1::usdc::USDC>"},
+        {"indexed_type": "0xc7ef::swap::SwapEvent<0x1::lp::LP<0x1::a::A, 0x1::b::B>, 0x1::usdc::USDC>"},
+        {"indexed_type": "0xc7ef::swap::SwapEventV2<0x1::aptos_coin::AptosCoin, 0x1::b::B>"},
+        {"indexed_type": null},
+    ]}});
+
+    let pairs = External::parse_event_pairs(&response, EVENT).unwrap();
+    assert_eq!(pairs.len(), 2);
+    assert!(pairs.contains(&(
+        "0x1::aptos_coin::AptosCoin".to_string(),
+        "0x1::usdc::USDC".to_string()
+    )));
+    assert!(pairs.contains(&(
+        "0x1::lp::LP<0x1::a::A,0x1::b::B>".to_string(),
+        "0x1::usdc::USDC".to_string()
+    )));
+
+    assert!(External::parse_event_pairs(&serde_json::json!({"errors": []}), EVENT).is_none());
+}
@@ -0,0 +1,99 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+use tracing::Instrument;
+
+use super::{spawn_with_permit, AptosClient, External};
+use crate::{database::PostgreDatabase, models::SwapTransaction};
+
+/// Prices and decimals of the tokens a [`Pricer`] tried to price, those that couldn't be
+/// priced are remembered too so they aren't tried again
+#[derive(Debug, Default)]
+pub(super) struct PriceBook {
+    prices: HashMap<String, (f64, u8)>,
+    tried: HashSet<String>,
+}
+
+/// Prices coins like [`External`] does, taking its permits, but owning what it needs so it
+/// can be moved into tasks outliving a request
+#[derive(Clone)]
+pub(super) struct Pricer {
+    pub(super) client: AptosClient,
+    pub(super) db: Option<PostgreDatabase>,
+    pub(super) semaphore: Arc<Semaphore>,
+    pub(super) stablecoins: Arc<HashSet<String>>,
+}
+
+impl Pricer {
+    /// Prices and decimals of the `tokens` that can be priced, each fetched in a task holding
+    /// a permit so a long list can't flood the upstream APIs
+    pub(super) async fn prices_and_decimals(
+        &self,
+        tokens: impl IntoIterator<Item = String>,
+    ) -> HashMap<String, (f64, u8)> {
+        let tasks: Vec<_> = tokens
+            .into_iter()
+            .map(|token| {
+                let (client, db, stablecoins) = (
+                    self.client.clone(),
+                    self.db.clone(),
+                    self.stablecoins.clone(),
+                );
+                let token_clone = token.clone();
+                let task = spawn_with_permit(
+                    self.semaphore.clone(),
+                    async move {
+                        External::get_price_and_decimals(client, db, stablecoins, &token_clone)
+                            .await
+                    }
+                    .in_current_span(),
+                );
+                (token, task)
+            })
+            .collect();
+
+        let mut prices = HashMap::new();
+        for (token, task) in tasks {
+            if let Ok(Some(price)) = task.await {
+                prices.insert(token, price);
+            }
+        }
+        prices
+    }
+
+    /// Sets the USD values of the swaps, pricing each of their tokens once. The values of the
+    /// tokens that can't be priced are left unset.
+    pub(super) async fn price_swaps(&self, swaps: Vec<SwapTransaction>) -> Vec<SwapTransaction> {
+        self.price_swaps_with(&mut PriceBook::default(), swaps)
+            .await
+    }
+
+    /// Same as [`Pricer::price_swaps`], only pricing the tokens missing from `book`, which
+    /// keeps the prices for the next swaps
+    pub(super) async fn price_swaps_with(
+        &self,
+        book: &mut PriceBook,
+        mut swaps: Vec<SwapTransaction>,
+    ) -> Vec<SwapTransaction> {
+        let tokens: HashSet<String> = swaps
+            .iter()
+            .flat_map(|swap| [&swap.token_sold, &swap.token_bought])
+            .filter(|token| !book.tried.contains(*token))
+            .cloned()
+            .collect();
+        book.tried.extend(tokens.iter().cloned());
+        book.prices.extend(self.prices_and_decimals(tokens).await);
+        let prices = &book.prices;
+
+        for swap in &mut swaps {
+            swap.token_sold_usd = prices
+                .get(&swap.token_sold)
+                .map(|(price, _)| price * swap.token_sold_amount);
+            swap.token_bought_usd = prices
+                .get(&swap.token_bought)
+                .map(|(price, _)| price * swap.token_bought_amount);
+        }
+        swaps
+    }
+}
@@ -0,0 +1,484 @@
+use rust_decimal::Decimal;
+use serde_json::Value;
+use tracing::{debug, instrument};
+
+use super::{parse_raw_amount, to_token_units, External, ExternalError, FULLNODE_API};
+use crate::models::{CirculatingSupply, KnownAddress};
+
+/// Circulating supply of a token, in whole tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenSupply {
+    pub supply: Decimal,
+    pub decimals: u8,
+    pub source: SupplySource,
+}
+
+/// Where a [`TokenSupply`] was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupplySource {
+    /// The supply tracked in the `0x1::coin::CoinInfo` of the coin
+    CoinInfo,
+    /// The sum of every balance of a coin whose supply isn't tracked
+    CoinBalances,
+    /// The `coin_supply` table of the indexer, when the balances can't be summed
+    CoinSupplyTable,
+    /// The `supply_v2` of a fungible asset
+    FungibleAsset,
+}
+
+/// Supply found in a `0x1::coin::CoinInfo` resource
+#[derive(Debug, PartialEq)]
+enum CoinInfoSupply {
+    Tracked {
+        supply: Decimal,
+        decimals: u8,
+    },
+    /// The coin was initialized with supply tracking turned off
+    Untracked {
+        decimals: u8,
+    },
+}
+
+impl External {
+    /// Supply of `token` without the balances of the `excluded` accounts, which must be
+    /// written out in full
+    #[instrument(skip(self))]
+    pub async fn get_circulating_supply(
+        &self,
+        token: &str,
+        excluded: &[String],
+    ) -> Result<CirculatingSupply, ExternalError> {
+        // The token goes into the queries as is
+        if !is_token(token) {
+            return Err(ExternalError::Unprocessable(format!(
+                "{token} is neither a coin type nor the address of a fungible asset"
+            )));
+        }
+        let address = token.split("::").next().unwrap_or_default();
+        let total = self.get_token_supply(address, token).await?;
+        debug!(source = ?total.source, "Read the supply of {token}");
+        if excluded.is_empty() {
+            return Ok(CirculatingSupply {
+                total: total.supply,
+                circulating: total.supply,
+            });
+        }
+
+        // A coin may also be held as its paired fungible asset, whose balances the indexer
+        // lists under the coin type as `asset_type_v1`, with `amount` adding up both stores
+        let type_field = if token.contains("::") {
+            "asset_type_v1"
+        } else {
+            "asset_type"
+        };
+        let owners = serde_json::json!(excluded);
+        let query = format!(
+            r#"
+            query ExcludedBalances {{
+                current_fungible_asset_balances(where: {{{type_field}: {{_eq: "{token}"}}, owner_address: {{_in: {owners}}}}}) {{
+                    owner_address
+                    amount
+                }}
+            }}
+            "#
+        );
+        let response = Self::post_graphql(&self.client, &query).await?;
+        let excluded_amount = Self::parse_excluded_balances(&response)
+            .ok_or_else(|| ExternalError::parse(FULLNODE_API, "current_fungible_asset_balances"))?;
+        let excluded_supply = to_token_units(excluded_amount, total.decimals);
+        Ok(CirculatingSupply {
+            total: total.supply,
+            circulating: (total.supply - excluded_supply).max(Decimal::ZERO),
+        })
+    }
+
+    /// Circulating supply of `token`, published at `address`. Coins read it from their
+    /// `CoinInfo`, or sum the balances of the holders when they don't track it. Fungible assets,
+    /// given by their metadata address, read their `supply_v2`.
+    #[instrument(skip(self))]
+    pub async fn get_token_supply(
+        &self,
+        address: &str,
+        token: &str,
+    ) -> Result<TokenSupply, ExternalError> {
+        if !token.contains("::") {
+            return self.get_fungible_asset_supply(token).await;
+        }
+
+        let url = self.client.fullnode(&format!(
+            "/accounts/{address}/resource/0x1::coin::CoinInfo<{token}>"
+        ));
+        let response = Self::get_json(&self.client, &url).await?;
+        match Self::parse_coin_info_supply(&response)
+            .ok_or_else(|| ExternalError::parse(FULLNODE_API, "CoinInfo"))?
+        {
+            CoinInfoSupply::Tracked { supply, decimals } => Ok(TokenSupply {
+                supply,
+                decimals,
+                source: SupplySource::CoinInfo,
+            }),
+            CoinInfoSupply::Untracked { decimals } => {
+                debug!(token, "Supply isn't tracked on-chain, summing the balances");
+                self.get_untracked_coin_supply(token, decimals).await
+            }
+        }
+    }
+
+    /// Supply of a coin without supply tracking, summed over the balances of its holders or
+    /// read from the `coin_supply` table when the indexer can't sum them
+    async fn get_untracked_coin_supply(
+        &self,
+        token: &str,
+        decimals: u8,
+    ) -> Result<TokenSupply, ExternalError> {
+        let query = format!(
+            r#"
+            query CoinBalancesSum {{
+                current_coin_balances_aggregate(where: {{coin_type: {{_eq: "{token}"}}}}) {{
+                    aggregate {{
+                        sum {{
+                            amount
+                        }}
+                    }}
+                }}
+            }}
+            "#
+        );
+        let balances = match Self::post_graphql(&self.client, &query).await {
+            Ok(response) => Self::parse_balances_sum(&response),
+            Err(e) => {
+                debug!("Could not sum the balances of {token}: {e}");
+                None
+            }
+        };
+        if let Some(amount) = balances {
+            return Ok(TokenSupply {
+                supply: to_token_units(amount, decimals),
+                decimals,
+                source: SupplySource::CoinBalances,
+            });
+        }
+
+        let query = format!(
+            r#"
+            query CoinSupply {{
+                coin_supply(
+                    limit: 1
+                    where: {{coin_type: {{_eq: "{token}"}}}}
+                    order_by: {{transaction_version: desc}}
+                ) {{
+                    supply
+                }}
+            }}
+            "#
+        );
+        let response = Self::post_graphql(&self.client, &query).await?;
+        let amount = Self::parse_coin_supply_table(&response).ok_or_else(|| {
+            ExternalError::NotFound(format!("The indexer has no supply of {token}"))
+        })?;
+        Ok(TokenSupply {
+            supply: to_token_units(amount, decimals),
+            decimals,
+            source: SupplySource::CoinSupplyTable,
+        })
+    }
+
+    /// Supply of the fungible asset whose metadata lives at `asset_type`
+    async fn get_fungible_asset_supply(
+        &self,
+        asset_type: &str,
+    ) -> Result<TokenSupply, ExternalError> {
+        let query = format!(
+            r#"
+            query FungibleAssetSupply {{
+                fungible_asset_metadata(where: {{asset_type: {{_eq: "{asset_type}"}}}}) {{
+                    decimals
+                    supply_v2
+                }}
+            }}
+            "#
+        );
+        let response = Self::post_graphql(&self.client, &query).await?;
+        let (supply, decimals) = Self::parse_fungible_asset_supply(&response).ok_or_else(|| {
+            ExternalError::NotFound(format!("The indexer has no supply of {asset_type}"))
+        })?;
+        Ok(TokenSupply {
+            supply,
+            decimals,
+            source: SupplySource::FungibleAsset,
+        })
+    }
+
+    /// Supply in a `CoinInfo` resource, `None` when it isn't one
+    fn parse_coin_info_supply(response: &Value) -> Option<CoinInfoSupply> {
+        let data = &response["data"];
+        let decimals = u8::try_from(data["decimals"].as_u64()?).ok()?;
+        // `supply` is an `Option<OptionalAggregator>`, empty without tracking
+        let supply = data["supply"]["vec"].as_array()?;
+        let Some(aggregator) = supply.first() else {
+            return Some(CoinInfoSupply::Untracked { decimals });
+        };
+        // Either a parallelizable aggregator or a plain integer is set
+        let value = aggregator["integer"]["vec"][0]["value"]
+            .as_str()
+            .or_else(|| aggregator["aggregator"]["vec"][0]["value"].as_str())?;
+        let supply: u128 = value.parse().ok()?;
+        Some(CoinInfoSupply::Tracked {
+            supply: to_token_units(supply, decimals),
+            decimals,
+        })
+    }
+
+    /// Raw sum of the balances, `None` when the coin has no holders
+    fn parse_balances_sum(response: &Value) -> Option<u128> {
+        parse_raw_amount(
+            &response["data"]["current_coin_balances_aggregate"]["aggregate"]["sum"]["amount"],
+        )
+    }
+
+    /// Raw supply in the latest row of the `coin_supply` table
+    fn parse_coin_supply_table(response: &Value) -> Option<u128> {
+        parse_raw_amount(&response["data"]["coin_supply"][0]["supply"])
+    }
+
+    /// Supply in whole tokens of a fungible asset, with its decimals
+    fn parse_fungible_asset_supply(response: &Value) -> Option<(Decimal, u8)> {
+        let metadata = &response["data"]["fungible_asset_metadata"][0];
+        let decimals = u8::try_from(metadata["decimals"].as_u64()?).ok()?;
+        let supply = parse_raw_amount(&metadata["supply_v2"])?;
+        Some((to_token_units(supply, decimals), decimals))
+    }
+
+    /// Raw sum of the fungible asset balances in an answer
+    fn parse_excluded_balances(response: &Value) -> Option<u128> {
+        response["data"]["current_fungible_asset_balances"]
+            .as_array()?
+            .iter()
+            .try_fold(0u128, |sum, balance| {
+                Some(sum.saturating_add(parse_raw_amount(&balance["amount"])?))
+            })
+    }
+}
+
+/// Whether `token` is a Move coin type, e.g. `0x1::aptos_coin::AptosCoin`, or the address of
+/// the metadata of a fungible asset
+fn is_token(token: &str) -> bool {
+    if token.contains("::") {
+        External::check_coin_type(token).is_ok()
+    } else {
+        token.starts_with("0x") && KnownAddress::normalize(token).is_some()
+    }
+}
+
+#[test]
+fn test_parse_coin_info_supply() {
+    let tracked = serde_json::json!({
+        "type": "0x1::coin::CoinInfo<0x159d::oft::CakeOFT>",
+        "data": {
+            "decimals": 8,
+            "name": "PancakeSwap Token",
+            "supply": {"vec": [{
+                "aggregator": {"vec": []},
+                "integer": {"vec": [{"limit": "340282366920938463463374607431768211455", "value": "1234500000000"}]}
+            }]},
+            "symbol": "Cake"
+        }
+    });
+    assert_eq!(
+        External::parse_coin_info_supply(&tracked),
+        Some(CoinInfoSupply::Tracked {
+            supply: Decimal::from(12345),
+            decimals: 8
+        })
+    );
+
+    let parallel = serde_json::json!({"data": {
+        "decimals": 6,
+        "supply": {"vec": [{
+            "aggregator": {"vec": [{"handle": "0xa", "key": "0xb", "limit": "1", "value": "2500000"}]},
+            "integer": {"vec": []}
+        }]}
+    }});
+    assert_eq!(
+        External::parse_coin_info_supply(&parallel),
+        Some(CoinInfoSupply::Tracked {
+            supply: Decimal::new(25, 1),
+            decimals: 6
+        })
+    );
+
+    let untracked = serde_json::json!({"data": {"decimals": 6, "supply": {"vec": []}}});
+    assert_eq!(
+        External::parse_coin_info_supply(&untracked),
+        Some(CoinInfoSupply::Untracked { decimals: 6 })
+    );
+
+    let missing = serde_json::json!({"error_code": "resource_not_found"});
+    assert_eq!(External::parse_coin_info_supply(&missing), None);
+}
+
+#[test]
+fn test_parse_untracked_supply() {
+    let balances = serde_json::json!({"data": {"current_coin_balances_aggregate": {
+        "aggregate": {"sum": {"amount": "98765000000"}}
+    }}});
+    assert_eq!(External::parse_balances_sum(&balances), Some(98765000000));
+
+    let no_holders = serde_json::json!({"data": {"current_coin_balances_aggregate": {
+        "aggregate": {"sum": {"amount": null}}
+    }}});
+    assert_eq!(External::parse_balances_sum(&no_holders), None);
+
+    let table = serde_json::json!({"data": {"coin_supply": [{"supply": 4200000}]}});
+    assert_eq!(External::parse_coin_supply_table(&table), Some(4200000));
+    let empty = serde_json::json!({"data": {"coin_supply": []}});
+    assert_eq!(External::parse_coin_supply_table(&empty), None);
+}
+
+#[test]
+fn test_parse_fungible_asset_supply() {
+    let response = serde_json::json!({"data": {"fungible_asset_metadata": [
+        {"decimals": 6, "supply_v2": "1500000000000"}
+    ]}});
+    assert_eq!(
+        External::parse_fungible_asset_supply(&response),
+        Some((Decimal::from(1_500_000), 6))
+    );
+
+    let unknown = serde_json::json!({"data": {"fungible_asset_metadata": []}});
+    assert_eq!(External::parse_fungible_asset_supply(&unknown), None);
+
+    // Assets created without supply tracking leave `supply_v2` empty
+    let untracked = serde_json::json!({"data": {"fungible_asset_metadata": [
+        {"decimals": 8, "supply_v2": null}
+    ]}});
+    assert_eq!(External::parse_fungible_asset_supply(&untracked), None);
+}
+
+#[test]
+fn test_parse_excluded_balances() {
+    let response = serde_json::json!({"data": {"current_fungible_asset_balances": [
+        {"owner_address": "0x0a", "amount": "250000000"},
+        {"owner_address": "0x0b", "amount": 50000000},
+    ]}});
+    assert_eq!(
+        External::parse_excluded_balances(&response),
+        Some(300000000)
+    );
+
+    // Accounts without a balance are left out of the answer
+    let none = serde_json::json!({"data": {"current_fungible_asset_balances": []}});
+    assert_eq!(External::parse_excluded_balances(&none), Some(0));
+    let coins = serde_json::json!({"data": {"current_coin_balances": []}});
+    assert_eq!(External::parse_excluded_balances(&coins), None);
+}
+
+#[test]
+fn test_is_token() {
+    assert!(is_token("0x1::aptos_coin::AptosCoin"));
+    assert!(is_token("0xc7ef::lp::LP<0x1::a::A, 0x1::b::B>"));
+    assert!(is_token("0xa"));
+    assert!(!is_token("0xa::b::C\"}}) {{ evil"));
+    assert!(!is_token("0xnothex"));
+    assert!(!is_token("a"));
+}
+
+#[tokio::test]
+async fn test_get_token_supply() {
+    const CAKE: &str = "0x159d::oft::CakeOFT";
+    const MEME: &str = "0xcafe::meme::MEME";
+    let external = super::mock::MockAptos::new()
+        .rest(
+            &format!("/accounts/0x159d/resource/0x1::coin::CoinInfo<{CAKE}>"),
+            serde_json::json!({"data": {
+                "decimals": 8,
+                "supply": {"vec": [{
+                    "aggregator": {"vec": []},
+                    "integer": {"vec": [{"limit": "1", "value": "1234500000000"}]}
+                }]}
+            }}),
+        )
+        .rest(
+            &format!("/accounts/0xcafe/resource/0x1::coin::CoinInfo<{MEME}>"),
+            serde_json::json!({"data": {"decimals": 6, "supply": {"vec": []}}}),
+        )
+        .graphql(
+            "current_coin_balances_aggregate",
+            serde_json::json!({"data": {"current_coin_balances_aggregate": {
+                "aggregate": {"sum": {"amount": "98765000000"}}
+            }}}),
+        )
+        .graphql(
+            "fungible_asset_metadata",
+            serde_json::json!({"data": {"fungible_asset_metadata": [
+                {"decimals": 6, "supply_v2": "1500000000000"}
+            ]}}),
+        )
+        .start()
+        .await;
+
+    let cake = external.get_token_supply("0x159d", CAKE).await.unwrap();
+    assert_eq!(
+        (cake.supply, cake.source),
+        (Decimal::from(12345), SupplySource::CoinInfo)
+    );
+
+    let meme = external.get_token_supply("0xcafe", MEME).await.unwrap();
+    assert_eq!(
+        (meme.supply, meme.source),
+        (Decimal::from(98765), SupplySource::CoinBalances)
+    );
+
+    let asset = external.get_token_supply("0xa", "0xa").await.unwrap();
+    assert_eq!(
+        (asset.supply, asset.source),
+        (Decimal::from(1_500_000), SupplySource::FungibleAsset)
+    );
+
+    // Coins without a `CoinInfo` aren't coins at all
+    assert!(external
+        .get_token_supply("0xbeef", "0xbeef::fake::FAKE")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_get_circulating_supply() {
+    const CAKE: &str = "0x159d::oft::CakeOFT";
+    let external = super::mock::MockAptos::new()
+        .rest(
+            &format!("/accounts/0x159d/resource/0x1::coin::CoinInfo<{CAKE}>"),
+            serde_json::json!({"data": {
+                "decimals": 8,
+                "supply": {"vec": [{
+                    "aggregator": {"vec": []},
+                    "integer": {"vec": [{"limit": "1", "value": "1000000000000"}]}
+                }]}
+            }}),
+        )
+        // Held in a coin store and in the store of the paired fungible asset
+        .graphql(
+            r#"asset_type_v1: {_eq: "0x159d::oft::CakeOFT"}"#,
+            serde_json::json!({"data": {"current_fungible_asset_balances": [
+                {"owner_address": "0x0a", "amount": "200000000000"},
+                {"owner_address": "0x0b", "amount": "50000000000"},
+            ]}}),
+        )
+        .start()
+        .await;
+
+    let supply = external
+        .get_circulating_supply(CAKE, &["0x0a".to_string(), "0x0b".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(
+        (supply.total, supply.circulating),
+        (Decimal::from(10000), Decimal::from(7500))
+    );
+
+    let error = external
+        .get_circulating_supply(r#"0x1::a::A"}}) { evil"#, &[])
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ExternalError::Unprocessable(_)));
+}
@@ -0,0 +1,235 @@
+use std::cmp::Reverse;
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use tracing::instrument;
+
+use super::{External, ExternalError};
+use crate::models::TokenTerminalData;
+
+const TOKEN_TERMINAL_API: &str = "https://api.tokenterminal.com/v2";
+
+/// Days of daily figures summed into the `_30d` metrics
+const METRIC_DAYS: i64 = 30;
+
+/// Client of the TokenTerminal API, open to paid keys only. Gives the metrics of the project
+/// pages in the format the pages display them, so it can stand in for the scraper.
+pub struct TokenTerminalClient {
+    client: Client,
+    api_key: String,
+}
+
+impl TokenTerminalClient {
+    pub fn new(client: Client, api_key: String) -> Self {
+        Self { client, api_key }
+    }
+
+    /// Metrics of the TokenTerminal project slug `project`, from its daily figures
+    #[instrument(skip(self))]
+    pub async fn get_metrics(&self, project: &str) -> Result<TokenTerminalData, ExternalError> {
+        let request = self
+            .client
+            .get(format!("{TOKEN_TERMINAL_API}/projects/{project}/metrics"))
+            .bearer_auth(&self.api_key);
+        let response = External::correlate(request).send().await?;
+        let res: Value = ExternalError::check_status(response)?.json().await?;
+
+        Self::parse_metrics(&res, Utc::now()).ok_or_else(|| {
+            ExternalError::NotFound(format!("TokenTerminal has no metrics for {project}"))
+        })
+    }
+
+    /// Sums the flows of the last [`METRIC_DAYS`] days and takes the latest of the levels,
+    /// `None` when there are no days. The ATH and ATL are over every day, with their age at
+    /// `now`.
+    fn parse_metrics(response: &Value, now: DateTime<Utc>) -> Option<TokenTerminalData> {
+        let mut days: Vec<(DateTime<Utc>, &Value)> = response["data"]
+            .as_array()?
+            .iter()
+            .filter_map(|day| {
+                let timestamp = DateTime::parse_from_rfc3339(day["timestamp"].as_str()?).ok()?;
+                Some((timestamp.with_timezone(&Utc), day))
+            })
+            .collect();
+        days.sort_by_key(|(timestamp, _)| Reverse(*timestamp));
+        let latest_day = days.first()?.0;
+        let recent = days
+            .iter()
+            .take_while(|(timestamp, _)| *timestamp > latest_day - Duration::days(METRIC_DAYS));
+
+        let sum = |metric: &str| -> Option<f64> {
+            recent
+                .clone()
+                .filter_map(|(_, day)| day[metric].as_f64())
+                .reduce(|total, value| total + value)
+        };
+        let latest = |metric: &str| days.iter().find_map(|(_, day)| day[metric].as_f64());
+        let annualized = |total: Option<f64>| total.map(|total| total * 365.0 / METRIC_DAYS as f64);
+        let per_user = |total: Option<f64>, users: Option<f64>| match (total, users) {
+            (Some(total), Some(users)) if users > 0.0 => Some(total / users),
+            _ => None,
+        };
+
+        let prices = days
+            .iter()
+            .filter_map(|(timestamp, day)| Some((*timestamp, day["price"].as_f64()?)));
+        let ath = prices.clone().max_by(|a, b| a.1.total_cmp(&b.1));
+        let atl = prices.min_by(|a, b| a.1.total_cmp(&b.1));
+
+        let revenue_30d = sum("revenue");
+        let fees_30d = sum("fees");
+        let monthly_active_users = latest("user_mau");
+        Some(TokenTerminalData {
+            ath: format_usd(ath.map(|(_, price)| price)),
+            ath_last: ath.map(|(at, _)| format_age(now - at)).unwrap_or_default(),
+            atl: format_usd(atl.map(|(_, price)| price)),
+            atl_last: atl.map(|(at, _)| format_age(now - at)).unwrap_or_default(),
+            revenue_30d: format_usd(revenue_30d),
+            revenue_annualized: format_usd(annualized(revenue_30d)),
+            expenses_30d: format_usd(sum("expenses")),
+            earnings_30d: format_usd(sum("earnings")),
+            fees_30d: format_usd(fees_30d),
+            fees_annualized: format_usd(annualized(fees_30d)),
+            token_incentives_30d: format_usd(sum("token_incentives")),
+            monthly_active_users: format_count(monthly_active_users),
+            afpu: format_usd(per_user(fees_30d, monthly_active_users)),
+            arpu: format_usd(per_user(revenue_30d, monthly_active_users)),
+            token_trading_volume_30d: format_usd(sum("token_trading_volume")),
+            pf_ratio: format_ratio(latest("pf_circulating")),
+            ps_ratio: format_ratio(latest("ps_circulating")),
+            market_cap_circulating: format_usd(latest("market_cap_circulating")),
+            market_cap_fully_diluted: format_usd(latest("market_cap_fully_diluted")),
+            circulating_supply: format_count(latest("token_supply_circulating")),
+        })
+    }
+}
+
+/// Formats like the TokenTerminal pages, e.g. `$4.32m`, blank without a value
+fn format_usd(value: Option<f64>) -> String {
+    value.map_or_else(String::new, |value| {
+        let sign = if value < 0.0 { "-" } else { "" };
+        format!("{sign}${}", format_magnitude(value.abs()))
+    })
+}
+
+/// Formats like the TokenTerminal pages, e.g. `1.98m`, blank without a value
+fn format_count(value: Option<f64>) -> String {
+    value.map_or_else(String::new, |value| {
+        let sign = if value < 0.0 { "-" } else { "" };
+        format!("{sign}{}", format_magnitude(value.abs()))
+    })
+}
+
+/// Formats like the TokenTerminal pages, e.g. `12.4x`, blank without a value
+fn format_ratio(value: Option<f64>) -> String {
+    value.map_or_else(String::new, |value| format!("{value:.1}x"))
+}
+
+/// Two decimals with a `k`, `m` or `b` suffix, four decimals below one for token prices
+fn format_magnitude(value: f64) -> String {
+    match value {
+        v if v >= 1e9 => format!("{:.2}b", v / 1e9),
+        v if v >= 1e6 => format!("{:.2}m", v / 1e6),
+        v if v >= 1e3 => format!("{:.2}k", v / 1e3),
+        v if v >= 1.0 => format!("{v:.2}"),
+        v => format!("{v:.4}"),
+    }
+}
+
+/// Age of an ATH or ATL like the TokenTerminal pages, e.g. `3.4y ago`
+fn format_age(age: Duration) -> String {
+    let days = age.num_days();
+    if days >= 365 {
+        format!("{:.1}y ago", days as f64 / 365.0)
+    } else {
+        format!("{days}d ago")
+    }
+}
+
+#[test]
+fn test_parse_token_terminal_metrics() {
+    let response = serde_json::json!({"data": [
+        {
+            "timestamp": "2024-05-30T00:00:00.000Z",
+            "price": 2.5,
+            "revenue": 100000.0,
+            "fees": 400000.0,
+            "expenses": 50000.0,
+            "earnings": 50000.0,
+            "token_incentives": 50000.0,
+            "token_trading_volume": 2000000.0,
+            "user_mau": 1980000.0,
+            "pf_circulating": 12.44,
+            "ps_circulating": 38.9,
+            "market_cap_circulating": 612400000.0,
+            "market_cap_fully_diluted": 1010000000.0,
+            "token_supply_circulating": 283600000.0
+        },
+        {
+            "timestamp": "2024-05-01T00:00:00.000Z",
+            "price": 0.2234,
+            "revenue": 4220000.0,
+            "fees": 12900000.0,
+            "expenses": -80000.0,
+            "earnings": null,
+            "user_mau": 1750000.0
+        },
+        // Out of the last 30 days, only counts for the ATH
+        {
+            "timestamp": "2021-04-30T00:00:00.000Z",
+            "price": 42.46,
+            "revenue": 9000000.0
+        },
+        {"price": 1000.0}
+    ]});
+    let now = DateTime::parse_from_rfc3339("2024-09-19T00:00:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    let data = TokenTerminalClient::parse_metrics(&response, now).unwrap();
+
+    assert_eq!(data.ath, "$42.46");
+    assert_eq!(data.ath_last, "3.4y ago");
+    assert_eq!(data.atl, "$0.2234");
+    assert_eq!(data.atl_last, "141d ago");
+    assert_eq!(data.revenue_30d, "$4.32m");
+    assert_eq!(data.revenue_annualized, "$52.56m");
+    assert_eq!(data.fees_30d, "$13.30m");
+    assert_eq!(data.expenses_30d, "-$30.00k");
+    assert_eq!(data.earnings_30d, "$50.00k");
+    assert_eq!(data.token_incentives_30d, "$50.00k");
+    assert_eq!(data.monthly_active_users, "1.98m");
+    assert_eq!(data.afpu, "$6.72");
+    assert_eq!(data.arpu, "$2.18");
+    assert_eq!(data.token_trading_volume_30d, "$2.00m");
+    assert_eq!(data.pf_ratio, "12.4x");
+    assert_eq!(data.ps_ratio, "38.9x");
+    assert_eq!(data.market_cap_circulating, "$612.40m");
+    assert_eq!(data.market_cap_fully_diluted, "$1.01b");
+    assert_eq!(data.circulating_supply, "283.60m");
+    assert!(data.has_metrics());
+
+    // The figures parse back like the scraped ones
+    let response = crate::models::dto::TokenTerminalResponse::from(data);
+    assert_eq!(response.revenue_30d_usd, Some(4_320_000.0));
+    assert_eq!(response.expenses_30d_usd, Some(-30_000.0));
+    assert_eq!(response.pf_ratio_value, Some(12.4));
+}
+
+#[test]
+fn test_parse_token_terminal_metrics_without_days() {
+    let now = Utc::now();
+    let empty = serde_json::json!({"data": []});
+    assert!(TokenTerminalClient::parse_metrics(&empty, now).is_none());
+
+    let sparse = serde_json::json!({"data": [{"timestamp": "2024-05-30T00:00:00.000Z"}]});
+    let data = TokenTerminalClient::parse_metrics(&sparse, now).unwrap();
+    assert_eq!(data.revenue_30d, "");
+    assert_eq!(data.ath_last, "");
+    assert!(!data.has_metrics());
+
+    assert!(
+        TokenTerminalClient::parse_metrics(&serde_json::json!({"error": "Unauthorized"}), now)
+            .is_none()
+    );
+}
@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use tracing::{debug, instrument, warn, Instrument};
+
+use super::{parse_numeric, AptosClient, External, ExternalError, FULLNODE_API};
+use crate::models::{NetFlow, TreasuryFlow};
+
+/// Coin activities fetched per page, the most the indexer returns at once
+const FLOW_PAGE_SIZE: usize = 100;
+
+/// Coin activities summed for one account, the busiest treasuries can move more and their
+/// flow is reported truncated
+const MAX_FLOW_ACTIVITIES: usize = 5000;
+
+const DEPOSIT_EVENTS: [&str; 2] = ["0x1::coin::DepositEvent", "0x1::fungible_asset::Deposit"];
+const WITHDRAW_EVENTS: [&str; 2] = ["0x1::coin::WithdrawEvent", "0x1::fungible_asset::Withdraw"];
+
+/// Raw amount of a coin deposited to or withdrawn from an account by a transaction
+#[derive(Debug, Clone, PartialEq)]
+struct CoinMove {
+    version: i64,
+    coin_type: String,
+    amount: f64,
+    deposited: bool,
+}
+
+impl External {
+    /// USD value deposited to and withdrawn from each of `addresses` since `since`, gas left
+    /// out, and of them taken together, where the coins one of them sent to another in the
+    /// same transaction are left out. Each coin is valued at its current price, coins without
+    /// one are left out.
+    ///
+    /// The accounts are fetched concurrently, each up to [`MAX_FLOW_ACTIVITIES`] activities.
+    #[instrument(skip(self))]
+    pub async fn calculate_net_flows(
+        &self,
+        addresses: &[String],
+        since: DateTime<Utc>,
+    ) -> Result<TreasuryFlow, ExternalError> {
+        let since = since.naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string();
+        let tasks: Vec<_> = addresses
+            .iter()
+            .map(|address| {
+                let client = self.client.clone();
+                let (address, since) = (address.clone(), since.clone());
+                self.spawn_limited(
+                    async move { Self::fetch_coin_moves(&client, &address, &since).await }
+                        .in_current_span(),
+                )
+            })
+            .collect();
+        let mut accounts = Vec::new();
+        let mut truncated = false;
+        for task in tasks {
+            let (moves, account_truncated) = task.await??;
+            truncated |= account_truncated;
+            accounts.push(moves);
+        }
+
+        let coin_types: HashSet<String> = accounts
+            .iter()
+            .flatten()
+            .map(|coin_move| coin_move.coin_type.clone())
+            .collect();
+        let prices = self.pricer().await.prices_and_decimals(coin_types).await;
+        let value = |flows: BTreeMap<String, (f64, f64)>| {
+            let mut flow = NetFlow::default();
+            for (coin_type, (deposited, withdrawn)) in flows {
+                let Some(&(price, decimals)) = prices.get(&coin_type) else {
+                    debug!(coin_type, "Left a coin without a price out of a flow");
+                    continue;
+                };
+                let usd = |amount: f64| amount / 10f64.powi(decimals.into()) * price;
+                flow.inflow_usd += usd(deposited);
+                flow.outflow_usd += usd(withdrawn);
+            }
+            flow
+        };
+
+        let internal = value(Self::internal_transfers(&accounts));
+        let accounts: Vec<NetFlow> = accounts
+            .iter()
+            .map(|moves| value(Self::sum_coin_moves(moves)))
+            .collect();
+        let sum = NetFlow::sum(&accounts);
+        if truncated {
+            warn!(addresses = ?addresses, "Left the oldest coin activities out of a treasury flow");
+        }
+        Ok(TreasuryFlow {
+            accounts,
+            total: NetFlow {
+                inflow_usd: sum.inflow_usd - internal.inflow_usd,
+                outflow_usd: sum.outflow_usd - internal.outflow_usd,
+            },
+            truncated,
+        })
+    }
+
+    /// Coin moves of `address` since `since`, oldest first, along with whether there were more
+    /// than [`MAX_FLOW_ACTIVITIES`] of them
+    async fn fetch_coin_moves(
+        client: &AptosClient,
+        address: &str,
+        since: &str,
+    ) -> Result<(Vec<CoinMove>, bool), ExternalError> {
+        let mut moves = Vec::new();
+        let mut offset = 0;
+        while offset < MAX_FLOW_ACTIVITIES {
+            // Oldest first, so activities made while paging are added after the pages already
+            // fetched and no offset shifts
+            let query = format!(
+                r#"
+                query CoinFlows {{
+                    coin_activities(
+                        limit: {FLOW_PAGE_SIZE}
+                        offset: {offset}
+                        where: {{owner_address: {{_eq: "{address}"}}, is_gas_fee: {{_eq: false}}, is_transaction_success: {{_eq: true}}, transaction_timestamp: {{_gte: "{since}"}}}}
+                        order_by: [{{transaction_version: asc}}, {{event_index: asc}}]
+                    ) {{
+                        activity_type
+                        amount
+                        coin_type
+                        transaction_version
+                    }}
+                }}
+                "#
+            );
+            let response = Self::post_graphql(client, &query).await?;
+            let (page, activities) = Self::parse_coin_moves(&response)
+                .ok_or_else(|| ExternalError::parse(FULLNODE_API, "coin_activities"))?;
+            moves.extend(page);
+            if activities < FLOW_PAGE_SIZE {
+                return Ok((moves, false));
+            }
+            offset += activities;
+        }
+        Ok((moves, true))
+    }
+
+    /// Deposits and withdrawals among the listed activities, with the number of activities
+    fn parse_coin_moves(response: &Value) -> Option<(Vec<CoinMove>, usize)> {
+        let activities = response["data"]["coin_activities"].as_array()?;
+        let mut moves = Vec::new();
+        for activity in activities {
+            let version = &activity["transaction_version"];
+            let (Some(activity_type), Some(coin_type), Some(amount), Some(version)) = (
+                activity["activity_type"].as_str(),
+                activity["coin_type"].as_str(),
+                parse_numeric(&activity["amount"]),
+                version.as_i64().or_else(|| version.as_str()?.parse().ok()),
+            ) else {
+                continue;
+            };
+            let deposited = if DEPOSIT_EVENTS.contains(&activity_type) {
+                true
+            } else if WITHDRAW_EVENTS.contains(&activity_type) {
+                false
+            } else {
+                continue;
+            };
+            moves.push(CoinMove {
+                version,
+                coin_type: coin_type.to_string(),
+                amount,
+                deposited,
+            });
+        }
+        Some((moves, activities.len()))
+    }
+
+    /// Raw amounts of each coin deposited and withdrawn by `moves`
+    fn sum_coin_moves(moves: &[CoinMove]) -> BTreeMap<String, (f64, f64)> {
+        let mut flows = BTreeMap::new();
+        for coin_move in moves {
+            let flow: &mut (f64, f64) = flows.entry(coin_move.coin_type.clone()).or_default();
+            if coin_move.deposited {
+                flow.0 += coin_move.amount;
+            } else {
+                flow.1 += coin_move.amount;
+            }
+        }
+        flows
+    }
+
+    /// Raw amounts of each coin the accounts sent to each other, counted on both sides: in a
+    /// transaction where some of the accounts withdrew a coin and others deposited it, the
+    /// lesser of both amounts went from one to another
+    fn internal_transfers(accounts: &[Vec<CoinMove>]) -> BTreeMap<String, (f64, f64)> {
+        // Deposited then withdrawn amount, with the indexes of the accounts moving it
+        type Sides = [(f64, HashSet<usize>); 2];
+        // By transaction and coin
+        let mut moves: HashMap<(i64, &str), Sides> = HashMap::new();
+        for (account, coin_moves) in accounts.iter().enumerate() {
+            for coin_move in coin_moves {
+                let sides = moves
+                    .entry((coin_move.version, coin_move.coin_type.as_str()))
+                    .or_default();
+                let side = &mut sides[usize::from(!coin_move.deposited)];
+                side.0 += coin_move.amount;
+                side.1.insert(account);
+            }
+        }
+
+        let mut transfers = BTreeMap::new();
+        for ((_, coin_type), [(deposited, depositors), (withdrawn, withdrawers)]) in moves {
+            // An account swapping a coin for itself moved it to no other account
+            if depositors.is_empty() || withdrawers.is_empty() || depositors == withdrawers {
+                continue;
+            }
+            let amount = deposited.min(withdrawn);
+            let transfer: &mut (f64, f64) = transfers.entry(coin_type.to_string()).or_default();
+            transfer.0 += amount;
+            transfer.1 += amount;
+        }
+        transfers
+    }
+}
+
+#[test]
+fn test_parse_coin_moves() {
+    let response = serde_json::json!({"data": {"coin_activities": [
+        {"activity_type": "0x1::coin::DepositEvent", "amount": 150000000, "coin_type": "0x1::aptos_coin::AptosCoin", "transaction_version": 7},
+        {"activity_type": "0x1::coin::WithdrawEvent", "amount": "50000000", "coin_type": "0x1::aptos_coin::AptosCoin", "transaction_version": "8"},
+        {"activity_type": "0x1::fungible_asset::Deposit", "amount": 2000000, "coin_type": "0x1::usdc::USDC", "transaction_version": 9},
+        {"activity_type": "0x1::aptos_coin::GasFeeEvent", "amount": 900, "coin_type": "0x1::aptos_coin::AptosCoin", "transaction_version": 9},
+        {"activity_type": "0x1::coin::MintEvent", "amount": 5, "coin_type": "0x1::moon::Moon", "transaction_version": 10},
+    ]}});
+    let (moves, activities) = External::parse_coin_moves(&response).unwrap();
+    assert_eq!(activities, 5);
+    assert_eq!(moves.len(), 3);
+    assert_eq!(moves[1].version, 8);
+    assert!(!moves[1].deposited);
+    let flows = External::sum_coin_moves(&moves);
+    assert_eq!(flows.len(), 2);
+    assert_eq!(
+        flows["0x1::aptos_coin::AptosCoin"],
+        (150000000.0, 50000000.0)
+    );
+    assert_eq!(flows["0x1::usdc::USDC"], (2000000.0, 0.0));
+
+    assert!(External::parse_coin_moves(&serde_json::json!({"errors": []})).is_none());
+}
+
+#[tokio::test]
+async fn test_calculate_net_flows_leaves_internal_transfers_out() {
+    use super::USDC;
+    let activity = |activity_type: &str, amount: u64, version: i64| serde_json::json!({"activity_type": activity_type, "amount": amount, "coin_type": USDC, "transaction_version": version});
+    let external = super::mock::MockAptos::new()
+        .graphql(
+            "0xa\"",
+            serde_json::json!({"data": {"coin_activities": [
+                activity("0x1::coin::WithdrawEvent", 1_000_000, 5),
+            ]}}),
+        )
+        .graphql(
+            "0xb\"",
+            serde_json::json!({"data": {"coin_activities": [
+                activity("0x1::coin::DepositEvent", 1_000_000, 5),
+                activity("0x1::coin::DepositEvent", 2_000_000, 6),
+            ]}}),
+        )
+        .start()
+        .await;
+
+    let addresses = ["0xa".to_string(), "0xb".to_string()];
+    let flow = external
+        .calculate_net_flows(&addresses, Utc::now())
+        .await
+        .unwrap();
+    assert!(!flow.truncated);
+    assert_eq!(
+        flow.accounts[0],
+        NetFlow {
+            inflow_usd: 0.0,
+            outflow_usd: 1.0
+        }
+    );
+    assert_eq!(
+        flow.accounts[1],
+        NetFlow {
+            inflow_usd: 3.0,
+            outflow_usd: 0.0
+        }
+    );
+    // The dollar sent from one account to the other neither came in nor left
+    assert_eq!(
+        flow.total,
+        NetFlow {
+            inflow_usd: 2.0,
+            outflow_usd: 0.0
+        }
+    );
+}
@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveTime};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use tracing::{debug, instrument};
+
+use super::{
+    parse_raw_amount, pricer::PriceBook, to_f64, usd_value, External, ExternalError, FULLNODE_API,
+};
+use crate::models::SwapTotals;
+
+/// Transactions fetched per page, the most the indexer returns at once
+const VOLUME_PAGE_SIZE: usize = 100;
+
+/// Transactions summed per batch of the volume task, which stores its progress after each
+pub const VOLUME_PROGRESS_TRANSACTIONS: usize = 10_000;
+
+/// Volume of the transactions following a transaction version
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct VolumeBatch {
+    pub volume_usd: f64,
+    /// Version of the last transaction whose coins were summed, `None` when there were none
+    pub last_processed_version: Option<i64>,
+    pub transactions: usize,
+}
+
+/// Coins moved by a page of transactions
+struct VolumePage {
+    amounts: BTreeMap<String, u128>,
+    /// Version of the last transaction of the page, `None` when it's empty
+    last_version: Option<i64>,
+    transactions: usize,
+}
+
+impl External {
+    /// USD value of the coins moved by at least `max_transactions` transactions of `address`
+    /// calling `entry_function_id` after the transaction `after_version`, oldest first, or by
+    /// the remaining ones when there are fewer. Gas fees are left out and each coin is valued
+    /// at its current price, coins without one are left out. The batches can be chained
+    /// through their `last_processed_version`, from `None` for the first transaction.
+    #[instrument(skip(self))]
+    pub async fn get_volume_after_version(
+        &self,
+        address: &str,
+        entry_function_id: &str,
+        after_version: Option<i64>,
+        max_transactions: usize,
+    ) -> Result<VolumeBatch, ExternalError> {
+        let mut amounts: BTreeMap<String, u128> = BTreeMap::new();
+        let mut batch = VolumeBatch {
+            last_processed_version: after_version,
+            ..Default::default()
+        };
+        while batch.transactions < max_transactions {
+            let version_filter = batch
+                .last_processed_version
+                .map(|version| format!(", transaction_version: {{_gt: {version}}}"))
+                .unwrap_or_default();
+            let query = format!(
+                r#"
+                query AllTimeVolume {{
+                    account_transactions(
+                        limit: {VOLUME_PAGE_SIZE}
+                        where: {{account_address: {{_eq: "{address}"}}, user_transaction: {{entry_function_id_str: {{_eq: "{entry_function_id}"}}}}{version_filter}}}
+                        order_by: {{transaction_version: asc}}
+                    ) {{
+                        transaction_version
+                        coin_activities(where: {{is_gas_fee: {{_eq: false}}}}) {{
+                            amount
+                            coin_type
+                        }}
+                    }}
+                }}
+                "#
+            );
+            let response = Self::post_graphql(&self.client, &query).await?;
+            let page = Self::parse_volume_page(&response)
+                .ok_or_else(|| ExternalError::parse(FULLNODE_API, "account_transactions"))?;
+            let Some(last_version) = page.last_version else {
+                break;
+            };
+            for (coin_type, amount) in page.amounts {
+                let total = amounts.entry(coin_type).or_default();
+                *total = total.saturating_add(amount);
+            }
+            batch.transactions += page.transactions;
+            batch.last_processed_version = Some(last_version);
+            if page.transactions < VOLUME_PAGE_SIZE {
+                break;
+            }
+        }
+
+        let prices = self
+            .pricer()
+            .await
+            .prices_and_decimals(amounts.keys().cloned())
+            .await;
+        let mut volume_usd = Decimal::ZERO;
+        for (coin_type, amount) in amounts {
+            let Some(&(price, decimals)) = prices.get(&coin_type) else {
+                debug!(coin_type, "Left a coin without a price out of a volume");
+                continue;
+            };
+            volume_usd = volume_usd.saturating_add(usd_value(price, amount, decimals));
+        }
+        batch.volume_usd = to_f64(volume_usd);
+        debug!(?batch, "Summed a batch of the all time volume");
+        Ok(batch)
+    }
+
+    /// Totals of the swaps made through `entry_function_id` of `address` on the UTC day `date`,
+    /// valued at the current prices of their tokens. Each page is priced and added before the
+    /// next is fetched, so a busy day isn't held in memory, and every token is priced once.
+    #[instrument(skip(self))]
+    pub async fn get_swap_totals_of_day(
+        &self,
+        address: &str,
+        entry_function_id: &str,
+        date: NaiveDate,
+    ) -> Result<SwapTotals, ExternalError> {
+        let start = date.and_time(NaiveTime::MIN);
+        let end = start + chrono::Duration::days(1);
+        let (start, end) = (
+            start.format("%Y-%m-%dT%H:%M:%S"),
+            end.format("%Y-%m-%dT%H:%M:%S"),
+        );
+        let pricer = self.pricer().await;
+        let mut prices = PriceBook::default();
+        let mut totals = SwapTotals::default();
+        let mut before_version: Option<i64> = None;
+        loop {
+            let version_filter = before_version
+                .map(|version| format!(", transaction_version: {{_lt: {version}}}"))
+                .unwrap_or_default();
+            let query = format!(
+                r#"
+                query SwapsOfDay {{
+                    account_transactions(
+                        limit: {VOLUME_PAGE_SIZE}
+                        where: {{account_address: {{_eq: "{address}"}}, user_transaction: {{entry_function_id_str: {{_eq: "{entry_function_id}"}}, timestamp: {{_gte: "{start}", _lt: "{end}"}}}}{version_filter}}}
+                        order_by: {{transaction_version: desc}}
+                    ) {{
+                        transaction_version
+                        user_transaction {{
+                            sender
+                            entry_function_id_str
+                        }}
+                        coin_activities {{
+                            activity_type
+                            amount
+                            coin_type
+                            coin_info {{
+                                decimals
+                            }}
+                        }}
+                    }}
+                }}
+                "#
+            );
+            let response = Self::post_graphql(&self.client, &query).await?;
+            let page = response["data"]["account_transactions"]
+                .as_array()
+                .ok_or_else(|| ExternalError::parse(FULLNODE_API, "account_transactions"))?;
+            let swaps = page.iter().map(Self::parse_swap_transaction).collect();
+            let swaps = pricer.price_swaps_with(&mut prices, swaps).await;
+            totals.add(&swaps);
+            match swaps.last() {
+                Some(swap) if page.len() == VOLUME_PAGE_SIZE => before_version = Some(swap.version),
+                _ => break,
+            }
+        }
+        debug!(?totals, "Summed the swaps of a day");
+        Ok(totals)
+    }
+
+    /// Raw amounts of each coin moved by the transactions of a page
+    fn parse_volume_page(response: &Value) -> Option<VolumePage> {
+        let mut amounts = BTreeMap::new();
+        let mut last_version = None;
+        let transactions = response["data"]["account_transactions"].as_array()?;
+        for transaction in transactions {
+            let version = &transaction["transaction_version"];
+            last_version = Some(
+                version
+                    .as_i64()
+                    .or_else(|| version.as_str()?.parse().ok())?,
+            );
+            let activities = transaction["coin_activities"].as_array();
+            for activity in activities.into_iter().flatten() {
+                let (Some(coin_type), Some(amount)) = (
+                    activity["coin_type"].as_str(),
+                    parse_raw_amount(&activity["amount"]),
+                ) else {
+                    continue;
+                };
+                let total: &mut u128 = amounts.entry(coin_type.to_string()).or_default();
+                *total = total.saturating_add(amount);
+            }
+        }
+        Some(VolumePage {
+            amounts,
+            last_version,
+            transactions: transactions.len(),
+        })
+    }
+}
+
+#[test]
+fn test_parse_volume_page() {
+    let response = serde_json::json!({"data": {"account_transactions": [
+        {"transaction_version": 900, "coin_activities": [
+            {"amount": 150000000, "coin_type": "0x1::aptos_coin::AptosCoin"},
+            {"amount": "2000000", "coin_type": "0x1::usdc::USDC"},
+        ]},
+        {"transaction_version": "850", "coin_activities": [
+            {"amount": "50000000", "coin_type": "0x1::aptos_coin::AptosCoin"},
+            {"amount": null, "coin_type": "0x1::usdc::USDC"},
+        ]},
+    ]}});
+    let page = External::parse_volume_page(&response).unwrap();
+    assert_eq!(page.last_version, Some(850));
+    assert_eq!(page.transactions, 2);
+    assert_eq!(page.amounts.len(), 2);
+    assert_eq!(page.amounts["0x1::aptos_coin::AptosCoin"], 200000000);
+    assert_eq!(page.amounts["0x1::usdc::USDC"], 2000000);
+
+    let empty = serde_json::json!({"data": {"account_transactions": []}});
+    let page = External::parse_volume_page(&empty).unwrap();
+    assert!(page.amounts.is_empty());
+    assert_eq!(page.last_version, None);
+
+    assert!(External::parse_volume_page(&serde_json::json!({"errors": []})).is_none());
+}
+
+#[tokio::test]
+async fn test_get_swap_totals_of_day() {
+    use super::{USDC, USDT};
+    let swap = |version: i64, sender: &str| {
+        serde_json::json!({
+            "transaction_version": version,
+            "user_transaction": { "sender": sender, "entry_function_id_str": "0xdex::router::swap_exact_input" },
+            "coin_activities": [
+                { "activity_type": "0x1::transaction_fee::FeeStatement", "amount": 600, "coin_type": "0x1::aptos_coin::AptosCoin", "coin_info": { "decimals": 8 } },
+                { "activity_type": "0x1::coin::WithdrawEvent", "amount": 2000000, "coin_type": USDC, "coin_info": { "decimals": 6 } },
+                { "activity_type": "0x1::coin::DepositEvent", "amount": 1990000, "coin_type": USDT, "coin_info": { "decimals": 6 } }
+            ]
+        })
+    };
+    let external = super::mock::MockAptos::new()
+        .graphql(
+            "SwapsOfDay",
+            serde_json::json!({"data": {"account_transactions": [
+                swap(9, "0xa"),
+                swap(7, "0xb"),
+            ]}}),
+        )
+        .start()
+        .await;
+
+    let date = NaiveDate::from_ymd_opt(2026, 10, 13).unwrap();
+    let summary = external
+        .get_swap_totals_of_day("0xdex", "0xdex::router::swap_exact_input", date)
+        .await
+        .unwrap()
+        .summary(7, date);
+    assert_eq!(summary.tx_count, 2);
+    assert_eq!(summary.unique_traders, 2);
+    // Valued at the USDC sold
+    assert_eq!(summary.volume_usd, 4.0);
+    assert_eq!(summary.avg_trade_usd, 2.0);
+}
+
+#[tokio::test]
+async fn test_get_volume_after_version() {
+    use super::USDC;
+    let external = super::mock::MockAptos::new()
+        .graphql(
+            "AllTimeVolume",
+            serde_json::json!({"data": {"account_transactions": [
+                {"transaction_version": 901, "coin_activities": [{"amount": 2000000, "coin_type": USDC}]},
+                {"transaction_version": 950, "coin_activities": [{"amount": "3000000", "coin_type": USDC}]},
+            ]}}),
+        )
+        .start()
+        .await;
+
+    let batch = external
+        .get_volume_after_version("0xdex", "0xdex::router::swap_exact_input", Some(900), 1000)
+        .await
+        .unwrap();
+    // The page is short, so it is the last one
+    assert_eq!(
+        batch,
+        VolumeBatch {
+            volume_usd: 5.0,
+            last_processed_version: Some(950),
+            transactions: 2,
+        }
+    );
+}
@@ -0,0 +1,189 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect, Client, Url,
+};
+
+/// Checks that `webhook_url` is an http(s) URL whose host only resolves to public addresses,
+/// so a webhook can't make the server call itself, its network or a cloud metadata endpoint.
+/// The error tells what is wrong with the URL.
+pub async fn check_public_url(webhook_url: &str) -> Result<(), String> {
+    let url = Url::parse(webhook_url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| format!("{webhook_url} is not an http(s) URL"))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("{webhook_url} has no host"))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses = resolve_public(host.trim_matches(['[', ']']), port).await?;
+    if addresses.is_empty() {
+        return Err(format!("{host} does not resolve to any address"));
+    }
+    Ok(())
+}
+
+/// Client for posting to webhooks whose requests only go to public addresses, checked on
+/// every connection so a host can't resolve to a private address after its URL was accepted.
+/// Redirects aren't followed, they could lead anywhere.
+pub fn public_client(timeout: Duration) -> Client {
+    Client::builder()
+        .timeout(timeout)
+        .redirect(redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .expect("Webhook client must build")
+}
+
+/// Addresses of `host`, an error naming the host when any of them isn't public
+async fn resolve_public(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let addresses: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| format!("{host} could not be resolved"))?
+            .collect(),
+    };
+    if addresses.iter().any(|address| !is_public_ip(address.ip())) {
+        return Err(format!("{host} is not a public address"));
+    }
+    Ok(addresses)
+}
+
+/// Resolves host names like the system does, refusing those with a non-public address
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses = resolve_public(&host, 0).await?;
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether `ip` is a unicast address of the public internet, rather than one of loopback,
+/// private networks, link-local (where cloud metadata endpoints live), carrier-grade NAT or
+/// another special-purpose range
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8, "this network"
+        || a == 0
+        // 100.64.0.0/10, carrier-grade NAT, also used by some metadata endpoints
+        || (a == 100 && (64..128).contains(&b))
+        // 192.0.0.0/24, IETF protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // 198.18.0.0/15, benchmarking
+        || (a == 198 && (b == 18 || b == 19))
+        // 240.0.0.0/4, reserved
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(embedded) = embedded_ipv4(ip) {
+        return is_public_ipv4(embedded);
+    }
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7, unique local
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10, link-local
+        || (first & 0xffc0) == 0xfe80
+        // 2001:db8::/32, documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// IPv4 address an IPv6 address reaches through a translator or tunnel, so it is checked as
+/// that address. IPv4-mapped addresses are unwrapped by [`is_public_ip`] already.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let ipv4 = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match ip.segments() {
+        // 64:ff9b::/96, NAT64
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] => Some(ipv4(high, low)),
+        // 2002::/16, 6to4, the IPv4 address in the 32 bits after the prefix
+        [0x2002, high, low, ..] => Some(ipv4(high, low)),
+        // ::/96, IPv4-compatible, deprecated but still understood by some stacks
+        [0, 0, 0, 0, 0, 0, high, low] => Some(ipv4(high, low)),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_is_public_ip() {
+    for public in [
+        "93.184.216.34",
+        "8.8.8.8",
+        "2606:4700::1111",
+        "64:ff9b::5db8:d822",
+        "2002:5db8:d822::1",
+        "::93.184.216.34",
+    ] {
+        assert!(is_public_ip(public.parse().unwrap()), "{public}");
+    }
+    for internal in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.100.100.200",
+        "0.0.0.0",
+        "255.255.255.255",
+        "::1",
+        "::",
+        "fd00:ec2::254",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+        "::ffff:169.254.169.254",
+        "64:ff9b::127.0.0.1",
+        "64:ff9b::a9fe:a9fe",
+        "2002:7f00:1::1",
+        "2002:a9fe:a9fe::",
+        "2002:0a00:0005::1",
+        "::127.0.0.1",
+        "::169.254.169.254",
+    ] {
+        assert!(!is_public_ip(internal.parse().unwrap()), "{internal}");
+    }
+}
+
+#[tokio::test]
+async fn test_check_public_url() {
+    assert!(check_public_url("https://93.184.216.34/hook").await.is_ok());
+    for internal in [
+        "http://127.0.0.1:8080/hook",
+        "http://169.254.169.254/latest/meta-data",
+        "http://10.0.0.5/hook",
+        "http://[::1]/hook",
+        "http://localhost/hook",
+    ] {
+        assert!(check_public_url(internal).await.is_err(), "{internal}");
+    }
+    assert!(check_public_url("ftp://93.184.216.34/hook").await.is_err());
+    assert!(check_public_url("not a url").await.is_err());
+}
@@ -1,23 +1,113 @@
 mod app_state;
+mod audit;
+mod cli;
 mod config;
 mod database;
 mod models;
 mod routes;
+mod scheduler;
+mod storage;
+mod telemetry;
 pub mod external;
 pub use app_state::AppState;
 pub use config::Config;
 use external::External;
 
-use crate::routes::make_app;
-use dotenv::dotenv;
-use std::{error::Error, time::Instant};
+use crate::cli::{Command, USAGE};
+use crate::routes::{make_app, App};
+use std::error::Error;
+use std::future::IntoFuture;
 use tokio::net::TcpListener;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let app = make_app().await?;
-    let listener = TcpListener::bind("0.0.0.0:8080").await?;
-    println!("🚀 Server started successfully");
-    axum::serve(listener, app).await?;
+async fn main() {
+    let command = match Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    let result = match command {
+        Command::Serve => serve().await,
+        Command::Migrate => cli::migrate().await,
+        Command::RunTask { task, project_id } => cli::run_task(&task, project_id).await,
+        Command::BackfillMetrics { project_id, days } => {
+            cli::backfill_metrics(project_id, days).await
+        }
+        Command::ExportOpenapi { output } => cli::export_openapi(&output),
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    };
+    telemetry::shutdown();
+    if let Err(error) = result {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
+
+/// Serves the API until Ctrl+C or SIGTERM, then waits for the in-flight work
+async fn serve() -> Result<(), Box<dyn Error>> {
+    let App {
+        router,
+        scheduler,
+        shutdown_timeout,
+        port,
+    } = make_app().await?;
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    tracing::info!("🚀 Server started successfully");
+
+    let shutdown = scheduler.shutdown_token();
+    let mut server = tokio::spawn(
+        axum::serve(listener, router)
+            .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+            .into_future(),
+    );
+    tokio::select! {
+        result = &mut server => result??,
+        _ = shutdown_signal() => {
+            tracing::info!(
+                timeout_secs = shutdown_timeout.as_secs(),
+                "Shutting down, waiting for in-flight work"
+            );
+            shutdown.cancel();
+            let drained = tokio::time::timeout(shutdown_timeout, async {
+                let result = server.await;
+                scheduler.stop().await;
+                result
+            })
+            .await;
+            match drained {
+                Ok(result) => result??,
+                Err(_) => tracing::warn!("In-flight work did not finish in time, exiting anyway"),
+            }
+        }
+    }
     Ok(())
 }
+
+/// Resolves on Ctrl+C, or on the SIGTERM sent by orchestrators before killing the process
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Ctrl+C handler must install");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("SIGTERM handler must install")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
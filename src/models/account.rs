@@ -5,7 +5,126 @@ use serde::{Deserialize, Serialize};
 pub struct Account {
     pub id: i32,
     pub address: String,
+    pub network: String,
     pub entity_id: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+impl Account {
+    /// Network an account is on when none is given
+    pub const DEFAULT_NETWORK: &'static str = "aptos-mainnet";
+}
+
+/// Account as the Aptos fullnode knows it, only there once it sent or received a transaction
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct OnChainAccount {
+    /// Transactions the account has sent
+    pub sequence_number: u64,
+    pub authentication_key: String,
+}
+
+/// USD value of a watched account at one point in time
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct AccountBalanceSnapshot {
+    pub id: i32,
+    pub account_id: i32,
+    pub total_usd: f64,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// USD value that came into and left an account over a period
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct NetFlow {
+    pub inflow_usd: f64,
+    pub outflow_usd: f64,
+}
+
+impl NetFlow {
+    /// Days the treasury flows stored on projects are summed over
+    pub const TREASURY_DAYS: i64 = 7;
+
+    /// Positive when more came in than left
+    pub fn net_usd(&self) -> f64 {
+        self.inflow_usd - self.outflow_usd
+    }
+
+    /// Flow of several accounts taken together
+    pub fn sum<'a>(flows: impl IntoIterator<Item = &'a NetFlow>) -> NetFlow {
+        flows
+            .into_iter()
+            .fold(NetFlow::default(), |total, flow| NetFlow {
+                inflow_usd: total.inflow_usd + flow.inflow_usd,
+                outflow_usd: total.outflow_usd + flow.outflow_usd,
+            })
+    }
+}
+
+/// Flows of the treasury accounts of a project over a period
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TreasuryFlow {
+    /// Flow of each account, in the order the accounts were given
+    pub accounts: Vec<NetFlow>,
+    /// Flow of the accounts taken together, transfers between them left out
+    pub total: NetFlow,
+    /// Whether an account moved more coins than are summed, its flow is then undercounted
+    pub truncated: bool,
+}
+
+/// Whether a transaction was sent by the account or received from someone else
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    #[default]
+    Out,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+        }
+    }
+}
+
+/// Transaction an account took part in, as listed by the indexer
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Transaction {
+    pub version: i64,
+    /// Missing for transactions not sent by a user, e.g. block metadata
+    pub sender: Option<String>,
+    /// Account the coins were deposited to, the sender itself for a swap
+    pub receiver: Option<String>,
+    /// Labels of the sender and the receiver, when they are known addresses
+    pub sender_label: Option<String>,
+    pub receiver_label: Option<String>,
+    /// Address of the module of the called entry function
+    pub contract: Option<String>,
+    pub direction: Direction,
+    pub entry_function_id: Option<String>,
+    /// Coin deposited to the receiver, and its amount in the smallest unit of the coin
+    pub coin_type: Option<String>,
+    pub amount: Option<f64>,
+    pub timestamp: Option<String>,
+}
+
+#[test]
+fn test_sum_net_flows() {
+    let flows = [
+        NetFlow {
+            inflow_usd: 1_000.0,
+            outflow_usd: 250.0,
+        },
+        NetFlow {
+            inflow_usd: 0.0,
+            outflow_usd: 1_500.0,
+        },
+    ];
+    let total = NetFlow::sum(&flows);
+    assert_eq!(total.inflow_usd, 1_000.0);
+    assert_eq!(total.outflow_usd, 1_750.0);
+    assert_eq!(total.net_usd(), -750.0);
+    assert_eq!(NetFlow::sum(&[]), NetFlow::default());
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{AnomalyAlert, SwapTransaction};
+
+/// Rule posting to a webhook the swaps of a project's token worth at least `min_usd`, or the
+/// spikes of its trading volume when the volume is at least `min_usd`, depending on its kind
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AlertRule {
+    pub id: i32,
+    pub project_id: i32,
+    /// Either [`AlertRule::SWAP_KIND`] or [`AlertRule::ANOMALY_KIND`]
+    pub kind: String,
+    pub min_usd: f64,
+    pub webhook_url: String,
+    /// Id of the user who created the rule
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AlertRule {
+    /// Kind of the rules fired by large swaps
+    pub const SWAP_KIND: &'static str = "swap";
+    /// Kind of the rules fired by trading volume anomalies
+    pub const ANOMALY_KIND: &'static str = "anomaly";
+    pub const KINDS: [&'static str; 2] = [Self::SWAP_KIND, Self::ANOMALY_KIND];
+
+    /// Whether a swap worth `value_usd` fires the rule, which takes the swap to sell or buy
+    /// `project_token`, the token of the project of the rule
+    pub fn is_fired_by(&self, project_token: &str, swap: &SwapTransaction, value_usd: f64) -> bool {
+        let trades_token = swap.token_sold == project_token || swap.token_bought == project_token;
+        self.kind == Self::SWAP_KIND && trades_token && value_usd >= self.min_usd
+    }
+
+    /// Whether an anomaly of the trading volume fires the rule, which takes the anomalous
+    /// volume to be worth at least `min_usd`
+    pub fn is_fired_by_anomaly(&self, alert: &AnomalyAlert) -> bool {
+        self.kind == Self::ANOMALY_KIND && alert.value >= self.min_usd
+    }
+}
+
+/// Attempt to post a swap to the webhook of an alert rule
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AlertDelivery {
+    pub id: i32,
+    pub rule_id: i32,
+    pub transaction_version: i64,
+    /// USD value of the tokens sold in the swap
+    pub value_usd: f64,
+    pub attempts: i32,
+    pub delivered: bool,
+    /// Status of the last attempt, `None` when the webhook could not be reached
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[test]
+fn test_alert_rule_fired_by_large_swaps_of_the_token() {
+    let rule = AlertRule {
+        kind: AlertRule::SWAP_KIND.to_string(),
+        min_usd: 10_000.0,
+        ..Default::default()
+    };
+    let swap = SwapTransaction {
+        token_sold: "0x1::aptos_coin::AptosCoin".to_string(),
+        token_bought: "0x1::cake::Cake".to_string(),
+        ..Default::default()
+    };
+
+    assert!(rule.is_fired_by("0x1::cake::Cake", &swap, 10_000.0));
+    assert!(rule.is_fired_by("0x1::aptos_coin::AptosCoin", &swap, 25_000.0));
+    assert!(!rule.is_fired_by("0x1::cake::Cake", &swap, 9_999.0));
+    assert!(!rule.is_fired_by("0x1::usdc::USDC", &swap, 25_000.0));
+}
+
+#[test]
+fn test_alert_rule_fired_by_large_anomalies() {
+    let rule = AlertRule {
+        kind: AlertRule::ANOMALY_KIND.to_string(),
+        min_usd: 10_000.0,
+        ..Default::default()
+    };
+    let alert = |value| AnomalyAlert {
+        value,
+        ..Default::default()
+    };
+
+    assert!(rule.is_fired_by_anomaly(&alert(10_000.0)));
+    assert!(!rule.is_fired_by_anomaly(&alert(9_999.0)));
+    let swap_rule = AlertRule {
+        kind: AlertRule::SWAP_KIND.to_string(),
+        ..rule.clone()
+    };
+    assert!(!swap_rule.is_fired_by_anomaly(&alert(25_000.0)));
+    let swap = SwapTransaction {
+        token_sold: "0x1::cake::Cake".to_string(),
+        ..Default::default()
+    };
+    assert!(!rule.is_fired_by("0x1::cake::Cake", &swap, 25_000.0));
+}
@@ -0,0 +1,148 @@
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::database::PostgreDatabase;
+
+/// Days of trading volume a new volume is compared with
+pub const VOLUME_SPIKE_DAYS: i64 = 30;
+
+/// Fewest daily values a spike can be told from, the deviation of fewer means little
+const MIN_DAILY_VALUES: usize = 7;
+
+/// Standard deviations from the mean past which a value is anomalous
+const SPIKE_STD_DEVS: f64 = 3.0;
+
+/// Share of the mean the standard deviation is taken to be at least, so a metric that barely
+/// moved for days doesn't turn anomalous on the smallest change
+const MIN_STD_DEV_SHARE: f64 = 0.05;
+
+/// Time after an anomaly of a metric during which no other anomaly of it is reported, a spike
+/// lasting for hours is one anomaly
+pub const ANOMALY_COOLDOWN_HOURS: i64 = 24;
+
+/// Value of a metric of a project far out of its usual range, kept as an `anomaly_event`
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AnomalyAlert {
+    pub id: i32,
+    pub project_id: i32,
+    /// Attribute the value is of, one of [`crate::models::Project::NUMERIC_ATTRIBUTES`]
+    pub metric: String,
+    pub value: f64,
+    /// Mean and standard deviation of the daily values the value was compared with
+    pub mean: f64,
+    pub std_dev: f64,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Flags metric values that may be a bot attack or a major market event
+pub struct AnomalyDetector;
+
+impl AnomalyDetector {
+    /// Attribute checked by [`AnomalyDetector::check_volume_spike`]
+    pub const VOLUME_METRIC: &'static str = "trading_volume";
+
+    /// Alert when `current_volume` is more than 3 standard deviations away from the mean of
+    /// the daily trading volumes of the project over the last [`VOLUME_SPIKE_DAYS`] days.
+    /// Today's values are left out so a spike isn't compared with itself. Nothing is alerted
+    /// within [`ANOMALY_COOLDOWN_HOURS`] of the last volume anomaly of the project.
+    pub async fn check_volume_spike(
+        project_id: i32,
+        current_volume: f64,
+        db: &PostgreDatabase,
+    ) -> Option<AnomalyAlert> {
+        match db
+            .get_latest_anomaly_event(project_id, Self::VOLUME_METRIC)
+            .await
+        {
+            Ok(Some(last))
+                if Utc::now() - last.detected_at < Duration::hours(ANOMALY_COOLDOWN_HOURS) =>
+            {
+                return None;
+            }
+            Ok(_) => {}
+            Err(error) => {
+                warn!(project = project_id, %error, "Could not fetch the last volume anomaly of a project");
+                return None;
+            }
+        }
+        let today = Utc::now().date_naive().and_time(NaiveTime::MIN).and_utc();
+        let daily_volumes = match db
+            .get_daily_attribute_values(
+                project_id,
+                Self::VOLUME_METRIC,
+                today - Duration::days(VOLUME_SPIKE_DAYS),
+                today,
+            )
+            .await
+        {
+            Ok(daily_volumes) => daily_volumes
+                .into_iter()
+                .map(|(_, volume)| volume)
+                .collect::<Vec<_>>(),
+            Err(error) => {
+                warn!(project = project_id, %error, "Could not fetch the daily volumes of a project");
+                return None;
+            }
+        };
+        Self::check_spike(
+            project_id,
+            Self::VOLUME_METRIC,
+            current_volume,
+            &daily_volumes,
+        )
+    }
+
+    fn check_spike(
+        project_id: i32,
+        metric: &str,
+        value: f64,
+        daily_values: &[f64],
+    ) -> Option<AnomalyAlert> {
+        if daily_values.len() < MIN_DAILY_VALUES {
+            return None;
+        }
+        let count = daily_values.len() as f64;
+        let mean = daily_values.iter().sum::<f64>() / count;
+        let variance = daily_values
+            .iter()
+            .map(|daily| (daily - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let std_dev = variance.sqrt().max(mean.abs() * MIN_STD_DEV_SHARE);
+        if std_dev == 0.0 {
+            return None;
+        }
+
+        ((value - mean).abs() > SPIKE_STD_DEVS * std_dev).then(|| AnomalyAlert {
+            project_id,
+            metric: metric.to_string(),
+            value,
+            mean,
+            std_dev,
+            detected_at: Utc::now(),
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn test_check_volume_spike() {
+    let daily = [90.0, 110.0, 100.0, 95.0, 105.0, 100.0, 100.0];
+
+    let alert = AnomalyDetector::check_spike(3, "trading_volume", 1_000.0, &daily).unwrap();
+    assert_eq!(alert.project_id, 3);
+    assert_eq!(alert.mean, 100.0);
+    assert!((alert.std_dev - 5.976).abs() < 1e-3);
+    // Drops are as suspicious as spikes
+    assert!(AnomalyDetector::check_spike(3, "trading_volume", 50.0, &daily).is_some());
+    assert!(AnomalyDetector::check_spike(3, "trading_volume", 115.0, &daily).is_none());
+    assert!(AnomalyDetector::check_spike(3, "trading_volume", 1_000.0, &daily[..6]).is_none());
+
+    // A flat history only alerts on changes far from the mean
+    let flat = [100.0; 7];
+    let alert = AnomalyDetector::check_spike(3, "trading_volume", 120.0, &flat).unwrap();
+    assert_eq!(alert.std_dev, 5.0);
+    assert!(AnomalyDetector::check_spike(3, "trading_volume", 101.0, &flat).is_none());
+    assert!(AnomalyDetector::check_spike(3, "trading_volume", 1.0, &[0.0; 7]).is_none());
+}
@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Change a user made to a resource, written by [`AuditLogger`](crate::audit::AuditLogger)
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub id: i32,
+    /// `None` once the user was deleted from the database
+    pub user_id: Option<i32>,
+    /// One of [`AuditEntry::ACTIONS`]
+    pub action: String,
+    /// One of [`AuditEntry::RESOURCE_TYPES`]
+    pub resource_type: String,
+    pub resource_id: i32,
+    /// Resource as the API returned it before the change, `None` for a creation
+    pub before: Option<Value>,
+    /// Resource as the API returned it after the change
+    pub after: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    pub const CREATE: &'static str = "create";
+    pub const UPDATE: &'static str = "update";
+    pub const ACTIONS: [&'static str; 2] = [Self::CREATE, Self::UPDATE];
+
+    pub const USER: &'static str = "user";
+    pub const ACCOUNT: &'static str = "account";
+    pub const ENTITY: &'static str = "entity";
+    pub const PROJECT: &'static str = "project";
+    pub const RESOURCE_TYPES: [&'static str; 4] =
+        [Self::USER, Self::ACCOUNT, Self::ENTITY, Self::PROJECT];
+}
@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Coin an admin vouched for or flagged, deciding whether it is priced from its pools
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CoinFilter {
+    pub coin_type: String,
+    /// One of [`CoinFilter::STATUSES`]
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CoinFilter {
+    /// Coins still priced when only the whitelisted ones are, see `STRICT_TOKEN_WHITELIST`
+    pub const WHITELIST: &'static str = "WHITELIST";
+    /// Coins never priced, e.g. scam tokens with fake liquidity
+    pub const BLACKLIST: &'static str = "BLACKLIST";
+    pub const STATUSES: [&'static str; 2] = [Self::WHITELIST, Self::BLACKLIST];
+}
+
+/// Coins of the `coin_filter` table, looked up for every reserve valued
+#[derive(Debug, Default, Clone)]
+pub struct CoinFilters {
+    whitelist: HashSet<String>,
+    blacklist: HashSet<String>,
+    /// Only the whitelisted coins are priced
+    strict: bool,
+}
+
+impl CoinFilters {
+    pub fn new(filters: impl IntoIterator<Item = CoinFilter>, strict: bool) -> Self {
+        let mut coins = CoinFilters {
+            strict,
+            ..Default::default()
+        };
+        for filter in filters {
+            if filter.status == CoinFilter::BLACKLIST {
+                coins.blacklist.insert(filter.coin_type);
+            } else {
+                coins.whitelist.insert(filter.coin_type);
+            }
+        }
+        coins
+    }
+
+    /// Whether `coin_type` may be priced, never when blacklisted and in strict mode only
+    /// when whitelisted
+    pub fn allows(&self, coin_type: &str) -> bool {
+        if self.blacklist.contains(coin_type) {
+            return false;
+        }
+        !self.strict || self.whitelist.contains(coin_type)
+    }
+}
+
+#[test]
+fn test_coin_filters_allow() {
+    let filter = |coin_type: &str, status: &str| CoinFilter {
+        coin_type: coin_type.to_string(),
+        status: status.to_string(),
+        ..Default::default()
+    };
+    let filters = || {
+        vec![
+            filter("0x1::aptos_coin::AptosCoin", CoinFilter::WHITELIST),
+            filter("0xbad::scam::SCAM", CoinFilter::BLACKLIST),
+        ]
+    };
+
+    let lenient = CoinFilters::new(filters(), false);
+    assert!(lenient.allows("0x1::aptos_coin::AptosCoin"));
+    assert!(lenient.allows("0xcafe::meme::MEME"));
+    assert!(!lenient.allows("0xbad::scam::SCAM"));
+
+    let strict = CoinFilters::new(filters(), true);
+    assert!(strict.allows("0x1::aptos_coin::AptosCoin"));
+    assert!(!strict.allows("0xcafe::meme::MEME"));
+    assert!(!strict.allows("0xbad::scam::SCAM"));
+
+    assert!(CoinFilters::default().allows("0xbad::scam::SCAM"));
+}
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Metadata of a coin type as published on chain, cached to spare the indexer
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CoinInfo {
+    pub coin_type: String,
+    pub decimals: i16,
+    pub symbol: String,
+    pub name: String,
+    pub last_refreshed: DateTime<Utc>,
+}
+
+impl CoinInfo {
+    /// Days after which the cached metadata is fetched again from the indexer, the decimals
+    /// of a coin seldom change but sometimes do
+    pub const MAX_AGE_DAYS: i64 = 30;
+
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        now - self.last_refreshed > Duration::days(Self::MAX_AGE_DAYS)
+    }
+}
+
+#[test]
+fn test_coin_info_is_stale() {
+    let now = Utc::now();
+    let mut info = CoinInfo {
+        last_refreshed: now - Duration::days(29),
+        ..Default::default()
+    };
+    assert!(!info.is_stale(now));
+
+    info.last_refreshed = now - Duration::days(31);
+    assert!(info.is_stale(now));
+}
@@ -1,13 +1,133 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::NaiveDate;
+use rust_decimal::prelude::{Decimal, FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
 
+use super::dto::parse_financial_string;
+
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct SwapTransaction {
     pub version: i64,
     pub sender: String,
+    /// Set when the sender is a known address
+    pub sender_label: Option<String>,
+    pub entry_function_type: SwapType,
+    /// Tokens a routed trade went through between `token_sold` and `token_bought`, empty for a
+    /// trade in a single pool
+    pub route: Vec<String>,
     pub token_sold: String,
     pub token_sold_amount: f64,
     pub token_bought: String,
     pub token_bought_amount: f64,
+    /// USD value of the tokens sold, `None` when they can't be priced
+    pub token_sold_usd: Option<f64>,
+    /// USD value of the tokens bought, `None` when they can't be priced
+    pub token_bought_usd: Option<f64>,
+}
+
+/// Router entry function of a swap, fixing the amount sold or the amount bought
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapType {
+    #[default]
+    ExactInput,
+    ExactOutput,
+}
+
+impl SwapType {
+    /// `swap_exact_output` calls, every other router swap sells an exact input
+    pub fn from_entry_function(entry_function_id: &str) -> Self {
+        if entry_function_id.ends_with("::swap_exact_output") {
+            SwapType::ExactOutput
+        } else {
+            SwapType::ExactInput
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwapType::ExactInput => "exact_input",
+            SwapType::ExactOutput => "exact_output",
+        }
+    }
+}
+
+impl SwapTransaction {
+    /// Whether the swap sold one of `token_a` and `token_b` for the other, either way round
+    pub fn trades_pair(&self, token_a: &str, token_b: &str) -> bool {
+        (self.token_sold == token_a && self.token_bought == token_b)
+            || (self.token_sold == token_b && self.token_bought == token_a)
+    }
+
+    /// USD value of the trade, the value of the tokens sold or of those bought when only they
+    /// can be priced
+    pub fn usd_value(&self) -> Option<f64> {
+        self.token_sold_usd.or(self.token_bought_usd)
+    }
+}
+
+/// Swaps made through the volume entry function of a project on a UTC day
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SwapDailySummary {
+    pub project_id: i32,
+    pub date: NaiveDate,
+    pub tx_count: i32,
+    /// Summed over the swaps that could be valued
+    pub volume_usd: f64,
+    /// Distinct senders of the swaps
+    pub unique_traders: i32,
+    /// Mean value of the swaps that could be valued, 0 when none could
+    pub avg_trade_usd: f64,
+}
+
+impl SwapDailySummary {
+    pub fn from_swaps(project_id: i32, date: NaiveDate, swaps: &[SwapTransaction]) -> Self {
+        let mut totals = SwapTotals::default();
+        totals.add(swaps);
+        totals.summary(project_id, date)
+    }
+}
+
+/// Running totals of a [`SwapDailySummary`], so the swaps of a day can be added a page at a
+/// time and dropped, only their senders are kept
+#[derive(Debug, Default)]
+pub struct SwapTotals {
+    tx_count: i32,
+    volume_usd: f64,
+    /// Swaps that could be valued
+    valued: usize,
+    traders: HashSet<String>,
+}
+
+impl SwapTotals {
+    pub fn add(&mut self, swaps: &[SwapTransaction]) {
+        self.tx_count += swaps.len() as i32;
+        for swap in swaps {
+            if let Some(value) = swap.usd_value() {
+                self.volume_usd += value;
+                self.valued += 1;
+            }
+            if !self.traders.contains(&swap.sender) {
+                self.traders.insert(swap.sender.clone());
+            }
+        }
+    }
+
+    pub fn summary(self, project_id: i32, date: NaiveDate) -> SwapDailySummary {
+        SwapDailySummary {
+            project_id,
+            date,
+            tx_count: self.tx_count,
+            volume_usd: self.volume_usd,
+            unique_traders: self.traders.len() as i32,
+            avg_trade_usd: if self.valued == 0 {
+                0.0
+            } else {
+                self.volume_usd / self.valued as f64
+            },
+        }
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
@@ -27,10 +147,465 @@ pub struct TokenTerminalData {
     pub afpu: String,
     pub arpu: String,
     pub token_trading_volume_30d: String,
+    /// Market cap over annualized fees and over annualized revenue, e.g. `12.4x`
+    pub pf_ratio: String,
+    pub ps_ratio: String,
+    pub market_cap_circulating: String,
+    pub market_cap_fully_diluted: String,
+    /// Tokens in circulation, e.g. `283.6m`
+    pub circulating_supply: String,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+impl TokenTerminalData {
+    /// Whether any financial metric was scraped, the ATH and ATL render apart from them
+    pub fn has_metrics(&self) -> bool {
+        [
+            &self.revenue_30d,
+            &self.revenue_annualized,
+            &self.expenses_30d,
+            &self.earnings_30d,
+            &self.fees_30d,
+            &self.fees_annualized,
+            &self.token_incentives_30d,
+            &self.monthly_active_users,
+            &self.afpu,
+            &self.arpu,
+            &self.token_trading_volume_30d,
+            &self.pf_ratio,
+            &self.ps_ratio,
+            &self.market_cap_circulating,
+            &self.market_cap_fully_diluted,
+            &self.circulating_supply,
+        ]
+        .iter()
+        .any(|metric| !metric.trim().is_empty())
+    }
+
+    /// Share of the fees of the last 30 days kept as protocol revenue, `None` when either
+    /// figure can't be parsed or there were no fees
+    pub fn take_rate_30d(&self) -> Option<f64> {
+        let fees = parse_financial_string(&self.fees_30d)?;
+        let revenue = parse_financial_string(&self.revenue_30d)?;
+        (fees != 0.0).then(|| revenue / fees)
+    }
+
+    /// Circulating market cap over annualized revenue, the price-to-earnings multiple of
+    /// TradFi, `None` when either figure can't be parsed or there is no revenue
+    pub fn pe_ratio(&self) -> Option<f64> {
+        let market_cap = parse_financial_string(&self.market_cap_circulating)?;
+        let revenue = parse_financial_string(&self.revenue_annualized)?;
+        (revenue != 0.0).then(|| market_cap / revenue)
+    }
+}
+
+/// Supply of a token in whole tokens
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct CirculatingSupply {
+    /// Every token minted and not burnt
+    pub total: Decimal,
+    /// What's left of `total` once the balances of the excluded supply addresses are taken out
+    pub circulating: Decimal,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
 pub struct MarketCap {
-    pub fully_diluted: f64,
+    /// `None` when the max supply of the token isn't known
+    pub fully_diluted: Option<f64>,
+    /// Of the circulating supply
     pub normal: f64,
+    pub supply: CirculatingSupply,
+}
+
+impl MarketCap {
+    /// Market caps at `price` of the circulating supply and of the max supply, if known,
+    /// multiplied as decimals
+    pub fn new(price: f64, supply: CirculatingSupply, max_supply: Option<f64>) -> Self {
+        let price = Decimal::from_f64(price).unwrap_or_default();
+        let cap = |supply: Decimal| price.saturating_mul(supply).to_f64().unwrap_or_default();
+        Self {
+            fully_diluted: max_supply
+                .map(|supply| cap(Decimal::from_f64(supply).unwrap_or_default())),
+            normal: cap(supply.circulating),
+            supply,
+        }
+    }
+}
+
+/// Activity of a project's GitHub repository
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct GithubStats {
+    pub stars: u64,
+    pub forks: u64,
+    pub contributors: u64,
+    /// Commits over the last 4 weeks
+    pub commits_4w: u64,
+}
+
+/// USD market data of a token as quoted by CoinMarketCap
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct CmcPriceData {
+    pub price: f64,
+    pub market_cap: f64,
+    pub volume_24h: f64,
+    pub circulating_supply: f64,
+    pub total_supply: f64,
+}
+
+/// USD market data of a token as quoted by CoinGecko
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CoinGeckoMarketData {
+    pub price: f64,
+    pub market_cap: f64,
+    /// Market cap if the whole max supply was in circulation, `None` without a max supply
+    pub fully_diluted_valuation: Option<f64>,
+    pub volume_24h: f64,
+    /// Change of the price over the last 24 hours, in percent
+    pub price_change_24h: f64,
+}
+
+/// Where the USD price of a token comes from
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceSource {
+    /// Reserves of the token's pools with USDC or USDT
+    Onchain,
+    /// CoinGecko quote of the coin id of a project with the token
+    Coingecko,
+    /// On-chain, falling back to CoinGecko when the token has no stablecoin pool
+    #[default]
+    Auto,
+}
+
+/// Where a point of a price history comes from
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PricePointSource {
+    /// Daily snapshot of the `price_usd` of the project
+    #[default]
+    Local,
+    /// CoinGecko market chart, filling the days without a snapshot
+    Coingecko,
+}
+
+impl PricePointSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PricePointSource::Local => "local",
+            PricePointSource::Coingecko => "coingecko",
+        }
+    }
+}
+
+/// USD price of a token on a UTC day
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PricePoint {
+    pub date: NaiveDate,
+    pub price: f64,
+    pub source: PricePointSource,
+}
+
+impl PricePoint {
+    /// One point per day, oldest first, taken from `local` when it has the day and from
+    /// `external` otherwise
+    pub fn merge(local: &[(NaiveDate, f64)], external: &[(NaiveDate, f64)]) -> Vec<PricePoint> {
+        let mut points: BTreeMap<NaiveDate, PricePoint> = BTreeMap::new();
+        let sourced = [
+            (external, PricePointSource::Coingecko),
+            (local, PricePointSource::Local),
+        ];
+        for (prices, source) in sourced {
+            for (date, price) in prices {
+                points.insert(
+                    *date,
+                    PricePoint {
+                        date: *date,
+                        price: *price,
+                        source,
+                    },
+                );
+            }
+        }
+        points.into_values().collect()
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct CrossRate {
+    pub base_token: String,
+    pub quote_token: String,
+    pub rate: f64,
+    pub base_price_usd: f64,
+    pub quote_price_usd: f64,
+}
+
+impl CrossRate {
+    /// Builds the rate of `base` expressed in `quote` from both USD prices.
+    /// Returns `None` when the quote price can't be divided by.
+    pub fn from_prices(
+        base: &str,
+        quote: &str,
+        base_price_usd: f64,
+        quote_price_usd: f64,
+    ) -> Option<Self> {
+        if !quote_price_usd.is_normal() || !base_price_usd.is_finite() {
+            return None;
+        }
+
+        Some(CrossRate {
+            base_token: base.to_string(),
+            quote_token: quote.to_string(),
+            rate: base_price_usd / quote_price_usd,
+            base_price_usd,
+            quote_price_usd,
+        })
+    }
+}
+
+#[test]
+fn test_cross_rate_from_prices() {
+    // e.g. CAKE at $2.50 and APT at $10.00
+    let rate = CrossRate::from_prices("CAKE", "APT", 2.5, 10.0).unwrap();
+
+    assert_eq!(rate.base_token, "CAKE");
+    assert_eq!(rate.quote_token, "APT");
+    assert_eq!(rate.rate, 0.25);
+    assert_eq!(rate.base_price_usd, 2.5);
+    assert_eq!(rate.quote_price_usd, 10.0);
+}
+
+#[test]
+fn test_cross_rate_same_price_is_one() {
+    let rate = CrossRate::from_prices("USDT", "USDC", 1.0, 1.0).unwrap();
+
+    assert_eq!(rate.rate, 1.0);
+}
+
+#[test]
+fn test_cross_rate_rejects_unusable_quote_price() {
+    assert!(CrossRate::from_prices("CAKE", "APT", 2.5, 0.0).is_none());
+    assert!(CrossRate::from_prices("CAKE", "APT", 2.5, f64::NAN).is_none());
+    assert!(CrossRate::from_prices("CAKE", "APT", f64::INFINITY, 10.0).is_none());
+}
+
+/// Gas paid by the transactions calling one entry function, in APT
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct EntryFunctionGas {
+    pub entry_function_id: String,
+    pub transactions: u64,
+    pub mean_apt: f64,
+    pub median_apt: f64,
+    pub p95_apt: f64,
+    pub total_apt: f64,
+    /// `None` when the APT price couldn't be fetched
+    pub total_usd: Option<f64>,
+}
+
+impl EntryFunctionGas {
+    /// Summarises the gas fees of `entry_function_id`, returns `None` when there are none
+    pub fn from_fees(
+        entry_function_id: &str,
+        mut fees_apt: Vec<f64>,
+        apt_price_usd: Option<f64>,
+    ) -> Option<Self> {
+        if fees_apt.is_empty() {
+            return None;
+        }
+        fees_apt.sort_by(f64::total_cmp);
+
+        let count = fees_apt.len();
+        let total_apt: f64 = fees_apt.iter().sum();
+        let median_apt = if count.is_multiple_of(2) {
+            (fees_apt[count / 2 - 1] + fees_apt[count / 2]) / 2.0
+        } else {
+            fees_apt[count / 2]
+        };
+        // Nearest-rank percentile
+        let p95_rank = (count as f64 * 0.95).ceil() as usize;
+
+        Some(EntryFunctionGas {
+            entry_function_id: entry_function_id.to_string(),
+            transactions: count as u64,
+            mean_apt: total_apt / count as f64,
+            median_apt,
+            p95_apt: fees_apt[p95_rank.max(1) - 1],
+            total_apt,
+            total_usd: apt_price_usd.map(|price| total_apt * price),
+        })
+    }
+}
+
+/// Gas spent on a project's entry functions over the last `days`
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct GasAnalytics {
+    pub address: String,
+    pub days: u64,
+    pub apt_price_usd: Option<f64>,
+    /// Sorted by total gas spent, most expensive first
+    pub entry_functions: Vec<EntryFunctionGas>,
+}
+
+#[test]
+fn test_entry_function_gas_from_fees() {
+    let fees = (1..=20).map(|n| n as f64 / 1000.0).collect();
+    let gas = EntryFunctionGas::from_fees("0x1::coin::transfer", fees, Some(10.0)).unwrap();
+    assert_eq!(gas.transactions, 20);
+    assert!((gas.total_apt - 0.21).abs() < 1e-9);
+    assert!((gas.mean_apt - 0.0105).abs() < 1e-9);
+    assert!((gas.median_apt - 0.0105).abs() < 1e-9);
+    assert_eq!(gas.p95_apt, 0.019);
+    assert!((gas.total_usd.unwrap() - 2.1).abs() < 1e-9);
+
+    let gas =
+        EntryFunctionGas::from_fees("0x1::coin::transfer", vec![0.3, 0.1, 0.2], None).unwrap();
+    assert_eq!(gas.median_apt, 0.2);
+    assert_eq!(gas.p95_apt, 0.3);
+    assert_eq!(gas.total_usd, None);
+
+    assert!(EntryFunctionGas::from_fees("0x1::coin::transfer", Vec::new(), None).is_none());
+}
+
+#[test]
+fn test_token_terminal_has_metrics() {
+    let data = TokenTerminalData {
+        ath: "$42.46".to_string(),
+        ..Default::default()
+    };
+    assert!(!data.has_metrics());
+    let data = TokenTerminalData {
+        fees_30d: "$13.30m".to_string(),
+        ..data
+    };
+    assert!(data.has_metrics());
+}
+
+#[test]
+fn test_take_rate_30d() {
+    let data = TokenTerminalData {
+        fees_30d: "$13.30m".to_string(),
+        revenue_30d: "$4.32m".to_string(),
+        ..Default::default()
+    };
+    assert!((data.take_rate_30d().unwrap() - 4.32 / 13.30).abs() < 1e-12);
+
+    let data = TokenTerminalData {
+        fees_30d: "$0".to_string(),
+        revenue_30d: "$4.32m".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(data.take_rate_30d(), None);
+    assert_eq!(TokenTerminalData::default().take_rate_30d(), None);
+}
+
+#[test]
+fn test_pe_ratio() {
+    let data = TokenTerminalData {
+        market_cap_circulating: "$612.4m".to_string(),
+        revenue_annualized: "$52.56m".to_string(),
+        ..Default::default()
+    };
+    assert!((data.pe_ratio().unwrap() - 612.4 / 52.56).abs() < 1e-12);
+
+    let data = TokenTerminalData {
+        market_cap_circulating: "$612.4m".to_string(),
+        revenue_annualized: "$0".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(data.pe_ratio(), None);
+    let data = TokenTerminalData {
+        revenue_annualized: "$52.56m".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(data.pe_ratio(), None);
+}
+
+#[test]
+fn test_swap_trades_pair() {
+    let swap = SwapTransaction {
+        token_sold: "0x1::aptos_coin::AptosCoin".to_string(),
+        token_bought: "0x1::cake::Cake".to_string(),
+        ..Default::default()
+    };
+
+    assert!(swap.trades_pair("0x1::aptos_coin::AptosCoin", "0x1::cake::Cake"));
+    assert!(swap.trades_pair("0x1::cake::Cake", "0x1::aptos_coin::AptosCoin"));
+    assert!(!swap.trades_pair("0x1::cake::Cake", "0x1::usdc::USDC"));
+}
+
+#[test]
+fn test_merge_price_history() {
+    let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+    let local = [(day(3), 8.1), (day(4), 8.3)];
+    let external = [(day(1), 7.5), (day(2), 7.9), (day(3), 8.0)];
+
+    let points = PricePoint::merge(&local, &external);
+    let sources: Vec<_> = points.iter().map(|point| point.source).collect();
+    assert_eq!(
+        points.iter().map(|point| point.date).collect::<Vec<_>>(),
+        [day(1), day(2), day(3), day(4)]
+    );
+    assert_eq!(
+        sources,
+        [
+            PricePointSource::Coingecko,
+            PricePointSource::Coingecko,
+            PricePointSource::Local,
+            PricePointSource::Local
+        ]
+    );
+    assert_eq!(points[2].price, 8.1);
+}
+
+#[test]
+fn test_market_cap_without_max_supply() {
+    let supply = CirculatingSupply {
+        total: Decimal::from(1_500_000),
+        circulating: Decimal::from(1_000_000),
+    };
+    let market_cap = MarketCap::new(2.5, supply, Some(4_000_000.0));
+    assert_eq!(market_cap.normal, 2_500_000.0);
+    assert_eq!(market_cap.fully_diluted, Some(10_000_000.0));
+
+    // An unknown max supply is no fully diluted cap rather than a zero one
+    let market_cap = MarketCap::new(2.5, supply, None);
+    assert_eq!(market_cap.normal, 2_500_000.0);
+    assert_eq!(market_cap.fully_diluted, None);
+}
+
+#[test]
+fn test_market_cap_is_exact() {
+    let supply = CirculatingSupply {
+        total: Decimal::from(7),
+        circulating: Decimal::from(3),
+    };
+    let market_cap = MarketCap::new(0.1, supply, Some(7.0));
+    assert_eq!(market_cap.normal, 0.3);
+    assert_eq!(market_cap.fully_diluted, Some(0.7));
+    // Multiplied as f64, both end up off in their last digit
+    assert_ne!(0.1 * 3.0, 0.3);
+    assert_ne!(0.1 * 7.0, 0.7);
+}
+
+#[test]
+fn test_swap_daily_summary_from_swaps() {
+    let date = NaiveDate::from_ymd_opt(2026, 10, 13).unwrap();
+    let swap = |sender: &str, sold_usd: Option<f64>, bought_usd: Option<f64>| SwapTransaction {
+        sender: sender.to_string(),
+        token_sold_usd: sold_usd,
+        token_bought_usd: bought_usd,
+        ..Default::default()
+    };
+    let swaps = [
+        swap("0xa", Some(100.0), Some(99.0)),
+        swap("0xb", None, Some(50.0)),
+        swap("0xa", None, None),
+    ];
+    let summary = SwapDailySummary::from_swaps(7, date, &swaps);
+    assert_eq!(summary.tx_count, 3);
+    assert_eq!(summary.volume_usd, 150.0);
+    assert_eq!(summary.unique_traders, 2);
+    // The swap that can't be valued doesn't drag the mean down
+    assert_eq!(summary.avg_trade_usd, 75.0);
+
+    let empty = SwapDailySummary::from_swaps(7, date, &[]);
+    assert_eq!((empty.tx_count, empty.avg_trade_usd), (0, 0.0));
 }
@@ -1,9 +1,18 @@
+use crate::models::{
+    Account, AccountBalanceSnapshot, Entity, NftHolding, OnChainAccount, Portfolio, PortfolioAsset,
+    Transaction,
+};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+
+use super::{nullable, StakingPositionResponse};
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct NewAccount {
     pub address: String,
+    /// Blockchain the address lives on, `aptos-mainnet` when omitted
+    pub network: Option<String>,
     pub entity_id: Option<i32>,
 }
 
@@ -11,12 +20,294 @@ pub struct NewAccount {
 pub struct AccountResponse {
     pub id: i32,
     pub address: String,
+    pub network: String,
     pub entity_id: Option<i32>,
+    /// Set when the address is a known one, only returned with a single account
+    pub label: Option<String>,
+    /// Entity owning the account, only returned with `include_entity=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity: Option<AccountEntityResponse>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+/// Name and type of the entity owning an account
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AccountEntityResponse {
+    pub id: i32,
+    pub name: String,
+    pub entity_type: Option<String>,
+}
+
+impl From<Entity> for AccountEntityResponse {
+    fn from(entity: Entity) -> Self {
+        Self {
+            id: entity.id,
+            name: entity.name,
+            entity_type: entity.entity_type,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AccountEntityQuery {
+    /// Whether to embed the name and type of the entity owning each account
+    pub include_entity: Option<bool>,
+}
+
+impl From<Account> for AccountResponse {
+    fn from(account: Account) -> Self {
+        Self {
+            id: account.id,
+            address: account.address,
+            network: account.network,
+            entity_id: account.entity_id,
+            label: None,
+            entity: None,
+            created_at: account.created_at.to_string(),
+            updated_at: account.updated_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AddressVerificationResponse {
+    pub address: String,
+    /// Whether the fullnode knows the account, which it does once it sent or received a
+    /// transaction
+    pub exists_on_chain: bool,
+    /// Missing when the account isn't on-chain
+    pub sequence_number: Option<u64>,
+    pub authentication_key: Option<String>,
+}
+
+impl AddressVerificationResponse {
+    pub fn new(address: String, account: Option<OnChainAccount>) -> Self {
+        Self {
+            address,
+            exists_on_chain: account.is_some(),
+            sequence_number: account.as_ref().map(|account| account.sequence_number),
+            authentication_key: account.map(|account| account.authentication_key),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TxCountResponse {
+    pub address: String,
+    /// Transactions the account sent or took part in
+    pub count: u64,
+}
+
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct UpdateAccount {
     pub entity_id: Option<i32>,
+    /// `updated_at` of the account as last read. The update is refused with a 409 if the
+    /// account changed since. Left out, only a change made while the update is applied is
+    /// refused.
+    #[schema(value_type = Option<String>, example = "2026-10-14 13:07:12.009780 UTC")]
+    pub expected_updated_at: Option<DateTime<Utc>>,
+}
+
+/// Fields to change, the others are left as they are
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PatchAccount {
+    /// `null` detaches the account from its entity
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i32>)]
+    pub entity_id: Option<Option<i32>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PortfolioAssetResponse {
+    pub coin_type: String,
+    pub symbol: String,
+    /// Amount in whole coins
+    pub amount: f64,
+    /// `false` when no USD price could be found for the coin, its value is then missing
+    pub price_available: bool,
+    pub price_usd: Option<f64>,
+    pub value_usd: Option<f64>,
+}
+
+impl From<PortfolioAsset> for PortfolioAssetResponse {
+    fn from(asset: PortfolioAsset) -> Self {
+        Self {
+            coin_type: asset.coin_type,
+            symbol: asset.symbol,
+            amount: asset.amount,
+            price_available: asset.price_usd.is_some(),
+            price_usd: asset.price_usd,
+            value_usd: asset.value_usd,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PortfolioResponse {
+    pub address: String,
+    /// Value of the priced coins and of the delegated stake
+    pub total_usd: f64,
+    /// Most valuable first, coins without a price last
+    pub assets: Vec<PortfolioAssetResponse>,
+    /// Stake delegated to pools, missing when it couldn't be fetched
+    pub staking: Option<Vec<StakingPositionResponse>>,
+    pub staked_usd: Option<f64>,
+}
+
+impl From<Portfolio> for PortfolioResponse {
+    fn from(portfolio: Portfolio) -> Self {
+        Self {
+            address: portfolio.address,
+            total_usd: portfolio.total_usd,
+            assets: portfolio
+                .assets
+                .into_iter()
+                .map(PortfolioAssetResponse::from)
+                .collect(),
+            staking: portfolio.staking.map(|positions| {
+                positions
+                    .into_iter()
+                    .map(StakingPositionResponse::from)
+                    .collect()
+            }),
+            staked_usd: portfolio.staked_usd,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TransactionsQuery {
+    /// Only return transactions older than this version, `next_before_version` of the previous
+    /// page
+    pub before_version: Option<i64>,
+    /// Same as `before_version`, the transactions after it in the listing
+    pub after_version: Option<i64>,
+    /// Number of transactions to return, 25 by default and at most 100
+    pub limit: Option<i64>,
+    /// Only return transactions calling an entry function starting with this, e.g. `0x1::coin::`
+    pub function: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TransactionResponse {
+    pub version: i64,
+    pub sender: Option<String>,
+    pub receiver: Option<String>,
+    /// Set when the sender is a known address
+    #[schema(example = "PancakeSwap Router")]
+    pub sender_label: Option<String>,
+    pub receiver_label: Option<String>,
+    pub contract: Option<String>,
+    /// `out` when the account sent the transaction, `in` otherwise
+    #[schema(example = "out")]
+    pub direction: String,
+    pub entry_function_id: Option<String>,
+    pub coin_type: Option<String>,
+    /// Amount deposited to the receiver, in the smallest unit of the coin
+    pub amount: Option<f64>,
+    pub timestamp: Option<String>,
+}
+
+impl From<Transaction> for TransactionResponse {
+    fn from(transaction: Transaction) -> Self {
+        Self {
+            version: transaction.version,
+            sender: transaction.sender,
+            receiver: transaction.receiver,
+            sender_label: transaction.sender_label,
+            receiver_label: transaction.receiver_label,
+            contract: transaction.contract,
+            direction: transaction.direction.as_str().to_string(),
+            entry_function_id: transaction.entry_function_id,
+            coin_type: transaction.coin_type,
+            amount: transaction.amount,
+            timestamp: transaction.timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TransactionHistoryResponse {
+    /// Newest first
+    pub items: Vec<TransactionResponse>,
+    pub limit: i64,
+    /// `before_version` of the next page, missing on the last one
+    pub next_before_version: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NftHoldingResponse {
+    pub token_data_id: String,
+    pub collection_name: String,
+    pub token_name: String,
+    /// Editions of the token held, 1 for a regular NFT
+    pub amount: f64,
+    /// Missing until the indexer has cached the image of the token
+    pub image_uri: Option<String>,
+}
+
+impl From<NftHolding> for NftHoldingResponse {
+    fn from(holding: NftHolding) -> Self {
+        Self {
+            token_data_id: holding.token_data_id,
+            collection_name: holding.collection_name,
+            token_name: holding.token_name,
+            amount: holding.amount,
+            image_uri: holding.image_uri,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BalanceHistoryQuery {
+    /// Number of snapshots to return, the latest 90 by default and at most 1000
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BalanceSnapshotResponse {
+    pub total_usd: f64,
+    pub recorded_at: String,
+}
+
+impl From<AccountBalanceSnapshot> for BalanceSnapshotResponse {
+    fn from(snapshot: AccountBalanceSnapshot) -> Self {
+        Self {
+            total_usd: snapshot.total_usd,
+            recorded_at: snapshot.recorded_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BalanceHistoryResponse {
+    pub address: String,
+    /// Whether the account is still snapshotted daily
+    pub watched: bool,
+    /// Oldest first
+    pub snapshots: Vec<BalanceSnapshotResponse>,
+}
+
+#[test]
+fn test_transactions_query_after_version() {
+    use axum::extract::Query;
+
+    let uri = "/transactions?after_version=99999&limit=25"
+        .parse()
+        .unwrap();
+    let Query(query) = Query::<TransactionsQuery>::try_from_uri(&uri).unwrap();
+    assert_eq!(query.after_version, Some(99999));
+    assert_eq!(query.limit, Some(25));
+
+    // Documented along with before_version
+    let params: Vec<String> = TransactionsQuery::into_params(|| None)
+        .into_iter()
+        .map(|param| param.name)
+        .collect();
+    assert!(params.contains(&"before_version".to_string()));
+    assert!(params.contains(&"after_version".to_string()));
 }
@@ -0,0 +1,101 @@
+use crate::models::{AlertDelivery, AlertRule};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{AnomalyResponse, SwapTransactionResponse};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewAlertRule {
+    /// `swap` for a rule fired by large swaps, the default, or `anomaly` for one fired by
+    /// spikes of the trading volume
+    #[schema(example = "swap")]
+    pub kind: Option<String>,
+    /// USD value of the tokens sold above which a swap fires the rule, or trading volume above
+    /// which an anomaly does
+    #[schema(example = 50000.0)]
+    pub min_usd: f64,
+    /// Receives a POST with the swap or the anomaly as JSON
+    #[schema(example = "https://hooks.example.com/whales")]
+    pub webhook_url: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateAlertRule {
+    pub min_usd: Option<f64>,
+    pub webhook_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlertRuleResponse {
+    pub id: i32,
+    pub project_id: i32,
+    /// `swap` or `anomaly`
+    pub kind: String,
+    pub min_usd: f64,
+    pub webhook_url: String,
+    /// Id of the user who created the rule
+    pub created_by: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<AlertRule> for AlertRuleResponse {
+    fn from(rule: AlertRule) -> Self {
+        Self {
+            id: rule.id,
+            project_id: rule.project_id,
+            kind: rule.kind,
+            min_usd: rule.min_usd,
+            webhook_url: rule.webhook_url,
+            created_by: rule.created_by,
+            created_at: rule.created_at.to_string(),
+            updated_at: rule.updated_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlertDeliveryResponse {
+    pub transaction_version: i64,
+    pub value_usd: f64,
+    /// Number of times the webhook was called, retries included
+    pub attempts: i32,
+    pub delivered: bool,
+    /// Status the webhook answered the last attempt with, missing when it could not be reached
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: String,
+}
+
+impl From<AlertDelivery> for AlertDeliveryResponse {
+    fn from(delivery: AlertDelivery) -> Self {
+        Self {
+            transaction_version: delivery.transaction_version,
+            value_usd: delivery.value_usd,
+            attempts: delivery.attempts,
+            delivered: delivery.delivered,
+            status_code: delivery.status_code,
+            error: delivery.error,
+            created_at: delivery.created_at.to_string(),
+        }
+    }
+}
+
+/// Body posted to the webhook of an alert rule when a swap fires it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SwapAlertPayload {
+    pub rule_id: i32,
+    pub project_id: i32,
+    pub min_usd: f64,
+    /// USD value of the tokens sold
+    pub value_usd: f64,
+    pub swap: SwapTransactionResponse,
+}
+
+/// Body posted to the webhooks of the alert rules of a project when its metrics turn anomalous
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnomalyAlertPayload {
+    pub rule_id: i32,
+    pub project_id: i32,
+    pub anomaly: AnomalyResponse,
+}
@@ -0,0 +1,30 @@
+use crate::models::AnomalyAlert;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnomalyResponse {
+    pub id: i32,
+    pub project_id: i32,
+    #[schema(example = "trading_volume")]
+    pub metric: String,
+    pub value: f64,
+    /// Mean of the daily values of the last 30 days the value was compared with
+    pub mean: f64,
+    pub std_dev: f64,
+    pub detected_at: String,
+}
+
+impl From<AnomalyAlert> for AnomalyResponse {
+    fn from(alert: AnomalyAlert) -> Self {
+        Self {
+            id: alert.id,
+            project_id: alert.project_id,
+            metric: alert.metric,
+            value: alert.value,
+            mean: alert.mean,
+            std_dev: alert.std_dev,
+            detected_at: alert.detected_at.to_string(),
+        }
+    }
+}
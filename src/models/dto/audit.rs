@@ -0,0 +1,50 @@
+use crate::models::AuditEntry;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// `user`, `account`, `entity` or `project`
+    pub resource_type: Option<String>,
+    /// ID of a single resource, set along with `resource_type`
+    pub resource_id: Option<i32>,
+    /// Number of entries to return, 50 by default and at most 500
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditEntryResponse {
+    pub id: i32,
+    /// User who made the change, `null` once they are deleted
+    pub user_id: Option<i32>,
+    /// `create` or `update`
+    #[schema(example = "update")]
+    pub action: String,
+    #[schema(example = "project")]
+    pub resource_type: String,
+    pub resource_id: i32,
+    /// Resource as the API returned it before the change, `null` for a creation
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<Value>,
+    /// Resource as the API returned it after the change
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<Value>,
+    pub created_at: String,
+}
+
+impl From<AuditEntry> for AuditEntryResponse {
+    fn from(entry: AuditEntry) -> Self {
+        Self {
+            id: entry.id,
+            user_id: entry.user_id,
+            action: entry.action,
+            resource_type: entry.resource_type,
+            resource_id: entry.resource_id,
+            before: entry.before,
+            after: entry.after,
+            created_at: entry.created_at.to_string(),
+        }
+    }
+}
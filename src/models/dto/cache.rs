@@ -0,0 +1,13 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Lookups of an in-memory cache since the server started
+#[derive(Debug, Serialize, ToSchema, PartialEq)]
+pub struct CacheStatsResponse {
+    #[schema(example = "swap_transactions")]
+    pub cache: String,
+    /// Lookups answered from the cache, stale entries included
+    pub hits: u64,
+    /// Lookups the cache had no entry for
+    pub misses: u64,
+}
@@ -0,0 +1,29 @@
+use crate::models::CoinFilter;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewCoinFilter {
+    #[schema(example = "0x1::aptos_coin::AptosCoin")]
+    pub coin_type: String,
+    /// `WHITELIST` or `BLACKLIST`, replacing the status of a coin already listed
+    #[schema(example = "WHITELIST")]
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CoinFilterResponse {
+    pub coin_type: String,
+    pub status: String,
+    pub created_at: String,
+}
+
+impl From<CoinFilter> for CoinFilterResponse {
+    fn from(filter: CoinFilter) -> Self {
+        Self {
+            coin_type: filter.coin_type,
+            status: filter.status,
+            created_at: filter.created_at.to_string(),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::ProjectResponse;
+use crate::models::{DexTotals, Project};
+
+/// Sum of a metric over the DEX projects reporting it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MetricTotal {
+    /// Missing when no project reports the metric
+    pub total: Option<f64>,
+    /// Projects the total is over
+    pub projects: i64,
+}
+
+/// Figures of the whole DEX ecosystem, for the homepage
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EcosystemSummary {
+    pub dex_projects: i64,
+    pub total_value_locked: MetricTotal,
+    /// USD volume traded over the last 7 days
+    pub trading_volume: MetricTotal,
+    /// DEX projects with the most value locked, most first
+    pub top_projects: Vec<ProjectResponse>,
+    /// When the least recently changed of the summed values was set, missing when there are none
+    pub updated_at: Option<String>,
+}
+
+impl EcosystemSummary {
+    pub fn new(totals: DexTotals, top_projects: Vec<Project>) -> Self {
+        Self {
+            dex_projects: totals.projects,
+            total_value_locked: MetricTotal {
+                total: totals.total_value_locked,
+                projects: totals.total_value_locked_projects,
+            },
+            trading_volume: MetricTotal {
+                total: totals.trading_volume,
+                projects: totals.trading_volume_projects,
+            },
+            top_projects: top_projects
+                .into_iter()
+                .map(ProjectResponse::from)
+                .collect(),
+            updated_at: totals.updated_at.map(|date| date.to_string()),
+        }
+    }
+}
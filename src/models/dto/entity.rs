@@ -1,17 +1,91 @@
+use crate::models::{CoinBalance, Entity};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
+use super::AccountPage;
 
+/// Entity to create, or the one an existing entity is replaced with
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateEntityInfo {
     pub name: String,
+    /// CEX, PROTOCOL, FUND or INDIVIDUAL, in any case
+    #[schema(example = "CEX")]
+    pub entity_type: Option<String>,
+    /// http(s) URL
+    pub website: Option<String>,
+    /// Twitter handle, with or without the `@`
+    #[schema(example = "binance")]
+    pub twitter: Option<String>,
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct EntityResponse {
     pub id: i32,
     pub name: String,
+    /// CEX, PROTOCOL, FUND or INDIVIDUAL, `None` until the entity is classified
+    pub entity_type: Option<String>,
+    pub website: Option<String>,
+    pub twitter: Option<String>,
+    pub description: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+impl From<Entity> for EntityResponse {
+    fn from(entity: Entity) -> Self {
+        Self {
+            id: entity.id,
+            name: entity.name,
+            entity_type: entity.entity_type,
+            website: entity.website,
+            twitter: entity.twitter,
+            description: entity.description,
+            created_at: entity.created_at.to_string(),
+            updated_at: entity.updated_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EntityFilterQuery {
+    /// Only list the entities of this type, CEX, PROTOCOL, FUND or INDIVIDUAL
+    #[serde(rename = "type")]
+    pub entity_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EntityAccountsQuery {
+    /// Sum the coins held by the listed accounts into `coin_totals`
+    pub include_balances: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CoinTotalResponse {
+    pub coin_type: String,
+    pub symbol: String,
+    /// Amount in whole coins, across all the listed accounts
+    pub amount: f64,
+}
+
+impl From<CoinBalance> for CoinTotalResponse {
+    fn from(balance: CoinBalance) -> Self {
+        Self {
+            amount: balance.amount / 10f64.powi(balance.decimals as i32),
+            coin_type: balance.coin_type,
+            symbol: balance.symbol,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EntityAccountsResponse {
+    pub entity: EntityResponse,
+    /// Page of the accounts of the entity
+    #[serde(flatten)]
+    pub accounts: AccountPage,
+    /// Coins held by the accounts of this page, only set with `include_balances=true`
+    pub coin_totals: Option<Vec<CoinTotalResponse>>,
+}
@@ -0,0 +1,80 @@
+use crate::models::{EntryFunctionGas, GasAnalytics};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GasQuery {
+    /// Only analyse this entry function, e.g. `0x1::coin::transfer`
+    pub entry_function: Option<String>,
+    /// Number of days to look back, 7 by default and at most 30
+    pub days: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EntryFunctionGasResponse {
+    pub entry_function_id: String,
+    pub transactions: u64,
+    pub mean_apt: f64,
+    pub median_apt: f64,
+    pub p95_apt: f64,
+    pub total_apt: f64,
+    /// Missing when the APT price couldn't be fetched
+    pub total_usd: Option<f64>,
+}
+
+impl From<EntryFunctionGas> for EntryFunctionGasResponse {
+    fn from(gas: EntryFunctionGas) -> Self {
+        Self {
+            entry_function_id: gas.entry_function_id,
+            transactions: gas.transactions,
+            mean_apt: gas.mean_apt,
+            median_apt: gas.median_apt,
+            p95_apt: gas.p95_apt,
+            total_apt: gas.total_apt,
+            total_usd: gas.total_usd,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GasAnalyticsResponse {
+    pub project_id: i32,
+    pub address: String,
+    pub days: u64,
+    pub apt_price_usd: Option<f64>,
+    /// Mean gas fee of a transaction across all entry functions, in APT
+    pub avg_gas_fee_apt: Option<f64>,
+    pub total_gas_spent_apt: f64,
+    pub total_gas_spent_usd: Option<f64>,
+    /// Most expensive entry function first
+    pub entry_functions: Vec<EntryFunctionGasResponse>,
+}
+
+impl GasAnalyticsResponse {
+    pub fn from_analytics(project_id: i32, analytics: GasAnalytics) -> Self {
+        let transactions: u64 = analytics
+            .entry_functions
+            .iter()
+            .map(|f| f.transactions)
+            .sum();
+        let total_gas_spent_apt: f64 = analytics.entry_functions.iter().map(|f| f.total_apt).sum();
+
+        Self {
+            project_id,
+            address: analytics.address,
+            days: analytics.days,
+            apt_price_usd: analytics.apt_price_usd,
+            avg_gas_fee_apt: (transactions > 0).then(|| total_gas_spent_apt / transactions as f64),
+            total_gas_spent_apt,
+            total_gas_spent_usd: analytics
+                .apt_price_usd
+                .map(|price| total_gas_spent_apt * price),
+            entry_functions: analytics
+                .entry_functions
+                .into_iter()
+                .map(EntryFunctionGasResponse::from)
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DependencyStatus {
+    #[schema(example = "database")]
+    pub name: String,
+    pub healthy: bool,
+    /// Why the dependency is considered unhealthy, if it is
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub healthy: bool,
+    pub dependencies: Vec<DependencyStatus>,
+}
+
+impl DependencyStatus {
+    pub fn new<E: ToString>(name: &str, result: Result<(), E>) -> Self {
+        Self {
+            name: name.to_string(),
+            healthy: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::models::KnownAddress;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewKnownAddress {
+    #[schema(example = "0xc7efb4076dbe143cbcd98cfaaa929ecfc8f299203dfff63b95ccb6bfe19850fa")]
+    pub address: String,
+    #[schema(example = "PancakeSwap Router")]
+    pub label: String,
+    #[schema(example = "DEX")]
+    pub category: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateKnownAddress {
+    pub label: Option<String>,
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KnownAddressResponse {
+    /// With all 64 hex digits written out
+    pub address: String,
+    pub label: String,
+    pub category: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<KnownAddress> for KnownAddressResponse {
+    fn from(known: KnownAddress) -> Self {
+        Self {
+            address: known.address,
+            label: known.label,
+            category: known.category,
+            created_at: known.created_at.to_string(),
+            updated_at: known.updated_at.to_string(),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::models::LendingStats;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::ProjectResponse;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LendingProjectResponse {
+    pub project: ProjectResponse,
+    pub total_supplied_usd: f64,
+    pub total_borrowed_usd: f64,
+    /// Share of the supplied value that is borrowed, missing when nothing is supplied
+    pub utilization_rate_pct: Option<f64>,
+    /// Yearly rates paid to lenders and by borrowers, in percent
+    pub supply_apy: Option<f64>,
+    pub borrow_apy: Option<f64>,
+    /// Missing while DeFiLlama doesn't report it
+    pub bad_debt_usd: Option<f64>,
+}
+
+impl LendingProjectResponse {
+    pub fn new(project: ProjectResponse, stats: LendingStats) -> Self {
+        Self {
+            project,
+            utilization_rate_pct: stats.utilization_rate_pct(),
+            total_supplied_usd: stats.total_supplied_usd,
+            total_borrowed_usd: stats.total_borrowed_usd,
+            supply_apy: stats.supply_apy,
+            borrow_apy: stats.borrow_apy,
+            bad_debt_usd: stats.bad_debt_usd,
+        }
+    }
+}
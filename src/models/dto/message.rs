@@ -1,5 +1,6 @@
 use serde::Serialize;
-#[derive(Debug, Serialize)]
+use utoipa::ToSchema;
+#[derive(Debug, Serialize, ToSchema)]
 pub struct Message {
     pub message: String,
 }
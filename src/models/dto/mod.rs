@@ -1,34 +1,160 @@
+pub mod health;
 pub mod message;
+pub mod page;
 pub mod user;
 pub mod entity;
+pub mod error;
 pub mod account;
+pub mod alert;
+pub mod anomaly;
+pub mod dashboard;
 pub mod project;
+pub mod staking;
+pub mod task;
+pub mod gas;
+pub mod known_address;
+pub mod coin_filter;
+pub mod lending;
+pub mod utils;
+pub mod version;
+pub mod audit;
+pub mod cache;
+pub use health::*;
 pub use message::Message;
+pub use page::*;
 pub use user::*;
 pub use entity::*;
+pub use error::ErrorBody;
 pub use account::*;
+pub use alert::*;
+pub use anomaly::*;
+pub use dashboard::*;
 pub use project::*;
+pub use staking::*;
+pub use task::*;
+pub use gas::*;
+pub use known_address::*;
+pub use coin_filter::*;
+pub use lending::*;
+pub use utils::*;
+pub use version::*;
+pub use audit::*;
+pub use cache::*;
 
+use serde::{Deserialize, Deserializer};
 use utoipa::{
     openapi::security::{Http, HttpAuthScheme, SecurityScheme},
     Modify, OpenApi,
 };
+
+/// Tells a field missing from a PATCH body, `None`, apart from one set to `null`, `Some(None)`.
+/// Needs `#[serde(default)]` on the field too.
+pub fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
 #[derive(OpenApi)]
 #[openapi(
     components(
         schemas(
+            ErrorBody,
+            Message,
             Profile,
             LoginInfo,
             RegisterInfo,
             TokenResponse,
+            PartialTokenResponse,
+            LoginResponse,
+            TotpSetupResponse,
+            TotpCode,
+            TotpChallenge,
             CreateEntityInfo,
             EntityResponse,
+            EntityAccountsResponse,
+            EntityPage,
+            CoinTotalResponse,
             NewAccount,
             UpdateAccount,
+            PatchAccount,
             AccountResponse,
+            AccountEntityResponse,
+            AddressVerificationResponse,
+            TxCountResponse,
+            AccountPage,
+            PortfolioResponse,
+            PortfolioAssetResponse,
+            TransactionResponse,
+            TransactionHistoryResponse,
+            NftHoldingResponse,
+            NftPage,
+            BalanceSnapshotResponse,
+            BalanceHistoryResponse,
             NewProject,
             UpdateProject,
+            PatchProject,
             ProjectResponse,
+            BasicProjectResponse,
+            AvatarResponse,
+            ProjectPage,
+            CloneProjectRequest,
+            ComputeFormulaResponse,
+            NewProjectFormula,
+            ProjectFormulaResponse,
+            MarketShareResponse,
+            MetricTotal,
+            EcosystemSummary,
+            AttributeChangeResponse,
+            AttributeHistoryResponse,
+            AttributeChangeEvent,
+            DexProjectResponse,
+            TokenTerminalResponse,
+            TreasuryProjectMixin,
+            TreasuryAccountFlowResponse,
+            TreasuryFlowResponse,
+            PricePointResponse,
+            PriceHistoryResponse,
+            SwapDailySummaryResponse,
+            TwickResponse,
+            HolderDiffResponse,
+            KnownPairResponse,
+            NewAlertRule,
+            UpdateAlertRule,
+            AlertRuleResponse,
+            AlertRulePage,
+            AlertDeliveryResponse,
+            AlertDeliveryPage,
+            SwapAlertPayload,
+            AnomalyResponse,
+            AnomalyPage,
+            AnomalyAlertPayload,
+            StakingProjectResponse,
+            LendingProjectResponse,
+            ValidatorInfoResponse,
+            StakingPositionResponse,
+            GasAnalyticsResponse,
+            EntryFunctionGasResponse,
+            CrossRateResponse,
+            SwapTransactionResponse,
+            SwapPage,
+            NewKnownAddress,
+            UpdateKnownAddress,
+            KnownAddressResponse,
+            KnownAddressPage,
+            UserPage,
+            WatchlistPage,
+            NewCoinFilter,
+            CoinFilterResponse,
+            TvlBackfillRequest,
+            TaskStartedResponse,
+            DependencyStatus,
+            ReadinessResponse,
+            ApiVersionResponse,
+            VersionsResponse,
+            AuditEntryResponse,
+            CacheStatsResponse,
         ),
     ),     
     modifiers(&SecurityAddon)
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{
+    AccountResponse, AlertDeliveryResponse, AlertRuleResponse, AnomalyResponse,
+    BasicProjectResponse, EntityResponse, KnownAddressResponse, NftHoldingResponse, Profile,
+    ProjectResponse, SwapTransactionResponse,
+};
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaginationQuery {
+    /// Number of items to return, 25 by default and at most 100
+    pub limit: Option<i64>,
+    /// Number of items to skip
+    pub offset: Option<i64>,
+    /// Opaque cursor returned as `next_cursor` by endpoints that paginate by cursor
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// Part of the name to look for, ignoring case
+    #[param(example = "pancake")]
+    pub q: String,
+    /// Number of results to return, 25 by default and at most 100
+    pub limit: Option<i64>,
+}
+
+/// One page of a list endpoint
+#[derive(Debug, Serialize, ToSchema)]
+#[aliases(
+    ProjectPage = Page<ProjectResponse>,
+    AccountPage = Page<AccountResponse>,
+    EntityPage = Page<EntityResponse>,
+    KnownAddressPage = Page<KnownAddressResponse>,
+    NftPage = Page<NftHoldingResponse>,
+    AlertRulePage = Page<AlertRuleResponse>,
+    AlertDeliveryPage = Page<AlertDeliveryResponse>,
+    AnomalyPage = Page<AnomalyResponse>,
+    SwapPage = Page<SwapTransactionResponse>,
+    UserPage = Page<Profile>,
+    WatchlistPage = Page<BasicProjectResponse>
+)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of items across all pages
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    /// Cursor of the next page, only set by endpoints that paginate by cursor
+    pub next_cursor: Option<String>,
+    /// Whether there are items after this page
+    pub has_more: bool,
+}
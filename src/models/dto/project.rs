@@ -1,15 +1,32 @@
+use std::collections::HashMap;
+
+use crate::models::{
+    Account, HolderDiff, KnownPair, NetFlow, PricePoint, Project, ProjectAttributeChange,
+    SwapDailySummary, TokenTerminalData,
+};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+
+use super::{nullable, parse_financial_string};
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct NewProject {
+    /// Unique among projects
+    pub name: Option<String>,
     pub token: String,
     pub category: String,
     pub contract_address: Option<String>,
+    /// Entry function of `contract_address` whose swaps make up the all time volume, computed
+    /// in the background once the project is created
+    pub volume_entry_function: Option<String>,
+    /// http(s) URL of the logo, or upload one to `/api/v1/project/{id}/avatar`
+    pub avatar_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateProject {
+    pub name: Option<String>,
     pub token: Option<String>,
     pub category: Option<String>,
     pub contract_address: Option<String>,
@@ -17,12 +34,133 @@ pub struct UpdateProject {
     pub core_developers: Option<i32>,
     pub code_commits: Option<i32>,
     pub total_value_locked: Option<f64>,
+    /// USD volume traded over the last 7 days
+    pub trading_volume: Option<f64>,
     pub token_max_supply: Option<i64>,
+    /// Protocol slug on DeFiLlama, e.g. `pancakeswap`
+    pub defi_llama_slug: Option<String>,
+    /// CoinMarketCap listing id, its quotes take precedence over on-chain prices
+    pub cmc_id: Option<i64>,
+    /// GitHub repository as `owner/name`, source of `core_developers` and `code_commits`
+    pub github_repo: Option<String>,
+    /// CoinGecko coin id, e.g. `pancakeswap-token`, source of the `_cg` market data
+    pub coingecko_id: Option<String>,
+    /// TokenTerminal project slug, e.g. `pancakeswap`, source of the DEX financials
+    pub token_terminal_slug: Option<String>,
+    /// Entry function of `contract_address` whose swaps make up the all time volume
+    pub volume_entry_function: Option<String>,
+    /// Fee taken on the input of each swap of the pools of `contract_address`, in basis
+    /// points, source of the all time fees
+    pub swap_fee_bps: Option<i32>,
+    /// Treasury, vesting and team accounts whose balances of the token are left out of its
+    /// circulating supply
+    pub excluded_supply_addresses: Option<Vec<String>>,
+    /// http(s) URL of the logo, or upload one to `/api/v1/project/{id}/avatar`
+    pub avatar_url: Option<String>,
+    /// `updated_at` of the project as last read. The update is refused with a 409 if the
+    /// project changed since, by another user or a background refresh. Left out, only a change
+    /// made while the update is applied is refused.
+    #[schema(value_type = Option<String>, example = "2026-10-14 13:07:12.009780 UTC")]
+    pub expected_updated_at: Option<DateTime<Utc>>,
+}
+
+/// Fields to change, the others are left as they are. Nullable fields set to `null` are cleared.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PatchProject {
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub name: Option<Option<String>>,
+    pub token: Option<String>,
+    pub category: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub contract_address: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i32>)]
+    pub num_chains: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i32>)]
+    pub core_developers: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i32>)]
+    pub code_commits: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<f64>)]
+    pub total_value_locked: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<f64>)]
+    pub trading_volume: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i64>)]
+    pub token_max_supply: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub defi_llama_slug: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i64>)]
+    pub cmc_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub github_repo: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub coingecko_id: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub token_terminal_slug: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub volume_entry_function: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i32>)]
+    pub swap_fee_bps: Option<Option<i32>>,
+    /// An empty list counts every balance as circulating again
+    pub excluded_supply_addresses: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub avatar_url: Option<Option<String>>,
+}
+
+impl PatchProject {
+    /// Writes the fields present in the body over `project`
+    pub fn apply_to(self, project: &mut Project) {
+        fn set<T>(field: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        set(&mut project.name, self.name);
+        set(&mut project.token, self.token);
+        set(&mut project.category, self.category);
+        set(&mut project.contract_address, self.contract_address);
+        set(&mut project.num_chains, self.num_chains);
+        set(&mut project.core_developers, self.core_developers);
+        set(&mut project.code_commits, self.code_commits);
+        set(&mut project.total_value_locked, self.total_value_locked);
+        set(&mut project.trading_volume, self.trading_volume);
+        set(&mut project.token_max_supply, self.token_max_supply);
+        set(&mut project.defi_llama_slug, self.defi_llama_slug);
+        set(&mut project.cmc_id, self.cmc_id);
+        set(&mut project.github_repo, self.github_repo);
+        set(&mut project.coingecko_id, self.coingecko_id);
+        set(&mut project.token_terminal_slug, self.token_terminal_slug);
+        set(
+            &mut project.volume_entry_function,
+            self.volume_entry_function,
+        );
+        set(&mut project.swap_fee_bps, self.swap_fee_bps);
+        set(
+            &mut project.excluded_supply_addresses,
+            self.excluded_supply_addresses,
+        );
+        set(&mut project.avatar_url, self.avatar_url);
+    }
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ProjectResponse {
     pub id: i32,
+    pub name: Option<String>,
     pub token: String,
     pub category: String,
     pub contract_address: Option<String>,
@@ -30,7 +168,742 @@ pub struct ProjectResponse {
     pub core_developers: Option<i32>,
     pub code_commits: Option<i32>,
     pub total_value_locked: Option<f64>,
+    pub trading_volume: Option<f64>,
     pub token_max_supply: Option<i64>,
+    pub defi_llama_slug: Option<String>,
+    pub cmc_id: Option<i64>,
+    pub github_repo: Option<String>,
+    pub coingecko_id: Option<String>,
+    /// USD price of the token as last quoted by CoinGecko
+    pub price_usd_cg: Option<f64>,
+    pub market_cap_cg: Option<f64>,
+    /// USD volume traded over the last 24 hours as last quoted by CoinGecko
+    pub volume_24h_cg: Option<f64>,
+    /// USD price of the token at the last daily snapshot
+    pub price_usd: Option<f64>,
+    pub token_terminal_slug: Option<String>,
+    pub volume_entry_function: Option<String>,
+    pub swap_fee_bps: Option<i32>,
+    pub excluded_supply_addresses: Vec<String>,
+    pub avatar_url: Option<String>,
+    /// Project this one was cloned from
+    pub cloned_from: Option<i32>,
+    /// `false` once no pair reserves are left at the contract address
+    pub active: bool,
     pub created_at: String,
     pub updated_at: String,
+    /// Every numeric attribute set, by name, including those without a field of their own.
+    /// Left out when asked with `include_attributes=false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<HashMap<String, f64>>, example = json!({"total_value_locked": 1250000.0, "twick_7d": 980000.0}))]
+    pub attributes: Option<HashMap<String, serde_json::Value>>,
+}
+
+impl From<Project> for ProjectResponse {
+    fn from(project: Project) -> Self {
+        let attributes = Project::NUMERIC_ATTRIBUTES
+            .iter()
+            .filter_map(|key| Some((key.to_string(), project.get_float(key)?.into())))
+            .collect();
+        Self {
+            id: project.id,
+            name: project.name,
+            token: project.token,
+            category: project.category,
+            contract_address: project.contract_address,
+            num_chains: project.num_chains,
+            core_developers: project.core_developers,
+            code_commits: project.code_commits,
+            total_value_locked: project.total_value_locked,
+            trading_volume: project.trading_volume,
+            token_max_supply: project.token_max_supply,
+            defi_llama_slug: project.defi_llama_slug,
+            cmc_id: project.cmc_id,
+            github_repo: project.github_repo,
+            coingecko_id: project.coingecko_id,
+            price_usd_cg: project.price_usd_cg,
+            market_cap_cg: project.market_cap_cg,
+            volume_24h_cg: project.volume_24h_cg,
+            price_usd: project.price_usd,
+            token_terminal_slug: project.token_terminal_slug,
+            volume_entry_function: project.volume_entry_function,
+            swap_fee_bps: project.swap_fee_bps,
+            excluded_supply_addresses: project.excluded_supply_addresses,
+            avatar_url: project.avatar_url,
+            cloned_from: project.cloned_from,
+            active: project.active,
+            created_at: project.created_at.to_string(),
+            updated_at: project.updated_at.to_string(),
+            attributes: Some(attributes),
+        }
+    }
+}
+
+/// Project with its key metrics only, as listed on a dashboard
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BasicProjectResponse {
+    pub id: i32,
+    pub name: Option<String>,
+    pub token: String,
+    pub category: String,
+    pub avatar_url: Option<String>,
+    pub total_value_locked: Option<f64>,
+    pub trading_volume: Option<f64>,
+    /// USD price of the token at the last daily snapshot
+    pub price_usd: Option<f64>,
+    pub market_cap_cg: Option<f64>,
+    /// USD volume traded over the last 24 hours as last quoted by CoinGecko
+    pub volume_24h_cg: Option<f64>,
+    /// `false` once no pair reserves are left at the contract address
+    pub active: bool,
+    pub updated_at: String,
+}
+
+impl From<Project> for BasicProjectResponse {
+    fn from(project: Project) -> Self {
+        Self {
+            id: project.id,
+            name: project.name,
+            token: project.token,
+            category: project.category,
+            avatar_url: project.avatar_url,
+            total_value_locked: project.total_value_locked,
+            trading_volume: project.trading_volume,
+            price_usd: project.price_usd,
+            market_cap_cg: project.market_cap_cg,
+            volume_24h_cg: project.volume_24h_cg,
+            active: project.active,
+            updated_at: project.updated_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AvatarResponse {
+    /// URL the uploaded avatar is served at, now the `avatar_url` of the project
+    #[schema(example = "/api/v1/avatars/4-1760448000000.png")]
+    pub avatar_url: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CloneProjectRequest {
+    /// Name of the clone, unique among projects
+    pub new_name: String,
+    /// Contract of the clone, which then has none when omitted
+    pub new_contract_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ComputeFormulaQuery {
+    /// Expression over the project's numeric attributes, e.g. `total_value_locked / num_chains`
+    pub formula: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComputeFormulaResponse {
+    pub formula: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewProjectFormula {
+    pub name: String,
+    #[schema(example = "total_value_locked / num_chains")]
+    pub formula: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProjectFormulaResponse {
+    pub id: i32,
+    pub project_id: i32,
+    pub name: String,
+    pub formula: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MarketShareResponse {
+    pub project_id: i32,
+    pub trading_volume: Option<f64>,
+    /// Percentage of the trading volume of all DEX projects, missing when there is no volume to compare
+    pub market_share_pct: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SwapsQuery {
+    /// Coin type of one side of the pair, e.g. `0x1::aptos_coin::AptosCoin`
+    pub token_a: Option<String>,
+    /// Coin type of the other side of the pair, set along with `token_a`
+    pub token_b: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectViewQuery {
+    /// Map every numeric attribute set into `attributes`, true by default. False gives the slim
+    /// payload, the typed fields only.
+    pub include_attributes: Option<bool>,
+}
+
+impl ProjectViewQuery {
+    pub fn response(&self, project: Project) -> ProjectResponse {
+        let response = ProjectResponse::from(project);
+        match self.include_attributes {
+            Some(false) => ProjectResponse {
+                attributes: None,
+                ..response
+            },
+            _ => response,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectFilterQuery {
+    /// Only list the projects with this numeric attribute set, e.g. `total_value_locked`
+    pub attr: Option<String>,
+    /// Lowest value of `attr` to list, inclusive
+    pub min: Option<f64>,
+    /// Highest value of `attr` to list, inclusive
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AttributeHistoryQuery {
+    /// Number of changes to return, 50 by default and at most 500
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AttributeHistoryCsvQuery {
+    /// Numeric attribute, e.g. `total_value_locked`
+    pub key: String,
+    /// Only export the changes of the last days, 30 by default and at most 365
+    pub days: Option<i64>,
+    /// Number of changes to export, 50 by default and at most 500
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttributeChangeResponse {
+    pub old_value: Option<f64>,
+    pub new_value: Option<f64>,
+    /// Id of the user who made the change, or `scheduler`
+    #[schema(example = "42")]
+    pub changed_by: String,
+    pub changed_at: String,
+}
+
+impl From<ProjectAttributeChange> for AttributeChangeResponse {
+    fn from(change: ProjectAttributeChange) -> Self {
+        Self {
+            old_value: change.old_value,
+            new_value: change.new_value,
+            changed_by: change.changed_by,
+            changed_at: change.changed_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttributeHistoryResponse {
+    pub project_id: i32,
+    #[schema(example = "total_value_locked")]
+    pub key: String,
+    /// Newest first
+    pub changes: Vec<AttributeChangeResponse>,
+}
+
+/// Data of the `attribute` events of a project metrics stream
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttributeChangeEvent {
+    pub project_id: i32,
+    #[schema(example = "total_value_locked")]
+    pub key: String,
+    pub old_value: Option<f64>,
+    pub new_value: Option<f64>,
+    #[schema(example = "42")]
+    pub changed_by: String,
+    pub changed_at: String,
+}
+
+impl From<ProjectAttributeChange> for AttributeChangeEvent {
+    fn from(change: ProjectAttributeChange) -> Self {
+        Self {
+            project_id: change.project_id,
+            key: change.key,
+            old_value: change.old_value,
+            new_value: change.new_value,
+            changed_by: change.changed_by,
+            changed_at: change.changed_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DexProjectResponse {
+    pub project: ProjectResponse,
+    /// Highest and lowest USD price of the token in its daily snapshots, missing until the
+    /// first snapshot
+    pub ath_onchain: Option<f64>,
+    pub atl_onchain: Option<f64>,
+    /// When the highest and lowest prices were snapshotted
+    pub ath_date: Option<String>,
+    pub atl_date: Option<String>,
+    /// USD market cap of the circulating supply of the token, missing when it couldn't be
+    /// priced or its supply read
+    pub market_cap_usd: Option<f64>,
+    /// Supply of the token in whole tokens, missing along with the market cap
+    pub total_supply: Option<f64>,
+    /// `total_supply` without the balances of the `excluded_supply_addresses`
+    pub circulating_supply: Option<f64>,
+    /// USD market cap of the max supply, also missing when the project has no
+    /// `token_max_supply`
+    pub fully_diluted_market_cap_usd: Option<f64>,
+    /// TokenTerminal circulating market cap over annualized revenue, missing without
+    /// TokenTerminal financials or revenue
+    #[schema(example = 11.65)]
+    pub pe_ratio: Option<f64>,
+    /// Annualized revenue as a percentage of the circulating market cap, the inverse of
+    /// `pe_ratio`
+    #[schema(example = 8.58)]
+    pub earnings_yield_pct: Option<f64>,
+    /// USD volume of every swap ever made through the volume entry function, refreshed weekly
+    /// and missing until first computed
+    pub all_time_volume_usd: Option<f64>,
+    /// USD fees earned on every swap of the pools at the swap fee, refreshed weekly and
+    /// missing until first summed
+    pub all_time_fees_usd: Option<f64>,
+    /// Financials scraped from TokenTerminal, missing when the project has no
+    /// `token_terminal_slug` or the page could not be scraped
+    pub token_terminal: Option<TokenTerminalResponse>,
+    /// Missing when the project has no treasury accounts
+    pub treasury: Option<TreasuryProjectMixin>,
+}
+
+/// Cash flow of the treasury of a project, as last stored by the treasury flow task
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TreasuryProjectMixin {
+    /// Addresses of the treasury accounts
+    pub treasury_accounts: Vec<String>,
+    /// Summed over the treasury accounts, missing until the task first ran
+    pub treasury_inflow_7d: Option<f64>,
+    pub treasury_outflow_7d: Option<f64>,
+    pub treasury_net_flow_7d: Option<f64>,
+}
+
+impl TreasuryProjectMixin {
+    /// `None` when there are no treasury accounts
+    pub fn new(project: &Project, accounts: &[Account]) -> Option<Self> {
+        (!accounts.is_empty()).then(|| Self {
+            treasury_accounts: accounts
+                .iter()
+                .map(|account| account.address.clone())
+                .collect(),
+            treasury_inflow_7d: project.treasury_inflow_7d,
+            treasury_outflow_7d: project.treasury_outflow_7d,
+            treasury_net_flow_7d: project.treasury_net_flow_7d,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TreasuryAccountFlowResponse {
+    pub address: String,
+    pub inflow_usd: f64,
+    pub outflow_usd: f64,
+    /// Positive when more came in than left
+    pub net_flow_usd: f64,
+}
+
+impl TreasuryAccountFlowResponse {
+    pub fn new(address: String, flow: &NetFlow) -> Self {
+        Self {
+            address,
+            inflow_usd: flow.inflow_usd,
+            outflow_usd: flow.outflow_usd,
+            net_flow_usd: flow.net_usd(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TreasuryFlowResponse {
+    pub project_id: i32,
+    /// Days the flows are summed over, today included
+    pub days: i64,
+    /// Summed over the treasury accounts, leaving out what they sent each other
+    pub inflow_usd: f64,
+    pub outflow_usd: f64,
+    pub net_flow_usd: f64,
+    /// Whether an account moved too many coins for all of them to be summed, the flows then
+    /// leave its oldest moves out
+    pub truncated: bool,
+    pub accounts: Vec<TreasuryAccountFlowResponse>,
+}
+
+/// Metrics of a project as TokenTerminal displays them, with each dollar figure also parsed
+/// into a number. A figure that can't be parsed, e.g. a blank one, has no number.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenTerminalResponse {
+    #[schema(example = "$4.32")]
+    pub ath: String,
+    pub ath_usd: Option<f64>,
+    pub ath_last: String,
+    pub atl: String,
+    pub atl_usd: Option<f64>,
+    pub atl_last: String,
+    #[schema(example = "$4.32m")]
+    pub revenue_30d: String,
+    #[schema(example = 4320000.0)]
+    pub revenue_30d_usd: Option<f64>,
+    pub revenue_annualized: String,
+    pub revenue_annualized_usd: Option<f64>,
+    pub expenses_30d: String,
+    pub expenses_30d_usd: Option<f64>,
+    /// Negative when expenses exceed revenue
+    pub earnings_30d: String,
+    pub earnings_30d_usd: Option<f64>,
+    pub fees_30d: String,
+    pub fees_30d_usd: Option<f64>,
+    pub fees_annualized: String,
+    pub fees_annualized_usd: Option<f64>,
+    pub token_incentives_30d: String,
+    pub token_incentives_30d_usd: Option<f64>,
+    #[schema(example = "120.5k")]
+    pub monthly_active_users: String,
+    #[schema(example = 120500.0)]
+    pub monthly_active_users_count: Option<f64>,
+    pub afpu: String,
+    pub afpu_usd: Option<f64>,
+    pub arpu: String,
+    pub arpu_usd: Option<f64>,
+    pub token_trading_volume_30d: String,
+    pub token_trading_volume_30d_usd: Option<f64>,
+    #[schema(example = "12.4x")]
+    pub pf_ratio: String,
+    #[schema(example = 12.4)]
+    pub pf_ratio_value: Option<f64>,
+    pub ps_ratio: String,
+    pub ps_ratio_value: Option<f64>,
+    pub market_cap_circulating: String,
+    pub market_cap_circulating_usd: Option<f64>,
+    pub market_cap_fully_diluted: String,
+    pub market_cap_fully_diluted_usd: Option<f64>,
+    pub circulating_supply: String,
+    pub circulating_supply_count: Option<f64>,
+    /// Share of the fees of the last 30 days kept as revenue
+    pub take_rate_30d: Option<f64>,
+}
+
+impl From<TokenTerminalData> for TokenTerminalResponse {
+    fn from(data: TokenTerminalData) -> Self {
+        Self {
+            ath_usd: parse_financial_string(&data.ath),
+            atl_usd: parse_financial_string(&data.atl),
+            revenue_30d_usd: parse_financial_string(&data.revenue_30d),
+            revenue_annualized_usd: parse_financial_string(&data.revenue_annualized),
+            expenses_30d_usd: parse_financial_string(&data.expenses_30d),
+            earnings_30d_usd: parse_financial_string(&data.earnings_30d),
+            fees_30d_usd: parse_financial_string(&data.fees_30d),
+            fees_annualized_usd: parse_financial_string(&data.fees_annualized),
+            token_incentives_30d_usd: parse_financial_string(&data.token_incentives_30d),
+            monthly_active_users_count: parse_financial_string(&data.monthly_active_users),
+            afpu_usd: parse_financial_string(&data.afpu),
+            arpu_usd: parse_financial_string(&data.arpu),
+            token_trading_volume_30d_usd: parse_financial_string(&data.token_trading_volume_30d),
+            pf_ratio_value: parse_ratio(&data.pf_ratio),
+            ps_ratio_value: parse_ratio(&data.ps_ratio),
+            market_cap_circulating_usd: parse_financial_string(&data.market_cap_circulating),
+            market_cap_fully_diluted_usd: parse_financial_string(&data.market_cap_fully_diluted),
+            circulating_supply_count: parse_financial_string(&data.circulating_supply),
+            take_rate_30d: data.take_rate_30d(),
+            ath: data.ath,
+            ath_last: data.ath_last,
+            atl: data.atl,
+            atl_last: data.atl_last,
+            revenue_30d: data.revenue_30d,
+            revenue_annualized: data.revenue_annualized,
+            expenses_30d: data.expenses_30d,
+            earnings_30d: data.earnings_30d,
+            fees_30d: data.fees_30d,
+            fees_annualized: data.fees_annualized,
+            token_incentives_30d: data.token_incentives_30d,
+            monthly_active_users: data.monthly_active_users,
+            afpu: data.afpu,
+            arpu: data.arpu,
+            token_trading_volume_30d: data.token_trading_volume_30d,
+            pf_ratio: data.pf_ratio,
+            ps_ratio: data.ps_ratio,
+            market_cap_circulating: data.market_cap_circulating,
+            market_cap_fully_diluted: data.market_cap_fully_diluted,
+            circulating_supply: data.circulating_supply,
+        }
+    }
+}
+
+/// Parses a multiple like `12.4x`
+fn parse_ratio(ratio: &str) -> Option<f64> {
+    parse_financial_string(ratio.trim().strip_suffix('x')?)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PriceHistoryQuery {
+    /// Number of days to chart, today included, 30 by default and at most 365
+    pub days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PricePointResponse {
+    #[schema(example = "2026-10-14")]
+    pub date: String,
+    pub price: f64,
+    /// `local` for a daily snapshot of the project, `coingecko` for a day filled from its
+    /// market chart
+    #[schema(example = "local")]
+    pub source: String,
+}
+
+impl From<PricePoint> for PricePointResponse {
+    fn from(point: PricePoint) -> Self {
+        Self {
+            date: point.date.to_string(),
+            price: point.price,
+            source: point.source.as_str().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PriceHistoryResponse {
+    pub project_id: i32,
+    pub token: String,
+    pub days: i64,
+    /// One per day, oldest first. Days no source has a price for are left out.
+    pub points: Vec<PricePointResponse>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SwapSummaryQuery {
+    /// First day, 29 days before `end` by default
+    #[param(value_type = Option<String>, example = "2026-09-14")]
+    pub start: Option<NaiveDate>,
+    /// Last day included, yesterday by default. At most 365 days after `start`.
+    #[param(value_type = Option<String>, example = "2026-10-13")]
+    pub end: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SwapDailySummaryResponse {
+    #[schema(example = "2026-10-13")]
+    pub date: String,
+    pub tx_count: i32,
+    /// USD value of the swaps at the prices they had once the day was over, swaps of tokens
+    /// without a price left out
+    pub volume_usd: f64,
+    pub unique_traders: i32,
+    pub avg_trade_usd: f64,
+}
+
+impl From<SwapDailySummary> for SwapDailySummaryResponse {
+    fn from(summary: SwapDailySummary) -> Self {
+        Self {
+            date: summary.date.to_string(),
+            tx_count: summary.tx_count,
+            volume_usd: summary.volume_usd,
+            unique_traders: summary.unique_traders,
+            avg_trade_usd: summary.avg_trade_usd,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TwickQuery {
+    /// Hours of liquidity weighted, 168 (7 days) by default and at most 8760 (365 days)
+    pub window_hours: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TwickResponse {
+    pub project_id: i32,
+    pub window_hours: u64,
+    /// Total value locked integrated over the window, in USD-hours
+    pub twick_usd_hours: f64,
+    /// Total value locked averaged over the window, in USD
+    pub average_liquidity_usd: f64,
+}
+
+impl TwickResponse {
+    pub fn new(project_id: i32, window_hours: u64, twick_usd_hours: f64) -> Self {
+        Self {
+            project_id,
+            window_hours,
+            twick_usd_hours,
+            average_liquidity_usd: twick_usd_hours / window_hours as f64,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HolderDiffQuery {
+    /// Days between the latest snapshot and the one compared with, 7 by default and at most
+    /// 365. The latest snapshot before is used when none was taken that day.
+    pub days: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HolderDiffResponse {
+    pub project_id: i32,
+    #[schema(example = "0x1::aptos_coin::AptosCoin")]
+    pub token: String,
+    /// Holders on `date`
+    pub holders: u64,
+    /// Day of the latest snapshot of the holders, taken daily
+    #[schema(example = "2026-10-14")]
+    pub date: String,
+    /// Holders on `date` who didn't hold the token on `compared_to`
+    pub new_holders: u64,
+    /// Holders on `compared_to` who don't hold the token anymore
+    pub lost_holders: u64,
+    /// New holders who held the token in a snapshot before `compared_to`
+    pub returning_holders: u64,
+    /// Day of the snapshot compared with, `None` when no snapshot was taken that long ago
+    #[schema(example = "2026-10-07")]
+    pub compared_to: Option<String>,
+    /// Whether every holder was compared, the counts are scaled up from a sample of the
+    /// holders otherwise
+    pub exact: bool,
+}
+
+impl HolderDiffResponse {
+    pub fn new(project_id: i32, token: String, diff: HolderDiff) -> Self {
+        Self {
+            project_id,
+            token,
+            holders: diff.holders,
+            date: diff.date.to_string(),
+            new_holders: diff.new_holders,
+            lost_holders: diff.lost_holders,
+            returning_holders: diff.returning_holders,
+            compared_to: diff.compared_to.map(|date| date.to_string()),
+            exact: diff.exact,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KnownPairResponse {
+    #[schema(example = "0x1::aptos_coin::AptosCoin")]
+    pub token_a: String,
+    pub token_b: String,
+    /// When the pair was first found in the swap events of the project
+    pub first_seen_at: String,
+    pub last_seen_at: String,
+}
+
+impl From<KnownPair> for KnownPairResponse {
+    fn from(pair: KnownPair) -> Self {
+        Self {
+            token_a: pair.token_a,
+            token_b: pair.token_b,
+            first_seen_at: pair.first_seen_at.to_string(),
+            last_seen_at: pair.last_seen_at.to_string(),
+        }
+    }
+}
+
+#[test]
+fn test_patch_project_clears_null_fields_only() {
+    let mut project = Project {
+        token: "0x1::cake::Cake".to_string(),
+        contract_address: Some("0xc7ef".to_string()),
+        total_value_locked: Some(800.0),
+        github_repo: Some("pancakeswap/pancake-frontend".to_string()),
+        ..Default::default()
+    };
+    let patch: PatchProject =
+        serde_json::from_str(r#"{"contract_address": null, "total_value_locked": 900.0}"#).unwrap();
+    patch.apply_to(&mut project);
+
+    assert_eq!(project.contract_address, None);
+    assert_eq!(project.total_value_locked, Some(900.0));
+    assert_eq!(project.token, "0x1::cake::Cake");
+    assert_eq!(
+        project.github_repo.as_deref(),
+        Some("pancakeswap/pancake-frontend")
+    );
+}
+
+#[test]
+fn test_token_terminal_response_parses_figures() {
+    let data = TokenTerminalData {
+        ath: "$4.32".to_string(),
+        revenue_30d: "$1.2m".to_string(),
+        earnings_30d: "-$850k".to_string(),
+        fees_30d: "$4.8m".to_string(),
+        monthly_active_users: "120.5k".to_string(),
+        ..Default::default()
+    };
+    let response = TokenTerminalResponse::from(data);
+
+    assert_eq!(response.ath_usd, Some(4.32));
+    assert_eq!(response.revenue_30d_usd, Some(1_200_000.0));
+    assert_eq!(response.earnings_30d_usd, Some(-850_000.0));
+    assert_eq!(response.monthly_active_users_count, Some(120_500.0));
+    assert_eq!(response.take_rate_30d, Some(0.25));
+    assert_eq!(response.pf_ratio_value, None);
+    // Figures that weren't scraped have no number
+    assert_eq!(response.expenses_30d, "");
+    assert_eq!(response.expenses_30d_usd, None);
+}
+
+#[test]
+fn test_token_terminal_response_parses_valuation() {
+    let data = TokenTerminalData {
+        pf_ratio: "12.4x".to_string(),
+        ps_ratio: "38.9".to_string(),
+        market_cap_fully_diluted: "$1.01b".to_string(),
+        circulating_supply: "283.6m".to_string(),
+        ..Default::default()
+    };
+    let response = TokenTerminalResponse::from(data);
+
+    assert_eq!(response.pf_ratio_value, Some(12.4));
+    // A ratio without its `x` isn't a TokenTerminal multiple
+    assert_eq!(response.ps_ratio_value, None);
+    assert_eq!(response.market_cap_fully_diluted_usd, Some(1_010_000_000.0));
+    assert_eq!(response.circulating_supply_count, Some(283_600_000.0));
+}
+
+#[test]
+fn test_project_response_attributes() {
+    let project = Project {
+        num_chains: Some(3),
+        total_value_locked: Some(1250.0),
+        twick_7d: Some(980.0),
+        ..Default::default()
+    };
+    let view = |include_attributes| ProjectViewQuery { include_attributes };
+
+    let attributes = view(None).response(project.clone()).attributes.unwrap();
+    assert_eq!(attributes.len(), 3);
+    assert_eq!(attributes["num_chains"], 3.0);
+    // Attributes without a field of their own are only in the map
+    assert_eq!(attributes["twick_7d"], 980.0);
+    assert!(view(Some(true))
+        .response(project.clone())
+        .attributes
+        .is_some());
+
+    let slim = serde_json::to_value(view(Some(false)).response(project)).unwrap();
+    assert!(slim.get("attributes").is_none());
+    assert_eq!(slim["total_value_locked"], 1250.0);
 }
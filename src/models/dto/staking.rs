@@ -0,0 +1,52 @@
+use crate::models::{StakingPosition, ValidatorInfo};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::ProjectResponse;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ValidatorInfoResponse {
+    pub address: String,
+    /// Stake backing the validator, in octas
+    pub voting_power: u64,
+    pub consensus_pubkey: String,
+}
+
+impl From<ValidatorInfo> for ValidatorInfoResponse {
+    fn from(validator: ValidatorInfo) -> Self {
+        Self {
+            address: validator.address,
+            voting_power: validator.voting_power,
+            consensus_pubkey: validator.consensus_pubkey,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StakingProjectResponse {
+    pub project: ProjectResponse,
+    /// Yearly staking reward rate, in percent
+    pub staking_apr: f64,
+    /// Sum of the voting power of the active validators, in octas
+    pub total_voting_power: u64,
+    pub validators: Vec<ValidatorInfoResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StakingPositionResponse {
+    pub pool_address: String,
+    pub active_apt: f64,
+    pub pending_inactive_apt: f64,
+    pub inactive_apt: f64,
+}
+
+impl From<StakingPosition> for StakingPositionResponse {
+    fn from(position: StakingPosition) -> Self {
+        Self {
+            pool_address: position.pool_address,
+            active_apt: position.active_apt,
+            pending_inactive_apt: position.pending_inactive_apt,
+            inactive_apt: position.inactive_apt,
+        }
+    }
+}
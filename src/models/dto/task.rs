@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TvlBackfillRequest {
+    pub project_id: i32,
+    /// Days back from yesterday to compute, 30 by default and at most 365
+    pub days: Option<i64>,
+}
+
+/// Background task started by an admin, its outcome is logged and failures are notified
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaskStartedResponse {
+    #[schema(example = "tvl_backfill")]
+    pub task: String,
+    pub project_id: i32,
+    pub days: i64,
+}
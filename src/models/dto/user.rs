@@ -4,10 +4,15 @@ use utoipa::ToSchema;
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct Profile {
+    pub id: i32,
     pub name: String,
     pub email: String,
     #[schema(example = "ADMIN")]
     pub role: String,
+    /// Whether logging in asks for a TOTP code
+    pub totp_enabled: bool,
+    /// False once an admin deactivated the user
+    pub active: bool,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -17,12 +22,42 @@ pub struct TokenResponse {
     pub token: String,
 }
 
+/// First half of the login of a user with two-factor authentication, the token only lets the
+/// TOTP code be sent to `/api/v1/user/totp/challenge`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PartialTokenResponse {
+    pub partial_token: String,
+}
+
+/// Answer to a login, the JWT itself unless a TOTP code is needed first
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Token(TokenResponse),
+    TotpRequired(PartialTokenResponse),
+}
+
+/// Secret to add to an authenticator app, by scanning `provisioning_uri` as a QR code or by
+/// typing `secret`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpSetupResponse {
+    #[schema(example = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP")]
+    pub secret: String,
+    #[schema(
+        example = "otpauth://totp/Decentralized%20Data%20Warehouse:a@a.io?secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP&issuer=Decentralized+Data+Warehouse&algorithm=SHA1&digits=6&period=30"
+    )]
+    pub provisioning_uri: String,
+}
+
 impl From<User> for Profile {
     fn from(user: User) -> Self {
         Self {
+            id: user.id,
             email: user.email.to_owned(),
             name: user.name.to_owned(),
             role: user.role.to_owned(),
+            totp_enabled: user.totp_enabled,
+            active: user.active,
             created_at: user.created_at.to_string(),
             updated_at: user.updated_at.to_string(),
         }
@@ -40,3 +75,17 @@ pub struct RegisterInfo {
     pub email: String,
     pub password: String,
 }
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TotpCode {
+    #[schema(example = "287082")]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TotpChallenge {
+    /// Token returned by the login
+    pub partial_token: String,
+    #[schema(example = "287082")]
+    pub code: String,
+}
@@ -0,0 +1,152 @@
+use crate::models::{CrossRate, PriceSource, SwapTransaction};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CrossRateQuery {
+    /// Coin type of the token being priced
+    pub base: String,
+    /// Coin type of the token the price is expressed in
+    pub quote: String,
+    /// `onchain`, `coingecko` for the quote of the coin id of a project with the token, or
+    /// `auto` for on-chain falling back to CoinGecko
+    #[serde(default)]
+    #[param(value_type = Option<String>, example = "auto")]
+    pub source: PriceSource,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CrossRateResponse {
+    pub base_token: String,
+    pub quote_token: String,
+    /// Amount of `quote_token` worth one `base_token`
+    pub rate: f64,
+    pub base_price_usd: f64,
+    pub quote_price_usd: f64,
+}
+
+impl From<CrossRate> for CrossRateResponse {
+    fn from(cross_rate: CrossRate) -> Self {
+        Self {
+            base_token: cross_rate.base_token,
+            quote_token: cross_rate.quote_token,
+            rate: cross_rate.rate,
+            base_price_usd: cross_rate.base_price_usd,
+            quote_price_usd: cross_rate.quote_price_usd,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SwapTransactionResponse {
+    pub version: i64,
+    pub sender: String,
+    /// Set when the sender is a known address
+    pub sender_label: Option<String>,
+    /// Router entry function of the swap, `exact_input` or `exact_output`
+    #[schema(example = "exact_input")]
+    pub entry_function_type: String,
+    /// Tokens a routed trade went through, empty for a trade in a single pool
+    #[schema(example = json!(["0x1::aptos_coin::AptosCoin"]))]
+    pub route: Vec<String>,
+    pub token_sold: String,
+    pub token_sold_amount: f64,
+    pub token_bought: String,
+    pub token_bought_amount: f64,
+    /// USD value of the tokens sold, `null` when they can't be priced
+    #[schema(example = 12.5)]
+    pub token_sold_usd: Option<f64>,
+    /// USD value of the tokens bought, `null` when they can't be priced
+    #[schema(example = 12.48)]
+    pub token_bought_usd: Option<f64>,
+}
+
+impl From<SwapTransaction> for SwapTransactionResponse {
+    fn from(transaction: SwapTransaction) -> Self {
+        Self {
+            version: transaction.version,
+            sender: transaction.sender,
+            sender_label: transaction.sender_label,
+            entry_function_type: transaction.entry_function_type.as_str().to_string(),
+            route: transaction.route,
+            token_sold: transaction.token_sold,
+            token_sold_amount: transaction.token_sold_amount,
+            token_bought: transaction.token_bought,
+            token_bought_amount: transaction.token_bought_amount,
+            token_sold_usd: transaction.token_sold_usd,
+            token_bought_usd: transaction.token_bought_usd,
+        }
+    }
+}
+
+/// Parses amounts formatted like `$4.32m`, `-$850k` or `$1,204.5` into a plain number.
+/// Suffixes `k`, `m` and `b` are thousands, millions and billions, in either case.
+pub fn parse_financial_string(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let s = s.strip_prefix('$').unwrap_or(s);
+    // Also accept the sign after the currency, as in `$-4.32m`
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) if !negative => (true, rest),
+        _ => (negative, s),
+    };
+
+    let (number, multiplier) = match s.chars().last()?.to_ascii_lowercase() {
+        'k' => (&s[..s.len() - 1], 1e3),
+        'm' => (&s[..s.len() - 1], 1e6),
+        'b' => (&s[..s.len() - 1], 1e9),
+        _ => (s, 1.0),
+    };
+    let number = number.trim_end();
+    // A sign was already taken, `f64` parsing would accept one more
+    if number.starts_with(['-', '+']) {
+        return None;
+    }
+    let value: f64 = number.replace(',', "").parse().ok()?;
+    if !value.is_finite() {
+        return None;
+    }
+
+    let value = value * multiplier;
+    Some(if negative { -value } else { value })
+}
+
+#[test]
+fn test_parse_financial_string() {
+    assert_eq!(parse_financial_string("$4.32m"), Some(4_320_000.0));
+    assert_eq!(parse_financial_string("$13.30M"), Some(13_300_000.0));
+    assert_eq!(parse_financial_string("$1.5b"), Some(1_500_000_000.0));
+    assert_eq!(parse_financial_string("$850k"), Some(850_000.0));
+    assert_eq!(parse_financial_string("$1,204.5"), Some(1_204.5));
+    assert_eq!(parse_financial_string(" 42 "), Some(42.0));
+    assert_eq!(parse_financial_string("$4.32 m"), Some(4_320_000.0));
+    assert_eq!(parse_financial_string("120.5K"), Some(120_500.0));
+    assert_eq!(parse_financial_string("$0.2234"), Some(0.2234));
+    assert_eq!(parse_financial_string("$2.1B"), Some(2_100_000_000.0));
+}
+
+#[test]
+fn test_parse_negative_financial_string() {
+    assert_eq!(parse_financial_string("-$2.5m"), Some(-2_500_000.0));
+    assert_eq!(parse_financial_string("$-120k"), Some(-120_000.0));
+    assert_eq!(parse_financial_string("--$1m"), None);
+    assert_eq!(parse_financial_string("-$-1m"), None);
+    assert_eq!(parse_financial_string("$--1m"), None);
+    assert_eq!(parse_financial_string("$+1m"), None);
+    assert_eq!(parse_financial_string("$0"), Some(0.0));
+}
+
+#[test]
+fn test_parse_invalid_financial_string() {
+    assert_eq!(parse_financial_string(""), None);
+    assert_eq!(parse_financial_string("$"), None);
+    assert_eq!(parse_financial_string("N/A"), None);
+    assert_eq!(parse_financial_string("$4.32x"), None);
+    assert_eq!(parse_financial_string("$infm"), None);
+    assert_eq!(parse_financial_string("m"), None);
+    assert_eq!(parse_financial_string("$NaN"), None);
+    assert_eq!(parse_financial_string("3.4y ago"), None);
+}
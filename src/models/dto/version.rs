@@ -0,0 +1,21 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiVersionResponse {
+    #[schema(example = "v1")]
+    pub version: String,
+    /// Prefix every route of the version is served under
+    #[schema(example = "/api/v1")]
+    pub path: String,
+    pub deprecated: bool,
+    /// HTTP date after which the version may be removed, set when it is deprecated
+    #[schema(example = "Sat, 31 Oct 2026 23:59:59 GMT")]
+    pub sunset: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VersionsResponse {
+    /// Oldest version first
+    pub versions: Vec<ApiVersionResponse>,
+}
@@ -5,6 +5,20 @@ use serde::{Deserialize, Serialize};
 pub struct Entity {
     pub id: i32,
     pub name: String,
+    /// One of [`Entity::TYPES`], `None` until someone classified the entity
+    pub entity_type: Option<String>,
+    pub website: Option<String>,
+    /// Twitter handle, without the `@`
+    pub twitter: Option<String>,
+    pub description: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+impl Entity {
+    pub const CEX: &'static str = "CEX";
+    pub const PROTOCOL: &'static str = "PROTOCOL";
+    pub const FUND: &'static str = "FUND";
+    pub const INDIVIDUAL: &'static str = "INDIVIDUAL";
+    pub const TYPES: [&'static str; 4] = [Self::CEX, Self::PROTOCOL, Self::FUND, Self::INDIVIDUAL];
+}
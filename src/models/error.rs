@@ -1,84 +1,340 @@
 use core::fmt;
+use std::collections::BTreeMap;
 
-use axum::http::StatusCode;
+use axum::http::{header::RETRY_AFTER, HeaderValue, StatusCode};
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::Json;
+use serde_json::json;
 
-use super::dto::Message;
+use super::dto::ErrorBody;
+use super::FormulaError;
 
+/// Error returned by every handler, rendered as an [`ErrorBody`]
 #[derive(Debug)]
-pub struct Error {
-    pub code: StatusCode,
-    pub body: Json<Message>,
+pub enum AppError {
+    /// The request is malformed or refers to something that doesn't exist
+    Validation(String),
+    /// Fields of the request body that are invalid, each with what is wrong with it
+    InvalidFields(BTreeMap<String, String>),
+    Unauthorized(String),
+    /// The user is logged in but not allowed to do this
+    Forbidden(String),
+    NotFound(String),
+    /// The resource still exists but is retired, the message tells why
+    Gone(String),
+    /// The request conflicts with a resource as it is on the server, `current`, e.g. it
+    /// changed since the client read it or it already uses a unique name
+    Conflict {
+        message: String,
+        current: serde_json::Value,
+    },
+    /// The request is well formed but its data can't be processed
+    Unprocessable(String),
+    /// A query failed, the cause is logged but never sent to the client
+    Database(sqlx::Error),
+    Internal(String),
+    /// A third-party API failed or answered something unexpected
+    Upstream {
+        service: &'static str,
+        message: String,
+    },
+    /// A third-party API didn't answer in time
+    UpstreamTimeout {
+        service: &'static str,
+        message: String,
+    },
+    /// A third-party API is throttling us, `retry_after` seconds is forwarded to the client
+    RateLimited {
+        service: &'static str,
+        message: String,
+        retry_after: Option<u64>,
+    },
+    /// A dependency is temporarily unable to answer, the client may retry
+    Unavailable(String),
+    /// The client tried too many times, it may try again in `retry_after` seconds
+    TooManyAttempts {
+        message: String,
+        retry_after: u64,
+    },
 }
 
-impl Error {
-    pub fn new(code: StatusCode, message: &str) -> Self {
-        Self {
-            code,
-            body: Json(Message::new(message)),
+impl AppError {
+    pub fn upstream(service: &'static str, message: &str) -> Self {
+        AppError::Upstream {
+            service,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) | AppError::InvalidFields(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Upstream { .. } => StatusCode::BAD_GATEWAY,
+            AppError::UpstreamTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            AppError::RateLimited { .. } | AppError::Unavailable(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            AppError::TooManyAttempts { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation(_) | AppError::InvalidFields(_) => "VALIDATION_ERROR",
+            AppError::Unauthorized(_) => "UNAUTHORIZED",
+            AppError::Forbidden(_) => "FORBIDDEN",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Gone(_) => "GONE",
+            AppError::Conflict { .. } => "CONFLICT",
+            AppError::Unprocessable(_) => "UNPROCESSABLE",
+            AppError::Database(_) => "DATABASE_ERROR",
+            AppError::Internal(_) => "INTERNAL_ERROR",
+            AppError::Upstream { .. } => "UPSTREAM_ERROR",
+            AppError::UpstreamTimeout { .. } => "UPSTREAM_TIMEOUT",
+            AppError::RateLimited { .. } => "UPSTREAM_RATE_LIMITED",
+            AppError::Unavailable(_) => "SERVICE_UNAVAILABLE",
+            AppError::TooManyAttempts { .. } => "TOO_MANY_ATTEMPTS",
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            AppError::Upstream { service, .. } | AppError::UpstreamTimeout { service, .. } => {
+                Some(json!({ "service": service }))
+            }
+            AppError::RateLimited {
+                service,
+                retry_after,
+                ..
+            } => Some(json!({ "service": service, "retry_after": retry_after })),
+            AppError::TooManyAttempts { retry_after, .. } => {
+                Some(json!({ "retry_after": retry_after }))
+            }
+            AppError::InvalidFields(fields) => Some(json!({ "fields": fields })),
+            AppError::Conflict { current, .. } => Some(json!({ "current": current })),
+            _ => None,
         }
     }
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> Response {
-        (self.code, self.body).into_response()
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Validation(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::Gone(message)
+            | AppError::Unprocessable(message)
+            | AppError::Internal(message)
+            | AppError::Unavailable(message)
+            | AppError::Conflict { message, .. }
+            | AppError::Upstream { message, .. }
+            | AppError::UpstreamTimeout { message, .. }
+            | AppError::RateLimited { message, .. }
+            | AppError::TooManyAttempts { message, .. } => write!(f, "{}", message),
+            AppError::Database(_) => write!(f, "Database error"),
+            AppError::InvalidFields(fields) => {
+                let fields: Vec<_> = fields.keys().map(String::as_str).collect();
+                write!(f, "Invalid fields: {}", fields.join(", "))
+            }
+        }
     }
 }
 
-impl From<(StatusCode, &str)> for Error {
-    fn from((code, msg): (StatusCode, &str)) -> Self {
-        Self::new(code, msg)
+impl std::error::Error for AppError {}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        // Logged inside the request span, so the line carries the request id
+        match &self {
+            AppError::Database(error) => {
+                tracing::error!(status = %status, error = %error, "Request failed")
+            }
+            _ if status.is_server_error() => {
+                tracing::error!(status = %status, message = %self, "Request failed")
+            }
+            _ => tracing::warn!(status = %status, message = %self, "Request rejected"),
+        }
+
+        let body = ErrorBody {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            details: self.details(),
+        };
+        let mut response = (status, Json(body)).into_response();
+        let retry_after = match self {
+            AppError::RateLimited { retry_after, .. } => retry_after,
+            AppError::TooManyAttempts { retry_after, .. } => Some(retry_after),
+            _ => None,
+        };
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
-impl From<sqlx::error::Error> for Error {
+impl From<sqlx::error::Error> for AppError {
     fn from(error: sqlx::error::Error) -> Self {
-        Self::new(StatusCode::INTERNAL_SERVER_ERROR, &error.to_string())
+        AppError::Database(error)
     }
 }
 
-impl From<jsonwebtoken::errors::Error> for Error {
+impl From<jsonwebtoken::errors::Error> for AppError {
     fn from(error: jsonwebtoken::errors::Error) -> Self {
-        Self::new(StatusCode::INTERNAL_SERVER_ERROR, &error.to_string())
+        AppError::Internal(error.to_string())
     }
 }
 
-impl From<argon2::password_hash::errors::Error> for Error {
+impl From<argon2::password_hash::errors::Error> for AppError {
     fn from(error: argon2::password_hash::errors::Error) -> Self {
-        Self::new(StatusCode::BAD_REQUEST, &error.to_string())
+        match error {
+            argon2::password_hash::errors::Error::Password => {
+                AppError::Validation("Wrong password".to_string())
+            }
+            _ => AppError::Internal(error.to_string()),
+        }
     }
 }
 
-#[derive(Debug)]
-pub enum TokenHolderError {
-    ReqwestError(reqwest::Error),
-    JsonError(serde_json::Error),
-    ApiError(String),
-}
-
-impl fmt::Display for TokenHolderError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            TokenHolderError::ReqwestError(e) => write!(f, "Reqwest error: {}", e),
-            TokenHolderError::JsonError(e) => write!(f, "JSON error: {}", e),
-            TokenHolderError::ApiError(e) => write!(f, "API error: {}", e),
+impl From<FormulaError> for AppError {
+    fn from(error: FormulaError) -> Self {
+        match error {
+            FormulaError::MissingValue(_) | FormulaError::DivisionByZero => {
+                AppError::Unprocessable(error.to_string())
+            }
+            _ => AppError::Validation(error.to_string()),
         }
     }
 }
 
-impl std::error::Error for TokenHolderError {}
+#[cfg(test)]
+async fn error_body(error: AppError) -> (StatusCode, serde_json::Value) {
+    let response = error.into_response();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
 
-impl From<reqwest::Error> for TokenHolderError {
-    fn from(error: reqwest::Error) -> Self {
-        TokenHolderError::ReqwestError(error)
-    }
+#[tokio::test]
+async fn test_not_found_error_body() {
+    let (status, body) = error_body(AppError::NotFound("Project not found".to_string())).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        body,
+        json!({ "code": "NOT_FOUND", "message": "Project not found" })
+    );
 }
 
-impl From<serde_json::Error> for TokenHolderError {
-    fn from(error: serde_json::Error) -> Self {
-        TokenHolderError::JsonError(error)
-    }
+#[tokio::test]
+async fn test_upstream_error_body() {
+    let (status, body) = error_body(AppError::upstream("GitHub", "GitHub answered 500")).await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(
+        body,
+        json!({
+            "code": "UPSTREAM_ERROR",
+            "message": "GitHub answered 500",
+            "details": { "service": "GitHub" }
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_fields_error_body() {
+    let fields = BTreeMap::from([
+        (
+            "num_chains".to_string(),
+            "must be of type integer".to_string(),
+        ),
+        (
+            "contract_address".to_string(),
+            "is required for DEX projects".to_string(),
+        ),
+    ]);
+    let (status, body) = error_body(AppError::InvalidFields(fields)).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body,
+        json!({
+            "code": "VALIDATION_ERROR",
+            "message": "Invalid fields: contract_address, num_chains",
+            "details": { "fields": {
+                "contract_address": "is required for DEX projects",
+                "num_chains": "must be of type integer"
+            } }
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_conflict_error_body() {
+    let error = AppError::Conflict {
+        message: "Project was updated since".to_string(),
+        current: json!({ "id": 4, "name": "PancakeSwap" }),
+    };
+    let (status, body) = error_body(error).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body,
+        json!({
+            "code": "CONFLICT",
+            "message": "Project was updated since",
+            "details": { "current": { "id": 4, "name": "PancakeSwap" } }
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_database_error_body_hides_cause() {
+    let (status, body) = error_body(AppError::from(sqlx::Error::RowNotFound)).await;
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        body,
+        json!({ "code": "DATABASE_ERROR", "message": "Database error" })
+    );
+}
+
+#[tokio::test]
+async fn test_rate_limited_error_sets_retry_after() {
+    let error = AppError::RateLimited {
+        service: "GitHub",
+        message: "api.github.com is rate limiting our requests".to_string(),
+        retry_after: Some(30),
+    };
+    let response = error.into_response();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[RETRY_AFTER], "30");
+}
+
+#[tokio::test]
+async fn test_too_many_attempts_error_sets_retry_after() {
+    let error = AppError::TooManyAttempts {
+        message: "Too many wrong TOTP codes".to_string(),
+        retry_after: 900,
+    };
+    let response = error.into_response();
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[RETRY_AFTER], "900");
 }
@@ -0,0 +1,335 @@
+use core::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct ProjectMetricFormula {
+    pub id: i32,
+    pub project_id: i32,
+    pub name: String,
+    pub formula: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FormulaError {
+    UnexpectedCharacter(char),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    UnknownVariable(String),
+    MissingValue(String),
+    DivisionByZero,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormulaError::UnexpectedCharacter(c) => write!(f, "Unexpected character '{}'", c),
+            FormulaError::UnexpectedToken(t) => write!(f, "Unexpected token '{}'", t),
+            FormulaError::UnexpectedEnd => write!(f, "Formula ended unexpectedly"),
+            FormulaError::UnknownVariable(v) => write!(f, "Unknown attribute '{}'", v),
+            FormulaError::MissingValue(v) => write!(f, "Attribute '{}' has no value", v),
+            FormulaError::DivisionByZero => write!(f, "Division by zero"),
+        }
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LeftParen,
+    RightParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(i) => write!(f, "{}", i),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// Parsed form of a formula such as `fees_30d / trading_volume * 100`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FormulaError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '+' | '-' | '*' | '/' | '(' | ')' => {
+                tokens.push(match c {
+                    '+' => Token::Plus,
+                    '-' => Token::Minus,
+                    '*' => Token::Star,
+                    '/' => Token::Slash,
+                    '(' => Token::LeftParen,
+                    _ => Token::RightParen,
+                });
+                chars.next();
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(&d) = chars.peek() {
+                    if !(d.is_ascii_digit() || d == '.') {
+                        break;
+                    }
+                    number.push(d);
+                    chars.next();
+                }
+                let value = number
+                    .parse::<f64>()
+                    .map_err(|_| FormulaError::UnexpectedToken(number))?;
+                tokens.push(Token::Number(value));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&d) = chars.peek() {
+                    if !(d.is_ascii_alphanumeric() || d == '_') {
+                        break;
+                    }
+                    ident.push(d);
+                    chars.next();
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            c => return Err(FormulaError::UnexpectedCharacter(c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser, one method per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    // expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.term()?;
+        while let Some(operator) = match self.peek() {
+            Some(Token::Plus) => Some(Operator::Add),
+            Some(Token::Minus) => Some(Operator::Subtract),
+            _ => None,
+        } {
+            self.next();
+            let right = self.term()?;
+            left = Expr::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // term := factor (('*' | '/') factor)*
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.factor()?;
+        while let Some(operator) = match self.peek() {
+            Some(Token::Star) => Some(Operator::Multiply),
+            Some(Token::Slash) => Some(Operator::Divide),
+            _ => None,
+        } {
+            self.next();
+            let right = self.factor()?;
+            left = Expr::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // factor := '-' factor | number | identifier | '(' expression ')'
+    fn factor(&mut self) -> Result<Expr, FormulaError> {
+        match self.next() {
+            Some(Token::Minus) => Ok(Expr::Negate(Box::new(self.factor()?))),
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Ident(name)) => Ok(Expr::Variable(name)),
+            Some(Token::LeftParen) => {
+                let inner = self.expression()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(inner),
+                    Some(token) => Err(FormulaError::UnexpectedToken(token.to_string())),
+                    None => Err(FormulaError::UnexpectedEnd),
+                }
+            }
+            Some(token) => Err(FormulaError::UnexpectedToken(token.to_string())),
+            None => Err(FormulaError::UnexpectedEnd),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(formula: &str) -> Result<Expr, FormulaError> {
+        let mut parser = Parser {
+            tokens: tokenize(formula)?,
+            position: 0,
+        };
+        let expr = parser.expression()?;
+        match parser.next() {
+            None => Ok(expr),
+            Some(token) => Err(FormulaError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    /// Names of every attribute the formula refers to
+    pub fn variables(&self) -> Vec<&str> {
+        match self {
+            Expr::Number(_) => Vec::new(),
+            Expr::Variable(name) => vec![name.as_str()],
+            Expr::Negate(inner) => inner.variables(),
+            Expr::Binary(_, left, right) => {
+                let mut variables = left.variables();
+                variables.extend(right.variables());
+                variables
+            }
+        }
+    }
+
+    /// Fails with the first attribute the formula refers to that isn't in `known`
+    pub fn check_variables(&self, known: &[&str]) -> Result<(), FormulaError> {
+        match self.variables().into_iter().find(|name| !known.contains(name)) {
+            Some(name) => Err(FormulaError::UnknownVariable(name.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Evaluates the formula, resolving attribute names through `lookup`
+    pub fn evaluate<F>(&self, lookup: &F) -> Result<f64, FormulaError>
+    where
+        F: Fn(&str) -> Option<f64>,
+    {
+        match self {
+            Expr::Number(n) => Ok(*n),
+            Expr::Variable(name) => {
+                lookup(name).ok_or_else(|| FormulaError::MissingValue(name.to_string()))
+            }
+            Expr::Negate(inner) => Ok(-inner.evaluate(lookup)?),
+            Expr::Binary(operator, left, right) => {
+                let left = left.evaluate(lookup)?;
+                let right = right.evaluate(lookup)?;
+                match operator {
+                    Operator::Add => Ok(left + right),
+                    Operator::Subtract => Ok(left - right),
+                    Operator::Multiply => Ok(left * right),
+                    Operator::Divide if right == 0.0 => Err(FormulaError::DivisionByZero),
+                    Operator::Divide => Ok(left / right),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn lookup(name: &str) -> Option<f64> {
+    match name {
+        "fees_30d" => Some(50.0),
+        "trading_volume" => Some(1000.0),
+        "zero" => Some(0.0),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_formula_precedence() {
+    let expr = Expr::parse("fees_30d / trading_volume * 100").unwrap();
+    assert_eq!(expr.evaluate(&lookup), Ok(5.0));
+
+    let expr = Expr::parse("1 + 2 * 3 - 4 / 2").unwrap();
+    assert_eq!(expr.evaluate(&lookup), Ok(5.0));
+
+    let expr = Expr::parse("(1 + 2) * -3").unwrap();
+    assert_eq!(expr.evaluate(&lookup), Ok(-9.0));
+}
+
+#[test]
+fn test_formula_variables() {
+    let expr = Expr::parse("fees_30d / (trading_volume + 1)").unwrap();
+    assert_eq!(expr.variables(), vec!["fees_30d", "trading_volume"]);
+}
+
+#[test]
+fn test_formula_unknown_variable() {
+    let expr = Expr::parse("fees_30d / trading_volume").unwrap();
+    assert_eq!(expr.check_variables(&["fees_30d", "trading_volume"]), Ok(()));
+    assert_eq!(
+        expr.check_variables(&["fees_30d"]),
+        Err(FormulaError::UnknownVariable("trading_volume".to_string()))
+    );
+}
+
+#[test]
+fn test_formula_division_by_zero() {
+    let expr = Expr::parse("fees_30d / zero").unwrap();
+    assert_eq!(expr.evaluate(&lookup), Err(FormulaError::DivisionByZero));
+}
+
+#[test]
+fn test_formula_missing_value() {
+    let expr = Expr::parse("fees_30d + tvl").unwrap();
+    assert_eq!(
+        expr.evaluate(&lookup),
+        Err(FormulaError::MissingValue("tvl".to_string()))
+    );
+}
+
+#[test]
+fn test_formula_syntax_errors() {
+    assert_eq!(
+        Expr::parse("fees_30d +"),
+        Err(FormulaError::UnexpectedEnd)
+    );
+    assert_eq!(
+        Expr::parse("(fees_30d"),
+        Err(FormulaError::UnexpectedEnd)
+    );
+    assert_eq!(
+        Expr::parse("fees_30d % 2"),
+        Err(FormulaError::UnexpectedCharacter('%'))
+    );
+    assert_eq!(
+        Expr::parse("fees_30d 2"),
+        Err(FormulaError::UnexpectedToken("2".to_string()))
+    );
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+
+/// Most addresses a [`HolderSnapshot`] keeps, tokens with more holders keep one in
+/// `sample_rate`
+pub const MAX_HOLDER_SAMPLE: usize = 10_000;
+
+/// Holders of a token on a UTC day. The full set is only kept as a hash, telling whether it
+/// changed, along with the addresses whose hash falls under `1 / sample_rate`. An address is
+/// sampled by every snapshot of a lower rate than one it was sampled at, so two snapshots can
+/// always be compared on the addresses of the coarser one.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct HolderSnapshot {
+    pub token: String,
+    pub date: NaiveDate,
+    pub holder_count: i64,
+    /// SHA-256 of the sorted addresses, in hex
+    pub holders_hash: String,
+    /// Sorted sampled addresses
+    pub sample: Vec<String>,
+    pub sample_rate: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl HolderSnapshot {
+    /// Snapshot of the `holders` of `token`, duplicates are counted once
+    pub fn take(token: &str, date: NaiveDate, mut holders: Vec<String>) -> Self {
+        holders.sort_unstable();
+        holders.dedup();
+        let sample_rate = holders.len().div_ceil(MAX_HOLDER_SAMPLE).max(1) as i32;
+        let hash = digest(&SHA256, holders.join("\n").as_bytes());
+        HolderSnapshot {
+            token: token.to_string(),
+            date,
+            holder_count: holders.len() as i64,
+            holders_hash: hash
+                .as_ref()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+            sample: holders
+                .into_iter()
+                .filter(|address| is_sampled(address, sample_rate))
+                .collect(),
+            sample_rate,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Sampled addresses that a snapshot of `sample_rate` samples too
+    fn sample_at(&self, sample_rate: i32) -> HashSet<&str> {
+        self.sample
+            .iter()
+            .map(String::as_str)
+            .filter(|address| is_sampled(address, sample_rate))
+            .collect()
+    }
+}
+
+/// Whether `address` is kept by a sample of one address in `sample_rate`, when the first 4
+/// bytes of its SHA-256 fall in the lowest `1 / sample_rate` of their range
+fn is_sampled(address: &str, sample_rate: i32) -> bool {
+    let hash = digest(&SHA256, address.as_bytes());
+    let bucket = u32::from_be_bytes(hash.as_ref()[..4].try_into().expect("SHA-256 is 32 bytes"));
+    (bucket as u64) * (sample_rate.max(1) as u64) < 1 << 32
+}
+
+/// Holders a token gained and lost since a previous [`HolderSnapshot`]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HolderDiff {
+    /// Holders now who weren't in the previous snapshot
+    pub new_holders: u64,
+    /// Holders of the previous snapshot who are gone
+    pub lost_holders: u64,
+    /// New holders who held the token in a snapshot before the previous one
+    pub returning_holders: u64,
+    pub holders: u64,
+    /// Day of the current snapshot
+    pub date: NaiveDate,
+    /// Day of the previous snapshot, `None` when there is none to compare with
+    pub compared_to: Option<NaiveDate>,
+    /// Whether the counts were made on every holder rather than scaled up from samples
+    pub exact: bool,
+}
+
+impl HolderDiff {
+    /// Compares `current` with `previous`, and the new holders with the `older` snapshots.
+    /// The snapshots are compared on the sample of the coarsest of them and the differences
+    /// scaled by its rate.
+    pub fn between(
+        current: &HolderSnapshot,
+        previous: Option<&HolderSnapshot>,
+        older: &[HolderSnapshot],
+    ) -> Self {
+        let holders = current.holder_count as u64;
+        let Some(previous) = previous else {
+            return HolderDiff {
+                holders,
+                date: current.date,
+                exact: true,
+                ..Default::default()
+            };
+        };
+        if current.holders_hash == previous.holders_hash {
+            return HolderDiff {
+                holders,
+                date: current.date,
+                compared_to: Some(previous.date),
+                exact: true,
+                ..Default::default()
+            };
+        }
+
+        let sample_rate = older
+            .iter()
+            .chain([current, previous])
+            .map(|snapshot| snapshot.sample_rate)
+            .max()
+            .unwrap_or(1);
+        let now = current.sample_at(sample_rate);
+        let before = previous.sample_at(sample_rate);
+        let earlier: HashSet<&str> = older
+            .iter()
+            .flat_map(|snapshot| snapshot.sample_at(sample_rate))
+            .collect();
+        let new: Vec<&str> = now.difference(&before).copied().collect();
+        let returning = new
+            .iter()
+            .filter(|address| earlier.contains(*address))
+            .count();
+        let scale =
+            |count: usize, at_most: i64| (count as u64 * sample_rate as u64).min(at_most as u64);
+        HolderDiff {
+            new_holders: scale(new.len(), current.holder_count),
+            lost_holders: scale(before.difference(&now).count(), previous.holder_count),
+            returning_holders: scale(returning, current.holder_count),
+            holders,
+            date: current.date,
+            compared_to: Some(previous.date),
+            exact: sample_rate == 1,
+        }
+    }
+}
+
+#[test]
+fn test_take_holder_snapshot() {
+    let date = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap();
+    let holders = |count: usize| (0..count).map(|i| format!("0x{i:x}")).collect::<Vec<_>>();
+
+    let small = HolderSnapshot::take(
+        "0x1::coin::T",
+        date,
+        vec!["0xb".into(), "0xa".into(), "0xb".into()],
+    );
+    assert_eq!(small.holder_count, 2);
+    assert_eq!(small.sample_rate, 1);
+    assert_eq!(small.sample, ["0xa", "0xb"]);
+    assert_eq!(small.holders_hash.len(), 64);
+    let reordered = HolderSnapshot::take("0x1::coin::T", date, vec!["0xa".into(), "0xb".into()]);
+    assert_eq!(small.holders_hash, reordered.holders_hash);
+
+    let large = HolderSnapshot::take("0x1::coin::T", date, holders(3 * MAX_HOLDER_SAMPLE));
+    assert_eq!(large.sample_rate, 3);
+    // Sampled by hash, so about one in three
+    let expected = MAX_HOLDER_SAMPLE as f64;
+    assert!((large.sample.len() as f64 - expected).abs() < expected * 0.05);
+    // An address sampled at a rate is sampled at every lower rate
+    assert!(large.sample.iter().all(|address| is_sampled(address, 2)));
+}
+
+#[test]
+fn test_holder_diff() {
+    let day = |d: u32| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+    let snapshot = |d: u32, holders: &[&str]| {
+        HolderSnapshot::take(
+            "0x1::coin::T",
+            day(d),
+            holders.iter().map(|a| a.to_string()).collect(),
+        )
+    };
+    let oldest = snapshot(1, &["0xa", "0xb", "0xc"]);
+    let previous = snapshot(7, &["0xa", "0xd"]);
+    let current = snapshot(14, &["0xa", "0xb", "0xe", "0xf"]);
+
+    assert_eq!(
+        HolderDiff::between(&current, Some(&previous), std::slice::from_ref(&oldest)),
+        HolderDiff {
+            new_holders: 3,
+            lost_holders: 1,
+            returning_holders: 1,
+            holders: 4,
+            date: day(14),
+            compared_to: Some(day(7)),
+            exact: true,
+        }
+    );
+    assert_eq!(
+        HolderDiff::between(&current, None, &[]),
+        HolderDiff {
+            holders: 4,
+            date: day(14),
+            exact: true,
+            ..Default::default()
+        }
+    );
+    let unchanged = snapshot(14, &["0xd", "0xa"]);
+    assert_eq!(
+        HolderDiff::between(&unchanged, Some(&previous), &[oldest]),
+        HolderDiff {
+            holders: 2,
+            date: day(14),
+            compared_to: Some(day(7)),
+            exact: true,
+            ..Default::default()
+        }
+    );
+
+    // Scaled up from the samples once a snapshot is sampled
+    let holders =
+        |range: std::ops::Range<usize>| range.map(|i| format!("0x{i:x}")).collect::<Vec<_>>();
+    let previous = HolderSnapshot::take("0x1::coin::T", day(7), holders(0..2 * MAX_HOLDER_SAMPLE));
+    let current = HolderSnapshot::take(
+        "0x1::coin::T",
+        day(14),
+        holders(MAX_HOLDER_SAMPLE..3 * MAX_HOLDER_SAMPLE),
+    );
+    let diff = HolderDiff::between(&current, Some(&previous), &[]);
+    assert!(!diff.exact);
+    assert_eq!(diff.holders, 2 * MAX_HOLDER_SAMPLE as u64);
+    let expected = MAX_HOLDER_SAMPLE as f64;
+    assert!((diff.new_holders as f64 - expected).abs() < expected * 0.1);
+    assert!((diff.lost_holders as f64 - expected).abs() < expected * 0.1);
+    assert_eq!(diff.returning_holders, 0);
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Well-known contract or wallet, shown by its label instead of its address
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct KnownAddress {
+    /// Always stored in the form returned by [`KnownAddress::normalize`]
+    pub address: String,
+    pub label: String,
+    /// Kind of owner, e.g. `DEX` or `CEX`
+    pub category: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl KnownAddress {
+    /// Lowercase form of `address` with all 64 hex digits written out, so `0x1` and
+    /// `0x0…01` are labeled the same. `None` when it isn't a hex address.
+    pub fn normalize(address: &str) -> Option<String> {
+        let hex = address.strip_prefix("0x").unwrap_or(address);
+        if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(format!("0x{:0>64}", hex.to_lowercase()))
+    }
+}
+
+#[test]
+fn test_normalize_known_address() {
+    let framework = format!("0x{}1", "0".repeat(63));
+    assert_eq!(KnownAddress::normalize("0x1").as_deref(), Some(&*framework));
+    assert_eq!(
+        KnownAddress::normalize(&framework).as_deref(),
+        Some(&*framework)
+    );
+    assert_eq!(
+        KnownAddress::normalize("0xC7EF").as_deref(),
+        Some(&*format!("0x{}c7ef", "0".repeat(60)))
+    );
+    assert_eq!(KnownAddress::normalize("0x"), None);
+    assert_eq!(KnownAddress::normalize("0xpancake"), None);
+    assert_eq!(
+        KnownAddress::normalize(&format!("0x{}", "1".repeat(65))),
+        None
+    );
+}
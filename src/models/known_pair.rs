@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Token pair seen trading in the swap events of a DEX project
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct KnownPair {
+    pub project_id: i32,
+    pub token_a: String,
+    pub token_b: String,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
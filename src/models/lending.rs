@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// Market state of a lending protocol, in USD
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct LendingStats {
+    /// Value deposited by lenders, borrowed or not
+    pub total_supplied_usd: f64,
+    pub total_borrowed_usd: f64,
+    /// Yearly rates of the pools weighted by their size, in percent, missing when no pool
+    /// reports one
+    pub supply_apy: Option<f64>,
+    pub borrow_apy: Option<f64>,
+    /// Debt no longer covered by its collateral, missing when the source doesn't report it
+    pub bad_debt_usd: Option<f64>,
+}
+
+impl LendingStats {
+    /// Share of the supplied value that is borrowed, in percent, `None` when nothing is
+    /// supplied
+    pub fn utilization_rate_pct(&self) -> Option<f64> {
+        (self.total_supplied_usd > 0.0)
+            .then(|| self.total_borrowed_usd / self.total_supplied_usd * 100.0)
+    }
+}
+
+#[test]
+fn test_utilization_rate_pct() {
+    let stats = LendingStats {
+        total_supplied_usd: 8_000_000.0,
+        total_borrowed_usd: 2_000_000.0,
+        ..Default::default()
+    };
+    assert_eq!(stats.utilization_rate_pct(), Some(25.0));
+    assert_eq!(LendingStats::default().utilization_rate_pct(), None);
+}
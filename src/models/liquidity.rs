@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+
+/// Intervals the window of a time-weighted liquidity is cut into, sampled at both ends
+pub const LIQUIDITY_INTERVALS: i32 = 10;
+
+/// Liquidity held over the time spanned by `samples`, oldest first, in USD-hours, by the
+/// trapezoidal rule. Samples without a value count as no liquidity, as before it was first
+/// recorded. `None` when no sample has a value or there are fewer than two.
+pub fn time_weighted_liquidity(samples: &[(DateTime<Utc>, Option<f64>)]) -> Option<f64> {
+    if samples.len() < 2 || samples.iter().all(|(_, value)| value.is_none()) {
+        return None;
+    }
+    let usd_hours = samples
+        .windows(2)
+        .map(|pair| {
+            let ((start, from), (end, to)) = (pair[0], pair[1]);
+            let hours = (end - start).num_seconds() as f64 / 3600.0;
+            (from.unwrap_or_default() + to.unwrap_or_default()) / 2.0 * hours
+        })
+        .sum();
+    Some(usd_hours)
+}
+
+#[test]
+fn test_time_weighted_liquidity() {
+    let at = |hour: i64| DateTime::UNIX_EPOCH + chrono::Duration::hours(hour);
+
+    // A steady liquidity over 4 hours
+    let steady = [
+        (at(0), Some(100.0)),
+        (at(2), Some(100.0)),
+        (at(4), Some(100.0)),
+    ];
+    assert_eq!(time_weighted_liquidity(&steady), Some(400.0));
+
+    // Liquidity parked just before the end weighs little
+    let late = [(at(0), None), (at(1), None), (at(2), Some(1000.0))];
+    assert_eq!(time_weighted_liquidity(&late), Some(500.0));
+
+    let ramp = [(at(0), Some(0.0)), (at(10), Some(50.0))];
+    assert_eq!(time_weighted_liquidity(&ramp), Some(250.0));
+
+    assert_eq!(
+        time_weighted_liquidity(&[(at(0), None), (at(1), None)]),
+        None
+    );
+    assert_eq!(time_weighted_liquidity(&[(at(0), Some(5.0))]), None);
+}
@@ -1,15 +1,47 @@
 pub mod account;
+pub mod alert;
+pub mod anomaly;
+pub mod audit;
+pub mod coin_filter;
+pub mod coin_info;
 pub mod dex_data;
 pub mod dto;
 pub mod entity;
 pub mod error;
+pub mod formula;
+pub mod holder;
+pub mod known_address;
+pub mod known_pair;
+pub mod lending;
+pub mod liquidity;
+pub mod nft;
+pub mod portfolio;
 pub mod project;
+pub mod staking;
 pub mod token_claim;
+pub mod totp;
 pub mod user;
-pub use account::Account;
+pub use account::{
+    Account, AccountBalanceSnapshot, Direction, NetFlow, OnChainAccount, Transaction, TreasuryFlow,
+};
+pub use alert::{AlertDelivery, AlertRule};
+pub use anomaly::{AnomalyAlert, AnomalyDetector};
+pub use audit::AuditEntry;
+pub use coin_filter::{CoinFilter, CoinFilters};
+pub use coin_info::CoinInfo;
 pub use dex_data::*;
 pub use entity::Entity;
-pub use error::{Error, TokenHolderError};
-pub use project::Project;
+pub use error::AppError;
+pub use formula::{Expr, FormulaError, ProjectMetricFormula};
+pub use holder::{HolderDiff, HolderSnapshot};
+pub use known_address::KnownAddress;
+pub use known_pair::KnownPair;
+pub use lending::LendingStats;
+pub use liquidity::{time_weighted_liquidity, LIQUIDITY_INTERVALS};
+pub use nft::NftHolding;
+pub use portfolio::{CoinBalance, Portfolio, PortfolioAsset};
+pub use project::{DexTotals, Project, ProjectAttributeChange, ProjectAttributeSchema};
+pub use staking::{StakingPosition, ValidatorInfo};
 pub use token_claim::TokenClaim;
+pub use totp::Totp;
 pub use user::User;
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// Token owned by an account, as listed by the indexer
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct NftHolding {
+    pub token_data_id: String,
+    pub collection_name: String,
+    pub token_name: String,
+    /// Editions of the token held, 1 for a regular NFT
+    pub amount: f64,
+    /// Image of the token on the indexer CDN, once it has been cached there
+    pub image_uri: Option<String>,
+}
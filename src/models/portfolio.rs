@@ -0,0 +1,172 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::StakingPosition;
+
+/// Coin held by an account, as listed by the indexer
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CoinBalance {
+    pub coin_type: String,
+    pub symbol: String,
+    /// Amount in the smallest unit of the coin
+    pub amount: f64,
+    pub decimals: u8,
+}
+
+impl CoinBalance {
+    /// Sums the balances of the same coin held by several accounts, ordered by coin type
+    pub fn totals(balances: impl IntoIterator<Item = CoinBalance>) -> Vec<CoinBalance> {
+        let mut totals: BTreeMap<String, CoinBalance> = BTreeMap::new();
+        for balance in balances {
+            match totals.get_mut(&balance.coin_type) {
+                Some(total) => total.amount += balance.amount,
+                None => {
+                    totals.insert(balance.coin_type.clone(), balance);
+                }
+            }
+        }
+        totals.into_values().collect()
+    }
+}
+
+/// Coin held by an account along with its USD value
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PortfolioAsset {
+    pub coin_type: String,
+    pub symbol: String,
+    /// Amount in whole coins
+    pub amount: f64,
+    /// `None` when the coin couldn't be priced
+    pub price_usd: Option<f64>,
+    pub value_usd: Option<f64>,
+}
+
+impl PortfolioAsset {
+    /// Values `balance` at `price`, the USD price and decimals of the coin when it was found
+    pub fn new(balance: CoinBalance, price: Option<(f64, u8)>) -> Self {
+        let decimals = price.map_or(balance.decimals, |(_, decimals)| decimals);
+        let amount = balance.amount / 10f64.powi(decimals as i32);
+        let price_usd = price.map(|(price, _)| price);
+
+        PortfolioAsset {
+            coin_type: balance.coin_type,
+            symbol: balance.symbol,
+            amount,
+            price_usd,
+            value_usd: price_usd.map(|price| price * amount),
+        }
+    }
+}
+
+/// Every coin held by an account, valued in USD
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct Portfolio {
+    pub address: String,
+    /// Most valuable first, unpriced coins last
+    pub assets: Vec<PortfolioAsset>,
+    /// `None` when the delegated stake couldn't be fetched
+    pub staking: Option<Vec<StakingPosition>>,
+    /// Value of the delegated stake, `None` when it or the APT price is unknown
+    pub staked_usd: Option<f64>,
+    /// Value of the priced coins and of the delegated stake
+    pub total_usd: f64,
+}
+
+impl Portfolio {
+    pub fn new(address: &str, mut assets: Vec<PortfolioAsset>) -> Self {
+        assets.sort_by(|a, b| match (a.value_usd, b.value_usd) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        let total_usd = assets.iter().filter_map(|asset| asset.value_usd).sum();
+
+        Portfolio {
+            address: address.to_string(),
+            assets,
+            staking: None,
+            staked_usd: None,
+            total_usd,
+        }
+    }
+
+    /// Adds the stake delegated by the account, valued at `apt_price_usd`
+    pub fn with_staking(
+        mut self,
+        positions: Vec<StakingPosition>,
+        apt_price_usd: Option<f64>,
+    ) -> Self {
+        let staked_apt: f64 = positions.iter().map(StakingPosition::total_apt).sum();
+        self.staked_usd = apt_price_usd.map(|price| staked_apt * price);
+        self.total_usd += self.staked_usd.unwrap_or(0.0);
+        self.staking = Some(positions);
+        self
+    }
+}
+
+#[test]
+fn test_portfolio_sorted_by_value() {
+    let balance = |coin_type: &str, amount: f64| CoinBalance {
+        coin_type: coin_type.to_string(),
+        symbol: coin_type.to_uppercase(),
+        amount,
+        decimals: 8,
+    };
+    let portfolio = Portfolio::new(
+        "0xcafe",
+        vec![
+            PortfolioAsset::new(balance("unknown", 5e8), None),
+            PortfolioAsset::new(balance("apt", 2e8), Some((8.0, 8))),
+            PortfolioAsset::new(balance("usdc", 30e6), Some((1.0, 6))),
+        ],
+    );
+
+    let coins: Vec<&str> = portfolio
+        .assets
+        .iter()
+        .map(|a| a.coin_type.as_str())
+        .collect();
+    assert_eq!(coins, vec!["usdc", "apt", "unknown"]);
+    assert_eq!(portfolio.assets[0].value_usd, Some(30.0));
+    assert_eq!(portfolio.assets[2].amount, 5.0);
+    assert_eq!(portfolio.assets[2].value_usd, None);
+    assert_eq!(portfolio.total_usd, 46.0);
+}
+
+#[test]
+fn test_portfolio_with_staking() {
+    let position = StakingPosition {
+        pool_address: "0xpool".to_string(),
+        active_apt: 10.0,
+        pending_inactive_apt: 1.5,
+        inactive_apt: 0.5,
+    };
+    let portfolio =
+        Portfolio::new("0xcafe", Vec::new()).with_staking(vec![position.clone()], Some(8.0));
+    assert_eq!(portfolio.staked_usd, Some(96.0));
+    assert_eq!(portfolio.total_usd, 96.0);
+
+    let unpriced = Portfolio::new("0xcafe", Vec::new()).with_staking(vec![position], None);
+    assert_eq!(unpriced.staked_usd, None);
+    assert_eq!(unpriced.total_usd, 0.0);
+    assert_eq!(unpriced.staking.map(|positions| positions.len()), Some(1));
+}
+
+#[test]
+fn test_coin_balance_totals() {
+    let balance = |coin_type: &str, amount: f64| CoinBalance {
+        coin_type: coin_type.to_string(),
+        symbol: coin_type.to_uppercase(),
+        amount,
+        decimals: 8,
+    };
+    let totals = CoinBalance::totals(vec![
+        balance("usdc", 5.0),
+        balance("apt", 2.0),
+        balance("usdc", 10.0),
+    ]);
+
+    assert_eq!(totals, vec![balance("apt", 2.0), balance("usdc", 15.0)]);
+}
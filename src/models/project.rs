@@ -1,9 +1,12 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
 pub struct Project {
     pub id: i32,
+    /// Unique among projects when set
+    pub name: Option<String>,
     pub token: String,
     pub category: String,
     pub contract_address: Option<String>,
@@ -11,7 +14,390 @@ pub struct Project {
     pub core_developers: Option<i32>,
     pub code_commits: Option<i32>,
     pub total_value_locked: Option<f64>,
+    /// USD volume traded over the last 7 days
+    pub trading_volume: Option<f64>,
     pub token_max_supply: Option<i64>,
+    pub defi_llama_slug: Option<String>,
+    pub cmc_id: Option<i64>,
+    pub github_repo: Option<String>,
+    /// CoinGecko coin id, e.g. `pancakeswap-token`
+    pub coingecko_id: Option<String>,
+    /// USD price, market cap and 24h volume of the token as last quoted by CoinGecko
+    pub price_usd_cg: Option<f64>,
+    pub market_cap_cg: Option<f64>,
+    pub volume_24h_cg: Option<f64>,
+    /// USD price of the token, on-chain or from CoinGecko, snapshotted daily
+    pub price_usd: Option<f64>,
+    /// Project slug on TokenTerminal, e.g. `pancakeswap`, source of the DEX financials
+    pub token_terminal_slug: Option<String>,
+    /// USD value that came into and left the treasury accounts over the last 7 days
+    pub treasury_inflow_7d: Option<f64>,
+    pub treasury_outflow_7d: Option<f64>,
+    pub treasury_net_flow_7d: Option<f64>,
+    /// Entry function of the contract whose swaps make up the all time volume, e.g.
+    /// `0x1::router::swap_exact_input`
+    pub volume_entry_function: Option<String>,
+    /// USD volume of every swap made through `volume_entry_function` up to the transaction
+    /// `volume_processed_version`, the next volume refresh carries on from there
+    pub all_time_volume_usd: Option<f64>,
+    pub volume_processed_version: Option<i64>,
+    /// Fee taken on the input amount of each swap of the pools published at the contract
+    /// address, in basis points, e.g. `25` for 0.25%
+    pub swap_fee_bps: Option<i32>,
+    /// USD fees earned on every swap up to the transaction `last_processed_version`, the
+    /// next fee refresh carries on from there
+    pub all_time_fees_usd: Option<f64>,
+    pub last_processed_version: Option<i64>,
+    /// Total value locked integrated over the last 7 days, in USD-hours
+    pub twick_7d: Option<f64>,
+    /// Treasury, vesting and team accounts whose balances of the token are left out of its
+    /// circulating supply
+    pub excluded_supply_addresses: Vec<String>,
+    /// Logo of the project, uploaded to the avatar store or hosted elsewhere
+    pub avatar_url: Option<String>,
+    /// Project this one was cloned from
+    pub cloned_from: Option<i32>,
+    /// `false` once the contract health check found only empty pair reserves or no account at
+    /// the contract address, the project is then only kept for its history
+    pub active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+/// Change of a numeric attribute of a project, kept so metric regressions can be traced
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ProjectAttributeChange {
+    pub id: i32,
+    pub project_id: i32,
+    /// One of [`Project::NUMERIC_ATTRIBUTES`]
+    pub key: String,
+    pub old_value: Option<f64>,
+    pub new_value: Option<f64>,
+    /// Id of the user who made the change, or `scheduler` for background tasks
+    pub changed_by: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Type an attribute must have in the projects of a category, checked whenever one is written
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ProjectAttributeSchema {
+    /// Matched ignoring case, like [`Project::is_dex`]
+    pub category: String,
+    /// Field of the project, e.g. `total_value_locked`
+    pub key: String,
+    /// `float`, `integer` or `string`
+    pub expected_type: String,
+    /// Whether the attribute must be set, a `null` otherwise passes whatever the type
+    pub required: bool,
+    pub description: Option<String>,
+}
+
+impl ProjectAttributeSchema {
+    pub const FLOAT: &'static str = "float";
+    pub const INTEGER: &'static str = "integer";
+    pub const STRING: &'static str = "string";
+
+    /// Why `value` doesn't fit the schema, `None` when it does
+    pub fn violation(&self, value: &Value) -> Option<String> {
+        if value.is_null() {
+            return self
+                .required
+                .then(|| format!("is required for {} projects", self.category));
+        }
+        let fits = match self.expected_type.as_str() {
+            Self::FLOAT => value.is_number(),
+            Self::INTEGER => value.is_i64() || value.is_u64(),
+            Self::STRING => value.is_string(),
+            _ => true,
+        };
+        (!fits).then(|| format!("must be of type {}", self.expected_type))
+    }
+}
+
+/// Sums of the metrics of every DEX project, each over the projects reporting it
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DexTotals {
+    pub projects: i64,
+    /// `None` when no DEX reports a value locked
+    pub total_value_locked: Option<f64>,
+    pub total_value_locked_projects: i64,
+    /// `None` when no DEX reports a volume
+    pub trading_volume: Option<f64>,
+    pub trading_volume_projects: i64,
+    /// When the least recently changed of the summed values was set
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Project {
+    /// Category of liquid staking and validator projects
+    pub const STAKING_CATEGORY: &'static str = "Staking";
+    /// Category of decentralized exchanges, whose trading volumes are compared
+    pub const DEX_CATEGORY: &'static str = "DEX";
+    /// Category of lending protocols, whose markets are read from DeFiLlama
+    pub const LENDING_CATEGORY: &'static str = "Lending";
+    /// Basis points in a whole amount, the denominator of [`Project::swap_fee_bps`]
+    pub const BASIS_POINTS: i32 = 10_000;
+    /// Attribute holding the daily USD price snapshots of the token
+    pub const PRICE_ATTRIBUTE: &'static str = "price_usd";
+
+    /// Names of the numeric attributes that can be read with [`Project::get_float`]
+    pub const NUMERIC_ATTRIBUTES: [&'static str; 16] = [
+        "num_chains",
+        "core_developers",
+        "code_commits",
+        "total_value_locked",
+        "trading_volume",
+        "token_max_supply",
+        "price_usd_cg",
+        "market_cap_cg",
+        "volume_24h_cg",
+        "price_usd",
+        "treasury_inflow_7d",
+        "treasury_outflow_7d",
+        "treasury_net_flow_7d",
+        "all_time_volume_usd",
+        "all_time_fees_usd",
+        "twick_7d",
+    ];
+
+    /// Returns a numeric attribute by name, or `None` if it's unknown or unset
+    pub fn get_float(&self, key: &str) -> Option<f64> {
+        match key {
+            "num_chains" => self.num_chains.map(f64::from),
+            "core_developers" => self.core_developers.map(f64::from),
+            "code_commits" => self.code_commits.map(f64::from),
+            "total_value_locked" => self.total_value_locked,
+            "trading_volume" => self.trading_volume,
+            "token_max_supply" => self.token_max_supply.map(|supply| supply as f64),
+            "price_usd_cg" => self.price_usd_cg,
+            "market_cap_cg" => self.market_cap_cg,
+            "volume_24h_cg" => self.volume_24h_cg,
+            "price_usd" => self.price_usd,
+            "treasury_inflow_7d" => self.treasury_inflow_7d,
+            "treasury_outflow_7d" => self.treasury_outflow_7d,
+            "treasury_net_flow_7d" => self.treasury_net_flow_7d,
+            "all_time_volume_usd" => self.all_time_volume_usd,
+            "all_time_fees_usd" => self.all_time_fees_usd,
+            "twick_7d" => self.twick_7d,
+            _ => None,
+        }
+    }
+
+    /// Whether the project is in the [`Project::STAKING_CATEGORY`], ignoring case
+    pub fn is_staking(&self) -> bool {
+        self.category.eq_ignore_ascii_case(Self::STAKING_CATEGORY)
+    }
+
+    /// New project named `name` with the attributes of this one, except the metrics changing
+    /// over time which are left for the clone to collect on its own, the volume entry function
+    /// which belongs to the contract of this one, and the avatar, whose stored file goes away
+    /// along with the avatar of this one
+    pub fn clone_as(&self, name: String, contract_address: Option<String>) -> Project {
+        Project {
+            name: Some(name),
+            token: self.token.clone(),
+            category: self.category.clone(),
+            contract_address,
+            num_chains: self.num_chains,
+            token_max_supply: self.token_max_supply,
+            defi_llama_slug: self.defi_llama_slug.clone(),
+            cmc_id: self.cmc_id,
+            github_repo: self.github_repo.clone(),
+            coingecko_id: self.coingecko_id.clone(),
+            token_terminal_slug: self.token_terminal_slug.clone(),
+            excluded_supply_addresses: self.excluded_supply_addresses.clone(),
+            cloned_from: Some(self.id),
+            ..Default::default()
+        }
+    }
+
+    /// Numeric attributes whose value differs in `updated`, with their value before and after
+    pub fn changed_attributes(
+        &self,
+        updated: &Project,
+    ) -> Vec<(&'static str, Option<f64>, Option<f64>)> {
+        Self::NUMERIC_ATTRIBUTES
+            .into_iter()
+            .map(|key| (key, self.get_float(key), updated.get_float(key)))
+            .filter(|(_, old, new)| old != new)
+            .collect()
+    }
+
+    /// Clears the all time fees when the contract or the swap fee they were summed for
+    /// changed since `before`, so the fee task sums them again from the first swap
+    pub fn reset_fees_if_source_changed(&mut self, before: &Project) {
+        if self.contract_address != before.contract_address
+            || self.swap_fee_bps != before.swap_fee_bps
+        {
+            self.all_time_fees_usd = None;
+            self.last_processed_version = None;
+        }
+    }
+
+    /// Clears the all time volume when the contract or the entry function it was summed for
+    /// changed since `before`, so the volume task sums it again from the first swap
+    pub fn reset_volume_if_source_changed(&mut self, before: &Project) {
+        if self.contract_address != before.contract_address
+            || self.volume_entry_function != before.volume_entry_function
+        {
+            self.all_time_volume_usd = None;
+            self.volume_processed_version = None;
+        }
+    }
+
+    /// Whether the project is in the [`Project::DEX_CATEGORY`], ignoring case
+    pub fn is_dex(&self) -> bool {
+        self.category.eq_ignore_ascii_case(Self::DEX_CATEGORY)
+    }
+
+    /// Whether the project is in the [`Project::LENDING_CATEGORY`], ignoring case
+    pub fn is_lending(&self) -> bool {
+        self.category.eq_ignore_ascii_case(Self::LENDING_CATEGORY)
+    }
+}
+
+#[test]
+fn test_project_clone_resets_metrics() {
+    let project = Project {
+        id: 7,
+        name: Some("PancakeSwap".to_string()),
+        token: "0x1::cake::Cake".to_string(),
+        category: Project::DEX_CATEGORY.to_string(),
+        contract_address: Some("0xc7ef".to_string()),
+        num_chains: Some(3),
+        core_developers: Some(12),
+        code_commits: Some(4000),
+        total_value_locked: Some(1e8),
+        trading_volume: Some(5e7),
+        github_repo: Some("pancakeswap/pancake-frontend".to_string()),
+        excluded_supply_addresses: vec!["0xbeef".to_string()],
+        ..Default::default()
+    };
+    let clone = project.clone_as("PancakeFork".to_string(), None);
+
+    assert_eq!(clone.name.as_deref(), Some("PancakeFork"));
+    assert_eq!(clone.cloned_from, Some(7));
+    assert_eq!(clone.token, project.token);
+    assert_eq!(clone.num_chains, Some(3));
+    assert_eq!(clone.github_repo, project.github_repo);
+    assert_eq!(
+        clone.excluded_supply_addresses,
+        project.excluded_supply_addresses
+    );
+    assert_eq!(clone.contract_address, None);
+    for attribute in Project::NUMERIC_ATTRIBUTES {
+        if attribute != "num_chains" {
+            assert_eq!(clone.get_float(attribute), None, "{attribute}");
+        }
+    }
+}
+
+#[test]
+fn test_project_changed_attributes() {
+    let project = Project {
+        num_chains: Some(2),
+        total_value_locked: Some(1e6),
+        ..Default::default()
+    };
+    let updated = Project {
+        num_chains: Some(2),
+        total_value_locked: Some(8e5),
+        code_commits: Some(120),
+        ..project.clone()
+    };
+
+    assert_eq!(
+        project.changed_attributes(&updated),
+        vec![
+            ("code_commits", None, Some(120.0)),
+            ("total_value_locked", Some(1e6), Some(8e5)),
+        ]
+    );
+    assert!(updated.changed_attributes(&updated).is_empty());
+}
+
+#[test]
+fn test_project_reset_fees_if_source_changed() {
+    let project = Project {
+        contract_address: Some("0xc7ef".to_string()),
+        swap_fee_bps: Some(25),
+        all_time_fees_usd: Some(1e6),
+        last_processed_version: Some(900),
+        ..Default::default()
+    };
+
+    let mut unchanged = Project {
+        trading_volume: Some(5e7),
+        ..project.clone()
+    };
+    unchanged.reset_fees_if_source_changed(&project);
+    assert_eq!(unchanged.all_time_fees_usd, Some(1e6));
+    assert_eq!(unchanged.last_processed_version, Some(900));
+
+    let mut refee = Project {
+        swap_fee_bps: Some(30),
+        ..project.clone()
+    };
+    refee.reset_fees_if_source_changed(&project);
+    assert_eq!(refee.all_time_fees_usd, None);
+    assert_eq!(refee.last_processed_version, None);
+}
+
+#[test]
+fn test_project_reset_volume_if_source_changed() {
+    let project = Project {
+        contract_address: Some("0xc7ef".to_string()),
+        volume_entry_function: Some("0xc7ef::router::swap_exact_input".to_string()),
+        all_time_volume_usd: Some(1e8),
+        volume_processed_version: Some(900),
+        ..Default::default()
+    };
+
+    let mut unchanged = Project {
+        swap_fee_bps: Some(30),
+        ..project.clone()
+    };
+    unchanged.reset_volume_if_source_changed(&project);
+    assert_eq!(unchanged.all_time_volume_usd, Some(1e8));
+    assert_eq!(unchanged.volume_processed_version, Some(900));
+
+    let mut rerouted = Project {
+        volume_entry_function: Some("0xc7ef::router::swap_exact_output".to_string()),
+        ..project.clone()
+    };
+    rerouted.reset_volume_if_source_changed(&project);
+    assert_eq!(rerouted.all_time_volume_usd, None);
+    assert_eq!(rerouted.volume_processed_version, None);
+}
+
+#[test]
+fn test_project_attribute_schema_violation() {
+    let schema = ProjectAttributeSchema {
+        category: Project::DEX_CATEGORY.to_string(),
+        key: "num_chains".to_string(),
+        expected_type: ProjectAttributeSchema::INTEGER.to_string(),
+        ..Default::default()
+    };
+    assert_eq!(schema.violation(&serde_json::json!(3)), None);
+    assert_eq!(schema.violation(&Value::Null), None);
+    assert_eq!(
+        schema.violation(&serde_json::json!(2.5)).as_deref(),
+        Some("must be of type integer")
+    );
+    assert_eq!(
+        schema.violation(&serde_json::json!("3")).as_deref(),
+        Some("must be of type integer")
+    );
+
+    let required = ProjectAttributeSchema {
+        expected_type: ProjectAttributeSchema::FLOAT.to_string(),
+        required: true,
+        ..schema
+    };
+    assert_eq!(required.violation(&serde_json::json!(3)), None);
+    assert_eq!(required.violation(&serde_json::json!(1.5e8)), None);
+    assert_eq!(
+        required.violation(&Value::Null).as_deref(),
+        Some("is required for DEX projects")
+    );
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// Member of the active Aptos validator set
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ValidatorInfo {
+    pub address: String,
+    /// Stake backing the validator, in octas
+    pub voting_power: u64,
+    pub consensus_pubkey: String,
+}
+
+/// Stake an account delegated to one delegation pool, in APT
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct StakingPosition {
+    pub pool_address: String,
+    pub active_apt: f64,
+    /// Unlocked at the end of the current lockup cycle
+    pub pending_inactive_apt: f64,
+    /// Unlocked and ready to be withdrawn
+    pub inactive_apt: f64,
+}
+
+impl StakingPosition {
+    pub fn total_apt(&self) -> f64 {
+        self.active_apt + self.pending_inactive_apt + self.inactive_apt
+    }
+}
@@ -5,4 +5,8 @@ pub struct TokenClaim {
     pub sub: String,
     pub iat: usize,
     pub exp: usize,
+    /// Set on the short-lived token of a login still waiting for its TOTP code, only
+    /// accepted by `/api/v1/user/totp/challenge`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub totp_pending: bool,
 }
@@ -0,0 +1,199 @@
+use reqwest::Url;
+use ring::{
+    aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, NONCE_LEN},
+    constant_time, hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+/// Seconds a code is valid for and digits in a code, what authenticator apps expect by default
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+
+/// Steps before and after the current one whose codes are still accepted, for clock drift
+const SKEW_STEPS: i64 = 1;
+
+/// Length of a generated secret, the HMAC-SHA1 output size RFC 4226 recommends
+const SECRET_BYTES: usize = 20;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Secret shared with the authenticator app of a user, giving RFC 6238 codes (HMAC-SHA1)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Totp {
+    secret: Vec<u8>,
+}
+
+impl Totp {
+    /// Name shown next to the codes in authenticator apps
+    pub const ISSUER: &'static str = "Decentralized Data Warehouse";
+
+    pub fn generate() -> Self {
+        let mut secret = vec![0; SECRET_BYTES];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .expect("System random generator must be available");
+        Self { secret }
+    }
+
+    /// Code valid during the step `timestamp`, in seconds since the epoch, falls in
+    pub fn code_at(&self, timestamp: i64) -> String {
+        let counter = timestamp.div_euclid(STEP_SECS) as u64;
+        let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &self.secret);
+        let digest = hmac::sign(&key, &counter.to_be_bytes());
+        let digest = digest.as_ref();
+
+        // Dynamic truncation, RFC 4226 section 5.3
+        let offset = usize::from(digest[digest.len() - 1] & 0x0f);
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        format!(
+            "{:0width$}",
+            binary % 10u32.pow(DIGITS),
+            width = DIGITS as usize
+        )
+    }
+
+    /// Whether `code` is the one of the step of `timestamp` or of one next to it
+    pub fn verify(&self, code: &str, timestamp: i64) -> bool {
+        self.matching_step(code, timestamp).is_some()
+    }
+
+    /// Step, counted from the epoch, whose code `code` is among the step of `timestamp` and
+    /// those next to it. Codes are compared in constant time.
+    pub fn matching_step(&self, code: &str, timestamp: i64) -> Option<i64> {
+        let code = code.trim();
+        if code.len() != DIGITS as usize {
+            return None;
+        }
+        let step = timestamp.div_euclid(STEP_SECS);
+        (step - SKEW_STEPS..=step + SKEW_STEPS).find(|step| {
+            let expected = self.code_at(step * STEP_SECS);
+            constant_time::verify_slices_are_equal(expected.as_bytes(), code.as_bytes()).is_ok()
+        })
+    }
+
+    /// Secret in unpadded base32, as typed into an authenticator app by hand
+    pub fn base32(&self) -> String {
+        let mut encoded = String::with_capacity(self.secret.len().div_ceil(5) * 8);
+        for chunk in self.secret.chunks(5) {
+            let mut buffer = [0u8; 5];
+            buffer[..chunk.len()].copy_from_slice(chunk);
+            let bits = buffer
+                .iter()
+                .fold(0u64, |bits, byte| bits << 8 | u64::from(*byte));
+            let chars = (chunk.len() * 8).div_ceil(5);
+            for i in 0..chars {
+                let index = (bits >> (35 - i * 5)) & 0x1f;
+                encoded.push(char::from(BASE32_ALPHABET[index as usize]));
+            }
+        }
+        encoded
+    }
+
+    /// `otpauth://` URI authenticator apps read from a QR code, labeled with `account`
+    pub fn provisioning_uri(&self, account: &str) -> String {
+        let mut uri = Url::parse("otpauth://totp/").expect("otpauth URI must parse");
+        uri.set_path(&format!("{}:{account}", Self::ISSUER));
+        uri.query_pairs_mut()
+            .append_pair("secret", &self.base32())
+            .append_pair("issuer", Self::ISSUER)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &DIGITS.to_string())
+            .append_pair("period", &STEP_SECS.to_string());
+        uri.to_string()
+    }
+
+    /// Secret encrypted with AES-256-GCM under `key`, hex encoded after its random nonce
+    pub fn encrypt(&self, key: &[u8; 32]) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .expect("System random generator must be available");
+        let mut sealed = self.secret.clone();
+        cipher(key)
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .expect("TOTP secret must fit in a sealed message");
+
+        nonce
+            .iter()
+            .chain(&sealed)
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Secret from the output of [`Totp::encrypt`], `None` if it was tampered with or
+    /// encrypted under another key
+    pub fn decrypt(encrypted: &str, key: &[u8; 32]) -> Option<Self> {
+        let mut bytes = parse_hex(encrypted)?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let mut sealed = bytes.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&bytes).ok()?;
+        let secret = cipher(key)
+            .open_in_place(nonce, Aad::empty(), &mut sealed)
+            .ok()?;
+        Some(Self {
+            secret: secret.to_vec(),
+        })
+    }
+}
+
+/// Decodes a 64 hex digit encryption key
+pub fn parse_key(hex: &str) -> Option<[u8; 32]> {
+    parse_hex(hex)?.try_into().ok()
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn cipher(key: &[u8; 32]) -> LessSafeKey {
+    LessSafeKey::new(UnboundKey::new(&aead::AES_256_GCM, key).expect("Key must be 32 bytes"))
+}
+
+#[test]
+fn test_totp_codes() {
+    // Test vectors of RFC 6238 appendix B, truncated to 6 digits
+    let totp = Totp {
+        secret: b"12345678901234567890".to_vec(),
+    };
+    assert_eq!(totp.code_at(59), "287082");
+    assert_eq!(totp.code_at(1111111109), "081804");
+    assert_eq!(totp.code_at(1234567890), "005924");
+    assert_eq!(totp.base32(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+
+    assert!(totp.verify("287082", 59));
+    assert!(totp.verify(" 287082 ", 59 + STEP_SECS));
+    assert!(!totp.verify("287082", 59 + 2 * STEP_SECS));
+    assert!(!totp.verify("28708", 59));
+    assert_eq!(totp.matching_step("287082", 59 + STEP_SECS), Some(1));
+    assert_eq!(totp.matching_step("081804", 59), None);
+}
+
+#[test]
+fn test_totp_secret_encryption() {
+    let key = [7u8; 32];
+    let totp = Totp::generate();
+    let encrypted = totp.encrypt(&key);
+
+    assert_eq!(Totp::decrypt(&encrypted, &key), Some(totp));
+    assert_eq!(Totp::decrypt(&encrypted, &[8u8; 32]), None);
+    assert_eq!(Totp::decrypt("zz", &key), None);
+    assert_eq!(parse_key(&"ab".repeat(32)), Some([0xab; 32]));
+    assert_eq!(parse_key("abcd"), None);
+}
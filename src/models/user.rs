@@ -8,6 +8,21 @@ pub struct User {
     pub email: String,
     pub hashed_password: String,
     pub role: String,
+    /// TOTP secret encrypted under `TOTP_ENCRYPTION_KEY`, see [`Totp::encrypt`](super::Totp::encrypt)
+    pub totp_secret: Option<String>,
+    /// Whether logging in asks for a TOTP code, set once a code confirmed the secret
+    pub totp_enabled: bool,
+    /// False once an admin deactivated the user, who can then no longer log in
+    pub active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+impl User {
+    /// Role of the users allowed to use the admin endpoints, granted directly in the database
+    pub const ADMIN_ROLE: &'static str = "admin";
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Self::ADMIN_ROLE
+    }
+}
@@ -1,35 +1,74 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::State, http::StatusCode, middleware, response::IntoResponse, routing::{get, post, put}, Json, Router
+    extract::{Query, State}, http::StatusCode, middleware, response::IntoResponse, routing::{get, post, put}, Extension, Json, Router
 };
+use chrono::{DateTime, Utc};
+use tracing::warn;
 use utoipa::OpenApi;
 
-use crate::{models::{dto::{AccountResponse, NewAccount, UpdateAccount}, Account, Error}, AppState};
+use crate::{audit::AuditLogger, database::is_unique_violation, models::{dto::{AccountEntityQuery, AccountEntityResponse, AccountPage, AccountResponse, AddressVerificationResponse, BalanceHistoryQuery, BalanceHistoryResponse, BalanceSnapshotResponse, NewAccount, NftHoldingResponse, NftPage, PaginationQuery, PatchAccount, SearchQuery, PortfolioResponse, TransactionHistoryResponse, TransactionResponse, TransactionsQuery, TxCountResponse, UpdateAccount}, Account, AppError, AuditEntry, KnownAddress, User}, AppState, External};
 
-use super::middlewares::auth_guard;
+use super::{
+    extractors::{
+        pagination::{DEFAULT_LIMIT, MAX_LIMIT},
+        Pagination, Search,
+    },
+    middlewares::auth_guard,
+};
 
 /// Defines the OpenAPI spec for account endpoints
 #[derive(OpenApi)]
-#[openapi(paths(create_account_handler, get_account_handler, update_account_handler))]
+#[openapi(paths(
+    create_account_handler,
+    list_accounts_handler,
+    search_accounts_handler,
+    get_account_handler,
+    update_account_handler,
+    patch_account_handler,
+    get_portfolio_handler,
+    get_transactions_handler,
+    get_nft_holdings_handler,
+    watch_account_handler,
+    unwatch_account_handler,
+    get_balance_history_handler,
+    verify_address_handler,
+    get_transaction_count_handler
+))]
 pub struct AccountsApi;
 
 /// Used to group entity endpoints together in the OpenAPI documentation
 pub const ACCOUNT_API_GROUP: &str = "ACCOUNT";
 
-/// Builds a router for account routes
+const DEFAULT_HISTORY_LIMIT: i64 = 90;
+const MAX_HISTORY_LIMIT: i64 = 1000;
+
+/// Builds a router for account routes. Those under `/address/:address` find the account by
+/// its address, the others by its id.
 pub fn account_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/", post(create_account_handler))
+        .route("/", get(list_accounts_handler))
+        .route("/search", get(search_accounts_handler))
         .route("/:id", get(get_account_handler))
-        .route("/:id", put(update_account_handler))
+        .route("/:id", put(update_account_handler).patch(patch_account_handler))
+        .route("/address/:address/verify", get(verify_address_handler))
+        .route("/address/:address/tx-count", get(get_transaction_count_handler))
+        .route("/address/:address/portfolio", get(get_portfolio_handler))
+        .route("/address/:address/nfts", get(get_nft_holdings_handler))
+        .route("/address/:address/transactions", get(get_transactions_handler))
+        .route(
+            "/address/:address/watch",
+            post(watch_account_handler).delete(unwatch_account_handler),
+        )
+        .route("/address/:address/history", get(get_balance_history_handler))
         .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
 }
 
 /// Create account handler function
 #[utoipa::path(
     post,
-    path = "/api/account",
+    path = "/api/v1/account",
     tag = ACCOUNT_API_GROUP,
     request_body = NewAccount,
     security(
@@ -37,87 +76,173 @@ pub fn account_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     ),
     responses(
         (status = 201, description = "Account successfully created", body = AccountResponse),
+        (status = 400, description = "Unknown entity or Aptos address not on-chain", body = ErrorBody),
+        (status = 409, description = "Address already registered, `details.current` is the account registered", body = ErrorBody),
     )
 )]
 pub async fn create_account_handler(
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
     Json(body): Json<NewAccount>,
-) -> Result<Json<AccountResponse>, Error> {
-    // Check if account with the same address already exists
-    if state
-        .db
-        .get_account_by_address(&body.address)
-        .await?
-        .is_some()
-    {
-        return Err(Error::new(
-            StatusCode::BAD_REQUEST,
-            "Account address already exists",
-        ));
-    }
-
+) -> Result<(StatusCode, Json<AccountResponse>), AppError> {
     // Check if the entity associated with the account exists
     if let Some(entity_id) = body.entity_id { 
         if state.db.get_entity_by_id(entity_id).await?.is_none() {
-            return Err(Error::new(StatusCode::BAD_REQUEST, "Entity does not exist"));
+            return Err(AppError::Validation("Entity does not exist".to_string()));
         }
     }
 
+    let network = match body.network.as_deref().map(str::trim) {
+        Some("") => {
+            return Err(AppError::Validation("Network must not be empty".to_string()))
+        }
+        Some(network) => network.to_lowercase(),
+        None => Account::DEFAULT_NETWORK.to_string(),
+    };
+    if network == Account::DEFAULT_NETWORK {
+        check_on_chain(&state, &body.address).await?;
+    }
+
     // Create the new account
     let new_account = Account {
         address: body.address.clone(),
+        network,
         entity_id: body.entity_id,
         ..Default::default()
     };
 
-    let account = state.db.create_account(&new_account).await?;
+    // The unique address refuses a duplicate, a lookup beforehand would race with other requests
+    let mut tx = state.db.begin().await?;
+    let account = match tx.create_account(&new_account).await {
+        Err(error) if is_unique_violation(&error) => {
+            let existing = state
+                .db
+                .get_account_by_address(&new_account.address)
+                .await?
+                .ok_or(error)?;
+            return Err(AppError::Conflict {
+                message: format!(
+                    "Account address {} is already registered as account {}",
+                    existing.address, existing.id
+                ),
+                current: serde_json::to_value(AccountResponse::from(existing))
+                    .map_err(|e| AppError::Internal(e.to_string()))?,
+            });
+        }
+        account => account?,
+    };
+    let response = AccountResponse::from(account);
+    AuditLogger::new(&mut tx, &user)
+        .created(AuditEntry::ACCOUNT, response.id, &response)
+        .await?;
+    tx.commit().await?;
 
-    Ok(Json(AccountResponse {
-        id: account.id,
-        address: account.address,
-        entity_id: account.entity_id,
-        created_at: account.created_at.to_string(),
-        updated_at: account.updated_at.to_string(),
-    }))
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// List accounts handler function
+#[utoipa::path(
+    get,
+    path = "/api/v1/account",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    params(PaginationQuery, AccountEntityQuery),
+    responses(
+        (status = 200, description = "Page of accounts", body = AccountPage),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+    )
+)]
+pub async fn list_accounts_handler(
+    State(state): State<Arc<AppState>>,
+    pagination: Pagination,
+    Query(query): Query<AccountEntityQuery>,
+) -> Result<Json<AccountPage>, AppError> {
+    let (accounts, total) = tokio::try_join!(
+        state.db.list_accounts(pagination.limit, pagination.offset),
+        state.db.count_accounts()
+    )?;
+
+    let mut accounts: Vec<_> = accounts.into_iter().map(AccountResponse::from).collect();
+    if query.include_entity.unwrap_or(false) {
+        embed_entities(&state, &mut accounts).await?;
+    }
+    Ok(Json(pagination.page(accounts, total)))
+}
+
+/// Search the accounts by a part of the name of their entity, ignoring case
+#[utoipa::path(
+    get,
+    path = "/api/v1/account/search",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    params(SearchQuery, AccountEntityQuery),
+    responses(
+        (status = 200, description = "Accounts of the matching entities by entity name, empty when none match", body = [AccountResponse]),
+        (status = 400, description = "Empty query or invalid limit", body = ErrorBody),
+    )
+)]
+pub async fn search_accounts_handler(
+    State(state): State<Arc<AppState>>,
+    search: Search,
+    Query(query): Query<AccountEntityQuery>,
+) -> Result<Json<Vec<AccountResponse>>, AppError> {
+    let accounts = state.db.search_accounts_by_name(&search.q, search.limit).await?;
+
+    let mut accounts: Vec<_> = accounts.into_iter().map(AccountResponse::from).collect();
+    if query.include_entity.unwrap_or(false) {
+        embed_entities(&state, &mut accounts).await?;
+    }
+    Ok(Json(accounts))
 }
 
 /// Get account handler function
 #[utoipa::path(
     get,
-    path = "/api/account/{id}",
+    path = "/api/v1/account/{id}",
     tag = ACCOUNT_API_GROUP,
     security(
         ("bearerAuth" = [])
     ),
     responses(
         (status = 200, description = "Account found", body = AccountResponse),
-        (status = 404, description = "Account not found"),
+        (status = 404, description = "Account not found", body = ErrorBody),
     ),
     params(
-        ("id" = i32, Path, description = "Account ID")
+        ("id" = i32, Path, description = "Account ID"),
+        AccountEntityQuery
     )
 )]
 pub async fn get_account_handler(
     State(state): State<Arc<AppState>>,
     axum::extract::Path(id): axum::extract::Path<i32>,
-) -> Result<impl IntoResponse, StatusCode> {
+    Query(query): Query<AccountEntityQuery>,
+) -> Result<Json<AccountResponse>, AppError> {
     let account = state
         .db
         .get_account_by_id(id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+    let known = match KnownAddress::normalize(&account.address) {
+        Some(address) => state.db.get_known_address(&address).await?,
+        None => None,
+    };
 
-    if let Some(account) = account {
-        Ok((StatusCode::OK, Json(account)))
-    } else {
-        Err(StatusCode::NOT_FOUND)
+    let mut response = AccountResponse::from(account);
+    response.label = known.map(|known| known.label);
+    if query.include_entity.unwrap_or(false) {
+        embed_entities(&state, std::slice::from_mut(&mut response)).await?;
     }
+    Ok(Json(response))
 }
 
 /// Update account handler function
 #[utoipa::path(
     put,
-    path = "/api/account/{id}",
+    path = "/api/v1/account/{id}",
     tag = ACCOUNT_API_GROUP,
     request_body = UpdateAccount,
     security(
@@ -125,8 +250,9 @@ pub async fn get_account_handler(
     ),
     responses(
         (status = 200, description = "Account successfully updated", body = AccountResponse),
-        (status = 404, description = "Account not found"),
-        (status = 400, description = "Invalid entity ID"),
+        (status = 404, description = "Account not found", body = ErrorBody),
+        (status = 400, description = "Invalid entity ID", body = ErrorBody),
+        (status = 409, description = "Account updated since `expected_updated_at`, or while the update was applied, `details.current` is its current state", body = ErrorBody),
     ),
     params(
         ("id" = i32, Path, description = "Account ID")
@@ -134,22 +260,24 @@ pub async fn get_account_handler(
 )]
 pub async fn update_account_handler(
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
     axum::extract::Path(id): axum::extract::Path<i32>,
     Json(body): Json<UpdateAccount>,
-) -> Result<impl IntoResponse, Error> {
+) -> Result<impl IntoResponse, AppError> {
     // Fetch the account by ID
     let account = state
         .db
         .get_account_by_id(id)
-        .await
-        .map_err(|_| Error::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch account"))?;
+        .await?;
 
     if let Some(mut account) = account {
+        let before = AccountResponse::from(account.clone());
+        let read_at = account.updated_at;
         // Check if the entity_id is provided
         if let Some(entity_id) = body.entity_id {
             // If entity_id is Some(value), check if it exists
             if state.db.get_entity_by_id(entity_id).await?.is_none() {
-                return Err(Error::new(StatusCode::BAD_REQUEST, "Entity does not exist"));
+                return Err(AppError::Validation("Entity does not exist".to_string()));
             }
             // Update the entity_id to the provided valid value
             account.entity_id = Some(entity_id);
@@ -158,17 +286,573 @@ pub async fn update_account_handler(
             account.entity_id = None;
         }
 
-        // Persist the updated account to the database
-        let updated_account = state.db.update_account(&account).await?;
+        // Persist the updated account to the database, unless it changed since the client read
+        // it, or else since the handler did
+        let (read_at, message) = match body.expected_updated_at {
+            Some(expected_updated_at) => {
+                (expected_updated_at, "Account was updated since expected_updated_at")
+            }
+            None => (read_at, "Account was updated while the update was applied, send it again"),
+        };
+        let response =
+            write_account_update(&state, &user, &before, &account, read_at, message).await?;
+
+        Ok(Json(response))
+    } else {
+        Err(AppError::NotFound("Account not found".to_string()))
+    }
+}
+
+/// Change only the fields present in the body, `"entity_id": null` detaches the account
+#[utoipa::path(
+    patch,
+    path = "/api/v1/account/{id}",
+    tag = ACCOUNT_API_GROUP,
+    request_body = PatchAccount,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Account successfully updated", body = AccountResponse),
+        (status = 404, description = "Account not found", body = ErrorBody),
+        (status = 400, description = "Invalid entity ID", body = ErrorBody),
+        (status = 409, description = "Account updated while the patch was applied, `details.current` is its current state", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Account ID")
+    )
+)]
+pub async fn patch_account_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Json(body): Json<PatchAccount>,
+) -> Result<Json<AccountResponse>, AppError> {
+    let mut account = state
+        .db
+        .get_account_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+    let before = AccountResponse::from(account.clone());
+    let read_at = account.updated_at;
+
+    if let Some(entity_id) = body.entity_id {
+        if let Some(entity_id) = entity_id {
+            if state.db.get_entity_by_id(entity_id).await?.is_none() {
+                return Err(AppError::Validation("Entity does not exist".to_string()));
+            }
+        }
+        account.entity_id = entity_id;
+    }
+
+    let response = write_account_update(
+        &state,
+        &user,
+        &before,
+        &account,
+        read_at,
+        "Account was updated while the patch was applied, send it again",
+    )
+    .await?;
+    Ok(Json(response))
+}
 
-        Ok(Json(AccountResponse {
-            id: updated_account.id,
-            address: updated_account.address,
-            entity_id: updated_account.entity_id,
-            created_at: updated_account.created_at.to_string(),
-            updated_at: updated_account.updated_at.to_string(),
-        }))
+/// Writes `account`, read as `before` and changed by `user`, along with its audit entry,
+/// unless it was updated since `read_at`. The 409 answered then holds the account as it is,
+/// under `message`.
+async fn write_account_update(
+    state: &AppState,
+    user: &User,
+    before: &AccountResponse,
+    account: &Account,
+    read_at: DateTime<Utc>,
+    message: &str,
+) -> Result<AccountResponse, AppError> {
+    let mut tx = state.db.begin().await?;
+    let Some(updated) = tx
+        .update_account_if_unchanged(account, Some(read_at))
+        .await?
+    else {
+        let current = state
+            .db
+            .get_account_by_id(account.id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+        return Err(AppError::Conflict {
+            message: message.to_string(),
+            current: serde_json::to_value(AccountResponse::from(current))
+                .map_err(|e| AppError::Internal(e.to_string()))?,
+        });
+    };
+    let response = AccountResponse::from(updated);
+    AuditLogger::new(&mut tx, user)
+        .updated(AuditEntry::ACCOUNT, account.id, before, &response)
+        .await?;
+    tx.commit().await?;
+    Ok(response)
+}
+
+/// Get the coins held by an account, valued in USD
+#[utoipa::path(
+    get,
+    path = "/api/v1/account/address/{address}/portfolio",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Coins held by the account, most valuable first", body = PortfolioResponse),
+        (status = 404, description = "Account not found", body = ErrorBody),
+        (status = 422, description = "Account is not on a supported network", body = ErrorBody),
+        (status = 502, description = "Aptos indexer could not be reached", body = ErrorBody),
+        (status = 503, description = "Aptos indexer is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Aptos indexer did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Account address")
+    )
+)]
+pub async fn get_portfolio_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+) -> Result<Json<PortfolioResponse>, AppError> {
+    let account = state
+        .db
+        .get_account_by_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+    External::check_network(&account)?;
+
+    let portfolio = state.external.get_portfolio(&account.address).await?;
+    Ok(Json(PortfolioResponse::from(portfolio)))
+}
+
+/// Get the transactions of an account, newest first, a page at a time
+#[utoipa::path(
+    get,
+    path = "/api/v1/account/address/{address}/transactions",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Page of transactions of the account", body = TransactionHistoryResponse),
+        (status = 400, description = "Invalid limit or function prefix, or both `before_version` and `after_version`", body = ErrorBody),
+        (status = 404, description = "Account not found", body = ErrorBody),
+        (status = 422, description = "Account is not on a supported network", body = ErrorBody),
+        (status = 502, description = "Aptos indexer could not be reached", body = ErrorBody),
+        (status = 503, description = "Aptos indexer is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Aptos indexer did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Account address"),
+        TransactionsQuery
+    )
+)]
+pub async fn get_transactions_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+    Query(query): Query<TransactionsQuery>,
+) -> Result<Json<TransactionHistoryResponse>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        )));
+    }
+    let function = query.function.as_deref().filter(|prefix| !prefix.is_empty());
+    if let Some(prefix) = function {
+        External::check_function_prefix(prefix)?;
+    }
+    let before_version = match (query.before_version, query.after_version) {
+        (Some(_), Some(_)) => {
+            return Err(AppError::Validation(
+                "Only one of before_version and after_version can be set".to_string(),
+            ))
+        }
+        (before, after) => before.or(after),
+    };
+
+    let account = state
+        .db
+        .get_account_by_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+    External::check_network(&account)?;
+
+    let transactions = state
+        .external
+        .fetch_transactions(&account.address, before_version, limit, function)
+        .await?;
+
+    // A short page is the last one
+    let next_before_version = if transactions.len() as i64 == limit {
+        transactions.iter().map(|transaction| transaction.version).min()
     } else {
-        Err(Error::new(StatusCode::NOT_FOUND, "Account not found"))
+        None
+    };
+
+    Ok(Json(TransactionHistoryResponse {
+        items: transactions
+            .into_iter()
+            .map(TransactionResponse::from)
+            .collect(),
+        limit,
+        next_before_version,
+    }))
+}
+
+/// Get the NFTs held by an account, most recently moved first, a page at a time
+#[utoipa::path(
+    get,
+    path = "/api/v1/account/address/{address}/nfts",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Page of tokens owned by the account", body = NftPage),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+        (status = 404, description = "Account not found", body = ErrorBody),
+        (status = 422, description = "Account is not on a supported network", body = ErrorBody),
+        (status = 502, description = "Aptos indexer could not be reached", body = ErrorBody),
+        (status = 503, description = "Aptos indexer is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Aptos indexer did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Account address"),
+        PaginationQuery
+    )
+)]
+pub async fn get_nft_holdings_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+    pagination: Pagination,
+) -> Result<Json<NftPage>, AppError> {
+    let account = state
+        .db
+        .get_account_by_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+    External::check_network(&account)?;
+
+    let (holdings, total) = state
+        .external
+        .fetch_nft_holdings(&account.address, pagination.limit, pagination.offset)
+        .await?;
+    let holdings = holdings.into_iter().map(NftHoldingResponse::from).collect();
+    Ok(Json(pagination.page(holdings, total)))
+}
+
+/// Snapshot the USD value of the account at `address` every day, see its history
+#[utoipa::path(
+    post,
+    path = "/api/v1/account/address/{address}/watch",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "Account is watched"),
+        (status = 404, description = "Account not found", body = ErrorBody),
+        (status = 422, description = "Account is not on a supported network or the watchlist is full", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Account address")
+    )
+)]
+pub async fn watch_account_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+) -> Result<StatusCode, AppError> {
+    let account = state
+        .db
+        .get_account_by_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+    External::check_network(&account)?;
+
+    let max = state.config.watchlist_max_accounts;
+    if !state.db.watch_account(account.id, max).await? {
+        return Err(AppError::Unprocessable(format!(
+            "The watchlist is full, at most {max} accounts can be watched"
+        )));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Stop snapshotting the USD value of the account at `address`, its history is kept
+#[utoipa::path(
+    delete,
+    path = "/api/v1/account/address/{address}/watch",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "Account is no longer watched"),
+        (status = 404, description = "Account not found or not watched", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Account address")
+    )
+)]
+pub async fn unwatch_account_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+) -> Result<StatusCode, AppError> {
+    let account = state
+        .db
+        .get_account_by_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+
+    if !state.db.unwatch_account(account.id).await? {
+        return Err(AppError::NotFound("Account is not watched".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get the daily USD value of the account at `address` since it was first watched
+#[utoipa::path(
+    get,
+    path = "/api/v1/account/address/{address}/history",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Balance snapshots of the account, oldest first", body = BalanceHistoryResponse),
+        (status = 400, description = "Invalid limit", body = ErrorBody),
+        (status = 404, description = "Account not found", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Account address"),
+        BalanceHistoryQuery
+    )
+)]
+pub async fn get_balance_history_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+    Query(query): Query<BalanceHistoryQuery>,
+) -> Result<Json<BalanceHistoryResponse>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if !(1..=MAX_HISTORY_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {MAX_HISTORY_LIMIT}"
+        )));
+    }
+    let account = state
+        .db
+        .get_account_by_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+
+    let (watched, snapshots) = tokio::try_join!(
+        state.db.is_account_watched(account.id),
+        state.db.get_balance_history(account.id, limit)
+    )?;
+    Ok(Json(BalanceHistoryResponse {
+        address: account.address,
+        watched,
+        snapshots: snapshots.into_iter().map(BalanceSnapshotResponse::from).collect(),
+    }))
+}
+
+/// Check whether an Aptos address exists on-chain
+#[utoipa::path(
+    get,
+    path = "/api/v1/account/address/{address}/verify",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Whether the fullnode knows the address", body = AddressVerificationResponse),
+        (status = 400, description = "Not an account address", body = ErrorBody),
+        (status = 502, description = "Aptos fullnode could not be reached", body = ErrorBody),
+        (status = 503, description = "Aptos fullnode is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Aptos fullnode did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Account address")
+    )
+)]
+pub async fn verify_address_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+) -> Result<Json<AddressVerificationResponse>, AppError> {
+    let normalized = normalize_address(&address)?;
+    let account = state.external.verify_address(&normalized).await?;
+    Ok(Json(AddressVerificationResponse::new(address, account)))
+}
+
+/// Count the transactions of an Aptos address, without fetching them
+#[utoipa::path(
+    get,
+    path = "/api/v1/account/address/{address}/tx-count",
+    tag = ACCOUNT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Number of transactions of the address", body = TxCountResponse),
+        (status = 400, description = "Not an account address", body = ErrorBody),
+        (status = 502, description = "Aptos indexer could not be reached", body = ErrorBody),
+        (status = 503, description = "Aptos indexer is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Aptos indexer did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Account address, leading zeros may be omitted")
+    )
+)]
+pub async fn get_transaction_count_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+) -> Result<Json<TxCountResponse>, AppError> {
+    let address = normalize_address(&address)?;
+    let count = state.external.get_transaction_count_for_address(&address).await?;
+    Ok(Json(TxCountResponse { address, count }))
+}
+
+/// Sets the entity of the `accounts` owned by one, fetching the entities in one query
+async fn embed_entities(state: &AppState, accounts: &mut [AccountResponse]) -> Result<(), AppError> {
+    let mut ids: Vec<i32> = accounts.iter().filter_map(|account| account.entity_id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let entities = state.db.get_entities_by_ids(&ids).await?;
+    for account in accounts {
+        account.entity = entities
+            .iter()
+            .find(|entity| Some(entity.id) == account.entity_id)
+            .cloned()
+            .map(AccountEntityResponse::from);
+    }
+    Ok(())
+}
+
+fn normalize_address(address: &str) -> Result<String, AppError> {
+    KnownAddress::normalize(address)
+        .ok_or_else(|| AppError::Validation(format!("{address} is not an account address")))
+}
+
+/// Rejects an address the Aptos fullnode has never seen. When the fullnode can't be reached
+/// the account is let through, registering one shouldn't wait on the chain being up.
+async fn check_on_chain(state: &AppState, address: &str) -> Result<(), AppError> {
+    let normalized = normalize_address(address)?;
+    match state.external.verify_address(&normalized).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(AppError::Validation(format!(
+            "Account {address} does not exist on {}, it must have sent or received a transaction",
+            Account::DEFAULT_NETWORK
+        ))),
+        Err(error) => {
+            warn!(address, %error, "Could not check that an account exists on-chain");
+            Ok(())
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_patch_after_concurrent_update_conflicts() {
+    let test_db = crate::database::test_db::TestDatabase::migrated().await;
+    let state = AppState::for_test(test_db.db.clone());
+    let user = state
+        .db
+        .create_user(&User {
+            name: "Ada".to_string(),
+            email: "ada@example.com".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let entity = state
+        .db
+        .create_entity(&crate::models::Entity {
+            name: "Treasury".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let account = state
+        .db
+        .create_account(&Account {
+            address: "0xa".to_string(),
+            network: Account::DEFAULT_NETWORK.to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // Another admin attaches the account while the patch detaching it is applied
+    state
+        .db
+        .update_account(&Account {
+            entity_id: Some(entity.id),
+            ..account.clone()
+        })
+        .await
+        .unwrap();
+    let result = write_account_update(
+        &state,
+        &user,
+        &AccountResponse::from(account.clone()),
+        &Account {
+            entity_id: None,
+            ..account.clone()
+        },
+        account.updated_at,
+        "stale",
+    )
+    .await;
+    let Err(AppError::Conflict { current, .. }) = result else {
+        panic!("Expected a conflict, got {:?}", result.map(|response| response.id));
+    };
+    assert_eq!(current["entity_id"], entity.id);
+    let stored = state.db.get_account_by_id(account.id).await.unwrap().unwrap();
+    assert_eq!(stored.entity_id, Some(entity.id));
+}
+
+#[tokio::test]
+async fn test_documented_routes_are_served() {
+    use axum::{
+        body::Body,
+        http::{Method, Request},
+    };
+    use tower::ServiceExt;
+    use utoipa::openapi::PathItemType;
+
+    let test_db = crate::database::test_db::TestDatabase::migrated().await;
+    let state = AppState::for_test(test_db.db.clone());
+    let app = Router::new()
+        .nest("/api/v1/account", account_routes(state.clone()))
+        .with_state(state);
+    for (path, item) in AccountsApi::openapi().paths.paths {
+        let uri = path.replace("{id}", "1").replace("{address}", "0x1");
+        for operation in item.operations.keys() {
+            let method = match operation {
+                PathItemType::Get => Method::GET,
+                PathItemType::Post => Method::POST,
+                PathItemType::Put => Method::PUT,
+                PathItemType::Patch => Method::PATCH,
+                PathItemType::Delete => Method::DELETE,
+                _ => unreachable!("Account routes only take these methods"),
+            };
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            // Without a token a route that is served stops at the auth guard
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::UNAUTHORIZED,
+                "{method} {uri}"
+            );
+        }
     }
 }
@@ -0,0 +1,567 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use utoipa::OpenApi;
+
+use crate::{
+    audit::AuditLogger,
+    models::{
+        dto::{
+            AuditEntryResponse, AuditQuery, CacheStatsResponse, CoinFilterResponse,
+            KnownAddressPage, KnownAddressResponse, NewCoinFilter, NewKnownAddress,
+            PaginationQuery, Profile, TaskStartedResponse, TvlBackfillRequest, UpdateKnownAddress,
+            UserPage,
+        },
+        AppError, AuditEntry, CoinFilter, KnownAddress, User,
+    },
+    scheduler::{spawn_tvl_backfill, MAX_BACKFILL_DAYS, TVL_BACKFILL_TASK},
+    AppState, External,
+};
+
+use super::{
+    extractors::Pagination,
+    middlewares::{admin_guard, auth_guard},
+};
+
+/// Defines the OpenAPI spec for admin endpoints
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_labels_handler,
+    create_label_handler,
+    get_label_handler,
+    update_label_handler,
+    delete_label_handler,
+    list_coin_filters_handler,
+    upsert_coin_filter_handler,
+    delete_coin_filter_handler,
+    backfill_tvl_handler,
+    list_users_handler,
+    deactivate_user_handler,
+    list_audit_handler,
+    list_cache_stats_handler
+))]
+pub struct AdminApi;
+
+/// Used to group admin endpoints together in the OpenAPI documentation
+pub const ADMIN_API_GROUP: &str = "ADMIN";
+
+/// Builds a router for the admin routes, only open to admins
+pub fn admin_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/labels",
+            get(list_labels_handler).post(create_label_handler),
+        )
+        .route(
+            "/labels/:address",
+            get(get_label_handler)
+                .put(update_label_handler)
+                .delete(delete_label_handler),
+        )
+        .route(
+            "/tokens",
+            get(list_coin_filters_handler).post(upsert_coin_filter_handler),
+        )
+        .route("/tokens/:coin_type", delete(delete_coin_filter_handler))
+        .route("/tasks/tvl-backfill", post(backfill_tvl_handler))
+        .route("/users", get(list_users_handler))
+        .route("/users/:id", delete(deactivate_user_handler))
+        .route("/audit", get(list_audit_handler))
+        .route("/caches", get(list_cache_stats_handler))
+        .route_layer(middleware::from_fn(admin_guard))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+}
+
+fn normalize_address(address: &str) -> Result<String, AppError> {
+    KnownAddress::normalize(address)
+        .ok_or_else(|| AppError::Validation(format!("{address} is not an account address")))
+}
+
+fn check_not_blank(field: &str, value: &str) -> Result<(), AppError> {
+    if value.trim().is_empty() {
+        return Err(AppError::Validation(format!("{field} must not be empty")));
+    }
+    Ok(())
+}
+
+/// List the labeled addresses
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/labels",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    params(PaginationQuery),
+    responses(
+        (status = 200, description = "Page of labeled addresses", body = KnownAddressPage),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+    )
+)]
+pub async fn list_labels_handler(
+    State(state): State<Arc<AppState>>,
+    pagination: Pagination,
+) -> Result<Json<KnownAddressPage>, AppError> {
+    let (labels, total) = tokio::try_join!(
+        state
+            .db
+            .list_known_addresses(pagination.limit, pagination.offset),
+        state.db.count_known_addresses()
+    )?;
+
+    let labels = labels.into_iter().map(KnownAddressResponse::from).collect();
+    Ok(Json(pagination.page(labels, total)))
+}
+
+/// Label an address, which then shows up by its label in transaction lists
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/labels",
+    tag = ADMIN_API_GROUP,
+    request_body = NewKnownAddress,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "Address successfully labeled", body = KnownAddressResponse),
+        (status = 400, description = "Invalid address or address already labeled", body = ErrorBody),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+    )
+)]
+pub async fn create_label_handler(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<NewKnownAddress>,
+) -> Result<impl IntoResponse, AppError> {
+    let address = normalize_address(&body.address)?;
+    check_not_blank("label", &body.label)?;
+    check_not_blank("category", &body.category)?;
+    if state.db.get_known_address(&address).await?.is_some() {
+        return Err(AppError::Validation(
+            "Address is already labeled".to_string(),
+        ));
+    }
+
+    let known = KnownAddress {
+        address,
+        label: body.label,
+        category: body.category,
+        ..Default::default()
+    };
+    let known = state.db.create_known_address(&known).await?;
+    Ok((StatusCode::CREATED, Json(KnownAddressResponse::from(known))))
+}
+
+/// Get the label of an address
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/labels/{address}",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Label found", body = KnownAddressResponse),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+        (status = 404, description = "Address is not labeled", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Labeled address, leading zeros may be omitted")
+    )
+)]
+pub async fn get_label_handler(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Result<Json<KnownAddressResponse>, AppError> {
+    let known = find_label(&state, &address).await?;
+    Ok(Json(KnownAddressResponse::from(known)))
+}
+
+/// Change the label or the category of an address
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/labels/{address}",
+    tag = ADMIN_API_GROUP,
+    request_body = UpdateKnownAddress,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Label successfully updated", body = KnownAddressResponse),
+        (status = 400, description = "Empty label or category", body = ErrorBody),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+        (status = 404, description = "Address is not labeled", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Labeled address, leading zeros may be omitted")
+    )
+)]
+pub async fn update_label_handler(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+    Json(body): Json<UpdateKnownAddress>,
+) -> Result<Json<KnownAddressResponse>, AppError> {
+    let mut known = find_label(&state, &address).await?;
+
+    if let Some(label) = body.label {
+        check_not_blank("label", &label)?;
+        known.label = label;
+    }
+
+    if let Some(category) = body.category {
+        check_not_blank("category", &category)?;
+        known.category = category;
+    }
+
+    let known = state.db.update_known_address(&known).await?;
+    Ok(Json(KnownAddressResponse::from(known)))
+}
+
+/// Remove the label of an address
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/labels/{address}",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "Label successfully removed"),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+        (status = 404, description = "Address is not labeled", body = ErrorBody),
+    ),
+    params(
+        ("address" = String, Path, description = "Labeled address, leading zeros may be omitted")
+    )
+)]
+pub async fn delete_label_handler(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let address = normalize_address(&address)?;
+    if !state.db.delete_known_address(&address).await? {
+        return Err(AppError::NotFound("Address is not labeled".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn find_label(state: &AppState, address: &str) -> Result<KnownAddress, AppError> {
+    let address = normalize_address(address)?;
+    state
+        .db
+        .get_known_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Address is not labeled".to_string()))
+}
+
+/// List the whitelisted and blacklisted coins
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/tokens",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Coins on the whitelist or the blacklist", body = [CoinFilterResponse]),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+    )
+)]
+pub async fn list_coin_filters_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<CoinFilterResponse>>, AppError> {
+    let filters = state.db.list_coin_filters().await?;
+    Ok(Json(
+        filters.into_iter().map(CoinFilterResponse::from).collect(),
+    ))
+}
+
+/// Whitelist or blacklist a coin. Blacklisted coins are left out of the total values locked,
+/// and with `STRICT_TOKEN_WHITELIST` only the whitelisted ones are counted.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/tokens",
+    tag = ADMIN_API_GROUP,
+    request_body = NewCoinFilter,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Coin successfully listed", body = CoinFilterResponse),
+        (status = 400, description = "Invalid coin type or status", body = ErrorBody),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+    )
+)]
+pub async fn upsert_coin_filter_handler(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<NewCoinFilter>,
+) -> Result<Json<CoinFilterResponse>, AppError> {
+    External::check_coin_type(&body.coin_type)?;
+    let status = body.status.to_uppercase();
+    if !CoinFilter::STATUSES.contains(&status.as_str()) {
+        return Err(AppError::Validation(format!(
+            "status must be one of {}",
+            CoinFilter::STATUSES.join(", ")
+        )));
+    }
+
+    let filter = state
+        .db
+        .upsert_coin_filter(&body.coin_type, &status)
+        .await?;
+    Ok(Json(CoinFilterResponse::from(filter)))
+}
+
+/// Take a coin off the whitelist or the blacklist
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/tokens/{coin_type}",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "Coin successfully unlisted"),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+        (status = 404, description = "Coin is on neither list", body = ErrorBody),
+    ),
+    params(
+        ("coin_type" = String, Path, description = "Coin type, e.g. `0x1::aptos_coin::AptosCoin`")
+    )
+)]
+pub async fn delete_coin_filter_handler(
+    State(state): State<Arc<AppState>>,
+    Path(coin_type): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if !state.db.delete_coin_filter(&coin_type).await? {
+        return Err(AppError::NotFound("Coin is on neither list".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Days backfilled when the request doesn't say
+const DEFAULT_BACKFILL_DAYS: i64 = 30;
+
+/// Compute the total value locked of a project on each of the last days and write it into
+/// its attribute history, so charts have data before the daily values accumulate. Runs in the
+/// background, as it takes a few dozen fullnode calls per day.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/tasks/tvl-backfill",
+    tag = ADMIN_API_GROUP,
+    request_body = TvlBackfillRequest,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 202, description = "Backfill started", body = TaskStartedResponse),
+        (status = 400, description = "Invalid number of days, or a backfill of the project is already running", body = ErrorBody),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 422, description = "Project has no contract address", body = ErrorBody),
+    )
+)]
+pub async fn backfill_tvl_handler(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<TvlBackfillRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let days = body.days.unwrap_or(DEFAULT_BACKFILL_DAYS);
+    if !(1..=MAX_BACKFILL_DAYS).contains(&days) {
+        return Err(AppError::Validation(format!(
+            "days must be between 1 and {MAX_BACKFILL_DAYS}"
+        )));
+    }
+    let project = state
+        .db
+        .get_project_by_id(body.project_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    if project.contract_address.is_none() {
+        return Err(AppError::Unprocessable(
+            "Project has no contract address".to_string(),
+        ));
+    }
+
+    let started = state
+        .tvl_backfills
+        .lock()
+        .expect("Backfills lock must not be poisoned")
+        .insert(project.id);
+    if !started {
+        return Err(AppError::Validation(
+            "A backfill of this project is already running".to_string(),
+        ));
+    }
+    spawn_tvl_backfill(state.clone(), project, days);
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(TaskStartedResponse {
+            task: TVL_BACKFILL_TASK.to_string(),
+            project_id: body.project_id,
+            days,
+        }),
+    ))
+}
+
+/// List the registered users, deactivated ones included
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    params(PaginationQuery),
+    responses(
+        (status = 200, description = "Page of users, oldest first", body = UserPage),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+    )
+)]
+pub async fn list_users_handler(
+    State(state): State<Arc<AppState>>,
+    pagination: Pagination,
+) -> Result<Json<UserPage>, AppError> {
+    let (users, total) = tokio::try_join!(
+        state.db.list_users(pagination.limit, pagination.offset),
+        state.db.count_users()
+    )?;
+
+    let users = users.into_iter().map(Profile::from).collect();
+    Ok(Json(pagination.page(users, total)))
+}
+
+/// Deactivate a user, who can then no longer log in or use their tokens. Their data is kept.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/users/{id}",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "User successfully deactivated"),
+        (status = 400, description = "Admins can't deactivate themselves", body = ErrorBody),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "User ID")
+    )
+)]
+pub async fn deactivate_user_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    // Keeps an admin from locking themselves out, possibly the last one
+    if admin.id == id {
+        return Err(AppError::Validation(
+            "Admins can't deactivate themselves".to_string(),
+        ));
+    }
+    let not_found = || AppError::NotFound("User not found".to_string());
+    let user = state.db.get_user_by_id(id).await?.ok_or_else(not_found)?;
+    let mut tx = state.db.begin().await?;
+    if !tx.deactivate_user(id).await? {
+        return Err(not_found());
+    }
+    let deactivated = Profile {
+        active: false,
+        ..Profile::from(user.clone())
+    };
+    AuditLogger::new(&mut tx, &admin)
+        .updated(AuditEntry::USER, id, &Profile::from(user), &deactivated)
+        .await?;
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Entries returned by the audit log when the request doesn't say
+const DEFAULT_AUDIT_LIMIT: i64 = 50;
+/// Most entries the audit log returns at once
+const MAX_AUDIT_LIMIT: i64 = 500;
+
+/// List the latest changes users made to users, accounts, entities and projects, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Latest audit entries, newest first", body = [AuditEntryResponse]),
+        (status = 400, description = "Unknown resource type, resource ID without a type or invalid limit", body = ErrorBody),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+    )
+)]
+pub async fn list_audit_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntryResponse>>, AppError> {
+    let resource_type = query.resource_type.as_deref();
+    if let Some(resource_type) = resource_type {
+        if !AuditEntry::RESOURCE_TYPES.contains(&resource_type) {
+            return Err(AppError::Validation(format!(
+                "resource_type must be one of {}",
+                AuditEntry::RESOURCE_TYPES.join(", ")
+            )));
+        }
+    } else if query.resource_id.is_some() {
+        return Err(AppError::Validation(
+            "resource_id must be set along with resource_type".to_string(),
+        ));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT);
+    if !(1..=MAX_AUDIT_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {MAX_AUDIT_LIMIT}"
+        )));
+    }
+
+    let entries = state
+        .db
+        .list_audit_entries(resource_type, query.resource_id, limit)
+        .await?;
+    Ok(Json(
+        entries.into_iter().map(AuditEntryResponse::from).collect(),
+    ))
+}
+
+/// Hits and misses of the in-memory caches of the external data, since the server started
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/caches",
+    tag = ADMIN_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Lookups of each cache", body = [CacheStatsResponse]),
+        (status = 403, description = "The user is not an admin", body = ErrorBody),
+    )
+)]
+pub async fn list_cache_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Json<Vec<CacheStatsResponse>> {
+    let stats = state
+        .external
+        .cache_stats()
+        .into_iter()
+        .map(|(cache, (hits, misses))| CacheStatsResponse {
+            cache: cache.to_string(),
+            hits,
+            misses,
+        });
+    Json(stats.collect())
+}
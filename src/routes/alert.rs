@@ -0,0 +1,375 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::get,
+    Extension, Json, Router,
+};
+use reqwest::Url;
+use utoipa::OpenApi;
+
+use crate::{
+    external::webhook,
+    models::{
+        dto::{
+            AlertDeliveryPage, AlertDeliveryResponse, AlertRulePage, AlertRuleResponse,
+            AnomalyPage, AnomalyResponse, NewAlertRule, PaginationQuery, UpdateAlertRule,
+        },
+        AlertRule, AppError, User,
+    },
+    AppState,
+};
+
+use super::{extractors::Pagination, middlewares::auth_guard};
+
+/// Defines the OpenAPI spec for the whale swap alert endpoints
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_alert_rules_handler,
+    create_alert_rule_handler,
+    get_alert_rule_handler,
+    update_alert_rule_handler,
+    delete_alert_rule_handler,
+    list_alert_deliveries_handler,
+    list_anomalies_handler
+))]
+pub struct AlertApi;
+
+/// Used to group alert endpoints together in the OpenAPI documentation
+pub const ALERT_API_GROUP: &str = "ALERT";
+
+/// Builds a router for the alert rules of projects, nested with the project routes
+pub fn alert_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/:id/alerts",
+            get(list_alert_rules_handler).post(create_alert_rule_handler),
+        )
+        .route(
+            "/:id/alerts/:alert_id",
+            get(get_alert_rule_handler)
+                .put(update_alert_rule_handler)
+                .delete(delete_alert_rule_handler),
+        )
+        .route(
+            "/:id/alerts/:alert_id/deliveries",
+            get(list_alert_deliveries_handler),
+        )
+        .route("/:id/anomalies", get(list_anomalies_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+}
+
+fn check_kind(kind: &str) -> Result<(), AppError> {
+    if !AlertRule::KINDS.contains(&kind) {
+        return Err(AppError::Validation(format!(
+            "kind must be one of {}",
+            AlertRule::KINDS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+fn check_min_usd(min_usd: f64) -> Result<(), AppError> {
+    if !min_usd.is_finite() || min_usd <= 0.0 {
+        return Err(AppError::Validation(
+            "min_usd must be a positive number".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects a webhook that isn't an http(s) URL, or that is on a loopback, private or
+/// link-local address unless the config allows it
+async fn check_webhook_url(state: &AppState, webhook_url: &str) -> Result<(), AppError> {
+    if state.config.allow_private_webhooks {
+        return match Url::parse(webhook_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+            _ => Err(AppError::Validation(format!(
+                "{webhook_url} is not an http(s) URL"
+            ))),
+        };
+    }
+    webhook::check_public_url(webhook_url)
+        .await
+        .map_err(AppError::Validation)
+}
+
+async fn check_project(state: &AppState, project_id: i32) -> Result<(), AppError> {
+    match state.db.get_project_by_id(project_id).await? {
+        Some(_) => Ok(()),
+        None => Err(AppError::NotFound("Project not found".to_string())),
+    }
+}
+
+/// Alert rule of a project created by `user`, the rules of other users are not found
+async fn find_rule(
+    state: &AppState,
+    user: &User,
+    project_id: i32,
+    id: i32,
+) -> Result<AlertRule, AppError> {
+    state
+        .db
+        .get_alert_rule(project_id, id, &user.id.to_string())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Alert rule not found".to_string()))
+}
+
+/// List the alert rules the user created for a project
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/alerts",
+    tag = ALERT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Page of the user's alert rules of the project", body = AlertRulePage),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        PaginationQuery
+    )
+)]
+pub async fn list_alert_rules_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Extension(user): Extension<User>,
+    pagination: Pagination,
+) -> Result<Json<AlertRulePage>, AppError> {
+    check_project(&state, id).await?;
+    let created_by = user.id.to_string();
+    let (rules, total) = tokio::try_join!(
+        state
+            .db
+            .list_alert_rules(id, Some(&created_by), pagination.limit, pagination.offset),
+        state.db.count_alert_rules(id, Some(&created_by))
+    )?;
+
+    let rules = rules.into_iter().map(AlertRuleResponse::from).collect();
+    Ok(Json(pagination.page(rules, total)))
+}
+
+/// Post the swaps of the project's token worth at least `min_usd` to a webhook, or the spikes
+/// of its trading volume when the volume is at least `min_usd` for an `anomaly` rule
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/alerts",
+    tag = ALERT_API_GROUP,
+    request_body = NewAlertRule,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "Alert rule successfully created", body = AlertRuleResponse),
+        (status = 400, description = "Invalid kind, threshold or webhook URL", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn create_alert_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Extension(user): Extension<User>,
+    Json(body): Json<NewAlertRule>,
+) -> Result<impl IntoResponse, AppError> {
+    let kind = body.kind.as_deref().unwrap_or(AlertRule::SWAP_KIND);
+    check_kind(kind)?;
+    check_min_usd(body.min_usd)?;
+    check_webhook_url(&state, &body.webhook_url).await?;
+    check_project(&state, id).await?;
+
+    let rule = AlertRule {
+        project_id: id,
+        kind: kind.to_string(),
+        min_usd: body.min_usd,
+        webhook_url: body.webhook_url,
+        created_by: user.id.to_string(),
+        ..Default::default()
+    };
+    let rule = state.db.create_alert_rule(&rule).await?;
+    Ok((StatusCode::CREATED, Json(AlertRuleResponse::from(rule))))
+}
+
+/// Get an alert rule of a project
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/alerts/{alert_id}",
+    tag = ALERT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Alert rule found", body = AlertRuleResponse),
+        (status = 404, description = "Alert rule not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        ("alert_id" = i32, Path, description = "Alert rule ID")
+    )
+)]
+pub async fn get_alert_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, alert_id)): Path<(i32, i32)>,
+    Extension(user): Extension<User>,
+) -> Result<Json<AlertRuleResponse>, AppError> {
+    let rule = find_rule(&state, &user, id, alert_id).await?;
+    Ok(Json(AlertRuleResponse::from(rule)))
+}
+
+/// Change the threshold or the webhook of an alert rule
+#[utoipa::path(
+    put,
+    path = "/api/v1/project/{id}/alerts/{alert_id}",
+    tag = ALERT_API_GROUP,
+    request_body = UpdateAlertRule,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Alert rule successfully updated", body = AlertRuleResponse),
+        (status = 400, description = "Invalid threshold or webhook URL", body = ErrorBody),
+        (status = 404, description = "Alert rule not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        ("alert_id" = i32, Path, description = "Alert rule ID")
+    )
+)]
+pub async fn update_alert_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, alert_id)): Path<(i32, i32)>,
+    Extension(user): Extension<User>,
+    Json(body): Json<UpdateAlertRule>,
+) -> Result<Json<AlertRuleResponse>, AppError> {
+    let mut rule = find_rule(&state, &user, id, alert_id).await?;
+
+    if let Some(min_usd) = body.min_usd {
+        check_min_usd(min_usd)?;
+        rule.min_usd = min_usd;
+    }
+
+    if let Some(webhook_url) = body.webhook_url {
+        check_webhook_url(&state, &webhook_url).await?;
+        rule.webhook_url = webhook_url;
+    }
+
+    let rule = state.db.update_alert_rule(&rule).await?;
+    Ok(Json(AlertRuleResponse::from(rule)))
+}
+
+/// Delete an alert rule of a project, along with its deliveries
+#[utoipa::path(
+    delete,
+    path = "/api/v1/project/{id}/alerts/{alert_id}",
+    tag = ALERT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "Alert rule successfully deleted"),
+        (status = 404, description = "Alert rule not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        ("alert_id" = i32, Path, description = "Alert rule ID")
+    )
+)]
+pub async fn delete_alert_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, alert_id)): Path<(i32, i32)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, AppError> {
+    let created_by = user.id.to_string();
+    if !state
+        .db
+        .delete_alert_rule(id, alert_id, &created_by)
+        .await?
+    {
+        return Err(AppError::NotFound("Alert rule not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the swaps an alert rule fired for and whether the webhook got them, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/alerts/{alert_id}/deliveries",
+    tag = ALERT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Page of deliveries of the alert rule", body = AlertDeliveryPage),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+        (status = 404, description = "Alert rule not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        ("alert_id" = i32, Path, description = "Alert rule ID"),
+        PaginationQuery
+    )
+)]
+pub async fn list_alert_deliveries_handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, alert_id)): Path<(i32, i32)>,
+    Extension(user): Extension<User>,
+    pagination: Pagination,
+) -> Result<Json<AlertDeliveryPage>, AppError> {
+    let rule = find_rule(&state, &user, id, alert_id).await?;
+    let (deliveries, total) = tokio::try_join!(
+        state
+            .db
+            .list_alert_deliveries(rule.id, pagination.limit, pagination.offset),
+        state.db.count_alert_deliveries(rule.id)
+    )?;
+
+    let deliveries = deliveries
+        .into_iter()
+        .map(AlertDeliveryResponse::from)
+        .collect();
+    Ok(Json(pagination.page(deliveries, total)))
+}
+
+/// List the anomalies found in the metrics of a project, such as trading volume spikes,
+/// newest first. Each was also posted to the webhooks of the project's alert rules.
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/anomalies",
+    tag = ALERT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Page of anomalies of the project", body = AnomalyPage),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        PaginationQuery
+    )
+)]
+pub async fn list_anomalies_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    pagination: Pagination,
+) -> Result<Json<AnomalyPage>, AppError> {
+    check_project(&state, id).await?;
+    let (anomalies, total) = tokio::try_join!(
+        state
+            .db
+            .list_anomaly_events(id, pagination.limit, pagination.offset),
+        state.db.count_anomaly_events(id)
+    )?;
+
+    let anomalies = anomalies.into_iter().map(AnomalyResponse::from).collect();
+    Ok(Json(pagination.page(anomalies, total)))
+}
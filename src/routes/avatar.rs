@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::header::{CACHE_CONTROL, CONTENT_TYPE},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use tracing::error;
+use utoipa::OpenApi;
+
+use crate::{models::AppError, storage::ImageFormat, AppState};
+
+use super::project::PROJECT_API_GROUP;
+
+#[derive(OpenApi)]
+#[openapi(paths(get_avatar_handler))]
+/// Defines the OpenAPI spec for the avatar endpoint
+pub struct AvatarApi;
+
+/// Builds a router for the stored avatars, public so pages can embed them
+pub fn avatar_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/:file_name", get(get_avatar_handler))
+}
+
+/// Serve a project avatar uploaded to `/api/v1/project/{id}/avatar`. Every upload gets a new
+/// file name, so the images can be cached for good.
+#[utoipa::path(
+    get,
+    path = "/api/v1/avatars/{file_name}",
+    tag = PROJECT_API_GROUP,
+    responses(
+        (status = 200, description = "JPEG, PNG or WebP image", content_type = "image/*"),
+        (status = 404, description = "Avatar not found", body = ErrorBody),
+    ),
+    params(
+        ("file_name" = String, Path, description = "File name, the last segment of the avatar URL")
+    )
+)]
+pub async fn get_avatar_handler(
+    State(state): State<Arc<AppState>>,
+    Path(file_name): Path<String>,
+) -> Result<Response, AppError> {
+    let not_found = || AppError::NotFound("Avatar not found".to_string());
+    let format = ImageFormat::of_file(&file_name).ok_or_else(not_found)?;
+    let bytes = state
+        .avatars
+        .read(&file_name)
+        .await
+        .map_err(|e| {
+            error!(error = %e, file_name, "Could not read an avatar");
+            AppError::Internal("Could not read the avatar".to_string())
+        })?
+        .ok_or_else(not_found)?;
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type()),
+            (CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        bytes,
+    )
+        .into_response())
+}
@@ -0,0 +1,94 @@
+use std::{borrow::Cow, convert::Infallible};
+
+use axum::{
+    body::Body,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderValue,
+    },
+    response::{IntoResponse, Response},
+};
+use futures::stream;
+
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Field quoted as RFC 4180 asks when it holds a separator, a quote or a line break
+pub fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// One CSV line, ended by CRLF
+pub fn row<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut line = fields
+        .iter()
+        .map(|field| escape(field.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// Filename made of `parts` joined with `_`, keeping only characters safe in a header and on
+/// every file system
+pub fn filename(parts: &[&str]) -> String {
+    let name: String = parts
+        .iter()
+        .filter(|part| !part.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '-',
+        })
+        .collect();
+    format!("{name}.csv")
+}
+
+/// Attachment streaming `header` then every row, each formatted only once the client reads it
+pub fn attachment<I>(filename: &str, header: &[&str], rows: I) -> Response
+where
+    I: IntoIterator<Item = String>,
+    I::IntoIter: Send + 'static,
+{
+    let lines = std::iter::once(row(header))
+        .chain(rows)
+        .map(Ok::<_, Infallible>);
+    let disposition = format!("attachment; filename=\"{filename}\"");
+
+    (
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(CSV_CONTENT_TYPE)),
+            (
+                CONTENT_DISPOSITION,
+                HeaderValue::from_str(&disposition).expect("Filename must be a valid header"),
+            ),
+        ],
+        Body::from_stream(stream::iter(lines)),
+    )
+        .into_response()
+}
+
+#[test]
+fn test_escape_csv_fields() {
+    assert_eq!(row(&["0x1", "Aptos Framework"]), "0x1,Aptos Framework\r\n");
+    assert_eq!(
+        row(&["a,b", "say \"hi\"", "two\nlines"]),
+        "\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n"
+    );
+    assert_eq!(row::<&str>(&["", ""]), ",\r\n");
+}
+
+#[test]
+fn test_csv_filename() {
+    assert_eq!(
+        filename(&["Pancake Swap/v2", "total_value_locked", "2026-10-01"]),
+        "Pancake-Swap-v2_total_value_locked_2026-10-01.csv"
+    );
+    assert_eq!(filename(&["project-7", ""]), "project-7.csv");
+}
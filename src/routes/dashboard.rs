@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use axum::{extract::State, middleware, routing::get, Json, Router};
+use utoipa::OpenApi;
+
+use crate::{
+    models::{dto::EcosystemSummary, AppError},
+    AppState,
+};
+
+use super::middlewares::auth_guard;
+
+/// Defines the OpenAPI spec for the dashboard endpoint
+#[derive(OpenApi)]
+#[openapi(paths(get_dashboard_handler))]
+pub struct DashboardApi;
+
+/// Used to group the dashboard endpoint in the OpenAPI documentation
+pub const DASHBOARD_API_GROUP: &str = "DASHBOARD";
+
+/// Projects listed in the dashboard
+const TOP_PROJECTS: i64 = 5;
+
+/// Builds a router for the dashboard of the ecosystem
+pub fn dashboard_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_dashboard_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+}
+
+/// Get the value locked and volume of all DEX projects together, with the largest of them
+#[utoipa::path(
+    get,
+    path = "/api/v1/dashboard",
+    tag = DASHBOARD_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Summary of the DEX ecosystem", body = EcosystemSummary),
+    )
+)]
+pub async fn get_dashboard_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<EcosystemSummary>, AppError> {
+    let (totals, top_projects) = tokio::try_join!(
+        state.db.get_dex_totals(),
+        state.db.list_top_dex_projects(TOP_PROJECTS)
+    )?;
+    Ok(Json(EcosystemSummary::new(totals, top_projects)))
+}
@@ -1,40 +1,61 @@
 use std::sync::Arc;
 
 use crate::{
+    audit::AuditLogger,
     models::{
-        dto::{CreateEntityInfo, EntityResponse},
-        Entity, Error,
+        dto::{
+            AccountResponse, CreateEntityInfo, EntityAccountsQuery, EntityAccountsResponse,
+            EntityFilterQuery, EntityPage, EntityResponse, PaginationQuery, SearchQuery,
+        },
+        AppError, AuditEntry, Entity, User,
     },
-    AppState,
+    AppState, External,
 };
 use axum::{
-    extract::State,
+    extract::{Query, State},
     http::StatusCode,
     middleware,
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
 use utoipa::OpenApi;
 
-use super::middlewares::auth_guard;
+use super::{
+    extractors::{Pagination, Search},
+    middlewares::auth_guard,
+};
 #[derive(OpenApi)]
-#[openapi(paths(create_entity_handler, get_entity_handler))]
+#[openapi(paths(
+    create_entity_handler,
+    list_entities_handler,
+    search_entities_handler,
+    get_entity_handler,
+    update_entity_handler,
+    list_entity_accounts_handler
+))]
 /// Defines the OpenAPI spec for entity endpoints
 pub struct EntityApi;
 
 /// Used to group entity endpoints together in the OpenAPI documentation
 pub const ENTITY_API_GROUP: &str = "ENTITY";
 
+/// Longest website the `entity.website` column holds
+const MAX_WEBSITE_LENGTH: usize = 2048;
+/// Longest handle Twitter allows
+const MAX_TWITTER_LENGTH: usize = 15;
+
 /// Builds a router for all the entity routes
 pub fn entity_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
-        .route("/", post(create_entity_handler))
-        .route("/:id", get(get_entity_handler))
+        .route("/", post(create_entity_handler).get(list_entities_handler))
+        .route("/search", get(search_entities_handler))
+        .route("/:id", get(get_entity_handler).put(update_entity_handler))
+        .route("/:id/accounts", get(list_entity_accounts_handler))
         .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
 }
 #[utoipa::path(
     post,
-    path = "/api/entity",
+    path = "/api/v1/entity",
     tag = ENTITY_API_GROUP,
     request_body = CreateEntityInfo,
     security(
@@ -42,37 +63,36 @@ pub fn entity_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     ),
     responses(
         (status = 201, description = "Entity successfully created", body = EntityResponse),
-        (status = 400, description = "Bad request"),
+        (status = 400, description = "Bad request", body = ErrorBody),
     )
 )]
 pub async fn create_entity_handler(
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
     Json(body): Json<CreateEntityInfo>,
-) -> Result<Json<EntityResponse>, Error> {
-    let new_entity = Entity {
-        name: body.name,
-        ..Default::default()
-    };
+) -> Result<(StatusCode, Json<EntityResponse>), AppError> {
+    let new_entity = entity_from_info(body)?;
 
-    let entity = state.db.create_entity(&new_entity).await?;
-    Ok(Json(EntityResponse {
-        id: entity.id,
-        name: entity.name,
-        created_at: entity.created_at.to_string(),
-        updated_at: entity.updated_at.to_string(),
-    }))
+    let mut tx = state.db.begin().await?;
+    let entity = tx.create_entity(&new_entity).await?;
+    let response = EntityResponse::from(entity);
+    AuditLogger::new(&mut tx, &user)
+        .created(AuditEntry::ENTITY, response.id, &response)
+        .await?;
+    tx.commit().await?;
+    Ok((StatusCode::CREATED, Json(response)))
 }
 
 #[utoipa::path(
     get,
-    path = "/api/entity/{id}",
+    path = "/api/v1/entity/{id}",
     tag = ENTITY_API_GROUP,
     security(
         ("bearerAuth" = [])
     ),
     responses(
         (status = 200, description = "Entity found", body = EntityResponse),
-        (status = 404, description = "Entity not found"),
+        (status = 404, description = "Entity not found", body = ErrorBody),
     ),
     params(
         ("id" = i32, Path, description = "Entity ID")
@@ -81,14 +101,266 @@ pub async fn create_entity_handler(
 pub async fn get_entity_handler(
     State(state): State<Arc<AppState>>,
     axum::extract::Path(id): axum::extract::Path<i32>,
-) -> Result<Json<EntityResponse>, Error> {
+) -> Result<Json<EntityResponse>, AppError> {
+    let entity = state.db.get_entity_by_id(id).await?;
+    let entity = entity.ok_or_else(|| AppError::NotFound("Entity not found".to_string()))?;
+
+    Ok(Json(EntityResponse::from(entity)))
+}
+
+/// List the entities, optionally only those of a type
+#[utoipa::path(
+    get,
+    path = "/api/v1/entity",
+    tag = ENTITY_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Page of entities", body = EntityPage),
+        (status = 400, description = "Unknown entity type or invalid pagination parameters", body = ErrorBody),
+    ),
+    params(PaginationQuery, EntityFilterQuery)
+)]
+pub async fn list_entities_handler(
+    State(state): State<Arc<AppState>>,
+    pagination: Pagination,
+    Query(query): Query<EntityFilterQuery>,
+) -> Result<Json<EntityPage>, AppError> {
+    let entity_type = query
+        .entity_type
+        .as_deref()
+        .map(check_entity_type)
+        .transpose()?;
+    let (entities, total) = tokio::try_join!(
+        state
+            .db
+            .list_entities(entity_type, pagination.limit, pagination.offset),
+        state.db.count_entities(entity_type)
+    )?;
+
+    let entities = entities.into_iter().map(EntityResponse::from).collect();
+    Ok(Json(pagination.page(entities, total)))
+}
+
+/// Search the entities by a part of their name, ignoring case
+#[utoipa::path(
+    get,
+    path = "/api/v1/entity/search",
+    tag = ENTITY_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Matching entities by name, empty when none match", body = [EntityResponse]),
+        (status = 400, description = "Empty query or invalid limit", body = ErrorBody),
+    ),
+    params(SearchQuery)
+)]
+pub async fn search_entities_handler(
+    State(state): State<Arc<AppState>>,
+    search: Search,
+) -> Result<Json<Vec<EntityResponse>>, AppError> {
+    let entities = state
+        .db
+        .search_entities_by_name(&search.q, search.limit)
+        .await?;
+    Ok(Json(
+        entities.into_iter().map(EntityResponse::from).collect(),
+    ))
+}
+
+/// Replace the name, type, links and description of an entity
+#[utoipa::path(
+    put,
+    path = "/api/v1/entity/{id}",
+    tag = ENTITY_API_GROUP,
+    request_body = CreateEntityInfo,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Entity successfully updated", body = EntityResponse),
+        (status = 400, description = "Bad request", body = ErrorBody),
+        (status = 404, description = "Entity not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Entity ID")
+    )
+)]
+pub async fn update_entity_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Json(body): Json<CreateEntityInfo>,
+) -> Result<Json<EntityResponse>, AppError> {
+    let not_found = || AppError::NotFound("Entity not found".to_string());
+    let before = state.db.get_entity_by_id(id).await?.ok_or_else(not_found)?;
+    let entity = Entity {
+        id,
+        ..entity_from_info(body)?
+    };
+
+    let mut tx = state.db.begin().await?;
+    let updated = tx.update_entity(&entity).await?.ok_or_else(not_found)?;
+    let response = EntityResponse::from(updated);
+    AuditLogger::new(&mut tx, &user)
+        .updated(
+            AuditEntry::ENTITY,
+            id,
+            &EntityResponse::from(before),
+            &response,
+        )
+        .await?;
+    tx.commit().await?;
+    Ok(Json(response))
+}
+
+/// List the accounts of an entity, optionally with the coins they hold in total
+#[utoipa::path(
+    get,
+    path = "/api/v1/entity/{id}/accounts",
+    tag = ENTITY_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Page of accounts of the entity", body = EntityAccountsResponse),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+        (status = 404, description = "Entity not found", body = ErrorBody),
+        (status = 502, description = "Aptos indexer could not be reached", body = ErrorBody),
+        (status = 504, description = "Aptos indexer did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Entity ID"),
+        PaginationQuery,
+        EntityAccountsQuery
+    )
+)]
+pub async fn list_entity_accounts_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    pagination: Pagination,
+    Query(query): Query<EntityAccountsQuery>,
+) -> Result<Json<EntityAccountsResponse>, AppError> {
     let entity = state.db.get_entity_by_id(id).await?;
-    let entity = entity.ok_or((StatusCode::NOT_FOUND, "Entity not found"))?;
+    let entity = entity.ok_or_else(|| AppError::NotFound("Entity not found".to_string()))?;
+
+    let (accounts, total) = tokio::try_join!(
+        state
+            .db
+            .get_accounts_by_entity(id, pagination.limit, pagination.offset),
+        state.db.count_accounts_by_entity(id)
+    )?;
 
-    Ok(Json(EntityResponse {
-        id: entity.id,
-        name: entity.name,
-        created_at: entity.created_at.to_string(),
-        updated_at: entity.updated_at.to_string(),
+    let coin_totals = if query.include_balances.unwrap_or(false) {
+        // Accounts on networks we can't query hold nothing as far as the totals go
+        let addresses: Vec<String> = accounts
+            .iter()
+            .filter(|account| External::check_network(account).is_ok())
+            .map(|account| account.address.clone())
+            .collect();
+        let totals = state.external.get_coin_totals(&addresses).await?;
+        Some(totals.into_iter().map(Into::into).collect())
+    } else {
+        None
+    };
+
+    let accounts = accounts.into_iter().map(AccountResponse::from).collect();
+    Ok(Json(EntityAccountsResponse {
+        entity: EntityResponse::from(entity),
+        accounts: pagination.page(accounts, total),
+        coin_totals,
     }))
 }
+
+/// Entity of a create or update body, with the type uppercased and the `@` of the Twitter
+/// handle dropped
+fn entity_from_info(info: CreateEntityInfo) -> Result<Entity, AppError> {
+    let entity_type = info
+        .entity_type
+        .as_deref()
+        .map(check_entity_type)
+        .transpose()?
+        .map(str::to_string);
+    if let Some(website) = info.website.as_deref() {
+        check_website(website)?;
+    }
+    let twitter = info.twitter.as_deref().map(check_twitter).transpose()?;
+    Ok(Entity {
+        name: info.name,
+        entity_type,
+        website: info.website,
+        twitter,
+        description: info.description,
+        ..Default::default()
+    })
+}
+
+/// One of [`Entity::TYPES`] matching `entity_type` in any case
+fn check_entity_type(entity_type: &str) -> Result<&'static str, AppError> {
+    Entity::TYPES
+        .into_iter()
+        .find(|known| known.eq_ignore_ascii_case(entity_type))
+        .ok_or_else(|| {
+            AppError::Validation(format!(
+                "Unknown entity type {entity_type}, expected one of {}",
+                Entity::TYPES.join(", ")
+            ))
+        })
+}
+
+fn check_website(website: &str) -> Result<(), AppError> {
+    let is_web_url = reqwest::Url::parse(website)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+    if !is_web_url || website.len() > MAX_WEBSITE_LENGTH {
+        return Err(AppError::Validation(format!(
+            "website must be an http(s) URL of at most {MAX_WEBSITE_LENGTH} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// Twitter handle without its `@`, at most 15 letters, digits or underscores
+fn check_twitter(twitter: &str) -> Result<String, AppError> {
+    let handle = twitter.strip_prefix('@').unwrap_or(twitter);
+    let is_handle = (1..=MAX_TWITTER_LENGTH).contains(&handle.len())
+        && handle
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_handle {
+        return Err(AppError::Validation(format!(
+            "{twitter} is not a Twitter handle, at most {MAX_TWITTER_LENGTH} letters, digits or underscores"
+        )));
+    }
+    Ok(handle.to_string())
+}
+
+#[test]
+fn test_entity_from_info() {
+    let info = |entity_type: &str, website: &str, twitter: &str| CreateEntityInfo {
+        name: "Binance".to_string(),
+        entity_type: Some(entity_type.to_string()),
+        website: Some(website.to_string()),
+        twitter: Some(twitter.to_string()),
+        description: None,
+    };
+    let entity = entity_from_info(info("cex", "https://binance.com", "@binance")).unwrap();
+    assert_eq!(entity.entity_type.as_deref(), Some(Entity::CEX));
+    assert_eq!(entity.website.as_deref(), Some("https://binance.com"));
+    assert_eq!(entity.twitter.as_deref(), Some("binance"));
+
+    for invalid in [
+        info("BANK", "https://binance.com", "binance"),
+        info("CEX", "ftp://binance.com", "binance"),
+        info("CEX", "binance.com", "binance"),
+        info("CEX", "https://binance.com", "@"),
+        info("CEX", "https://binance.com", "binance-exchange"),
+        info("CEX", "https://binance.com", "a_very_long_handle"),
+    ] {
+        assert!(matches!(
+            entity_from_info(invalid),
+            Err(AppError::Validation(_))
+        ));
+    }
+}
@@ -0,0 +1,6 @@
+pub mod pagination;
+pub mod search;
+pub mod upload;
+pub use pagination::Pagination;
+pub use search::Search;
+pub use upload::FileUpload;
@@ -0,0 +1,116 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+
+use crate::models::{
+    dto::{Page, PaginationQuery},
+    AppError,
+};
+
+pub const DEFAULT_LIMIT: i64 = 25;
+pub const MAX_LIMIT: i64 = 100;
+
+/// Validated `limit`, `offset` and `cursor` query parameters of a list endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pagination {
+    pub limit: i64,
+    pub offset: i64,
+    pub cursor: Option<String>,
+}
+
+impl TryFrom<PaginationQuery> for Pagination {
+    type Error = AppError;
+
+    fn try_from(query: PaginationQuery) -> Result<Self, Self::Error> {
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(AppError::Validation(format!(
+                "limit must be between 1 and {MAX_LIMIT}"
+            )));
+        }
+
+        let offset = query.offset.unwrap_or(0);
+        if offset < 0 {
+            return Err(AppError::Validation(
+                "offset must not be negative".to_string(),
+            ));
+        }
+
+        Ok(Pagination {
+            limit,
+            offset,
+            cursor: query.cursor.filter(|cursor| !cursor.is_empty()),
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Pagination {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<PaginationQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| AppError::Validation(e.body_text()))?;
+        Pagination::try_from(query)
+    }
+}
+
+impl Pagination {
+    /// Wraps the items of the requested page
+    pub fn page<T>(&self, items: Vec<T>, total: i64) -> Page<T> {
+        let has_more = self.offset + (items.len() as i64) < total;
+        Page {
+            items,
+            total,
+            limit: self.limit,
+            offset: self.offset,
+            next_cursor: None,
+            has_more,
+        }
+    }
+}
+
+#[test]
+fn test_pagination_defaults() {
+    let pagination = Pagination::try_from(PaginationQuery::default()).unwrap();
+    assert_eq!(
+        pagination,
+        Pagination {
+            limit: DEFAULT_LIMIT,
+            offset: 0,
+            cursor: None,
+        }
+    );
+}
+
+#[test]
+fn test_pagination_rejects_out_of_range() {
+    for (limit, offset) in [
+        (Some(0), None),
+        (Some(MAX_LIMIT + 1), None),
+        (None, Some(-1)),
+    ] {
+        let query = PaginationQuery {
+            limit,
+            offset,
+            cursor: None,
+        };
+        let error = Pagination::try_from(query).unwrap_err();
+        assert_eq!(error.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+}
+
+#[test]
+fn test_page_has_more() {
+    let pagination = Pagination {
+        limit: 2,
+        offset: 2,
+        cursor: None,
+    };
+    assert!(pagination.page(vec![3, 4], 5).has_more);
+    assert!(!pagination.page(vec![3, 4], 4).has_more);
+    assert!(!pagination.page(Vec::<i32>::new(), 1).has_more);
+}
@@ -0,0 +1,74 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+
+use super::pagination::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::models::{dto::SearchQuery, AppError};
+
+/// Validated `q` and `limit` query parameters of a search endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Search {
+    /// Part of a name to look for, trimmed and never empty
+    pub q: String,
+    pub limit: i64,
+}
+
+impl TryFrom<SearchQuery> for Search {
+    type Error = AppError;
+
+    fn try_from(query: SearchQuery) -> Result<Self, Self::Error> {
+        let q = query.q.trim();
+        if q.is_empty() {
+            return Err(AppError::Validation("q must not be empty".to_string()));
+        }
+
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(AppError::Validation(format!(
+                "limit must be between 1 and {MAX_LIMIT}"
+            )));
+        }
+
+        Ok(Search {
+            q: q.to_string(),
+            limit,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Search {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<SearchQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| AppError::Validation(e.body_text()))?;
+        Search::try_from(query)
+    }
+}
+
+#[test]
+fn test_search() {
+    let query = |q: &str, limit: Option<i64>| SearchQuery {
+        q: q.to_string(),
+        limit,
+    };
+    assert_eq!(
+        Search::try_from(query(" pancake ", None)).unwrap(),
+        Search {
+            q: "pancake".to_string(),
+            limit: DEFAULT_LIMIT,
+        }
+    );
+    for invalid in [
+        query("  ", None),
+        query("pancake", Some(0)),
+        query("pancake", Some(MAX_LIMIT + 1)),
+    ] {
+        let error = Search::try_from(invalid).unwrap_err();
+        assert_eq!(error.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+}
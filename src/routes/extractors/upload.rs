@@ -0,0 +1,134 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::header::CONTENT_TYPE,
+};
+
+use crate::models::AppError;
+
+/// File of a `multipart/form-data` body, the first part sent with a file name
+#[derive(Debug, PartialEq)]
+pub struct FileUpload {
+    pub file_name: String,
+    pub bytes: Bytes,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for FileUpload {
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let boundary = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(boundary)
+            .ok_or_else(|| {
+                AppError::Validation("Expected a multipart/form-data body".to_string())
+            })?;
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(|e| AppError::Validation(e.body_text()))?;
+        parse_file_part(&body, &boundary)
+            .ok_or_else(|| AppError::Validation("Expected a file in the form data".to_string()))
+    }
+}
+
+/// Boundary of a `multipart/form-data` content type, `None` for any other content type
+fn boundary(content_type: &str) -> Option<String> {
+    let (mime, params) = content_type.split_once(';')?;
+    if !mime.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params
+        .split(';')
+        .find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            name.trim()
+                .eq_ignore_ascii_case("boundary")
+                .then(|| value.trim().trim_matches('"').to_string())
+        })
+        .filter(|boundary| !boundary.is_empty())
+}
+
+/// First part of `body` with a file name, `None` when there is none or the body is cut short
+fn parse_file_part(body: &[u8], boundary: &str) -> Option<FileUpload> {
+    let delimiter = format!("--{boundary}");
+    let next_delimiter = format!("\r\n{delimiter}");
+    let mut rest = &body[find(body, delimiter.as_bytes())? + delimiter.len()..];
+    loop {
+        // The last delimiter is followed by `--`
+        if rest.starts_with(b"--") {
+            return None;
+        }
+        rest = rest.strip_prefix(b"\r\n")?;
+        let headers_end = find(rest, b"\r\n\r\n")?;
+        let headers = std::str::from_utf8(&rest[..headers_end]).ok()?;
+        let content = &rest[headers_end + 4..];
+        let content_end = find(content, next_delimiter.as_bytes())?;
+        if let Some(file_name) = file_name(headers) {
+            return Some(FileUpload {
+                file_name,
+                bytes: Bytes::copy_from_slice(&content[..content_end]),
+            });
+        }
+        rest = &content[content_end + next_delimiter.len()..];
+    }
+}
+
+/// `filename` of the `Content-Disposition` among the headers of a part
+fn file_name(headers: &str) -> Option<String> {
+    headers.split("\r\n").find_map(|header| {
+        let (name, value) = header.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("content-disposition") {
+            return None;
+        }
+        value.split(';').find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            (key.trim() == "filename").then(|| value.trim().trim_matches('"').to_string())
+        })
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[test]
+fn test_boundary() {
+    assert_eq!(
+        boundary("multipart/form-data; boundary=X-abc").as_deref(),
+        Some("X-abc")
+    );
+    assert_eq!(
+        boundary("Multipart/Form-Data; charset=utf-8; boundary=\"quoted\"").as_deref(),
+        Some("quoted")
+    );
+    assert_eq!(boundary("multipart/form-data"), None);
+    assert_eq!(boundary("multipart/form-data; boundary="), None);
+    assert_eq!(boundary("application/json; boundary=X"), None);
+}
+
+#[test]
+fn test_parse_file_part() {
+    let body = b"--X\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        Logo\r\n\
+        --X\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"logo.png\"\r\n\
+        Content-Type: image/png\r\n\r\n\
+        \x89PNG\r\n--\r\n\
+        --X--\r\n";
+    let upload = parse_file_part(body, "X").unwrap();
+    assert_eq!(upload.file_name, "logo.png");
+    assert_eq!(upload.bytes.as_ref(), b"\x89PNG\r\n--");
+
+    let without_file =
+        b"--X\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nLogo\r\n--X--\r\n";
+    assert_eq!(parse_file_part(without_file, "X"), None);
+    let cut_short = b"--X\r\nContent-Disposition: form-data; filename=\"a.png\"\r\n\r\n\x89PNG";
+    assert_eq!(parse_file_part(cut_short, "X"), None);
+}
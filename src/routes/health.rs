@@ -1,22 +1,94 @@
-use crate::models::dto::Message;
-use axum::{response::IntoResponse, Json};
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    models::dto::{DependencyStatus, Message, ReadinessResponse},
+    AppState,
+};
+use axum::{
+    extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router,
+};
 use utoipa::OpenApi;
+
+/// How long a single dependency probe may take before it's reported as unhealthy
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
 #[derive(OpenApi)]
-#[openapi(paths(
-    health_checker_handler
-))]
-/// Defines the OpenAPI spec for user endpoints
+#[openapi(paths(health_checker_handler, liveness_handler, readiness_handler))]
+/// Defines the OpenAPI spec for health endpoints
 pub struct HealthApi;
+
+/// Used to group health endpoints together in the OpenAPI documentation
+pub const HEALTH_API_GROUP: &str = "HEALTH";
+
+/// Builds a router for all the health routes
+pub fn health_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(health_checker_handler))
+        .route("/live", get(liveness_handler))
+        .route("/ready", get(readiness_handler))
+}
+
+/// Alias of the readiness check, kept for existing load balancer configurations
 #[utoipa::path(
     get,
     path = "/api/health",
-    tag = "HEALTH",
+    tag = HEALTH_API_GROUP,
+    responses(
+        (status = 200, description = "All dependencies are reachable", body = ReadinessResponse),
+        (status = 503, description = "At least one dependency is unreachable", body = ReadinessResponse),
+    )
+)]
+pub async fn health_checker_handler(state: State<Arc<AppState>>) -> impl IntoResponse {
+    readiness_handler(state).await
+}
+
+/// Reports that the process is up, without checking any dependency
+#[utoipa::path(
+    get,
+    path = "/api/health/live",
+    tag = HEALTH_API_GROUP,
     responses(
-        (status = OK, description = "Success", body = str, content_type = "text/plain")
+        (status = 200, description = "Process is up", body = Message)
     )
 )]
-pub async fn health_checker_handler() -> impl IntoResponse {
-    Json(Message::new(
-        "OK, I'm alive!",
-    ))
+pub async fn liveness_handler() -> impl IntoResponse {
+    Json(Message::new("OK, I'm alive!"))
+}
+
+/// Probes the database, and optionally the Aptos fullnode, to tell whether requests can be served
+#[utoipa::path(
+    get,
+    path = "/api/health/ready",
+    tag = HEALTH_API_GROUP,
+    responses(
+        (status = 200, description = "All dependencies are reachable", body = ReadinessResponse),
+        (status = 503, description = "At least one dependency is unreachable", body = ReadinessResponse),
+    )
+)]
+pub async fn readiness_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let database = tokio::time::timeout(PROBE_TIMEOUT, state.db.ping())
+        .await
+        .map_err(|_| "timed out".to_string())
+        .and_then(|result| result.map_err(|e| e.to_string()));
+    let mut dependencies = vec![DependencyStatus::new("database", database)];
+
+    if state.config.health_check_fullnode {
+        let fullnode = state.external.ping_fullnode(PROBE_TIMEOUT).await;
+        dependencies.push(DependencyStatus::new("aptos_fullnode", fullnode));
+    }
+
+    let healthy = dependencies.iter().all(|dependency| dependency.healthy);
+    let code = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        code,
+        Json(ReadinessResponse {
+            healthy,
+            dependencies,
+        }),
+    )
 }
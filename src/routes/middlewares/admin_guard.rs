@@ -0,0 +1,44 @@
+use axum::{extract::Request, middleware::Next, response::IntoResponse};
+
+use crate::models::{AppError, User};
+
+/// Only lets admins through, must run after [`auth_guard`](super::auth_guard) has
+/// identified the user
+pub async fn admin_guard(req: Request, next: Next) -> Result<impl IntoResponse, AppError> {
+    let is_admin = req.extensions().get::<User>().is_some_and(User::is_admin);
+    if !is_admin {
+        return Err(AppError::Forbidden(
+            "Only admins can use this endpoint".to_string(),
+        ));
+    }
+    Ok(next.run(req).await)
+}
+
+#[tokio::test]
+async fn test_admin_guard_rejects_other_roles() {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    let app = |role: &str| {
+        let user = User {
+            role: role.to_string(),
+            ..Default::default()
+        };
+        Router::new()
+            .route("/labels", get(|| async { "labels" }))
+            .route_layer(axum::middleware::from_fn(admin_guard))
+            .layer(axum::Extension(user))
+    };
+    let request = || {
+        Request::builder()
+            .uri("/labels")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app(User::ADMIN_ROLE).oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+    let response = app("analyst").oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+}
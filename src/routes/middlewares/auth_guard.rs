@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use axum::{
     extract::{Request, State},
-    http::{header, StatusCode},
+    http::header,
     middleware::Next,
     response::IntoResponse,
 };
@@ -11,30 +11,41 @@ use jsonwebtoken::{decode, DecodingKey, Validation};
 
 use crate::{
     app_state::AppState,
-    models::{Error, TokenClaim, User},
+    models::{AppError, TokenClaim, User},
 };
 
 pub async fn auth_guard(
     State(state): State<Arc<AppState>>,
     mut req: Request,
     next: Next,
-) -> Result<impl IntoResponse, Error> {
+) -> Result<impl IntoResponse, AppError> {
     let token = req
         .headers()
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok())
         .and_then(|header| header.strip_prefix("Bearer "))
-        .ok_or((
-            StatusCode::UNAUTHORIZED,
-            "You are not logged in, please provide token",
-        ))?;
+        .ok_or_else(|| {
+            AppError::Unauthorized("You are not logged in, please provide token".to_string())
+        })?;
     let token = decode::<TokenClaim>(
         token,
         &DecodingKey::from_secret(state.config.jwt_secret.as_ref()),
         &Validation::default(),
-    )?;
+    )
+    .map_err(|_| AppError::Unauthorized("Invalid or expired token".to_string()))?;
+    if token.claims.totp_pending {
+        return Err(AppError::Unauthorized(
+            "Two-factor authentication is not complete, send a TOTP code to /api/v1/user/totp/challenge".to_string(),
+        ));
+    }
     let user: Option<User> = state.db.get_user_by_email(&token.claims.sub).await?;
-    let user = user.ok_or((StatusCode::UNAUTHORIZED, "No user match this token"))?;
+    let user =
+        user.ok_or_else(|| AppError::Unauthorized("No user match this token".to_string()))?;
+    if !user.active {
+        return Err(AppError::Unauthorized(
+            "This user is deactivated".to_string(),
+        ));
+    }
     req.extensions_mut().insert(user);
     Ok(next.run(req).await)
 }
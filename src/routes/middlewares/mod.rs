@@ -1,2 +1,4 @@
+pub mod admin_guard;
 pub mod auth_guard;
+pub use admin_guard::admin_guard;
 pub use auth_guard::auth_guard;
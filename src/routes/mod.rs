@@ -1,55 +1,449 @@
 mod account;
+mod admin;
+mod alert;
+mod avatar;
+mod csv;
+mod dashboard;
 mod entity;
+mod extractors;
 mod health;
 mod middlewares;
 mod project;
 mod swagger;
 mod user;
+mod utils;
+mod versions;
 use crate::database;
-use health::health_checker_handler;
-use tower_http::trace::TraceLayer;
-use tracing::info;
+use health::liveness_handler;
+pub use swagger::api_docs;
+use versions::{deprecate, sunset_of, version_prefix, API_V1};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, CorsLayer},
+    limit::RequestBodyLimitLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::{info, warn};
 
-use crate::{AppState, Config};
+use crate::{
+    scheduler::Scheduler,
+    storage::{AvatarStore, MAX_AVATAR_REQUEST_BYTES},
+    telemetry::{self, make_request_span, scope_request_id, REQUEST_ID_HEADER},
+    AppState, Config, External,
+};
 
-use axum::{routing::get, Router};
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{
+        header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+        HeaderName, HeaderValue, Method,
+    },
+    middleware,
+    routing::get,
+    Router,
+};
 use dotenv::dotenv;
 use std::error::Error;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 
-pub async fn make_app() -> Result<Router, Box<dyn Error>> {
+/// Longest the Aptos API may take to accept the warm-up connection
+const WARM_UP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server ready to be served, along with the background tasks it started
+pub struct App {
+    pub router: Router,
+    pub scheduler: Scheduler,
+    /// Longest in-flight requests and background tasks are waited for on shutdown
+    pub shutdown_timeout: Duration,
+    /// Port the router is to be served on
+    pub port: u16,
+}
+
+/// Reads the configuration and connects to the database and the third-party APIs, applying
+/// the pending migrations unless the configuration says not to
+pub async fn make_state() -> Result<Arc<AppState>, Box<dyn Error>> {
     if dotenv().is_err() {
         println!("Starting server without .env file.");
     }
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::DEBUG)
-        .init();
     let config = Config::init();
-    // configure_logger(&config.log_level);
+    telemetry::init(&config)?;
     info!("Connecting to PostgreSQL...");
     let sqlx_db_connection = database::connect_sqlx(&config.db_url).await;
     info!("Connected to PostgreSQL!");
-    //let cors = HeaderValue::from_str(&config.cors_url)?;
-    // TODO: Consider readding CORS here
-    //let cors = CorsLayer::new()
-    //    .allow_origin(cors)
-    //    .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
-    //    .allow_credentials(true)
-    //    .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
+    if config.run_migrations {
+        database::run_migrations(&sqlx_db_connection).await?;
+        info!("Applied the pending migrations");
+    }
 
     let db = database::PostgreDatabase::new(sqlx_db_connection);
-    let state = Arc::new(AppState { db, config });
+    let external = External::from_config(&config, db.clone());
+    Ok(Arc::new(AppState {
+        db,
+        external,
+        avatars: AvatarStore::from_config(&config)?,
+        config,
+        shutdown: CancellationToken::new(),
+        tvl_backfills: Default::default(),
+        one_shot_tasks: Default::default(),
+    }))
+}
+
+pub async fn make_app() -> Result<App, Box<dyn Error>> {
+    let state = make_state().await?;
+    let cors = cors_layer(&state.config.cors_origins)?;
+    let max_request_body_bytes = state.config.max_request_body_bytes;
+    warm_up(&state).await;
+    let scheduler = Scheduler::start(state.clone());
+    let shutdown_timeout = state.config.shutdown_timeout;
+    let port = state.config.port;
+    let v1_router = version_routes(state.clone(), API_V1);
     let ret = Router::new()
-        .route("/api", get(health_checker_handler))
-        .route("/api/health", get(health_checker_handler))
-        .nest("/api/user", user::user_routes(state.clone()))
-        .nest("/api/entity", entity::entity_routes(state.clone()))
-        .nest("/api/account", account::account_routes(state.clone()))
-        .nest("/api/project", project::project_routes(state.clone()))
+        .route("/api", get(liveness_handler))
+        .nest("/api/health", health::health_routes())
+        .nest("/api/versions", versions::versions_routes())
+        .nest(&version_prefix(API_V1), v1_router)
         .merge(swagger::build_documentation())
-        .with_state(state)
-        .layer(TraceLayer::new_for_http());
-    //.layer(cors);
+        .with_state(state);
+    let ret = payload_layers(ret, max_request_body_bytes)
+        .layer(middleware::from_fn(scope_request_id))
+        .layer(TraceLayer::new_for_http().make_span_with(make_request_span));
+    let ret = request_id_layers(ret).layer(cors);
+
+    Ok(App {
+        router: ret,
+        scheduler,
+        shutdown_timeout,
+        port,
+    })
+}
+
+/// Opens the minimum connections of the database pool and one to the Aptos API before the
+/// first request, so it doesn't pay for the handshakes
+async fn warm_up(state: &AppState) {
+    let started = Instant::now();
+    let (db, fullnode) = tokio::join!(
+        state.db.warm_up(),
+        state.external.ping_fullnode(WARM_UP_TIMEOUT)
+    );
+    if let Err(e) = db {
+        warn!(error = %e, "Could not warm up the database pool");
+    }
+    if let Err(e) = fullnode {
+        warn!(error = %e, "Could not warm up the connection to the Aptos API");
+    }
+    info!(
+        duration_ms = started.elapsed().as_millis() as u64,
+        "Warmed up connections"
+    );
+}
+
+/// Builds the routes served under the prefix of `version`, announcing their deprecation
+/// once the configuration gives `version` a sunset date
+fn version_routes(state: Arc<AppState>, version: &str) -> Router<Arc<AppState>> {
+    let router = Router::new()
+        .nest("/user", user::user_routes(state.clone()))
+        .nest("/entity", entity::entity_routes(state.clone()))
+        .nest("/account", account::account_routes(state.clone()))
+        .nest(
+            "/project",
+            project::project_routes(state.clone()).merge(alert::alert_routes(state.clone())),
+        )
+        .nest("/dashboard", dashboard::dashboard_routes(state.clone()))
+        .nest("/utils", utils::utils_routes(state.clone()))
+        .nest("/admin", admin::admin_routes(state.clone()))
+        .nest("/avatars", avatar::avatar_routes());
+    match sunset_of(&state.config, version) {
+        Some(sunset) => deprecate(router, sunset),
+        None => router,
+    }
+}
+
+/// Compresses responses the client accepts gzip or brotli for, and rejects request bodies
+/// above `max_request_body_bytes` with 413. Bodies announcing a larger size are rejected
+/// before they get buffered, unless they may be an avatar upload, whose route lifts the
+/// limit of the extractors to `MAX_AVATAR_REQUEST_BYTES`.
+fn payload_layers(router: Router, max_request_body_bytes: usize) -> Router {
+    router
+        .layer(DefaultBodyLimit::max(max_request_body_bytes))
+        .layer(RequestBodyLimitLayer::new(
+            max_request_body_bytes.max(MAX_AVATAR_REQUEST_BYTES),
+        ))
+        .layer(CompressionLayer::new())
+}
+
+/// Gives every request an id, the client's `X-Request-ID` when it sent one, and echoes it
+/// in the response
+fn request_id_layers(router: Router) -> Router {
+    router
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            REQUEST_ID_HEADER,
+        )))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            MakeRequestUuid,
+        ))
+}
+
+/// Builds the CORS policy for the given origins, where a single `*` allows any origin
+fn cors_layer(origins: &[String]) -> Result<CorsLayer, Box<dyn Error>> {
+    // Any site may call the API then, but not with the caller's cookies, which can't be
+    // combined with `*`. Tokens still go in the `Authorization` header.
+    let any_origin = origins.iter().any(|origin| origin == "*");
+    let allow_origin = if any_origin {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin))
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([AUTHORIZATION, CONTENT_TYPE, ACCEPT])
+        .expose_headers([AUTHORIZATION, CONTENT_TYPE, ACCEPT])
+        .allow_credentials(!any_origin))
+}
+
+#[cfg(test)]
+async fn preflight(origins: &[&str], origin: &str) -> axum::http::Response<axum::body::Body> {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let origins: Vec<String> = origins.iter().map(|origin| origin.to_string()).collect();
+    let app: Router = Router::new()
+        .route("/api/health/live", get(liveness_handler))
+        .layer(cors_layer(&origins).unwrap());
+
+    app.oneshot(
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/health/live")
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "GET")
+            .header("Access-Control-Request-Headers", "authorization")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_cors_preflight_allowed_origin() {
+    let response = preflight(&["http://localhost:3000"], "http://localhost:3000").await;
+    let headers = response.headers();
+
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "http://localhost:3000"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+    let allowed_headers = headers["access-control-allow-headers"].to_str().unwrap();
+    assert!(allowed_headers.contains("authorization"));
+    assert!(allowed_headers.contains("content-type"));
+    assert!(allowed_headers.contains("accept"));
+    let allowed_methods = headers["access-control-allow-methods"].to_str().unwrap();
+    assert!(allowed_methods.contains("GET"));
+    assert!(allowed_methods.contains("DELETE"));
+}
+
+#[tokio::test]
+async fn test_cors_preflight_unknown_origin() {
+    let response = preflight(&["http://localhost:3000"], "http://evil.example").await;
+
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_cors_preflight_wildcard_origin() {
+    let response = preflight(&["*"], "http://anything.example").await;
+
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+    assert!(response
+        .headers()
+        .get("access-control-allow-credentials")
+        .is_none());
+}
+
+#[cfg(test)]
+fn large_project_app(max_request_body_bytes: usize) -> Router {
+    use crate::models::dto::ProjectResponse;
+    use axum::Json;
+
+    let projects = || async {
+        Json(
+            (0..500)
+                .map(|id| ProjectResponse {
+                    id,
+                    name: Some(format!("Project {id}")),
+                    token: format!("0x{id:064x}::coin::T"),
+                    category: "DEX".to_string(),
+                    contract_address: Some(format!("0x{id:064x}")),
+                    num_chains: Some(1),
+                    core_developers: Some(10),
+                    code_commits: Some(1000),
+                    total_value_locked: Some(1_000_000.0),
+                    trading_volume: Some(250_000.0),
+                    token_max_supply: Some(1_000_000_000),
+                    defi_llama_slug: None,
+                    cmc_id: None,
+                    github_repo: None,
+                    coingecko_id: None,
+                    price_usd_cg: None,
+                    market_cap_cg: None,
+                    volume_24h_cg: None,
+                    price_usd: None,
+                    token_terminal_slug: None,
+                    volume_entry_function: None,
+                    swap_fee_bps: None,
+                    excluded_supply_addresses: Vec::new(),
+                    avatar_url: None,
+                    cloned_from: None,
+                    active: true,
+                    created_at: "2024-05-01 00:00:00 UTC".to_string(),
+                    updated_at: "2024-05-01 00:00:00 UTC".to_string(),
+                    attributes: None,
+                })
+                .collect::<Vec<_>>(),
+        )
+    };
+    let echo_size = |body: String| async move { body.len().to_string() };
+    let app = Router::new().route("/api/v1/project", get(projects).post(echo_size));
+    payload_layers(app, max_request_body_bytes)
+}
+
+#[tokio::test]
+async fn test_large_project_response_is_compressed() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let request = |encoding: Option<&str>| {
+        let mut request = Request::builder().uri("/api/v1/project");
+        if let Some(encoding) = encoding {
+            request = request.header("Accept-Encoding", encoding);
+        }
+        request.body(Body::empty()).unwrap()
+    };
+
+    let plain = large_project_app(1024)
+        .oneshot(request(None))
+        .await
+        .unwrap();
+    assert!(plain.headers().get("content-encoding").is_none());
+    let plain = axum::body::to_bytes(plain.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    let gzip = large_project_app(1024)
+        .oneshot(request(Some("gzip")))
+        .await
+        .unwrap();
+    assert_eq!(gzip.headers()["content-encoding"], "gzip");
+    let gzip = axum::body::to_bytes(gzip.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(gzip.len() < plain.len() / 4);
+
+    let brotli = large_project_app(1024)
+        .oneshot(request(Some("br")))
+        .await
+        .unwrap();
+    assert_eq!(brotli.headers()["content-encoding"], "br");
+}
+
+#[tokio::test]
+async fn test_oversized_request_body_is_rejected() {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    let request = |size: usize| {
+        Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/project")
+            .body(Body::from("a".repeat(size)))
+            .unwrap()
+    };
+
+    let response = large_project_app(1024)
+        .oneshot(request(1024))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = large_project_app(1024)
+        .oneshot(request(1025))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // A route lifting the limit takes larger bodies, up to the avatar uploads' limit
+    let echo_size = |body: axum::body::Bytes| async move { body.len().to_string() };
+    let upload_app = || {
+        let upload = axum::routing::post(echo_size)
+            .layer(DefaultBodyLimit::max(MAX_AVATAR_REQUEST_BYTES));
+        payload_layers(Router::new().route("/api/v1/project", upload), 1024)
+    };
+    let response = upload_app().oneshot(request(4096)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = upload_app()
+        .oneshot(request(MAX_AVATAR_REQUEST_BYTES + 1))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_request_id_is_echoed_and_scoped() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let handler = || async { telemetry::current_request_id().unwrap_or_default() };
+    let app = || {
+        let app = Router::new()
+            .route("/api", get(handler))
+            .layer(middleware::from_fn(scope_request_id));
+        request_id_layers(app)
+    };
+
+    let response = app()
+        .oneshot(
+            Request::builder()
+                .uri("/api")
+                .header(REQUEST_ID_HEADER, "client-id")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "client-id");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"client-id");
 
-    Ok(ret)
+    let response = app()
+        .oneshot(Request::builder().uri("/api").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+    assert_eq!(generated.len(), 36);
 }
@@ -1,46 +1,185 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{DefaultBodyLimit, Query, State},
+    http::{
+        header::{ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
     middleware,
-    response::IntoResponse,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::{get, post, put},
-    Json, Router,
+    Extension, Json, Router,
 };
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{stream, Stream, StreamExt};
+use rust_decimal::prelude::ToPrimitive;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, warn};
 use utoipa::OpenApi;
 
 use crate::{
+    audit::AuditLogger,
+    database::{is_unique_violation, PostgreTransaction},
     models::{
-        dto::{NewProject, ProjectResponse, UpdateProject},
-        Error, Project,
+        dto::{
+            AttributeChangeEvent, AttributeHistoryCsvQuery, AttributeHistoryQuery,
+            AttributeHistoryResponse, AvatarResponse, CloneProjectRequest, ComputeFormulaQuery,
+            ComputeFormulaResponse, DexProjectResponse, GasAnalyticsResponse, GasQuery,
+            HolderDiffQuery, HolderDiffResponse, KnownPairResponse, LendingProjectResponse,
+            MarketShareResponse, NewProject, NewProjectFormula, PaginationQuery, PatchProject,
+            PriceHistoryQuery, PriceHistoryResponse, PricePointResponse, ProjectFilterQuery,
+            ProjectFormulaResponse, ProjectPage, ProjectResponse, ProjectViewQuery,
+            StakingProjectResponse, SwapDailySummaryResponse, SwapPage, SwapSummaryQuery,
+            SwapTransactionResponse, SwapsQuery, TokenTerminalResponse, TransactionsQuery,
+            TreasuryAccountFlowResponse, TreasuryFlowResponse, TreasuryProjectMixin, TwickQuery,
+            TwickResponse, UpdateProject, ValidatorInfoResponse,
+        },
+        AppError, AuditEntry, Expr, KnownAddress, NetFlow, Project, ProjectAttributeChange,
+        ProjectMetricFormula, TokenTerminalData, User,
     },
-    AppState,
+    scheduler::spawn_all_time_volume,
+    storage::{
+        AvatarStore, ImageFormat, MAX_AVATAR_BYTES, MAX_AVATAR_REQUEST_BYTES, MIN_AVATAR_SIDE,
+    },
+    AppState, External,
 };
 
-use super::middlewares::auth_guard;
+use super::{
+    csv,
+    extractors::{
+        pagination::{DEFAULT_LIMIT, MAX_LIMIT},
+        FileUpload, Pagination,
+    },
+    middlewares::auth_guard,
+};
 
 /// Defines the OpenAPI spec for project endpoints
 #[derive(OpenApi)]
-#[openapi(paths(create_project_handler, get_project_handler, update_project_handler))]
+#[openapi(paths(
+    create_project_handler,
+    list_projects_handler,
+    get_project_handler,
+    update_project_handler,
+    patch_project_handler,
+    compute_project_formula_handler,
+    create_project_formula_handler,
+    get_staking_project_handler,
+    get_lending_project_handler,
+    get_project_gas_handler,
+    get_market_share_handler,
+    get_project_swaps_handler,
+    get_swap_summary_handler,
+    clone_project_handler,
+    get_attribute_history_handler,
+    export_attribute_history_handler,
+    export_transactions_handler,
+    stream_project_metrics_handler,
+    get_price_history_handler,
+    get_dex_project_handler,
+    add_treasury_account_handler,
+    remove_treasury_account_handler,
+    get_treasury_flow_handler,
+    get_twick_handler,
+    get_holder_diff_handler,
+    list_known_pairs_handler,
+    upload_avatar_handler
+))]
 pub struct ProjectsApi;
 
 /// Used to group project endpoints together in the OpenAPI documentation
 pub const PROJECT_API_GROUP: &str = "PROJECT";
 
+/// Days of gas fees analysed when the client doesn't ask for a window, and the longest window
+const DEFAULT_GAS_DAYS: u64 = 7;
+const MAX_GAS_DAYS: u64 = 30;
+
+/// Changes of an attribute returned when the client doesn't ask for a number, and the most
+const DEFAULT_HISTORY_LIMIT: i64 = 50;
+const MAX_HISTORY_LIMIT: i64 = 500;
+
+/// Days of attribute changes exported when the client doesn't ask for a window, and the longest
+const DEFAULT_EXPORT_DAYS: i64 = 30;
+const MAX_EXPORT_DAYS: i64 = 365;
+
+/// Days of prices charted when the client doesn't ask for a window, and the longest window
+const DEFAULT_PRICE_HISTORY_DAYS: i64 = 30;
+const MAX_PRICE_HISTORY_DAYS: i64 = 365;
+
+/// Days of swap summaries returned when the client doesn't ask for a window, and the longest
+/// window
+const DEFAULT_SWAP_SUMMARY_DAYS: i64 = 30;
+const MAX_SWAP_SUMMARY_DAYS: i64 = 365;
+
+/// Hours of liquidity weighted when the client doesn't ask for a window, and the longest
+/// window
+const DEFAULT_TWICK_WINDOW_HOURS: u64 = 168;
+const MAX_TWICK_WINDOW_HOURS: u64 = 365 * 24;
+
+/// Days back the holders are compared when the client doesn't ask, and the furthest back
+const DEFAULT_HOLDER_DIFF_DAYS: u64 = 7;
+const MAX_HOLDER_DIFF_DAYS: u64 = 365;
+
+/// Most accounts a project can leave out of its circulating supply, their balances are
+/// queried at once
+const MAX_EXCLUDED_SUPPLY_ADDRESSES: usize = 100;
+
+/// Longest avatar URL, the length of the `avatar_url` column
+const MAX_AVATAR_URL_LENGTH: usize = 2048;
+
+/// Time between two comments sent on an idle metrics stream, so proxies don't close it
+const STREAM_HEARTBEAT: Duration = Duration::from_secs(15);
+
 /// Builds a router for project routes
 pub fn project_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/", post(create_project_handler))
+        .route("/", get(list_projects_handler))
         .route("/:id", get(get_project_handler))
-        .route("/:id", put(update_project_handler))
+        .route(
+            "/:id",
+            put(update_project_handler).patch(patch_project_handler),
+        )
+        .route("/:id/compute", get(compute_project_formula_handler))
+        .route("/:id/formulas", post(create_project_formula_handler))
+        .route("/:id/staking", get(get_staking_project_handler))
+        .route("/:id/lending", get(get_lending_project_handler))
+        .route("/:id/gas", get(get_project_gas_handler))
+        .route("/:id/market-share", get(get_market_share_handler))
+        .route("/:id/swaps", get(get_project_swaps_handler))
+        .route("/:id/swaps/summary", get(get_swap_summary_handler))
+        .route("/:id/clone", post(clone_project_handler))
+        .route(
+            "/:id/attributes/:key/history",
+            get(get_attribute_history_handler),
+        )
+        .route("/:id/history.csv", get(export_attribute_history_handler))
+        .route("/:id/transactions.csv", get(export_transactions_handler))
+        .route("/:id/metrics/stream", get(stream_project_metrics_handler))
+        .route("/:id/price/history", get(get_price_history_handler))
+        .route("/:id/dex", get(get_dex_project_handler))
+        .route(
+            "/:id/treasury/accounts/:address",
+            post(add_treasury_account_handler).delete(remove_treasury_account_handler),
+        )
+        .route("/:id/treasury/flow", get(get_treasury_flow_handler))
+        .route("/:id/twick", get(get_twick_handler))
+        .route("/:id/holders/diff", get(get_holder_diff_handler))
+        .route("/:id/pairs", get(list_known_pairs_handler))
+        .route(
+            "/:id/avatar",
+            post(upload_avatar_handler).layer(DefaultBodyLimit::max(MAX_AVATAR_REQUEST_BYTES)),
+        )
         .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
 }
 
 /// Create project handler function
 #[utoipa::path(
     post,
-    path = "/api/project",
+    path = "/api/v1/project",
     tag = PROJECT_API_GROUP,
     request_body = NewProject,
     security(
@@ -48,84 +187,207 @@ pub fn project_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     ),
     responses(
         (status = 201, description = "Project successfully created", body = ProjectResponse),
+        (status = 400, description = "Unknown account, volume entry function not of the contract, invalid avatar URL or attribute breaking the schema of the category", body = ErrorBody),
+        (status = 409, description = "Name already used, `details.current` is the project using it", body = ErrorBody),
     )
 )]
 pub async fn create_project_handler(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<NewProject>,
-) -> Result<Json<ProjectResponse>, Error> {
+    Extension(user): Extension<User>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<ProjectResponse>), AppError> {
+    check_sent_attributes(&state, body["category"].as_str().unwrap_or_default(), &body).await?;
+    let body: NewProject = read_body(body)?;
+
     // Check if the account associated with the project exists
     if let Some(ref address) = body.contract_address {
         if state.db.get_account_by_address(address).await?.is_none() {
-            return Err(Error::new(
-                StatusCode::BAD_REQUEST,
-                "Account does not exist",
-            ));
+            return Err(AppError::Validation("Account does not exist".to_string()));
         }
     }
 
     // Create the new project
     let new_project = Project {
+        name: body.name.clone(),
         token: body.token.clone(),
         category: body.category.clone(),
         contract_address: body.contract_address.clone(),
+        volume_entry_function: body.volume_entry_function.clone(),
+        avatar_url: body.avatar_url.clone(),
         ..Default::default()
     };
+    check_volume_entry_function(&new_project)?;
+    check_avatar_url(&state.avatars, &new_project, None)?;
+    check_attribute_schema(&state, &new_project).await?;
 
-    let project = state.db.create_project(&new_project).await?;
+    let mut tx = state.db.begin().await?;
+    let project = or_name_conflict(
+        &state,
+        tx.create_project(&new_project).await,
+        new_project.name.as_deref(),
+    )
+    .await?;
+    let response = ProjectResponse::from(project.clone());
+    AuditLogger::new(&mut tx, &user)
+        .created(AuditEntry::PROJECT, response.id, &response)
+        .await?;
+    tx.commit().await?;
 
-    Ok(Json(ProjectResponse {
-        id: project.id,
-        token: project.token,
-        category: project.category,
-        contract_address: project.contract_address,
-        num_chains: project.num_chains,
-        core_developers: project.core_developers,
-        code_commits: project.code_commits,
-        total_value_locked: project.total_value_locked,
-        token_max_supply: project.token_max_supply,
-        created_at: project.created_at.to_string(),
-        updated_at: project.updated_at.to_string(),
-    }))
+    if project.volume_entry_function.is_some() {
+        spawn_all_time_volume(state.clone(), project);
+    }
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// List projects handler function
+#[utoipa::path(
+    get,
+    path = "/api/v1/project",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    params(PaginationQuery, ProjectFilterQuery, ProjectViewQuery),
+    responses(
+        (status = 200, description = "Page of projects", body = ProjectPage),
+        (status = 400, description = "Invalid pagination or attribute filter", body = ErrorBody),
+    )
+)]
+pub async fn list_projects_handler(
+    State(state): State<Arc<AppState>>,
+    pagination: Pagination,
+    Query(filter): Query<ProjectFilterQuery>,
+    Query(view): Query<ProjectViewQuery>,
+) -> Result<Json<ProjectPage>, AppError> {
+    let (projects, total) = match filter.attr {
+        Some(key) => {
+            check_attribute(&key)?;
+            if let (Some(min), Some(max)) = (filter.min, filter.max) {
+                if min > max {
+                    return Err(AppError::Validation(
+                        "min must not be greater than max".to_string(),
+                    ));
+                }
+            }
+            state
+                .db
+                .list_projects_by_attribute(
+                    &key,
+                    filter.min,
+                    filter.max,
+                    pagination.limit,
+                    pagination.offset,
+                )
+                .await?
+        }
+        None if filter.min.is_some() || filter.max.is_some() => {
+            return Err(AppError::Validation(
+                "min and max need an attr to filter on".to_string(),
+            ))
+        }
+        None => tokio::try_join!(
+            state.db.list_projects(pagination.limit, pagination.offset),
+            state.db.count_projects()
+        )?,
+    };
+
+    let projects = projects
+        .into_iter()
+        .map(|project| view.response(project))
+        .collect();
+    Ok(Json(pagination.page(projects, total)))
 }
 
 /// Get project handler function
 #[utoipa::path(
     get,
-    path = "/api/project/{id}",
+    path = "/api/v1/project/{id}",
     tag = PROJECT_API_GROUP,
     security(
         ("bearerAuth" = [])
     ),
     responses(
         (status = 200, description = "Project found", body = ProjectResponse),
-        (status = 404, description = "Project not found"),
+        (status = 304, description = "Project unchanged since the ETag sent in If-None-Match"),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 410, description = "Project deprecated, its contract is no longer active on-chain", body = ErrorBody),
     ),
     params(
-        ("id" = i32, Path, description = "Project ID")
+        ("id" = i32, Path, description = "Project ID"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previously fetched version"),
+        ProjectViewQuery
     )
 )]
 pub async fn get_project_handler(
     State(state): State<Arc<AppState>>,
     axum::extract::Path(id): axum::extract::Path<i32>,
-) -> Result<impl IntoResponse, StatusCode> {
+    Query(view): Query<ProjectViewQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     let project = state
         .db
         .get_project_by_id(id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    if !project.active {
+        return Err(AppError::Gone(deprecation_notice(&project)));
+    }
+
+    Ok(conditional_project_response(&headers, project, &view))
+}
+
+/// Why an inactive project is no longer served
+fn deprecation_notice(project: &Project) -> String {
+    format!(
+        "Project {} is deprecated, its contract {} no longer holds any pair reserves on-chain",
+        project.name.as_deref().unwrap_or(&project.token),
+        project.contract_address.as_deref().unwrap_or_default()
+    )
+}
+
+/// Weak ETag of a project, it changes whenever the row is updated
+fn project_etag(project: &Project) -> HeaderValue {
+    let etag = format!(
+        "W/\"{}-{}\"",
+        project.id,
+        project.updated_at.timestamp_micros()
+    );
+    HeaderValue::from_str(&etag).expect("ETag is always a valid header value")
+}
+
+/// Answers 304 without a body when the client already holds the current version of `project`
+fn conditional_project_response(
+    headers: &HeaderMap,
+    project: Project,
+    view: &ProjectViewQuery,
+) -> Response {
+    let etag = project_etag(&project);
+    let not_modified = headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|candidate| {
+            let candidate = candidate.trim();
+            candidate == "*" || candidate.trim_start_matches("W/") == etag_value(&etag)
+        });
 
-    if let Some(project) = project {
-        Ok((StatusCode::OK, Json(project)))
+    if not_modified {
+        (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response()
     } else {
-        Err(StatusCode::NOT_FOUND)
+        (StatusCode::OK, [(ETAG, etag)], Json(view.response(project))).into_response()
     }
 }
 
+/// Opaque part of an ETag, If-None-Match compares weakly so the `W/` prefix is ignored
+fn etag_value(etag: &HeaderValue) -> &str {
+    etag.to_str().unwrap_or_default().trim_start_matches("W/")
+}
+
 /// Update project handler function
 #[utoipa::path(
     put,
-    path = "/api/project/{id}",
+    path = "/api/v1/project/{id}",
     tag = PROJECT_API_GROUP,
     request_body = UpdateProject,
     security(
@@ -133,8 +395,9 @@ pub async fn get_project_handler(
     ),
     responses(
         (status = 200, description = "Project successfully updated", body = ProjectResponse),
-        (status = 404, description = "Project not found"),
-        (status = 400, description = "Invalid account ID"),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 400, description = "Invalid account ID, volume entry function not of the contract, invalid swap fee, excluded supply address, avatar URL or attribute breaking the schema of the category", body = ErrorBody),
+        (status = 409, description = "Project updated since `expected_updated_at`, or while the update was applied, or name already used, `details.current` is the project as it is or the project using the name", body = ErrorBody),
     ),
     params(
         ("id" = i32, Path, description = "Project ID")
@@ -142,23 +405,22 @@ pub async fn get_project_handler(
 )]
 pub async fn update_project_handler(
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
     axum::extract::Path(id): axum::extract::Path<i32>,
-    Json(body): Json<UpdateProject>,
-) -> Result<impl IntoResponse, Error> {
+    Json(body): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, AppError> {
     // Fetch the project by ID
-    let project =
-        state.db.get_project_by_id(id).await.map_err(|_| {
-            Error::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch project")
-        })?;
+    let project = state.db.get_project_by_id(id).await?;
 
     if let Some(mut project) = project {
+        let before = project.clone();
+        let category = body["category"].as_str().unwrap_or(&project.category);
+        check_sent_attributes(&state, category, &body).await?;
+        let body: UpdateProject = read_body(body)?;
         // Check if the contract_address is provided and exists
         if let Some(address) = body.contract_address {
             if state.db.get_account_by_address(&address).await?.is_none() {
-                return Err(Error::new(
-                    StatusCode::BAD_REQUEST,
-                    "Account does not exist",
-                ));
+                return Err(AppError::Validation("Account does not exist".to_string()));
             }
             project.contract_address = Some(address);
         } else {
@@ -166,6 +428,10 @@ pub async fn update_project_handler(
         }
 
         // Update the fields if they are provided
+        if let Some(name) = body.name {
+            project.name = Some(name);
+        }
+
         if let Some(token) = body.token {
             project.token = token;
         }
@@ -190,27 +456,1936 @@ pub async fn update_project_handler(
             project.total_value_locked = Some(total_value_locked);
         }
 
+        if let Some(trading_volume) = body.trading_volume {
+            project.trading_volume = Some(trading_volume);
+        }
+
         if let Some(token_max_supply) = body.token_max_supply {
             project.token_max_supply = Some(token_max_supply);
         }
 
-        // Persist the updated project to the database
-        let updated_project = state.db.update_project(&project).await?;
-
-        Ok(Json(ProjectResponse {
-            id: updated_project.id,
-            token: updated_project.token,
-            category: updated_project.category,
-            contract_address: updated_project.contract_address,
-            num_chains: updated_project.num_chains,
-            core_developers: updated_project.core_developers,
-            code_commits: updated_project.code_commits,
-            total_value_locked: updated_project.total_value_locked,
-            token_max_supply: updated_project.token_max_supply,
-            created_at: updated_project.created_at.to_string(),
-            updated_at: updated_project.updated_at.to_string(),
-        }))
+        if let Some(defi_llama_slug) = body.defi_llama_slug {
+            project.defi_llama_slug = Some(defi_llama_slug);
+        }
+
+        if let Some(cmc_id) = body.cmc_id {
+            project.cmc_id = Some(cmc_id);
+        }
+
+        if let Some(github_repo) = body.github_repo {
+            project.github_repo = Some(github_repo);
+        }
+
+        if let Some(coingecko_id) = body.coingecko_id {
+            project.coingecko_id = Some(coingecko_id);
+        }
+
+        if let Some(token_terminal_slug) = body.token_terminal_slug {
+            project.token_terminal_slug = Some(token_terminal_slug);
+        }
+
+        if let Some(volume_entry_function) = body.volume_entry_function {
+            project.volume_entry_function = Some(volume_entry_function);
+        }
+        check_volume_entry_function(&project)?;
+        check_coingecko_id(&project)?;
+
+        if let Some(swap_fee_bps) = body.swap_fee_bps {
+            project.swap_fee_bps = Some(swap_fee_bps);
+        }
+        check_swap_fee(&project)?;
+        project.reset_fees_if_source_changed(&before);
+        project.reset_volume_if_source_changed(&before);
+
+        if let Some(addresses) = body.excluded_supply_addresses {
+            project.excluded_supply_addresses = addresses;
+        }
+        normalize_excluded_supply_addresses(&mut project)?;
+
+        if let Some(avatar_url) = body.avatar_url {
+            project.avatar_url = Some(avatar_url);
+        }
+        check_avatar_url(&state.avatars, &project, Some(&before))?;
+        check_attribute_schema(&state, &project).await?;
+
+        // Persist the updated project to the database, unless it changed since the client read
+        // it, or else since the handler did
+        let (read_at, message) = match body.expected_updated_at {
+            Some(read_at) => (read_at, "Project was updated since expected_updated_at"),
+            None => (
+                before.updated_at,
+                "Project was updated while the update was applied, send it again",
+            ),
+        };
+        let response =
+            write_project_update(&state, &user, &before, &project, read_at, message).await?;
+        Ok(Json(response))
     } else {
-        Err(Error::new(StatusCode::NOT_FOUND, "Project not found"))
+        Err(AppError::NotFound("Project not found".to_string()))
+    }
+}
+
+/// Change only the fields present in the body, `null` clears a nullable field
+#[utoipa::path(
+    patch,
+    path = "/api/v1/project/{id}",
+    tag = PROJECT_API_GROUP,
+    request_body = PatchProject,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Project successfully updated", body = ProjectResponse),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 400, description = "Invalid account ID, volume entry function not of the contract, invalid swap fee, excluded supply address, avatar URL or attribute breaking the schema of the category", body = ErrorBody),
+        (status = 409, description = "Project updated while the patch was applied or name already used, `details.current` is the project as it is or the project using the name", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn patch_project_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<ProjectResponse>, AppError> {
+    let mut project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    let category = body["category"].as_str().unwrap_or(&project.category);
+    check_sent_attributes(&state, category, &body).await?;
+    let body: PatchProject = read_body(body)?;
+
+    if let Some(Some(address)) = &body.contract_address {
+        if state.db.get_account_by_address(address).await?.is_none() {
+            return Err(AppError::Validation("Account does not exist".to_string()));
+        }
+    }
+
+    let before = project.clone();
+    body.apply_to(&mut project);
+    check_volume_entry_function(&project)?;
+    check_coingecko_id(&project)?;
+    check_swap_fee(&project)?;
+    project.reset_fees_if_source_changed(&before);
+    project.reset_volume_if_source_changed(&before);
+    normalize_excluded_supply_addresses(&mut project)?;
+    check_avatar_url(&state.avatars, &project, Some(&before))?;
+    check_attribute_schema(&state, &project).await?;
+    // Only over the project as read, a write made meanwhile, e.g. by a background task
+    // moving its cursors, isn't overwritten
+    let response = write_project_update(
+        &state,
+        &user,
+        &before,
+        &project,
+        before.updated_at,
+        "Project was updated while the patch was applied, send it again",
+    )
+    .await?;
+    Ok(Json(response))
+}
+
+/// Upload the avatar of a project, a JPEG, PNG or WebP image of at most 2 MiB and at least
+/// 100×100 pixels sent as the file of a `multipart/form-data` body. It is stored on the
+/// configured storage backend, and the avatar it replaces deleted when it was uploaded too.
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/avatar",
+    tag = PROJECT_API_GROUP,
+    request_body(content = String, content_type = "multipart/form-data", description = "Form data with the image as its file"),
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Avatar stored, the project points at it", body = AvatarResponse),
+        (status = 400, description = "No file in the form data, file too large, image too small or not a JPEG, PNG or WebP image", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 409, description = "Project updated while the avatar was stored, `details.current` is the project as it is", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn upload_avatar_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    upload: FileUpload,
+) -> Result<Json<AvatarResponse>, AppError> {
+    let mut project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    if upload.bytes.len() > MAX_AVATAR_BYTES {
+        return Err(AppError::Validation(format!(
+            "The avatar must be at most {} MiB",
+            MAX_AVATAR_BYTES / (1024 * 1024)
+        )));
+    }
+    let format = ImageFormat::sniff(&upload.bytes).ok_or_else(|| {
+        AppError::Validation("The avatar must be a JPEG, PNG or WebP image".to_string())
+    })?;
+    let bytes = upload.bytes.clone();
+    let (width, height) = tokio::task::spawn_blocking(move || format.dimensions(&bytes))
+        .await
+        .map_err(|e| {
+            error!(error = %e, project = id, "Could not decode an avatar");
+            AppError::Internal("Could not decode the avatar".to_string())
+        })?
+        .ok_or_else(|| AppError::Validation("The avatar could not be decoded".to_string()))?;
+    if width < MIN_AVATAR_SIDE || height < MIN_AVATAR_SIDE {
+        return Err(AppError::Validation(format!(
+            "The avatar must be at least {MIN_AVATAR_SIDE}×{MIN_AVATAR_SIDE} pixels, got {width}×{height}"
+        )));
+    }
+
+    let before = project.clone();
+    let avatar_url = state
+        .avatars
+        .save(id, format, &upload.bytes)
+        .await
+        .map_err(|e| {
+            error!(error = %e, project = id, "Could not store an avatar");
+            AppError::Internal("Could not store the avatar".to_string())
+        })?;
+    project.avatar_url = Some(avatar_url.clone());
+    let written = write_project_update(
+        &state,
+        &user,
+        &before,
+        &project,
+        before.updated_at,
+        "Project was updated while the avatar was stored, send it again",
+    )
+    .await;
+    // Refused or failed, the project doesn't point at the stored file
+    if let Err(e) = written {
+        if let Err(e) = state.avatars.delete(id, &avatar_url).await {
+            warn!(error = %e, avatar_url, "Could not delete an unused avatar");
+        }
+        return Err(e);
+    }
+    Ok(Json(AvatarResponse { avatar_url }))
+}
+
+/// Evaluate a formula against the numeric attributes of a project
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/compute",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Formula successfully evaluated", body = ComputeFormulaResponse),
+        (status = 400, description = "Invalid formula", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 422, description = "Attribute has no value or division by zero", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        ComputeFormulaQuery
+    )
+)]
+pub async fn compute_project_formula_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<ComputeFormulaQuery>,
+) -> Result<Json<ComputeFormulaResponse>, AppError> {
+    let project = state.db.get_project_by_id(id).await?;
+    let project = project.ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    let expr = Expr::parse(&query.formula)?;
+    expr.check_variables(&Project::NUMERIC_ATTRIBUTES)?;
+    let value = expr.evaluate(&|key| project.get_float(key))?;
+
+    Ok(Json(ComputeFormulaResponse {
+        formula: query.formula,
+        value,
+    }))
+}
+
+/// Save a named formula for a project
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/formulas",
+    tag = PROJECT_API_GROUP,
+    request_body = NewProjectFormula,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "Formula successfully saved", body = ProjectFormulaResponse),
+        (status = 400, description = "Invalid formula or name already used", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn create_project_formula_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Json(body): Json<NewProjectFormula>,
+) -> Result<(StatusCode, Json<ProjectFormulaResponse>), AppError> {
+    if state.db.get_project_by_id(id).await?.is_none() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    // Reject formulas that could never be evaluated
+    Expr::parse(&body.formula)?.check_variables(&Project::NUMERIC_ATTRIBUTES)?;
+
+    if state
+        .db
+        .get_project_formula_by_name(id, &body.name)
+        .await?
+        .is_some()
+    {
+        return Err(AppError::Validation(
+            "Formula name already exists for this project".to_string(),
+        ));
+    }
+
+    let new_formula = ProjectMetricFormula {
+        project_id: id,
+        name: body.name,
+        formula: body.formula,
+        ..Default::default()
+    };
+
+    let formula = state.db.create_project_formula(&new_formula).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ProjectFormulaResponse {
+            id: formula.id,
+            project_id: formula.project_id,
+            name: formula.name,
+            formula: formula.formula,
+            created_at: formula.created_at.to_string(),
+        }),
+    ))
+}
+
+/// Get a staking project along with the state of the Aptos validator set
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/staking",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Staking metrics of the project", body = StakingProjectResponse),
+        (status = 400, description = "Project is not in the Staking category", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 502, description = "Aptos fullnode could not be reached", body = ErrorBody),
+        (status = 504, description = "Aptos fullnode did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn get_staking_project_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+) -> Result<Json<StakingProjectResponse>, AppError> {
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    if !project.is_staking() {
+        return Err(AppError::Validation(
+            "Project is not in the Staking category".to_string(),
+        ));
+    }
+
+    let (validators, staking_apr) = tokio::join!(
+        state.external.get_validator_set(),
+        state.external.get_staking_apr()
+    );
+    let validators = validators?;
+
+    Ok(Json(StakingProjectResponse {
+        project: ProjectResponse::from(project),
+        staking_apr: staking_apr?,
+        total_voting_power: validators.iter().map(|v| v.voting_power).sum(),
+        validators: validators
+            .into_iter()
+            .map(ValidatorInfoResponse::from)
+            .collect(),
+    }))
+}
+
+/// Get a lending project along with the state of its market as DeFiLlama reports it
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/lending",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Lending metrics of the project", body = LendingProjectResponse),
+        (status = 400, description = "Project is not in the Lending category", body = ErrorBody),
+        (status = 404, description = "Project not found, or DeFiLlama doesn't list its Aptos market", body = ErrorBody),
+        (status = 422, description = "Project has no DeFiLlama slug", body = ErrorBody),
+        (status = 502, description = "DeFiLlama could not be reached", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn get_lending_project_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+) -> Result<Json<LendingProjectResponse>, AppError> {
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    if !project.is_lending() {
+        return Err(AppError::Validation(
+            "Project is not in the Lending category".to_string(),
+        ));
+    }
+    let Some(slug) = project.defi_llama_slug.clone() else {
+        return Err(AppError::Unprocessable(
+            "Project has no DeFiLlama slug".to_string(),
+        ));
+    };
+
+    let stats = state.external.get_lending_stats(&slug).await?;
+    Ok(Json(LendingProjectResponse::new(
+        ProjectResponse::from(project),
+        stats,
+    )))
+}
+
+/// Get a DEX project along with the all-time high and low of its token, taken from the daily
+/// price snapshots rather than scraped, and its TokenTerminal financials when it has a
+/// `token_terminal_slug`, fetched at most every 6 hours
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/dex",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "DEX metrics of the project", body = DexProjectResponse),
+        (status = 400, description = "Project is not in the DEX category", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn get_dex_project_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+) -> Result<Json<DexProjectResponse>, AppError> {
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    if !project.is_dex() {
+        return Err(AppError::Validation(
+            "Project is not in the DEX category".to_string(),
+        ));
     }
+
+    let (ath, atl, treasury_accounts) = tokio::try_join!(
+        state.db.get_price_ath(&project.token),
+        state.db.get_price_atl(&project.token),
+        state.db.get_treasury_accounts(project.id)
+    )?;
+    let treasury = TreasuryProjectMixin::new(&project, &treasury_accounts);
+
+    let token_terminal = async {
+        match project.token_terminal_slug.as_deref() {
+            Some(slug) => match state.external.get_data_from_tokenterminal(slug).await {
+                Ok(data) => Some(data),
+                Err(error) => {
+                    warn!(project = project.id, slug, %error, "Could not scrape the TokenTerminal financials of a project");
+                    None
+                }
+            },
+            None => None,
+        }
+    };
+    let market_cap = async {
+        let max_supply = project.token_max_supply.map(|supply| supply as f64);
+        match state
+            .external
+            .calculate_market_cap(
+                &project.token,
+                max_supply,
+                project.cmc_id,
+                &project.excluded_supply_addresses,
+            )
+            .await
+        {
+            Ok(market_cap) => Some(market_cap),
+            Err(error) => {
+                warn!(project = project.id, %error, "Could not calculate the market cap of a project");
+                None
+            }
+        }
+    };
+    let (token_terminal, market_cap) = tokio::join!(token_terminal, market_cap);
+    let pe_ratio = token_terminal
+        .as_ref()
+        .and_then(TokenTerminalData::pe_ratio);
+
+    Ok(Json(DexProjectResponse {
+        all_time_volume_usd: project.all_time_volume_usd,
+        all_time_fees_usd: project.all_time_fees_usd,
+        project: ProjectResponse::from(project),
+        ath_onchain: ath.map(|(price, _)| price),
+        atl_onchain: atl.map(|(price, _)| price),
+        ath_date: ath.map(|(_, date)| date.to_string()),
+        atl_date: atl.map(|(_, date)| date.to_string()),
+        market_cap_usd: market_cap.as_ref().map(|market_cap| market_cap.normal),
+        total_supply: market_cap
+            .as_ref()
+            .and_then(|market_cap| market_cap.supply.total.to_f64()),
+        circulating_supply: market_cap
+            .as_ref()
+            .and_then(|market_cap| market_cap.supply.circulating.to_f64()),
+        fully_diluted_market_cap_usd: market_cap.and_then(|market_cap| market_cap.fully_diluted),
+        pe_ratio,
+        earnings_yield_pct: pe_ratio
+            .filter(|ratio| *ratio != 0.0)
+            .map(|ratio| 100.0 / ratio),
+        token_terminal: token_terminal.map(TokenTerminalResponse::from),
+        treasury,
+    }))
+}
+
+/// Count an account in the treasury of a project, its flows are refreshed every 6 hours
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/treasury/accounts/{address}",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "Account is a treasury account of the project"),
+        (status = 404, description = "Project or account not found", body = ErrorBody),
+        (status = 422, description = "Account is not on a supported network", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        ("address" = String, Path, description = "Account address")
+    )
+)]
+pub async fn add_treasury_account_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path((id, address)): axum::extract::Path<(i32, String)>,
+) -> Result<StatusCode, AppError> {
+    if state.db.get_project_by_id(id).await?.is_none() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+    let account = state
+        .db
+        .get_account_by_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+    External::check_network(&account)?;
+
+    state.db.add_treasury_account(id, account.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Stop counting an account in the treasury of a project, the stored flows are kept until
+/// the next refresh
+#[utoipa::path(
+    delete,
+    path = "/api/v1/project/{id}/treasury/accounts/{address}",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "Account is no longer a treasury account of the project"),
+        (status = 404, description = "Account not found or not a treasury account of the project", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        ("address" = String, Path, description = "Account address")
+    )
+)]
+pub async fn remove_treasury_account_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path((id, address)): axum::extract::Path<(i32, String)>,
+) -> Result<StatusCode, AppError> {
+    let account = state
+        .db
+        .get_account_by_address(&address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account not found".to_string()))?;
+
+    if !state.db.remove_treasury_account(id, account.id).await? {
+        return Err(AppError::NotFound(
+            "Account is not a treasury account of the project".to_string(),
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get the USD value that came into and left the treasury accounts of a project over the
+/// last 7 days, computed now rather than read from the stored `treasury_*_7d` attributes.
+/// Transfers between the treasury accounts count in the flows of the accounts but not in
+/// the total.
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/treasury/flow",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Treasury flow of the project", body = TreasuryFlowResponse),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 422, description = "Project has no treasury accounts", body = ErrorBody),
+        (status = 502, description = "Aptos indexer could not be reached", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn get_treasury_flow_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+) -> Result<Json<TreasuryFlowResponse>, AppError> {
+    if state.db.get_project_by_id(id).await?.is_none() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+    let addresses: Vec<String> = state
+        .db
+        .get_treasury_accounts(id)
+        .await?
+        .into_iter()
+        .map(|account| account.address)
+        .collect();
+    if addresses.is_empty() {
+        return Err(AppError::Unprocessable(
+            "Project has no treasury accounts".to_string(),
+        ));
+    }
+
+    let since = Utc::now() - chrono::Duration::days(NetFlow::TREASURY_DAYS);
+    let flow = state
+        .external
+        .calculate_net_flows(&addresses, since)
+        .await?;
+
+    Ok(Json(TreasuryFlowResponse {
+        project_id: id,
+        days: NetFlow::TREASURY_DAYS,
+        inflow_usd: flow.total.inflow_usd,
+        outflow_usd: flow.total.outflow_usd,
+        net_flow_usd: flow.total.net_usd(),
+        truncated: flow.truncated,
+        accounts: addresses
+            .into_iter()
+            .zip(&flow.accounts)
+            .map(|(address, flow)| TreasuryAccountFlowResponse::new(address, flow))
+            .collect(),
+    }))
+}
+
+/// Get the time-weighted liquidity of a project, its total value locked integrated over a
+/// window, so liquidity parked around a snapshot weighs no more than its share of the window
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/twick",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Time-weighted liquidity of the project", body = TwickResponse),
+        (status = 400, description = "Invalid window", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 422, description = "Project has no total value locked recorded over the window", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        TwickQuery
+    )
+)]
+pub async fn get_twick_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<TwickQuery>,
+) -> Result<Json<TwickResponse>, AppError> {
+    let window_hours = query.window_hours.unwrap_or(DEFAULT_TWICK_WINDOW_HOURS);
+    if !(1..=MAX_TWICK_WINDOW_HOURS).contains(&window_hours) {
+        return Err(AppError::Validation(format!(
+            "window_hours must be between 1 and {MAX_TWICK_WINDOW_HOURS}"
+        )));
+    }
+
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    let twick_usd_hours = state
+        .external
+        .get_time_weighted_liquidity(project.id, window_hours)
+        .await?;
+
+    Ok(Json(TwickResponse::new(
+        project.id,
+        window_hours,
+        twick_usd_hours,
+    )))
+}
+
+/// Compare the latest snapshot of the holders of the token of a project with the one taken days
+/// before. The snapshots are taken daily in the background and keep a sample of the holders, so
+/// the counts of tokens with more than 10000 holders are estimated from the samples.
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/holders/diff",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Holders gained and lost since the snapshot", body = HolderDiffResponse),
+        (status = 400, description = "Invalid number of days", body = ErrorBody),
+        (status = 404, description = "Project not found or no snapshot of its holders taken yet", body = ErrorBody),
+        (status = 422, description = "Token of the project isn't a coin", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        HolderDiffQuery
+    )
+)]
+pub async fn get_holder_diff_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<HolderDiffQuery>,
+) -> Result<Json<HolderDiffResponse>, AppError> {
+    let days = query.days.unwrap_or(DEFAULT_HOLDER_DIFF_DAYS);
+    if !(1..=MAX_HOLDER_DIFF_DAYS).contains(&days) {
+        return Err(AppError::Validation(format!(
+            "days must be between 1 and {MAX_HOLDER_DIFF_DAYS}"
+        )));
+    }
+
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    // Fungible assets aren't in the coin balances the holders are read from
+    if !project.token.contains("::") {
+        return Err(AppError::Unprocessable(
+            "Holders are only tracked for coins".to_string(),
+        ));
+    }
+    let diff = state
+        .external
+        .get_holder_diff(&project.token, days)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound("No snapshot of the holders was taken yet".to_string())
+        })?;
+
+    Ok(Json(HolderDiffResponse::new(
+        project.id,
+        project.token,
+        diff,
+    )))
+}
+
+/// List the token pairs found trading in the swap events of a DEX project, refreshed every 6
+/// hours in the background
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/pairs",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Pairs of the project, ordered by token", body = [KnownPairResponse]),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn list_known_pairs_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+) -> Result<Json<Vec<KnownPairResponse>>, AppError> {
+    if state.db.get_project_by_id(id).await?.is_none() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+    let pairs = state.db.list_known_pairs(id).await?;
+    Ok(Json(
+        pairs.into_iter().map(KnownPairResponse::from).collect(),
+    ))
+}
+
+/// Get the gas fees paid by the users of a project, broken down by entry function
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/gas",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Gas fees paid on the project's entry functions", body = GasAnalyticsResponse),
+        (status = 400, description = "Invalid window or entry function", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 422, description = "Project has no contract address", body = ErrorBody),
+        (status = 502, description = "Aptos indexer could not be reached", body = ErrorBody),
+        (status = 503, description = "Aptos indexer is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Aptos indexer did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        GasQuery
+    )
+)]
+pub async fn get_project_gas_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<GasQuery>,
+) -> Result<Json<GasAnalyticsResponse>, AppError> {
+    let days = query.days.unwrap_or(DEFAULT_GAS_DAYS);
+    if !(1..=MAX_GAS_DAYS).contains(&days) {
+        return Err(AppError::Validation(format!(
+            "days must be between 1 and {MAX_GAS_DAYS}"
+        )));
+    }
+
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    // The contract address may point at a module, entry functions live under its account
+    let address = project
+        .contract_address
+        .as_deref()
+        .and_then(|address| address.split("::").next())
+        .filter(|address| !address.is_empty())
+        .ok_or_else(|| AppError::Unprocessable("Project has no contract address".to_string()))?;
+
+    if let Some(entry_function) = &query.entry_function {
+        External::check_entry_function(address, entry_function)?;
+    }
+
+    let analytics = state
+        .external
+        .get_gas_analytics(address, query.entry_function.as_deref(), days)
+        .await?;
+
+    Ok(Json(GasAnalyticsResponse::from_analytics(
+        project.id, analytics,
+    )))
+}
+
+/// Get the share of a DEX in the trading volume of all DEX projects
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/market-share",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Market share of the project", body = MarketShareResponse),
+        (status = 400, description = "Project is not in the DEX category", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn get_market_share_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+) -> Result<Json<MarketShareResponse>, AppError> {
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    if !project.is_dex() {
+        return Err(AppError::Validation(
+            "Project is not in the DEX category".to_string(),
+        ));
+    }
+
+    let market_share_pct = state.db.calculate_market_share(project.id).await?;
+
+    Ok(Json(MarketShareResponse {
+        project_id: project.id,
+        trading_volume: project.trading_volume,
+        market_share_pct,
+    }))
+}
+
+/// Get the latest swaps through the router of a DEX, only those between `token_a` and `token_b`
+/// when both are set, which must be a pair known to be traded on the DEX once its pairs were
+/// discovered
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/swaps",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Page of the latest swaps, newest first", body = SwapPage),
+        (status = 400, description = "Invalid pair or pagination", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 422, description = "Project is not a DEX with a contract address, or the pair isn't traded on it", body = ErrorBody),
+        (status = 502, description = "Indexer could not be reached", body = ErrorBody),
+        (status = 503, description = "Indexer is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Indexer did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        SwapsQuery,
+        PaginationQuery
+    )
+)]
+pub async fn get_project_swaps_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<SwapsQuery>,
+    pagination: Pagination,
+) -> Result<Json<SwapPage>, AppError> {
+    let token_filter = match (query.token_a.as_deref(), query.token_b.as_deref()) {
+        (Some(token_a), Some(token_b)) => {
+            External::check_coin_type(token_a)?;
+            External::check_coin_type(token_b)?;
+            Some((token_a, token_b))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(AppError::Validation(
+                "token_a and token_b must be set together".to_string(),
+            ))
+        }
+    };
+
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    if !project.is_dex() {
+        return Err(AppError::Unprocessable(
+            "Project is not in the DEX category".to_string(),
+        ));
+    }
+    // The contract address may point at a module, the router lives under its account
+    let address = project
+        .contract_address
+        .as_deref()
+        .and_then(|address| address.split("::").next())
+        .filter(|address| !address.is_empty())
+        .ok_or_else(|| AppError::Unprocessable("Project has no contract address".to_string()))?;
+
+    if let Some((token_a, token_b)) = token_filter {
+        let pairs = state.db.list_known_pairs(project.id).await?;
+        let known = pairs.iter().any(|pair| {
+            (pair.token_a == token_a && pair.token_b == token_b)
+                || (pair.token_a == token_b && pair.token_b == token_a)
+        });
+        if !pairs.is_empty() && !known {
+            return Err(AppError::Unprocessable(format!(
+                "{token_a} and {token_b} are not a pair traded on the DEX"
+            )));
+        }
+    }
+
+    let (swaps, total) = state
+        .external
+        .get_swap_transactions(
+            address,
+            token_filter,
+            pagination.limit as u64,
+            pagination.offset as u64,
+        )
+        .await?;
+    let swaps = swaps
+        .into_iter()
+        .map(SwapTransactionResponse::from)
+        .collect();
+    Ok(Json(pagination.page(swaps, total)))
+}
+
+/// Create a project with the attributes of an existing one, e.g. for a fork of the protocol.
+/// The metrics changing over time are left empty.
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/clone",
+    tag = PROJECT_API_GROUP,
+    request_body = CloneProjectRequest,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "Project successfully cloned", body = ProjectResponse),
+        (status = 400, description = "Unknown account", body = ErrorBody),
+        (status = 409, description = "Name already used, `details.current` is the project using it", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "ID of the project to clone")
+    )
+)]
+pub async fn clone_project_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Json(body): Json<CloneProjectRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    if let Some(ref address) = body.new_contract_address {
+        if state.db.get_account_by_address(address).await?.is_none() {
+            return Err(AppError::Validation("Account does not exist".to_string()));
+        }
+    }
+
+    let clone = project.clone_as(body.new_name, body.new_contract_address);
+    let mut tx = state.db.begin().await?;
+    let clone = or_name_conflict(
+        &state,
+        tx.create_project(&clone).await,
+        clone.name.as_deref(),
+    )
+    .await?;
+    let response = ProjectResponse::from(clone);
+    AuditLogger::new(&mut tx, &user)
+        .created(AuditEntry::PROJECT, response.id, &response)
+        .await?;
+    tx.commit().await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Get the latest changes of a numeric attribute of a project, to trace metric regressions
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/attributes/{key}/history",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Changes of the attribute, newest first", body = AttributeHistoryResponse),
+        (status = 400, description = "Unknown attribute or invalid limit", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        ("key" = String, Path, description = "Numeric attribute, e.g. `total_value_locked`"),
+        AttributeHistoryQuery
+    )
+)]
+pub async fn get_attribute_history_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path((id, key)): axum::extract::Path<(i32, String)>,
+    Query(query): Query<AttributeHistoryQuery>,
+) -> Result<Json<AttributeHistoryResponse>, AppError> {
+    check_attribute(&key)?;
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if !(1..=MAX_HISTORY_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {MAX_HISTORY_LIMIT}"
+        )));
+    }
+    if state.db.get_project_by_id(id).await?.is_none() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    let changes = state
+        .db
+        .get_attribute_history(id, &key, None, limit)
+        .await?;
+    Ok(Json(AttributeHistoryResponse {
+        project_id: id,
+        key,
+        changes: changes.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Rejects `key` unless it names one of the numeric attributes whose changes are kept
+fn check_attribute(key: &str) -> Result<(), AppError> {
+    if !Project::NUMERIC_ATTRIBUTES.contains(&key) {
+        return Err(AppError::Validation(format!(
+            "{key} is not a numeric attribute, expected one of {}",
+            Project::NUMERIC_ATTRIBUTES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Name of a project in export filenames
+fn export_name(project: &Project) -> String {
+    project
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("project-{}", project.id))
+}
+
+/// Download the latest changes of a numeric attribute of a project as CSV, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/history.csv",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Changes of the attribute as CSV, with a header row", content_type = "text/csv", body = String),
+        (status = 400, description = "Unknown attribute, invalid window or limit", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        AttributeHistoryCsvQuery
+    )
+)]
+pub async fn export_attribute_history_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<AttributeHistoryCsvQuery>,
+) -> Result<Response, AppError> {
+    check_attribute(&query.key)?;
+    let days = query.days.unwrap_or(DEFAULT_EXPORT_DAYS);
+    if !(1..=MAX_EXPORT_DAYS).contains(&days) {
+        return Err(AppError::Validation(format!(
+            "days must be between 1 and {MAX_EXPORT_DAYS}"
+        )));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if !(1..=MAX_HISTORY_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {MAX_HISTORY_LIMIT}"
+        )));
+    }
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    let to = Utc::now();
+    let from = to - chrono::Duration::days(days);
+    let changes = state
+        .db
+        .get_attribute_history(id, &query.key, Some(from), limit)
+        .await?;
+
+    let filename = csv::filename(&[
+        &export_name(&project),
+        &query.key,
+        &from.format("%Y-%m-%d").to_string(),
+        &to.format("%Y-%m-%d").to_string(),
+    ]);
+    let rows = changes.into_iter().map(|change| {
+        let value = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+        csv::row(&[
+            change.changed_at.to_rfc3339(),
+            change.key,
+            value(change.old_value),
+            value(change.new_value),
+            change.changed_by,
+        ])
+    });
+    Ok(csv::attachment(
+        &filename,
+        &["changed_at", "key", "old_value", "new_value", "changed_by"],
+        rows,
+    ))
+}
+
+/// Download the transactions of the contract account of a project as CSV, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/transactions.csv",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Transactions as CSV, with a header row", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid limit or function prefix", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 422, description = "Project has no contract address", body = ErrorBody),
+        (status = 502, description = "Aptos indexer could not be reached", body = ErrorBody),
+        (status = 503, description = "Aptos indexer is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Aptos indexer did not answer in time", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        TransactionsQuery
+    )
+)]
+pub async fn export_transactions_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<TransactionsQuery>,
+) -> Result<Response, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        )));
+    }
+    let function = query
+        .function
+        .as_deref()
+        .filter(|prefix| !prefix.is_empty());
+    if let Some(prefix) = function {
+        External::check_function_prefix(prefix)?;
+    }
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    let Some(address) = &project.contract_address else {
+        return Err(AppError::Unprocessable(
+            "Project has no contract address".to_string(),
+        ));
+    };
+
+    let transactions = state
+        .external
+        .fetch_transactions(address, query.before_version, limit, function)
+        .await?;
+
+    // Newest first, so the range runs from the last transaction to the first
+    let date = |index: usize| {
+        transactions
+            .get(index)
+            .and_then(|transaction| transaction.timestamp.as_deref())
+            .and_then(|timestamp| timestamp.get(..10))
+            .unwrap_or_default()
+            .to_string()
+    };
+    let filename = csv::filename(&[
+        &export_name(&project),
+        "transactions",
+        &date(transactions.len().saturating_sub(1)),
+        &date(0),
+    ]);
+    let rows = transactions.into_iter().map(|transaction| {
+        csv::row(&[
+            transaction.version.to_string(),
+            transaction.timestamp.unwrap_or_default(),
+            transaction.sender.unwrap_or_default(),
+            transaction.sender_label.unwrap_or_default(),
+            transaction.receiver.unwrap_or_default(),
+            transaction.receiver_label.unwrap_or_default(),
+            transaction.direction.as_str().to_string(),
+            transaction.entry_function_id.unwrap_or_default(),
+            transaction.coin_type.unwrap_or_default(),
+            transaction
+                .amount
+                .map(|amount| amount.to_string())
+                .unwrap_or_default(),
+        ])
+    });
+    Ok(csv::attachment(
+        &filename,
+        &[
+            "version",
+            "timestamp",
+            "sender",
+            "sender_label",
+            "receiver",
+            "receiver_label",
+            "direction",
+            "entry_function_id",
+            "coin_type",
+            "amount",
+        ],
+        rows,
+    ))
+}
+
+/// Stream the changes of the numeric attributes of a project as they are written
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/metrics/stream",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Server-Sent Events stream of `attribute` events, with a comment every 15s while idle", content_type = "text/event-stream", body = AttributeChangeEvent),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID")
+    )
+)]
+pub async fn stream_project_metrics_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    if state.db.get_project_by_id(id).await?.is_none() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    let changes = project_changes(state.db.subscribe_attribute_changes(), id)
+        .map(|change| {
+            Event::default()
+                .event("attribute")
+                .json_data(AttributeChangeEvent::from(change))
+        })
+        // Ends the stream on shutdown instead of holding the server up until its timeout
+        .take_until(state.shutdown.clone().cancelled_owned());
+    Ok(Sse::new(changes).keep_alive(KeepAlive::new().interval(STREAM_HEARTBEAT)))
+}
+
+/// Changes of the project `id` received on `changes`, skipping the ones missed by lagging
+fn project_changes(
+    changes: broadcast::Receiver<ProjectAttributeChange>,
+    id: i32,
+) -> impl Stream<Item = ProjectAttributeChange> {
+    stream::unfold(changes, move |mut changes| async move {
+        loop {
+            match changes.recv().await {
+                Ok(change) if change.project_id == id => return Some((change, changes)),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(project = id, missed, "Metrics stream lagged behind");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+/// Get the daily USD price of the project's token for charting. Days before the project had
+/// price snapshots are filled from CoinGecko when it has a `coingecko_id`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/price/history",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Daily prices of the project's token, each with its source", body = PriceHistoryResponse),
+        (status = 400, description = "Invalid window", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+        (status = 502, description = "CoinGecko could not be reached and there are no snapshots", body = ErrorBody),
+        (status = 503, description = "CoinGecko is rate limiting and there are no snapshots, see `Retry-After`", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        PriceHistoryQuery
+    )
+)]
+pub async fn get_price_history_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<PriceHistoryQuery>,
+) -> Result<Json<PriceHistoryResponse>, AppError> {
+    let days = query.days.unwrap_or(DEFAULT_PRICE_HISTORY_DAYS);
+    if !(1..=MAX_PRICE_HISTORY_DAYS).contains(&days) {
+        return Err(AppError::Validation(format!(
+            "days must be between 1 and {MAX_PRICE_HISTORY_DAYS}"
+        )));
+    }
+
+    let project = state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    let points = state.external.get_price_history(&project, days).await?;
+
+    Ok(Json(PriceHistoryResponse {
+        project_id: project.id,
+        token: project.token,
+        days,
+        points: points.into_iter().map(PricePointResponse::from).collect(),
+    }))
+}
+
+/// Get the daily summaries of the swaps made through the volume entry function of the project
+/// between `start` and `end`. Each day is summarized shortly after midnight UTC, days before
+/// the project had a volume entry function have no summary.
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/swaps/summary",
+    tag = PROJECT_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Summaries of the days of the window, oldest first", body = [SwapDailySummaryResponse]),
+        (status = 400, description = "Invalid window", body = ErrorBody),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("id" = i32, Path, description = "Project ID"),
+        SwapSummaryQuery
+    )
+)]
+pub async fn get_swap_summary_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    Query(query): Query<SwapSummaryQuery>,
+) -> Result<Json<Vec<SwapDailySummaryResponse>>, AppError> {
+    let (start, end) = swap_summary_window(&query, Utc::now().date_naive())?;
+    state
+        .db
+        .get_project_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    let summaries = state.db.list_swap_daily_summaries(id, start, end).await?;
+    Ok(Json(
+        summaries
+            .into_iter()
+            .map(SwapDailySummaryResponse::from)
+            .collect(),
+    ))
+}
+
+/// First and last days of the swap summaries asked for, the last [`DEFAULT_SWAP_SUMMARY_DAYS`]
+/// days before `today` by default
+fn swap_summary_window(
+    query: &SwapSummaryQuery,
+    today: NaiveDate,
+) -> Result<(NaiveDate, NaiveDate), AppError> {
+    let end = query.end.unwrap_or(today - chrono::Duration::days(1));
+    let start = query
+        .start
+        .unwrap_or(end - chrono::Duration::days(DEFAULT_SWAP_SUMMARY_DAYS - 1));
+    if start > end {
+        return Err(AppError::Validation(
+            "start must not be after end".to_string(),
+        ));
+    }
+    if (end - start).num_days() >= MAX_SWAP_SUMMARY_DAYS {
+        return Err(AppError::Validation(format!(
+            "The window must be at most {MAX_SWAP_SUMMARY_DAYS} days"
+        )));
+    }
+    Ok((start, end))
+}
+
+/// Writes `project`, read as `before` and changed by `user`, along with its audit entry,
+/// unless it was updated since `read_at`. The 409 answered then holds the project as it is,
+/// under `message`.
+async fn write_project_update(
+    state: &AppState,
+    user: &User,
+    before: &Project,
+    project: &Project,
+    read_at: DateTime<Utc>,
+    message: &str,
+) -> Result<ProjectResponse, AppError> {
+    let mut tx = state.db.begin().await?;
+    let updated = tx
+        .update_project_if_unchanged(project, &user.id.to_string(), Some(read_at))
+        .await;
+    let Some(updated) = or_name_conflict(state, updated, project.name.as_deref()).await? else {
+        let current = state
+            .db
+            .get_project_by_id(project.id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+        return Err(AppError::Conflict {
+            message: message.to_string(),
+            current: serde_json::to_value(ProjectResponse::from(current))
+                .map_err(|e| AppError::Internal(e.to_string()))?,
+        });
+    };
+    let response = audit_project_update(&mut tx, user, before, &updated).await?;
+    tx.commit().await?;
+    forget_replaced_avatar(state, before, &updated).await;
+    Ok(response)
+}
+
+/// Records the update of a project by `user` in the audit log, in the transaction of the
+/// update, returning the response of the updated project
+async fn audit_project_update(
+    tx: &mut PostgreTransaction<'_>,
+    user: &User,
+    before: &Project,
+    updated: &Project,
+) -> Result<ProjectResponse, sqlx::Error> {
+    let response = ProjectResponse::from(updated.clone());
+    AuditLogger::new(tx, user)
+        .updated(
+            AuditEntry::PROJECT,
+            response.id,
+            &ProjectResponse::from(before.clone()),
+            &response,
+        )
+        .await?;
+    Ok(response)
+}
+
+/// Rejects a project whose fields break the attribute schema of its category, e.g. by leaving
+/// a required attribute unset, naming each offending field
+async fn check_attribute_schema(state: &AppState, project: &Project) -> Result<(), AppError> {
+    let serde_json::Value::Object(attributes) = serde_json::to_value(project)
+        .map_err(|e| AppError::Internal(format!("Could not serialize the project: {e}")))?
+    else {
+        return Ok(());
+    };
+    check_attributes(state, &project.category, &attributes).await
+}
+
+/// Rejects a body setting attributes to values of another type than the attribute schema of
+/// `category` expects, naming each offending field. Checked on the JSON as sent, before it is
+/// read into the typed body, which would refuse or convert such values without naming them.
+async fn check_sent_attributes(
+    state: &AppState,
+    category: &str,
+    body: &serde_json::Value,
+) -> Result<(), AppError> {
+    let Some(fields) = body.as_object() else {
+        return Ok(());
+    };
+    // Whether a null clears a field or leaves it is up to the body, the written project is
+    // checked for the required ones
+    let sent = fields
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    check_attributes(state, category, &sent).await
+}
+
+async fn check_attributes(
+    state: &AppState,
+    category: &str,
+    attributes: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), AppError> {
+    let violations = state.db.validate_attributes(category, attributes).await?;
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::InvalidFields(violations))
+    }
+}
+
+/// Reads a body checked by [`check_sent_attributes`] into its type, a 422 like the
+/// [`Json`] extractor answers when it can't
+fn read_body<T: serde::de::DeserializeOwned>(body: serde_json::Value) -> Result<T, AppError> {
+    serde_json::from_value(body).map_err(|e| AppError::Unprocessable(format!("Invalid body: {e}")))
+}
+
+/// Answers a write refused by the unique project names with a 409 naming the project already
+/// using `name`. Looking the name up before writing would race with concurrent writes.
+async fn or_name_conflict<T>(
+    state: &AppState,
+    written: Result<T, sqlx::Error>,
+    name: Option<&str>,
+) -> Result<T, AppError> {
+    match (written, name) {
+        (Err(error), Some(name)) if is_unique_violation(&error) => {
+            let Some(existing) = state.db.get_project_by_name(name).await? else {
+                return Err(error.into());
+            };
+            Err(AppError::Conflict {
+                message: format!(
+                    "Project name {name} is already used by project {}",
+                    existing.id
+                ),
+                current: serde_json::to_value(ProjectResponse::from(existing))
+                    .map_err(|e| AppError::Internal(e.to_string()))?,
+            })
+        }
+        (written, _) => Ok(written?),
+    }
+}
+
+/// Rejects a CoinGecko id that isn't a coin id, it ends up in the path of CoinGecko calls
+fn check_coingecko_id(project: &Project) -> Result<(), AppError> {
+    match project.coingecko_id.as_deref() {
+        Some(coingecko_id) => External::check_coingecko_id(coingecko_id),
+        None => Ok(()),
+    }
+}
+
+/// Rejects a volume entry function that isn't a function of the contract of the project, it
+/// ends up in the indexer queries of the all time volume
+fn check_volume_entry_function(project: &Project) -> Result<(), AppError> {
+    let Some(entry_function_id) = project.volume_entry_function.as_deref() else {
+        return Ok(());
+    };
+    let address = project.contract_address.as_deref().ok_or_else(|| {
+        AppError::Validation("volume_entry_function needs a contract_address".to_string())
+    })?;
+    External::check_entry_function(address, entry_function_id)
+}
+
+/// Rejects an avatar URL set to anything but an http(s) URL, unless it is the one the project
+/// already had, e.g. of an uploaded avatar. URLs of the stored avatars are only set by
+/// uploads, so a project can't take over the avatar of another one.
+fn check_avatar_url(
+    avatars: &AvatarStore,
+    project: &Project,
+    before: Option<&Project>,
+) -> Result<(), AppError> {
+    let Some(avatar_url) = project.avatar_url.as_deref() else {
+        return Ok(());
+    };
+    if before.is_some_and(|before| before.avatar_url.as_deref() == Some(avatar_url)) {
+        return Ok(());
+    }
+    if avatars.is_stored(avatar_url) {
+        return Err(AppError::Validation(
+            "avatar_url can't be a stored avatar, upload it instead".to_string(),
+        ));
+    }
+    let is_web_url = reqwest::Url::parse(avatar_url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+    if !is_web_url || avatar_url.len() > MAX_AVATAR_URL_LENGTH {
+        return Err(AppError::Validation(format!(
+            "avatar_url must be an http(s) URL of at most {MAX_AVATAR_URL_LENGTH} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// Deletes the stored avatar `before` had when `after` points elsewhere
+async fn forget_replaced_avatar(state: &AppState, before: &Project, after: &Project) {
+    let Some(replaced) = before.avatar_url.as_deref() else {
+        return;
+    };
+    if after.avatar_url.as_deref() == Some(replaced) {
+        return;
+    }
+    if let Err(e) = state.avatars.delete(before.id, replaced).await {
+        warn!(error = %e, avatar_url = replaced, "Could not delete a replaced avatar");
+    }
+}
+
+/// Rejects a swap fee that isn't a share of the input, in basis points
+fn check_swap_fee(project: &Project) -> Result<(), AppError> {
+    match project.swap_fee_bps {
+        Some(bps) if !(1..Project::BASIS_POINTS).contains(&bps) => {
+            Err(AppError::Validation(format!(
+                "swap_fee_bps must be between 1 and {}",
+                Project::BASIS_POINTS - 1
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Writes the excluded supply addresses out in full, as the indexer does, without repeats.
+/// Rejects anything that isn't an account address.
+fn normalize_excluded_supply_addresses(project: &mut Project) -> Result<(), AppError> {
+    if project.excluded_supply_addresses.len() > MAX_EXCLUDED_SUPPLY_ADDRESSES {
+        return Err(AppError::Validation(format!(
+            "At most {MAX_EXCLUDED_SUPPLY_ADDRESSES} excluded supply addresses are allowed"
+        )));
+    }
+    let mut normalized: Vec<String> = Vec::new();
+    for address in &project.excluded_supply_addresses {
+        let address = KnownAddress::normalize(address)
+            .ok_or_else(|| AppError::Validation(format!("{address} is not an account address")))?;
+        if !normalized.contains(&address) {
+            normalized.push(address);
+        }
+    }
+    project.excluded_supply_addresses = normalized;
+    Ok(())
+}
+
+#[cfg(test)]
+const FULL_VIEW: ProjectViewQuery = ProjectViewQuery {
+    include_attributes: None,
+};
+
+#[cfg(test)]
+fn project_with_etag() -> (Project, HeaderValue) {
+    let project = Project {
+        id: 7,
+        token: "0x1::aptos_coin::AptosCoin".to_string(),
+        category: "DEX".to_string(),
+        ..Default::default()
+    };
+    let etag = project_etag(&project);
+    (project, etag)
+}
+
+#[test]
+fn test_get_project_returns_etag() {
+    let (project, etag) = project_with_etag();
+    let response = conditional_project_response(&HeaderMap::new(), project, &FULL_VIEW);
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[ETAG], etag);
+    assert!(etag.to_str().unwrap().starts_with("W/\"7-"));
+}
+
+#[test]
+fn test_get_project_not_modified() {
+    let (project, etag) = project_with_etag();
+    let mut headers = HeaderMap::new();
+    headers.insert(IF_NONE_MATCH, etag.clone());
+    let response = conditional_project_response(&headers, project, &FULL_VIEW);
+
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[ETAG], etag);
+}
+
+#[test]
+fn test_get_project_stale_etag() {
+    let (mut project, etag) = project_with_etag();
+    project.updated_at += chrono::Duration::seconds(1);
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        IF_NONE_MATCH,
+        HeaderValue::from_str(&format!("\"other\", {}", etag_value(&etag))).unwrap(),
+    );
+    let response = conditional_project_response(&headers, project, &FULL_VIEW);
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[ETAG], etag);
+}
+
+#[tokio::test]
+async fn test_project_changes_only_streams_the_project() {
+    let (sender, receiver) = broadcast::channel(8);
+    let change = |project_id, key: &str| ProjectAttributeChange {
+        project_id,
+        key: key.to_string(),
+        ..Default::default()
+    };
+    sender.send(change(1, "total_value_locked")).unwrap();
+    sender.send(change(2, "total_value_locked")).unwrap();
+    sender.send(change(1, "trading_volume")).unwrap();
+    drop(sender);
+
+    let keys: Vec<String> = project_changes(receiver, 1)
+        .map(|change| change.key)
+        .collect()
+        .await;
+    assert_eq!(keys, ["total_value_locked", "trading_volume"]);
+}
+
+#[test]
+fn test_check_volume_entry_function() {
+    let mut project = Project::default();
+    assert!(check_volume_entry_function(&project).is_ok());
+
+    project.volume_entry_function = Some("0xc7ef::router::swap_exact_input".to_string());
+    assert!(matches!(
+        check_volume_entry_function(&project),
+        Err(AppError::Validation(_))
+    ));
+
+    project.contract_address = Some("0xc7ef".to_string());
+    assert!(check_volume_entry_function(&project).is_ok());
+
+    project.volume_entry_function = Some("0xc7ef::router::swap\"}}".to_string());
+    assert!(check_volume_entry_function(&project).is_err());
+}
+
+#[test]
+fn test_check_swap_fee() {
+    let mut project = Project::default();
+    assert!(check_swap_fee(&project).is_ok());
+    project.swap_fee_bps = Some(25);
+    assert!(check_swap_fee(&project).is_ok());
+    for bps in [0, -5, Project::BASIS_POINTS] {
+        project.swap_fee_bps = Some(bps);
+        assert!(check_swap_fee(&project).is_err(), "{bps}");
+    }
+}
+
+#[test]
+fn test_check_avatar_url() {
+    let avatars = AvatarStore::new("avatars", "https://cdn.example.com/avatars");
+    let uploaded = Project {
+        id: 4,
+        avatar_url: Some("https://cdn.example.com/avatars/4-1760400000000-7.png".to_string()),
+        ..Default::default()
+    };
+    let check_avatar_url =
+        |project: &Project, before: Option<&Project>| check_avatar_url(&avatars, project, before);
+    assert!(check_avatar_url(&Project::default(), None).is_ok());
+    // An uploaded avatar can be sent back as it is, but not given to another project
+    assert!(check_avatar_url(&uploaded, Some(&uploaded)).is_ok());
+    assert!(check_avatar_url(&uploaded, None).is_err());
+    let other = Project {
+        id: 5,
+        ..Default::default()
+    };
+    let taken = Project {
+        avatar_url: uploaded.avatar_url.clone(),
+        ..other.clone()
+    };
+    assert!(check_avatar_url(&taken, Some(&other)).is_err());
+
+    let mut project = Project {
+        avatar_url: Some("https://cdn.example.com/logo.png".to_string()),
+        ..Default::default()
+    };
+    assert!(check_avatar_url(&project, Some(&uploaded)).is_ok());
+    for avatar_url in [
+        "javascript:alert(1)".to_string(),
+        "logo.png".to_string(),
+        format!(
+            "https://cdn.example.com/{}",
+            "a".repeat(MAX_AVATAR_URL_LENGTH)
+        ),
+    ] {
+        project.avatar_url = Some(avatar_url);
+        assert!(check_avatar_url(&project, Some(&uploaded)).is_err());
+    }
+}
+
+#[test]
+fn test_normalize_excluded_supply_addresses() {
+    let mut project = Project {
+        excluded_supply_addresses: vec!["0xC7EF".to_string(), format!("0x{:0>64}", "c7ef")],
+        ..Default::default()
+    };
+    normalize_excluded_supply_addresses(&mut project).unwrap();
+    assert_eq!(
+        project.excluded_supply_addresses,
+        vec![format!("0x{:0>64}", "c7ef")]
+    );
+
+    project.excluded_supply_addresses = vec!["treasury".to_string()];
+    assert!(normalize_excluded_supply_addresses(&mut project).is_err());
+    project.excluded_supply_addresses = vec!["0x1".to_string(); MAX_EXCLUDED_SUPPLY_ADDRESSES + 1];
+    assert!(normalize_excluded_supply_addresses(&mut project).is_err());
+}
+
+#[test]
+fn test_swap_summary_window() {
+    let day = |day| NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+    let window = |start, end| swap_summary_window(&SwapSummaryQuery { start, end }, day(14));
+
+    assert_eq!(
+        window(None, None).unwrap(),
+        (NaiveDate::from_ymd_opt(2026, 9, 14).unwrap(), day(13))
+    );
+    assert_eq!(window(Some(day(1)), None).unwrap(), (day(1), day(13)));
+    assert_eq!(
+        window(Some(day(5)), Some(day(5))).unwrap(),
+        (day(5), day(5))
+    );
+    assert!(window(Some(day(6)), Some(day(5))).is_err());
+
+    let year_before = NaiveDate::from_ymd_opt(2025, 10, 14).unwrap();
+    assert!(window(Some(year_before), Some(day(13))).is_ok());
+    assert!(window(Some(year_before), Some(day(14))).is_err());
+}
+
+#[tokio::test]
+async fn test_patch_after_concurrent_update_conflicts() {
+    let test_db = crate::database::test_db::TestDatabase::migrated().await;
+    let state = AppState::for_test(test_db.db.clone());
+    let user = state
+        .db
+        .create_user(&User {
+            name: "Ada".to_string(),
+            email: "ada@example.com".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let before = state
+        .db
+        .create_project(&Project {
+            name: Some("PancakeSwap".to_string()),
+            token: "CAKE".to_string(),
+            category: Project::DEX_CATEGORY.to_string(),
+            total_value_locked: Some(100.0),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // A background task moves the volume while the patch of the TVL is applied
+    state
+        .db
+        .update_project(
+            &Project {
+                trading_volume: Some(40.0),
+                ..before.clone()
+            },
+            "scheduler",
+        )
+        .await
+        .unwrap();
+    let patched = Project {
+        total_value_locked: Some(250.0),
+        ..before.clone()
+    };
+    let result = write_project_update(
+        &state,
+        &user,
+        &before,
+        &patched,
+        before.updated_at,
+        "stale",
+    )
+    .await;
+    let Err(AppError::Conflict { current, .. }) = result else {
+        panic!("Expected a conflict, got {:?}", result.map(|response| response.id));
+    };
+    assert_eq!(current["trading_volume"], 40.0);
+    assert_eq!(current["total_value_locked"], 100.0);
+    let stored = state.db.get_project_by_id(before.id).await.unwrap().unwrap();
+    assert_eq!(stored.trading_volume, Some(40.0));
+    assert_eq!(stored.total_value_locked, Some(100.0));
+
+    // Applied again over the project as it is, the patch keeps the volume
+    let response = write_project_update(
+        &state,
+        &user,
+        &stored,
+        &Project {
+            total_value_locked: Some(250.0),
+            ..stored.clone()
+        },
+        stored.updated_at,
+        "stale",
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.trading_volume, Some(40.0));
+    assert_eq!(response.total_value_locked, Some(250.0));
 }
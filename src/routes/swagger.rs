@@ -13,6 +13,11 @@ struct Api;
 /// Merges in OpenAPI definitions from other locations in the app, such as the [dto] package
 /// and submodules of [api][crate::api]
 pub fn build_documentation() -> SwaggerUi {
+    SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", api_docs())
+}
+
+/// OpenAPI document of the whole API, served by [`build_documentation`]
+pub fn api_docs() -> utoipa::openapi::OpenApi {
     let mut api_docs = Api::openapi();
     api_docs.merge(dto::OpenApiSchemas::openapi());
     api_docs.merge(super::health::HealthApi::openapi());
@@ -20,6 +25,47 @@ pub fn build_documentation() -> SwaggerUi {
     api_docs.merge(super::entity::EntityApi::openapi());
     api_docs.merge(super::account::AccountsApi::openapi());
     api_docs.merge(super::project::ProjectsApi::openapi());
+    api_docs.merge(super::avatar::AvatarApi::openapi());
+    api_docs.merge(super::alert::AlertApi::openapi());
+    api_docs.merge(super::dashboard::DashboardApi::openapi());
+    api_docs.merge(super::utils::UtilsApi::openapi());
+    api_docs.merge(super::admin::AdminApi::openapi());
+    api_docs.merge(super::versions::VersionsApi::openapi());
+    api_docs
+}
+
+#[cfg(test)]
+/// `$ref`s of `value` and of the values nested in it
+fn collect_refs<'a>(value: &'a serde_json::Value, refs: &mut Vec<&'a str>) {
+    match value {
+        serde_json::Value::Object(object) => {
+            if let Some(serde_json::Value::String(reference)) = object.get("$ref") {
+                refs.push(reference);
+            }
+            object.values().for_each(|value| collect_refs(value, refs));
+        }
+        serde_json::Value::Array(values) => {
+            values.iter().for_each(|value| collect_refs(value, refs))
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn test_every_ref_resolves() {
+    let document: serde_json::Value = serde_json::from_str(&api_docs().to_json().unwrap()).unwrap();
+    let mut refs = Vec::new();
+    collect_refs(&document, &mut refs);
+    assert!(!refs.is_empty());
 
-    SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", api_docs)
+    let mut dangling: Vec<&str> = refs
+        .into_iter()
+        .filter(|reference| {
+            let pointer = reference.strip_prefix('#').unwrap_or(reference);
+            document.pointer(pointer).is_none()
+        })
+        .collect();
+    dangling.sort_unstable();
+    dangling.dedup();
+    assert!(dangling.is_empty(), "Dangling refs: {dangling:?}");
 }
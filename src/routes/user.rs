@@ -3,30 +3,46 @@ use argon2::{
     Argon2,
 };
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     middleware,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{get, post, put},
     Extension, Json, Router,
 };
-use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use std::sync::Arc;
 use utoipa::OpenApi;
 
 use crate::{
+    audit::AuditLogger,
+    database::is_foreign_key_violation,
     models::{
-        dto::{LoginInfo, Profile, RegisterInfo, TokenResponse},
-        Error, TokenClaim, User,
+        dto::{
+            BasicProjectResponse, LoginInfo, LoginResponse, PaginationQuery, PartialTokenResponse,
+            Profile, RegisterInfo, TokenResponse, TotpChallenge, TotpCode, TotpSetupResponse,
+            WatchlistPage,
+        },
+        AppError, AuditEntry, TokenClaim, Totp, User,
     },
     AppState,
 };
 
-use super::middlewares::auth_guard;
+use super::{extractors::Pagination, middlewares::auth_guard};
 
 #[derive(OpenApi)]
-#[openapi(paths(login_handler, register_user_handler, get_profile_handler))]
+#[openapi(paths(
+    login_handler,
+    register_user_handler,
+    get_profile_handler,
+    enable_totp_handler,
+    verify_totp_handler,
+    totp_challenge_handler,
+    list_watchlist_handler,
+    watch_project_handler,
+    unwatch_project_handler
+))]
 /// Defines the OpenAPI spec for user endpoints
 pub struct UsersApi;
 
@@ -43,62 +59,127 @@ pub fn user_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
             get(get_profile_handler)
                 .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard)),
         )
+        .route(
+            "/totp/enable",
+            post(enable_totp_handler)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard)),
+        )
+        .route(
+            "/totp/verify",
+            post(verify_totp_handler)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard)),
+        )
+        .route("/totp/challenge", post(totp_challenge_handler))
+        .route(
+            "/watchlist",
+            get(list_watchlist_handler)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard)),
+        )
+        .route(
+            "/watchlist/:project_id",
+            put(watch_project_handler)
+                .delete(unwatch_project_handler)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard)),
+        )
+}
+
+/// Lifetime of the token of a login, and of the one waiting for its TOTP code
+const TOKEN_LIFETIME: Duration = Duration::days(7);
+const PARTIAL_TOKEN_LIFETIME: Duration = Duration::minutes(5);
+
+/// Wrong TOTP codes in a row after which the challenge of a user is locked, and for how long
+const MAX_TOTP_ATTEMPTS: i32 = 5;
+const TOTP_LOCKOUT: Duration = Duration::minutes(15);
+
+fn issue_token(
+    state: &AppState,
+    email: String,
+    lifetime: Duration,
+    totp_pending: bool,
+) -> Result<String, AppError> {
+    let now = Utc::now();
+    let claims = TokenClaim {
+        sub: email,
+        exp: (now + lifetime).timestamp() as usize,
+        iat: now.timestamp() as usize,
+        totp_pending,
+    };
+
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(state.config.jwt_secret.as_ref()),
+    )?)
+}
+
+fn totp_key(state: &AppState) -> Result<&[u8; 32], AppError> {
+    state.config.totp_encryption_key.as_ref().ok_or_else(|| {
+        AppError::Unavailable("Two-factor authentication is not configured".to_string())
+    })
+}
+
+fn user_totp(state: &AppState, user: &User) -> Result<Totp, AppError> {
+    let encrypted = user.totp_secret.as_deref().ok_or_else(|| {
+        AppError::Validation(
+            "Two-factor authentication is not set up, call /api/v1/user/totp/enable first"
+                .to_string(),
+        )
+    })?;
+    Totp::decrypt(encrypted, totp_key(state)?)
+        .ok_or_else(|| AppError::Internal("TOTP secret could not be decrypted".to_string()))
 }
 
 // Login handler function
 #[utoipa::path(
     post,
-    path = "/api/user/login",
+    path = "/api/v1/user/login",
     tag = USER_API_GROUP,
     request_body = LoginInfo,
     responses(
-        (status = 201, description = "User successfully created"),
+        (status = 200, description = "JWT of the user, or a partial token to send with a TOTP code to `/api/v1/user/totp/challenge` when two-factor authentication is enabled", body = LoginResponse),
+        (status = 400, description = "Unknown email or wrong password", body = ErrorBody),
+        (status = 403, description = "The user was deactivated by an admin", body = ErrorBody),
     )
 )]
 pub async fn login_handler(
     State(state): State<Arc<AppState>>,
     Json(body): Json<LoginInfo>,
-) -> Result<impl IntoResponse, Error> {
+) -> Result<impl IntoResponse, AppError> {
     let user = state.db.get_user_by_email(&body.email).await?;
-    let user: User = user.ok_or((StatusCode::BAD_REQUEST, "User does not exist"))?;
+    let user: User = user.ok_or_else(|| AppError::Validation("User does not exist".to_string()))?;
     let hash = PasswordHash::new(&user.hashed_password)?;
     Argon2::default().verify_password(body.password.as_bytes(), &hash)?;
+    if !user.active {
+        return Err(AppError::Forbidden("This user is deactivated".to_string()));
+    }
 
-    let now = Utc::now();
-    let iat = now.timestamp() as usize;
-    let exp = (now + Duration::days(7)).timestamp() as usize;
-
-    let claims = TokenClaim {
-        sub: user.email,
-        exp,
-        iat,
-    };
-
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.config.jwt_secret.as_ref()),
-    )?;
-
-    Ok(Json(TokenResponse { token }))
+    if user.totp_enabled {
+        let partial_token = issue_token(&state, user.email, PARTIAL_TOKEN_LIFETIME, true)?;
+        return Ok(Json(LoginResponse::TotpRequired(PartialTokenResponse {
+            partial_token,
+        })));
+    }
+    let token = issue_token(&state, user.email, TOKEN_LIFETIME, false)?;
+    Ok(Json(LoginResponse::Token(TokenResponse { token })))
 }
 
 // Register user handler function
 #[utoipa::path(
     post,
-    path = "/api/user/signup",
+    path = "/api/v1/user/signup",
     tag = USER_API_GROUP,
     request_body = RegisterInfo,
     responses(
         (status = 201, description = "User successfully created", body = Profile),
+        (status = 400, description = "Email already exists", body = ErrorBody),
     )
 )]
 pub async fn register_user_handler(
     State(state): State<Arc<AppState>>,
     Json(body): Json<RegisterInfo>,
-) -> Result<impl IntoResponse, Error> {
+) -> Result<impl IntoResponse, AppError> {
     if state.db.get_user_by_email(&body.email).await?.is_some() {
-        return Err(Error::new(StatusCode::BAD_REQUEST, "Email already exists"));
+        return Err(AppError::Validation("Email already exists".to_string()));
     }
 
     let salt = SaltString::generate(&mut OsRng);
@@ -115,14 +196,20 @@ pub async fn register_user_handler(
         ..Default::default()
     };
 
-    let user: User = state.db.create_user(&data).await?;
-    Ok(Json(Profile::from(user)))
+    let mut tx = state.db.begin().await?;
+    let user: User = tx.create_user(&data).await?;
+    let profile = Profile::from(user.clone());
+    AuditLogger::new(&mut tx, &user)
+        .created(AuditEntry::USER, user.id, &profile)
+        .await?;
+    tx.commit().await?;
+    Ok((StatusCode::CREATED, Json(profile)))
 }
 
 // Get profile handler function
 #[utoipa::path(
     get,
-    path = "/api/user/profile",
+    path = "/api/v1/user/profile",
     tag = USER_API_GROUP,
     responses(
         (status = 200, description = "User profile successfully retrieved", body = Profile),
@@ -134,3 +221,248 @@ pub async fn register_user_handler(
 pub async fn get_profile_handler(Extension(user): Extension<User>) -> impl IntoResponse {
     Json(Profile::from(user))
 }
+
+/// Start setting up two-factor authentication, login keeps working with the password alone
+/// until a code from the app is sent to `/api/v1/user/totp/verify`
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/totp/enable",
+    tag = USER_API_GROUP,
+    responses(
+        (status = 200, description = "New secret to add to an authenticator app", body = TotpSetupResponse),
+        (status = 400, description = "Two-factor authentication is already enabled", body = ErrorBody),
+        (status = 503, description = "Two-factor authentication is not configured", body = ErrorBody),
+    ),
+    security(
+        ("bearerAuth" = [])
+    )
+)]
+pub async fn enable_totp_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<TotpSetupResponse>, AppError> {
+    if user.totp_enabled {
+        return Err(AppError::Validation(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    let totp = Totp::generate();
+    let encrypted = totp.encrypt(totp_key(&state)?);
+    state.db.set_totp_secret(user.id, &encrypted).await?;
+
+    Ok(Json(TotpSetupResponse {
+        secret: totp.base32(),
+        provisioning_uri: totp.provisioning_uri(&user.email),
+    }))
+}
+
+/// Finish setting up two-factor authentication with a code from the app, logins ask for a
+/// code from then on
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/totp/verify",
+    tag = USER_API_GROUP,
+    request_body = TotpCode,
+    responses(
+        (status = 200, description = "Two-factor authentication successfully enabled", body = Profile),
+        (status = 400, description = "Invalid code, already enabled or not set up", body = ErrorBody),
+        (status = 503, description = "Two-factor authentication is not configured", body = ErrorBody),
+    ),
+    security(
+        ("bearerAuth" = [])
+    )
+)]
+pub async fn verify_totp_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(body): Json<TotpCode>,
+) -> Result<Json<Profile>, AppError> {
+    if user.totp_enabled {
+        return Err(AppError::Validation(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    let totp = user_totp(&state, &user)?;
+    if !totp.verify(&body.code, Utc::now().timestamp()) {
+        return Err(AppError::Validation("Invalid TOTP code".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+    let enabled = tx.enable_totp(user.id).await?;
+    let profile = Profile::from(enabled);
+    AuditLogger::new(&mut tx, &user)
+        .updated(
+            AuditEntry::USER,
+            user.id,
+            &Profile::from(user.clone()),
+            &profile,
+        )
+        .await?;
+    tx.commit().await?;
+    Ok(Json(profile))
+}
+
+/// Finish the login of a user with two-factor authentication. A partial token and a code
+/// only log in once, and the challenge is locked for 15 minutes after 5 wrong codes in a row.
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/totp/challenge",
+    tag = USER_API_GROUP,
+    request_body = TotpChallenge,
+    responses(
+        (status = 200, description = "JWT of the user", body = TokenResponse),
+        (status = 401, description = "Invalid, expired or used partial token, or invalid or used code", body = ErrorBody),
+        (status = 429, description = "Too many wrong codes, `Retry-After` tells when to try again", body = ErrorBody),
+        (status = 503, description = "Two-factor authentication is not configured", body = ErrorBody),
+    )
+)]
+pub async fn totp_challenge_handler(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<TotpChallenge>,
+) -> Result<Json<TokenResponse>, AppError> {
+    let invalid_token = || AppError::Unauthorized("Invalid or expired token".to_string());
+    let claims = decode::<TokenClaim>(
+        &body.partial_token,
+        &DecodingKey::from_secret(state.config.jwt_secret.as_ref()),
+        &Validation::default(),
+    )
+    .map_err(|_| invalid_token())?
+    .claims;
+    if !claims.totp_pending {
+        return Err(invalid_token());
+    }
+    let user = state
+        .db
+        .get_user_by_email(&claims.sub)
+        .await?
+        .filter(|user| user.totp_enabled && user.active)
+        .ok_or_else(invalid_token)?;
+
+    if let Some(locked_until) = state.db.get_totp_locked_until(user.id).await? {
+        return Err(totp_locked(locked_until));
+    }
+
+    let totp = user_totp(&state, &user)?;
+    let now = Utc::now();
+    let Some(step) = totp.matching_step(&body.code, now.timestamp()) else {
+        let locked_until = now + TOTP_LOCKOUT;
+        if state
+            .db
+            .record_totp_failure(user.id, MAX_TOTP_ATTEMPTS, locked_until)
+            .await?
+        {
+            return Err(totp_locked(locked_until));
+        }
+        return Err(AppError::Unauthorized("Invalid TOTP code".to_string()));
+    };
+    let issued_at = DateTime::from_timestamp(claims.iat as i64, 0).ok_or_else(invalid_token)?;
+    if !state.db.record_totp_login(user.id, step, issued_at).await? {
+        return Err(AppError::Unauthorized(
+            "TOTP code or partial token already used".to_string(),
+        ));
+    }
+    let token = issue_token(&state, user.email, TOKEN_LIFETIME, false)?;
+    Ok(Json(TokenResponse { token }))
+}
+
+fn totp_locked(locked_until: DateTime<Utc>) -> AppError {
+    AppError::TooManyAttempts {
+        message: "Too many wrong TOTP codes, try again later".to_string(),
+        retry_after: (locked_until - Utc::now()).num_seconds().max(1) as u64,
+    }
+}
+
+/// List the projects the user starred, with their key metrics, the latest starred first
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/watchlist",
+    tag = USER_API_GROUP,
+    params(PaginationQuery),
+    responses(
+        (status = 200, description = "Page of the projects on the watchlist of the user", body = WatchlistPage),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorBody),
+    ),
+    security(
+        ("bearerAuth" = [])
+    )
+)]
+pub async fn list_watchlist_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    pagination: Pagination,
+) -> Result<Json<WatchlistPage>, AppError> {
+    let (projects, total) = tokio::try_join!(
+        state
+            .db
+            .list_watched_projects(user.id, pagination.limit, pagination.offset),
+        state.db.count_watched_projects(user.id)
+    )?;
+
+    let projects = projects
+        .into_iter()
+        .map(BasicProjectResponse::from)
+        .collect();
+    Ok(Json(pagination.page(projects, total)))
+}
+
+/// Star a project, adding it to the watchlist of the user. Starring it again does nothing.
+#[utoipa::path(
+    put,
+    path = "/api/v1/user/watchlist/{project_id}",
+    tag = USER_API_GROUP,
+    responses(
+        (status = 204, description = "Project is on the watchlist"),
+        (status = 404, description = "Project not found", body = ErrorBody),
+    ),
+    params(
+        ("project_id" = i32, Path, description = "Project ID")
+    ),
+    security(
+        ("bearerAuth" = [])
+    )
+)]
+pub async fn watch_project_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(project_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    // The project may be deleted between a lookup and the insert, its foreign key can't
+    match state.db.watch_project(user.id, project_id).await {
+        Err(error) if is_foreign_key_violation(&error) => {
+            Err(AppError::NotFound("Project not found".to_string()))
+        }
+        watched => {
+            watched?;
+            Ok(StatusCode::NO_CONTENT)
+        }
+    }
+}
+
+/// Remove a project from the watchlist of the user
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/watchlist/{project_id}",
+    tag = USER_API_GROUP,
+    responses(
+        (status = 204, description = "Project is no longer on the watchlist"),
+        (status = 404, description = "Project is not on the watchlist", body = ErrorBody),
+    ),
+    params(
+        ("project_id" = i32, Path, description = "Project ID")
+    ),
+    security(
+        ("bearerAuth" = [])
+    )
+)]
+pub async fn unwatch_project_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(project_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    if !state.db.unwatch_project(user.id, project_id).await? {
+        return Err(AppError::NotFound(
+            "Project is not on the watchlist".to_string(),
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::{header::CACHE_CONTROL, HeaderMap},
+    middleware,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use utoipa::OpenApi;
+
+use crate::{
+    models::{
+        dto::{CrossRateQuery, CrossRateResponse, SwapTransactionResponse},
+        AppError,
+    },
+    AppState,
+};
+
+use super::middlewares::auth_guard;
+
+/// Defines the OpenAPI spec for utility endpoints
+#[derive(OpenApi)]
+#[openapi(paths(get_cross_rate_handler, get_swap_transactions_handler))]
+pub struct UtilsApi;
+
+/// Used to group utility endpoints together in the OpenAPI documentation
+pub const UTILS_API_GROUP: &str = "UTILS";
+
+/// Builds a router for utility routes
+pub fn utils_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/cross-rate", get(get_cross_rate_handler))
+        .route("/swap-transactions", get(get_swap_transactions_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+}
+
+/// Get the price of one token expressed in another
+#[utoipa::path(
+    get,
+    path = "/api/v1/utils/cross-rate",
+    tag = UTILS_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    params(CrossRateQuery),
+    responses(
+        (status = 200, description = "Cross rate successfully computed", body = CrossRateResponse),
+        (status = 404, description = "USD price of one of the tokens could not be found", body = ErrorBody),
+        (status = 422, description = "USD price of the quote token is zero", body = ErrorBody),
+        (status = 503, description = "CoinGecko is rate limiting, see `Retry-After`", body = ErrorBody),
+    )
+)]
+pub async fn get_cross_rate_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CrossRateQuery>,
+) -> Result<Json<CrossRateResponse>, AppError> {
+    let cross_rate = state
+        .external
+        .get_cross_rate(&query.base, &query.quote, query.source)
+        .await?;
+
+    Ok(Json(CrossRateResponse::from(cross_rate)))
+}
+
+/// Get the latest PancakeSwap swaps, cached in memory for a short while
+#[utoipa::path(
+    get,
+    path = "/api/v1/utils/swap-transactions",
+    tag = UTILS_API_GROUP,
+    security(
+        ("bearerAuth" = [])
+    ),
+    params(
+        ("Cache-Control" = Option<String>, Header, description = "`no-cache` skips the cached swaps")
+    ),
+    responses(
+        (status = 200, description = "Latest swaps, `X-Cache` tells whether they came from the cache", body = [SwapTransactionResponse]),
+        (status = 502, description = "Indexer could not be reached", body = ErrorBody),
+        (status = 503, description = "Indexer is rate limiting, see `Retry-After`", body = ErrorBody),
+        (status = 504, description = "Indexer did not answer in time", body = ErrorBody),
+    )
+)]
+pub async fn get_swap_transactions_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let bypass_cache = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("no-cache"));
+
+    let (transactions, cache_status) = state
+        .external
+        .get_cached_swap_transactions(bypass_cache)
+        .await?;
+
+    let transactions: Vec<SwapTransactionResponse> = transactions
+        .into_iter()
+        .map(SwapTransactionResponse::from)
+        .collect();
+    Ok(([("x-cache", cache_status.as_str())], Json(transactions)))
+}
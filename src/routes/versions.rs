@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use crate::{
+    models::dto::{ApiVersionResponse, VersionsResponse},
+    AppState, Config,
+};
+use axum::{
+    extract::State,
+    http::{HeaderName, HeaderValue},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use utoipa::OpenApi;
+
+/// Version the current routes are served under, as `/api/v1/...`
+pub const API_V1: &str = "v1";
+
+/// Every version of the API still served, oldest first
+pub const API_VERSIONS: [&str; 1] = [API_V1];
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+#[derive(OpenApi)]
+#[openapi(paths(list_versions_handler))]
+/// Defines the OpenAPI spec for the versions endpoint
+pub struct VersionsApi;
+
+/// Used to group the versions endpoint in the OpenAPI documentation
+pub const VERSIONS_API_GROUP: &str = "VERSIONS";
+
+pub fn versions_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", get(list_versions_handler))
+}
+
+/// Prefix the routes of `version` are nested under
+pub fn version_prefix(version: &str) -> String {
+    format!("/api/{version}")
+}
+
+/// Date after which `version` may be removed, `None` while it isn't deprecated
+pub fn sunset_of<'a>(config: &'a Config, version: &str) -> Option<&'a str> {
+    match version {
+        API_V1 => config.api_v1_sunset.as_deref(),
+        _ => None,
+    }
+}
+
+/// Tells clients of every route of `router` that it is deprecated and when it goes away,
+/// with the `Deprecation` and `Sunset` headers
+pub fn deprecate<S>(router: Router<S>, sunset: &str) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let sunset = HeaderValue::from_str(sunset).expect("Sunset date must be a valid header");
+    router.layer(middleware::map_response(move |mut response: Response| {
+        let sunset = sunset.clone();
+        async move {
+            let headers = response.headers_mut();
+            headers.insert(DEPRECATION, HeaderValue::from_static("true"));
+            headers.insert(SUNSET, sunset);
+            response
+        }
+    }))
+}
+
+/// Lists the versions of the API and whether clients should move off them
+#[utoipa::path(
+    get,
+    path = "/api/versions",
+    tag = VERSIONS_API_GROUP,
+    responses(
+        (status = 200, description = "Versions served, oldest first", body = VersionsResponse)
+    )
+)]
+pub async fn list_versions_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let versions = API_VERSIONS
+        .iter()
+        .map(|version| {
+            let sunset = sunset_of(&state.config, version);
+            ApiVersionResponse {
+                version: version.to_string(),
+                path: version_prefix(version),
+                deprecated: sunset.is_some(),
+                sunset: sunset.map(str::to_string),
+            }
+        })
+        .collect();
+    Json(VersionsResponse { versions })
+}
+
+#[tokio::test]
+async fn test_deprecated_routes_announce_sunset() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let routes = Router::new().route("/entity", get(|| async { "entity" }));
+    let app: Router = Router::new()
+        .nest(
+            &version_prefix(API_V1),
+            deprecate(routes, "Sat, 31 Oct 2026 23:59:59 GMT"),
+        )
+        .route("/api/health", get(|| async { "OK" }));
+
+    let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = app
+        .clone()
+        .oneshot(request("/api/v1/entity"))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["deprecation"], "true");
+    assert_eq!(
+        response.headers()["sunset"],
+        "Sat, 31 Oct 2026 23:59:59 GMT"
+    );
+
+    let response = app.oneshot(request("/api/health")).await.unwrap();
+    assert!(response.headers().get("deprecation").is_none());
+}
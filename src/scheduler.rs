@@ -0,0 +1,1577 @@
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use tokio::{
+    sync::{broadcast::error::RecvError, Semaphore},
+    task::{JoinHandle, JoinSet},
+    time::MissedTickBehavior,
+};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{info, warn};
+
+use crate::{
+    external::{
+        fees::{FeeBatch, FEE_PROGRESS_EVENTS},
+        notifier::{Notifier, SlackNotifier, TaskFailure, TelegramNotifier},
+        volume::{VolumeBatch, VOLUME_PROGRESS_TRANSACTIONS},
+        webhook,
+    },
+    models::{
+        dto::{AnomalyAlertPayload, AnomalyResponse, SwapAlertPayload, SwapTransactionResponse},
+        AlertDelivery, AlertRule, AnomalyAlert, AnomalyDetector, CoinGeckoMarketData, NetFlow,
+        PriceSource, Project, ProjectAttributeChange, SwapTransaction,
+    },
+    AppState, Config,
+};
+
+/// Background tasks of the app, stopped together when it shuts down
+pub struct Scheduler {
+    shutdown: CancellationToken,
+    tasks: Vec<JoinHandle<()>>,
+    /// [`AppState::one_shot_tasks`]
+    one_shot_tasks: TaskTracker,
+}
+
+impl Scheduler {
+    /// Spawns every background task, stopped once [`AppState::shutdown`] is cancelled
+    pub fn start(state: Arc<AppState>) -> Self {
+        let shutdown = state.shutdown.clone();
+        let one_shot_tasks = state.one_shot_tasks.clone();
+        if !state.config.run_scheduler {
+            info!("Background tasks are disabled on this instance");
+            return Self {
+                shutdown,
+                tasks: Vec::new(),
+                one_shot_tasks,
+            };
+        }
+        let tasks = vec![
+            spawn_balance_snapshots(state.clone(), shutdown.clone()),
+            spawn_swap_alerts(state.clone(), shutdown.clone()),
+            spawn_volume_anomalies(state.clone(), shutdown.clone()),
+            spawn_coingecko_market_data(state.clone(), shutdown.clone()),
+            spawn_price_snapshots(state.clone(), shutdown.clone()),
+            spawn_treasury_flows(state.clone(), shutdown.clone()),
+            spawn_all_time_volumes(state.clone(), shutdown.clone()),
+            spawn_daily_swap_summaries(state.clone(), shutdown.clone()),
+            spawn_all_time_fees(state.clone(), shutdown.clone()),
+            spawn_twicks(state.clone(), shutdown.clone()),
+            spawn_pair_discovery(state.clone(), shutdown.clone()),
+            spawn_contract_health_checks(state.clone(), shutdown.clone()),
+            spawn_holder_snapshots(state, shutdown.clone()),
+        ];
+        Self {
+            shutdown,
+            tasks,
+            one_shot_tasks,
+        }
+    }
+
+    /// Token cancelled when the app starts shutting down
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Tells the tasks to stop and waits for the runs in progress to finish, one-shot tasks
+    /// included
+    pub async fn stop(self) {
+        self.shutdown.cancel();
+        for task in self.tasks {
+            if let Err(error) = task.await {
+                warn!(%error, "Background task ended abnormally");
+            }
+        }
+        self.one_shot_tasks.close();
+        self.one_shot_tasks.wait().await;
+    }
+}
+
+/// Time between two balance snapshots of a watched account
+pub const SNAPSHOT_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Time between two looks for accounts due for a snapshot, short enough that a restart
+/// doesn't push the snapshots back by a whole period
+const SNAPSHOT_CHECK_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+const BALANCE_SNAPSHOTS_TASK: &str = "balance_snapshots";
+
+/// Spawns the task snapshotting the USD value of every watched account once per
+/// [`SNAPSHOT_PERIOD`]. Accounts that fail are retried on the next check.
+fn spawn_balance_snapshots(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        BALANCE_SNAPSHOTS_TASK,
+        SNAPSHOT_CHECK_PERIOD,
+        |state| async move { snapshot_watched_accounts(&state).await },
+    )
+}
+
+/// Snapshots the watched accounts without a snapshot in the last [`SNAPSHOT_PERIOD`], one
+/// at a time to keep the load on the indexer low
+async fn snapshot_watched_accounts(state: &AppState) -> Result<(), TaskFailure> {
+    let failure = |error: &str| TaskFailure::new(BALANCE_SNAPSHOTS_TASK, error, None);
+    let accounts = state
+        .db
+        .get_watched_accounts_without_snapshot(Utc::now() - SNAPSHOT_PERIOD)
+        .await
+        .map_err(|error| failure(&error.to_string()))?;
+
+    let mut failed = 0;
+    let mut last_error = None;
+    for account in &accounts {
+        let snapshot = match state.external.get_portfolio(&account.address).await {
+            Ok(portfolio) => state
+                .db
+                .create_balance_snapshot(account.id, portfolio.total_usd)
+                .await
+                .map(|_| ())
+                .map_err(|error| error.to_string()),
+            Err(error) => Err(error.to_string()),
+        };
+        if let Err(error) = snapshot {
+            warn!(address = %account.address, %error, "Could not snapshot the balance of an account");
+            failed += 1;
+            last_error = Some(error);
+        }
+    }
+
+    info!(
+        accounts = accounts.len(),
+        failed, "Snapshotted the balances of watched accounts"
+    );
+    match last_error {
+        Some(error) => Err(failure(&format!(
+            "{failed} of {} accounts failed, last error: {error}",
+            accounts.len()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Time between two looks at the latest swaps for the alert rules they fire
+const SWAP_ALERTS_PERIOD: Duration = Duration::from_secs(60);
+
+/// Calls made to a webhook before a delivery is given up, and the delay before the first
+/// retry, doubled after each one
+const WEBHOOK_ATTEMPTS: i32 = 3;
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(2);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Deliveries made at once, the others wait for one of them to finish
+const WEBHOOK_CONCURRENCY: usize = 8;
+
+/// Pages of swaps a look goes back through at most to reach the last swap evaluated
+const SWAP_ALERTS_MAX_PAGES: u64 = 20;
+
+const SWAP_ALERTS_TASK: &str = "swap_alerts";
+
+/// Spawns the task posting every new PancakeSwap swap to the webhooks of the alert rules it
+/// fires. The version of the last swap evaluated is kept in `task_cursor`, so a look or a
+/// restart carries on from there. The deliveries run alongside the next looks, so a slow
+/// webhook only holds up its own.
+fn spawn_swap_alerts(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let webhooks = Webhooks::new(&state.config);
+        let mut deliveries = JoinSet::new();
+        let mut interval = tokio::time::interval(SWAP_ALERTS_PERIOD);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            while deliveries.try_join_next().is_some() {}
+            if let Err(failure) = evaluate_swap_alerts(&state, &webhooks, &mut deliveries).await {
+                warn!(error = %failure.error, "Swap alerts failed");
+                notify(&state, &failure).await;
+            }
+        }
+        while deliveries.join_next().await.is_some() {}
+    })
+}
+
+/// Posts alerts to webhooks, at most [`WEBHOOK_CONCURRENCY`] at a time. Webhooks on
+/// loopback, private or link-local addresses are refused unless the config allows them, both
+/// when the URL is checked before posting and when the client connects.
+#[derive(Clone)]
+struct Webhooks {
+    client: Client,
+    allow_private: bool,
+    permits: Arc<Semaphore>,
+}
+
+impl Webhooks {
+    fn new(config: &Config) -> Self {
+        let client = if config.allow_private_webhooks {
+            Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .expect("Webhook client must build")
+        } else {
+            webhook::public_client(WEBHOOK_TIMEOUT)
+        };
+        Webhooks {
+            client,
+            allow_private: config.allow_private_webhooks,
+            permits: Arc::new(Semaphore::new(WEBHOOK_CONCURRENCY)),
+        }
+    }
+
+    /// Posts `payload` to `webhook_url`, retrying when the webhook can't be reached or answers
+    /// with a server error. The attempts and the outcome of the last one are set on
+    /// `delivery`, its error only tells what went wrong in general terms since the owner of
+    /// the rule sees it.
+    async fn post(
+        &self,
+        webhook_url: &str,
+        payload: &impl Serialize,
+        delivery: &mut AlertDelivery,
+    ) {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("The webhook semaphore is never closed");
+        if !self.allow_private {
+            if let Err(error) = webhook::check_public_url(webhook_url).await {
+                warn!(webhook_url, %error, "Refused to call a webhook");
+                delivery.error = Some("The webhook is not on a public address".to_string());
+                return;
+            }
+        }
+
+        let mut delay = WEBHOOK_RETRY_DELAY;
+        while delivery.attempts < WEBHOOK_ATTEMPTS {
+            if delivery.attempts > 0 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            delivery.attempts += 1;
+
+            match self.client.post(webhook_url).json(payload).send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    delivery.status_code = Some(i32::from(status.as_u16()));
+                    if status.is_success() {
+                        delivery.delivered = true;
+                        delivery.error = None;
+                        break;
+                    }
+                    delivery.error = Some(format!("The webhook answered {}", status.as_u16()));
+                    // The same payload would be refused again
+                    if !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                        break;
+                    }
+                }
+                Err(error) => {
+                    warn!(webhook_url, %error, "Could not call a webhook");
+                    delivery.status_code = None;
+                    delivery.error = Some(delivery_error(&error).to_string());
+                }
+            }
+        }
+    }
+}
+
+/// What went wrong calling a webhook, without the details of the error that tell about the
+/// network of the server
+fn delivery_error(error: &reqwest::Error) -> &'static str {
+    if error.is_timeout() {
+        "The webhook did not answer in time"
+    } else if error.is_connect() {
+        "The webhook could not be reached"
+    } else {
+        "The webhook call failed"
+    }
+}
+
+/// Spawns the deliveries of the swaps newer than the cursor of the task to the rules they
+/// fire, then moves the cursor to the newest swap. Without a cursor yet, the first look only
+/// sets it.
+async fn evaluate_swap_alerts(
+    state: &Arc<AppState>,
+    webhooks: &Webhooks,
+    deliveries: &mut JoinSet<()>,
+) -> Result<(), TaskFailure> {
+    let failure = |error: &str| TaskFailure::new(SWAP_ALERTS_TASK, error, None);
+    let rules = state
+        .db
+        .get_alert_rules_with_token()
+        .await
+        .map_err(|error| failure(&error.to_string()))?;
+    // Spares the indexer while nobody wants alerts
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let cursor = state
+        .db
+        .get_task_cursor(SWAP_ALERTS_TASK)
+        .await
+        .map_err(|error| failure(&error.to_string()))?;
+    let (swaps, complete) = state
+        .external
+        .get_swap_transactions_after(
+            cursor.unwrap_or(-1),
+            cursor.map_or(1, |_| SWAP_ALERTS_MAX_PAGES),
+        )
+        .await
+        .map_err(|error| failure(&error.to_string()))?;
+    let Some(newest) = swaps.first().map(|swap| swap.version) else {
+        return Ok(());
+    };
+    let Some(after_version) = cursor else {
+        return store_swap_alerts_cursor(state, newest).await;
+    };
+    if !complete {
+        warn!(
+            after_version,
+            oldest_version = swaps.last().map(|swap| swap.version),
+            "More swaps were made since the last look than fetched, those in between aren't alerted on"
+        );
+    }
+
+    let mut prices = HashMap::new();
+    for swap in &swaps {
+        let price = match prices.get(&swap.token_sold) {
+            Some(price) => *price,
+            None => {
+                let price = state.external.get_usd_price(&swap.token_sold).await;
+                prices.insert(swap.token_sold.clone(), price);
+                price
+            }
+        };
+        let Some(price) = price else {
+            warn!(version = swap.version, token = %swap.token_sold, "Could not price a swap");
+            continue;
+        };
+
+        let value_usd = swap.token_sold_amount * price;
+        for (rule, token) in &rules {
+            if !rule.is_fired_by(token, swap, value_usd) {
+                continue;
+            }
+            let (state, webhooks) = (state.clone(), webhooks.clone());
+            let (rule, swap) = (rule.clone(), swap.clone());
+            deliveries.spawn(async move {
+                let delivery = deliver(&webhooks, &rule, &swap, value_usd).await;
+                if !delivery.delivered {
+                    warn!(
+                        rule = rule.id,
+                        version = swap.version,
+                        error = ?delivery.error,
+                        "Could not deliver a swap alert"
+                    );
+                }
+                if let Err(error) = state.db.create_alert_delivery(&delivery).await {
+                    warn!(rule = rule.id, %error, "Could not record a swap alert delivery");
+                }
+            });
+        }
+    }
+    store_swap_alerts_cursor(state, newest).await
+}
+
+async fn store_swap_alerts_cursor(state: &AppState, version: i64) -> Result<(), TaskFailure> {
+    state
+        .db
+        .set_task_cursor(SWAP_ALERTS_TASK, version)
+        .await
+        .map_err(|error| TaskFailure::new(SWAP_ALERTS_TASK, &error.to_string(), None))
+}
+
+/// Posts `swap` to the webhook of `rule`, retrying when the webhook can't be reached or
+/// answers with a server error
+async fn deliver(
+    webhooks: &Webhooks,
+    rule: &AlertRule,
+    swap: &SwapTransaction,
+    value_usd: f64,
+) -> AlertDelivery {
+    let payload = SwapAlertPayload {
+        rule_id: rule.id,
+        project_id: rule.project_id,
+        min_usd: rule.min_usd,
+        value_usd,
+        swap: SwapTransactionResponse::from(swap.clone()),
+    };
+    let mut delivery = AlertDelivery {
+        rule_id: rule.id,
+        transaction_version: swap.version,
+        value_usd,
+        ..Default::default()
+    };
+    webhooks
+        .post(&rule.webhook_url, &payload, &mut delivery)
+        .await;
+    delivery
+}
+
+const VOLUME_ANOMALIES_TASK: &str = "volume_anomalies";
+
+/// Spawns the task checking every new trading volume of a project for a spike, recording the
+/// spikes found and posting them to the webhooks of the alert rules of the project
+fn spawn_volume_anomalies(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let webhooks = Webhooks::new(&state.config);
+        let mut changes = state.db.subscribe_attribute_changes();
+        loop {
+            let change = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                change = changes.recv() => change,
+            };
+            let change = match change {
+                Ok(change) => change,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(missed, "Volume anomaly checks lagged behind");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let (AnomalyDetector::VOLUME_METRIC, Some(volume)) =
+                (change.key.as_str(), change.new_value)
+            else {
+                continue;
+            };
+            let Some(alert) =
+                AnomalyDetector::check_volume_spike(change.project_id, volume, &state.db).await
+            else {
+                continue;
+            };
+            if let Err(failure) = report_anomaly(&state, &webhooks, alert).await {
+                warn!(error = %failure.error, "Could not report a volume anomaly");
+                notify(&state, &failure).await;
+            }
+        }
+    })
+}
+
+/// Records `alert` and posts it to the webhooks of the anomaly alert rules of its project it
+/// fires
+async fn report_anomaly(
+    state: &AppState,
+    webhooks: &Webhooks,
+    alert: AnomalyAlert,
+) -> Result<(), TaskFailure> {
+    let failure = |error: &str| TaskFailure::new(VOLUME_ANOMALIES_TASK, error, None);
+    let alert = state
+        .db
+        .create_anomaly_event(&alert)
+        .await
+        .map_err(|error| failure(&error.to_string()))?;
+    info!(
+        project = alert.project_id,
+        value = alert.value,
+        mean = alert.mean,
+        std_dev = alert.std_dev,
+        "Trading volume anomaly detected"
+    );
+
+    let rules = state
+        .db
+        .list_alert_rules_of_kind(alert.project_id, AlertRule::ANOMALY_KIND)
+        .await
+        .map_err(|error| failure(&error.to_string()))?;
+    for rule in rules.iter().filter(|rule| rule.is_fired_by_anomaly(&alert)) {
+        let payload = AnomalyAlertPayload {
+            rule_id: rule.id,
+            project_id: rule.project_id,
+            anomaly: AnomalyResponse::from(alert.clone()),
+        };
+        let mut delivery = AlertDelivery {
+            rule_id: rule.id,
+            ..Default::default()
+        };
+        webhooks
+            .post(&rule.webhook_url, &payload, &mut delivery)
+            .await;
+        if !delivery.delivered {
+            warn!(
+                rule = rule.id,
+                anomaly = alert.id,
+                error = ?delivery.error,
+                "Could not deliver an anomaly alert"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Time between two refreshes of the CoinGecko market data of the projects. Calls are spaced
+/// out by the rate limiter of the client, so many projects make a refresh last longer.
+const COINGECKO_PERIOD: Duration = Duration::from_secs(15 * 60);
+
+/// Author of the attribute changes written by the periodic tasks
+const SCHEDULER_AUTHOR: &str = "scheduler";
+
+const COINGECKO_MARKET_DATA_TASK: &str = "coingecko_market_data";
+
+/// Spawns the task storing the CoinGecko price, market cap and 24h volume of every project
+/// with a `coingecko_id` once per [`COINGECKO_PERIOD`]
+fn spawn_coingecko_market_data(
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        COINGECKO_MARKET_DATA_TASK,
+        COINGECKO_PERIOD,
+        |state| async move { refresh_coingecko_market_data(&state, None).await },
+    )
+}
+
+async fn refresh_coingecko_market_data(
+    state: &AppState,
+    only: Option<i32>,
+) -> Result<(), TaskFailure> {
+    let projects = projects_to_run(
+        COINGECKO_MARKET_DATA_TASK,
+        state.db.list_coingecko_projects().await,
+        only,
+    )?;
+    run_per_project(state, COINGECKO_MARKET_DATA_TASK, &projects, |project| {
+        refresh_market_data(state, project)
+    })
+    .await
+}
+
+async fn refresh_market_data(state: &AppState, project: &Project) -> Result<(), String> {
+    let Some(coingecko_id) = project.coingecko_id.as_deref() else {
+        return Ok(());
+    };
+    let market_data = state
+        .external
+        .get_token_market_data(coingecko_id)
+        .await
+        .map_err(|error| error.to_string())?;
+    store_market_data(state, project.id, market_data)
+        .await
+        .map_err(|error| error.to_string())
+}
+
+async fn store_market_data(
+    state: &AppState,
+    project_id: i32,
+    market_data: CoinGeckoMarketData,
+) -> Result<(), sqlx::Error> {
+    update_project_with(state, project_id, |project| {
+        project.price_usd_cg = Some(market_data.price);
+        project.market_cap_cg = Some(market_data.market_cap);
+        project.volume_24h_cg = Some(market_data.volume_24h);
+        true
+    })
+    .await?;
+    Ok(())
+}
+
+/// Time between two snapshots of the USD price of the project tokens, which make the daily
+/// points of their price history
+const PRICE_SNAPSHOT_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+const PRICE_SNAPSHOTS_TASK: &str = "price_snapshots";
+
+/// Spawns the task storing the USD price of the token of every project in its `price_usd`
+/// once per [`PRICE_SNAPSHOT_PERIOD`], on-chain when there is a stablecoin pool and from
+/// CoinGecko otherwise
+fn spawn_price_snapshots(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        PRICE_SNAPSHOTS_TASK,
+        PRICE_SNAPSHOT_PERIOD,
+        |state| async move { snapshot_prices(&state, None).await },
+    )
+}
+
+async fn snapshot_prices(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let projects = projects_to_run(
+        PRICE_SNAPSHOTS_TASK,
+        state.db.list_projects(i64::MAX, 0).await,
+        only,
+    )?;
+    run_per_project(state, PRICE_SNAPSHOTS_TASK, &projects, |project| {
+        snapshot_price(state, project)
+    })
+    .await
+}
+
+/// Stores the price of the token of `project`, skipped when no source can price it
+async fn snapshot_price(state: &AppState, project: &Project) -> Result<(), String> {
+    let price = state
+        .external
+        .get_usd_price_from(&project.token, PriceSource::Auto)
+        .await
+        .map_err(|error| error.to_string())?;
+    let Some(price) = price else {
+        return Ok(());
+    };
+    update_project_with(state, project.id, |project| {
+        project.price_usd = Some(price);
+        true
+    })
+    .await
+    .map(|_| ())
+    .map_err(|error| error.to_string())
+}
+
+/// Time between two refreshes of the treasury flows of the projects
+const TREASURY_FLOW_PERIOD: Duration = Duration::from_secs(6 * 60 * 60);
+
+const TREASURY_FLOW_TASK: &str = "treasury_flow";
+
+/// Spawns the task storing the USD inflow, outflow and net flow of the last
+/// [`NetFlow::TREASURY_DAYS`] days of every project with treasury accounts once per
+/// [`TREASURY_FLOW_PERIOD`], summed over its treasury accounts
+fn spawn_treasury_flows(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        TREASURY_FLOW_TASK,
+        TREASURY_FLOW_PERIOD,
+        |state| async move { refresh_treasury_flows(&state, None).await },
+    )
+}
+
+async fn refresh_treasury_flows(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let projects = projects_to_run(
+        TREASURY_FLOW_TASK,
+        state.db.list_treasury_projects().await,
+        only,
+    )?;
+    let since = Utc::now() - chrono::Duration::days(NetFlow::TREASURY_DAYS);
+    run_per_project(state, TREASURY_FLOW_TASK, &projects, |project| async move {
+        let flow = treasury_flow(state, project.id, since).await?;
+        store_treasury_flow(state, project.id, flow)
+            .await
+            .map_err(|error| error.to_string())
+    })
+    .await
+}
+
+/// Flow of the treasury accounts of a project taken together, without the transfers
+/// between them
+async fn treasury_flow(
+    state: &AppState,
+    project_id: i32,
+    since: DateTime<Utc>,
+) -> Result<NetFlow, String> {
+    let accounts = state
+        .db
+        .get_treasury_accounts(project_id)
+        .await
+        .map_err(|error| error.to_string())?;
+    let addresses: Vec<String> = accounts
+        .into_iter()
+        .map(|account| account.address)
+        .collect();
+    let flow = state
+        .external
+        .calculate_net_flows(&addresses, since)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(flow.total)
+}
+
+async fn store_treasury_flow(
+    state: &AppState,
+    project_id: i32,
+    flow: NetFlow,
+) -> Result<(), sqlx::Error> {
+    update_project_with(state, project_id, |project| {
+        project.treasury_inflow_7d = Some(flow.inflow_usd);
+        project.treasury_outflow_7d = Some(flow.outflow_usd);
+        project.treasury_net_flow_7d = Some(flow.net_usd());
+        true
+    })
+    .await?;
+    Ok(())
+}
+
+/// Time between two refreshes of the all time volumes, the first of a contract pages through
+/// its whole history
+const ALL_TIME_VOLUME_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+const ALL_TIME_VOLUME_TASK: &str = "all_time_volume";
+
+/// Spawns the task adding the volume of the transactions made since the last refresh to the
+/// all time volume of every project with a contract address and a volume entry function, once
+/// per [`ALL_TIME_VOLUME_PERIOD`]
+fn spawn_all_time_volumes(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        ALL_TIME_VOLUME_TASK,
+        ALL_TIME_VOLUME_PERIOD,
+        |state| async move { refresh_all_time_volumes(&state, None).await },
+    )
+}
+
+/// Spawns a one-shot task storing the all time volume of a newly created `project`, so it
+/// doesn't wait up to a week for the first refresh. Tracked in [`AppState::one_shot_tasks`],
+/// it stops after the batch in progress on shutdown and the next refresh carries on.
+pub fn spawn_all_time_volume(state: Arc<AppState>, project: Project) -> JoinHandle<()> {
+    let tasks = state.one_shot_tasks.clone();
+    tasks.spawn(async move {
+        if let Err(error) = all_time_volume(&state, &project).await {
+            warn!(project = project.id, %error, "Could not compute the all time volume of a project");
+            let failure = TaskFailure::new(ALL_TIME_VOLUME_TASK, &error, None);
+            notify(&state, &failure).await;
+        }
+    })
+}
+
+async fn refresh_all_time_volumes(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let projects = projects_to_run(
+        ALL_TIME_VOLUME_TASK,
+        state.db.list_volume_projects().await,
+        only,
+    )?;
+    run_per_project(state, ALL_TIME_VOLUME_TASK, &projects, |project| {
+        all_time_volume(state, project)
+    })
+    .await
+}
+
+/// Sums the volume of the transactions after the `volume_processed_version` of `project` in
+/// batches of [`VOLUME_PROGRESS_TRANSACTIONS`], storing each, so an interrupted refresh resumes
+/// from the last batch stored. Skipped when it has no contract address or no volume entry
+/// function.
+async fn all_time_volume(state: &AppState, project: &Project) -> Result<(), String> {
+    let (Some(address), Some(entry_function_id)) = (
+        project.contract_address.as_deref(),
+        project.volume_entry_function.as_deref(),
+    ) else {
+        return Ok(());
+    };
+
+    let mut after_version = project.volume_processed_version;
+    let mut transactions = 0;
+    while !state.shutdown.is_cancelled() {
+        let batch = state
+            .external
+            .get_volume_after_version(
+                address,
+                entry_function_id,
+                after_version,
+                VOLUME_PROGRESS_TRANSACTIONS,
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        if batch.transactions == 0 {
+            break;
+        }
+        let stored = store_volume_batch(state, project, after_version, &batch)
+            .await
+            .map_err(|error| error.to_string())?;
+        if !stored {
+            break;
+        }
+        transactions += batch.transactions;
+        after_version = batch.last_processed_version;
+        info!(
+            project = project.id,
+            transactions, "Summed the volume of transactions"
+        );
+    }
+    Ok(())
+}
+
+/// Adds the volume of `batch` to the project, unless its volume was reset or summed elsewhere
+/// since `after_version` was read. Returns whether it was added.
+async fn store_volume_batch(
+    state: &AppState,
+    summed: &Project,
+    after_version: Option<i64>,
+    batch: &VolumeBatch,
+) -> Result<bool, sqlx::Error> {
+    update_project_with(state, summed.id, |project| {
+        if project.volume_processed_version != after_version
+            || project.contract_address != summed.contract_address
+            || project.volume_entry_function != summed.volume_entry_function
+        {
+            return false;
+        }
+        project.all_time_volume_usd =
+            Some(project.all_time_volume_usd.unwrap_or_default() + batch.volume_usd);
+        project.volume_processed_version = batch.last_processed_version;
+        true
+    })
+    .await
+}
+
+/// Time between two looks for the projects without a swap summary of the previous day, which
+/// is written within this long after midnight UTC
+const SWAP_SUMMARY_CHECK_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+const DAILY_SWAP_SUMMARY_TASK: &str = "daily_swap_summary";
+
+/// Spawns the task summing the swaps of the UTC days up to the previous one of every project
+/// with a contract address and a volume entry function, checking once per
+/// [`SWAP_SUMMARY_CHECK_PERIOD`]. Projects that fail are retried on the next check, from the
+/// day that failed.
+fn spawn_daily_swap_summaries(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        DAILY_SWAP_SUMMARY_TASK,
+        SWAP_SUMMARY_CHECK_PERIOD,
+        |state| async move { summarize_daily_swaps(&state, None).await },
+    )
+}
+
+/// Most days summarized for a project in one run, counting back from the previous day. Older
+/// days missing a summary stay missing.
+const MAX_SWAP_SUMMARY_CATCH_UP_DAYS: i64 = 7;
+
+/// Summarizes the swaps of the days since the last summary up to the previous day, for the
+/// projects without a summary of the previous day or for the project `only`, whose summary of
+/// the previous day is written again
+async fn summarize_daily_swaps(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let yesterday = Utc::now().date_naive() - chrono::Duration::days(1);
+    let listed = match only {
+        Some(_) => state.db.list_volume_projects().await,
+        None => {
+            state
+                .db
+                .list_volume_projects_without_summary(yesterday)
+                .await
+        }
+    };
+    let projects = projects_to_run(DAILY_SWAP_SUMMARY_TASK, listed, only)?;
+    run_per_project(state, DAILY_SWAP_SUMMARY_TASK, &projects, |project| {
+        daily_swap_summaries(state, project, yesterday)
+    })
+    .await
+}
+
+/// Fetches and stores the swap summaries `project` is missing up to `yesterday`, oldest first
+/// so a failure leaves no gap behind the last stored summary
+async fn daily_swap_summaries(
+    state: &AppState,
+    project: &Project,
+    yesterday: NaiveDate,
+) -> Result<(), String> {
+    let latest = state
+        .db
+        .get_latest_swap_summary_date(project.id)
+        .await
+        .map_err(|error| error.to_string())?;
+    for date in days_to_summarize(latest, yesterday) {
+        daily_swap_summary(state, project, date).await?;
+    }
+    Ok(())
+}
+
+/// Days after the `latest` summary up to `yesterday`, only `yesterday` when it is the latest,
+/// at most [`MAX_SWAP_SUMMARY_CATCH_UP_DAYS`] of them
+fn days_to_summarize(latest: Option<NaiveDate>, yesterday: NaiveDate) -> Vec<NaiveDate> {
+    let oldest = yesterday - chrono::Duration::days(MAX_SWAP_SUMMARY_CATCH_UP_DAYS - 1);
+    let first = match latest {
+        Some(latest) if latest >= yesterday => yesterday,
+        Some(latest) => (latest + chrono::Duration::days(1)).max(oldest),
+        None => oldest,
+    };
+    first
+        .iter_days()
+        .take_while(|date| *date <= yesterday)
+        .collect()
+}
+
+/// Fetches and stores the swap summary of `project` on `date`, skipped when it has no
+/// contract address or no volume entry function
+async fn daily_swap_summary(
+    state: &AppState,
+    project: &Project,
+    date: NaiveDate,
+) -> Result<(), String> {
+    let (Some(address), Some(entry_function_id)) = (
+        project.contract_address.as_deref(),
+        project.volume_entry_function.as_deref(),
+    ) else {
+        return Ok(());
+    };
+    let summary = state
+        .external
+        .get_swap_totals_of_day(address, entry_function_id, date)
+        .await
+        .map_err(|error| error.to_string())?
+        .summary(project.id, date);
+    state
+        .db
+        .upsert_swap_daily_summary(&summary)
+        .await
+        .map_err(|error| error.to_string())
+}
+
+/// Time between two refreshes of the all time fees
+const ALL_TIME_FEES_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+const ALL_TIME_FEES_TASK: &str = "all_time_fees";
+
+/// Spawns the task adding the fees of the swaps made since the last refresh to the all time
+/// fees of every project with a contract address and a swap fee, once per
+/// [`ALL_TIME_FEES_PERIOD`]
+fn spawn_all_time_fees(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        ALL_TIME_FEES_TASK,
+        ALL_TIME_FEES_PERIOD,
+        |state| async move { refresh_all_time_fees(&state, None).await },
+    )
+}
+
+async fn refresh_all_time_fees(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let projects = projects_to_run(ALL_TIME_FEES_TASK, state.db.list_fee_projects().await, only)?;
+    run_per_project(state, ALL_TIME_FEES_TASK, &projects, |project| {
+        all_time_fees(state, project)
+    })
+    .await
+}
+
+/// Sums the fees of the swap events after the `last_processed_version` of `project` in
+/// batches of [`FEE_PROGRESS_EVENTS`], storing each, so an interrupted refresh resumes from
+/// the last batch stored
+async fn all_time_fees(state: &AppState, project: &Project) -> Result<(), String> {
+    let (Some(address), Some(swap_fee_bps)) =
+        (project.contract_address.as_deref(), project.swap_fee_bps)
+    else {
+        return Ok(());
+    };
+    let fee_numerator = u64::try_from(swap_fee_bps).map_err(|error| error.to_string())?;
+
+    let mut after_version = project.last_processed_version;
+    let mut events = 0;
+    while !state.shutdown.is_cancelled() {
+        let batch = state
+            .external
+            .get_fees_after_version(
+                address,
+                fee_numerator,
+                Project::BASIS_POINTS as u64,
+                after_version,
+                FEE_PROGRESS_EVENTS,
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        if batch.events == 0 {
+            break;
+        }
+        let stored = store_fee_batch(state, project, after_version, &batch)
+            .await
+            .map_err(|error| error.to_string())?;
+        if !stored {
+            break;
+        }
+        events += batch.events;
+        after_version = batch.last_processed_version;
+        info!(
+            project = project.id,
+            events, "Summed the fees of swap events"
+        );
+    }
+    Ok(())
+}
+
+/// Adds the fees of `batch` to the project, unless its fees were reset or summed elsewhere
+/// since `after_version` was read. Returns whether they were added.
+async fn store_fee_batch(
+    state: &AppState,
+    summed: &Project,
+    after_version: Option<i64>,
+    batch: &FeeBatch,
+) -> Result<bool, sqlx::Error> {
+    update_project_with(state, summed.id, |project| {
+        if project.last_processed_version != after_version
+            || project.contract_address != summed.contract_address
+            || project.swap_fee_bps != summed.swap_fee_bps
+        {
+            return false;
+        }
+        project.all_time_fees_usd =
+            Some(project.all_time_fees_usd.unwrap_or_default() + batch.fees_usd);
+        project.last_processed_version = batch.last_processed_version;
+        true
+    })
+    .await
+}
+
+/// Time between two refreshes of the time-weighted liquidity of the projects
+const TWICK_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Window of the stored time-weighted liquidity
+const TWICK_WINDOW_HOURS: u64 = 7 * 24;
+
+const TWICK_TASK: &str = "twick";
+
+/// Spawns the task storing the time-weighted liquidity over the last [`TWICK_WINDOW_HOURS`] of
+/// every project with a contract address and a total value locked, once per [`TWICK_PERIOD`]
+fn spawn_twicks(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        TWICK_TASK,
+        TWICK_PERIOD,
+        |state| async move { refresh_twicks(&state, None).await },
+    )
+}
+
+async fn refresh_twicks(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let projects = projects_to_run(TWICK_TASK, state.db.list_tvl_projects().await, only)?;
+    run_per_project(state, TWICK_TASK, &projects, |project| {
+        refresh_twick(state, project)
+    })
+    .await
+}
+
+async fn refresh_twick(state: &AppState, project: &Project) -> Result<(), String> {
+    let twick = state
+        .external
+        .get_time_weighted_liquidity(project.id, TWICK_WINDOW_HOURS)
+        .await
+        .map_err(|error| error.to_string())?;
+    update_project_with(state, project.id, |project| {
+        project.twick_7d = Some(twick);
+        true
+    })
+    .await
+    .map(|_| ())
+    .map_err(|error| error.to_string())
+}
+
+/// Time between two scans of the swap events of the DEX projects for new pairs
+const PAIR_DISCOVERY_PERIOD: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Latest swap events of a project scanned for pairs, pairs trading less often than that are
+/// found once they do
+const PAIR_DISCOVERY_EVENTS: u64 = 1000;
+
+const PAIR_DISCOVERY_TASK: &str = "pair_discovery";
+
+/// Spawns the task recording the pairs traded in the latest [`PAIR_DISCOVERY_EVENTS`] swap
+/// events of every DEX project with a contract address, once per [`PAIR_DISCOVERY_PERIOD`]
+fn spawn_pair_discovery(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        PAIR_DISCOVERY_TASK,
+        PAIR_DISCOVERY_PERIOD,
+        |state| async move { discover_pairs(&state, None).await },
+    )
+}
+
+async fn discover_pairs(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let projects = projects_to_run(
+        PAIR_DISCOVERY_TASK,
+        state.db.list_dex_projects().await,
+        only,
+    )?;
+    run_per_project(state, PAIR_DISCOVERY_TASK, &projects, |project| {
+        discover_project_pairs(state, project)
+    })
+    .await
+}
+
+async fn discover_project_pairs(state: &AppState, project: &Project) -> Result<(), String> {
+    let Some(contract) = project.contract_address.as_deref() else {
+        return Ok(());
+    };
+    let pairs = state
+        .external
+        .discover_pairs_from_events(contract, PAIR_DISCOVERY_EVENTS)
+        .await
+        .map_err(|error| error.to_string())?;
+    let pairs: Vec<(String, String)> = pairs.into_iter().collect();
+    let new_pairs = state
+        .db
+        .upsert_known_pairs(project.id, &pairs, Utc::now())
+        .await
+        .map_err(|error| error.to_string())?;
+    if new_pairs > 0 {
+        info!(
+            project = project.id,
+            new_pairs, "Discovered new pairs of a DEX project"
+        );
+    }
+    Ok(())
+}
+
+/// Time between two checks that the contracts of the DEX projects are still in use
+const CONTRACT_HEALTH_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+const CONTRACT_HEALTH_TASK: &str = "contract_health_check";
+
+/// Spawns the task deactivating the DEX projects whose contract has only empty pair
+/// reserves or is gone, and reactivating those whose contract holds some tokens again, once per [`CONTRACT_HEALTH_PERIOD`]
+fn spawn_contract_health_checks(
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        CONTRACT_HEALTH_TASK,
+        CONTRACT_HEALTH_PERIOD,
+        |state| async move { check_contract_health(&state, None).await },
+    )
+}
+
+/// Checks the contracts of the DEX projects, a project whose check fails isn't deactivated
+async fn check_contract_health(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let projects = projects_to_run(
+        CONTRACT_HEALTH_TASK,
+        state.db.list_dex_projects().await,
+        only,
+    )?;
+    run_per_project(state, CONTRACT_HEALTH_TASK, &projects, |project| {
+        check_contract(state, project)
+    })
+    .await
+}
+
+async fn check_contract(state: &AppState, project: &Project) -> Result<(), String> {
+    let Some(contract) = project.contract_address.as_deref() else {
+        return Ok(());
+    };
+    // Nothing tells the contract isn't in use, and only this task deactivates projects
+    let active = state
+        .external
+        .is_contract_active(contract)
+        .await
+        .map_err(|error| error.to_string())?
+        .unwrap_or(true);
+    let changed = state
+        .db
+        .set_project_active(project.id, active)
+        .await
+        .map_err(|error| error.to_string())?;
+    if changed {
+        info!(
+            project = project.id,
+            active, "Contract activity of a project changed"
+        );
+    }
+    Ok(())
+}
+
+/// Time between two snapshots of the holders of the project tokens
+const HOLDER_SNAPSHOT_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+const HOLDER_SNAPSHOTS_TASK: &str = "holder_snapshots";
+
+/// Spawns the task storing a snapshot of the holders of the token of every project once per
+/// [`HOLDER_SNAPSHOT_PERIOD`], which `/project/{id}/holders/diff` compares. Only coins are
+/// snapshotted, fungible assets aren't in the coin balances the holders are read from.
+fn spawn_holder_snapshots(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    spawn_periodic(
+        state,
+        shutdown,
+        HOLDER_SNAPSHOTS_TASK,
+        HOLDER_SNAPSHOT_PERIOD,
+        |state| async move { snapshot_holders(&state, None).await },
+    )
+}
+
+async fn snapshot_holders(state: &AppState, only: Option<i32>) -> Result<(), TaskFailure> {
+    let listed = state.db.list_projects(i64::MAX, 0).await.map(|projects| {
+        projects
+            .into_iter()
+            .filter(|project| project.token.contains("::"))
+            .collect()
+    });
+    let projects = projects_to_run(HOLDER_SNAPSHOTS_TASK, listed, only)?;
+    run_per_project(
+        state,
+        HOLDER_SNAPSHOTS_TASK,
+        &projects,
+        |project| async move {
+            state
+                .external
+                .snapshot_token_holders(&project.token)
+                .await
+                .map_err(|error| error.to_string())
+        },
+    )
+    .await
+}
+
+/// Days of total value locked a backfill may compute, each costs about 30 fullnode calls and
+/// the pricing of every token of the pools
+pub const MAX_BACKFILL_DAYS: i64 = 365;
+
+/// Author of the attribute changes written by a backfill
+const BACKFILL_AUTHOR: &str = "backfill";
+
+pub const TVL_BACKFILL_TASK: &str = "tvl_backfill";
+
+/// Spawns a one-shot task writing the total value locked of `project` at midnight UTC of each
+/// of the last `days` days into its attribute history. The caller must have added the project
+/// to [`AppState::tvl_backfills`], it's removed once the task ends.
+pub fn spawn_tvl_backfill(state: Arc<AppState>, project: Project, days: i64) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        match backfill_total_value_locked(&state, &project, days).await {
+            Ok(written) => info!(
+                project = project.id,
+                days, written, "Backfilled the total value locked of a project"
+            ),
+            Err(failure) => {
+                warn!(project = project.id, error = %failure.error, "Total value locked backfill failed");
+                notify(&state, &failure).await;
+            }
+        }
+        state
+            .tvl_backfills
+            .lock()
+            .expect("Backfills lock must not be poisoned")
+            .remove(&project.id);
+    })
+}
+
+/// Computes the daily values oldest first, so each change starts from the value of the day
+/// before. Days from the first recorded change on are left to the actual history. Returns the
+/// number of days written.
+pub async fn backfill_total_value_locked(
+    state: &AppState,
+    project: &Project,
+    days: i64,
+) -> Result<usize, TaskFailure> {
+    const KEY: &str = "total_value_locked";
+    let failure = |error: &str| TaskFailure::new(TVL_BACKFILL_TASK, error, None);
+    let address = project
+        .contract_address
+        .as_deref()
+        .ok_or_else(|| failure("Project has no contract address"))?;
+    let first_change = state
+        .db
+        .get_first_attribute_change_at(project.id, KEY)
+        .await
+        .map_err(|error| failure(&error.to_string()))?;
+
+    let today = Utc::now().date_naive().and_time(NaiveTime::MIN).and_utc();
+    let mut changes: Vec<ProjectAttributeChange> = Vec::new();
+    let mut failed = 0;
+    let mut last_error = None;
+    for day in (1..=days).rev() {
+        let date = today - chrono::Duration::days(day);
+        if state.shutdown.is_cancelled() || first_change.is_some_and(|first| date >= first) {
+            break;
+        }
+        let value = match state.external.get_ledger_version_at(date).await {
+            Ok(Some(version)) => state
+                .external
+                .get_total_value_locked_at(address, version)
+                .await
+                .map_err(|error| error.to_string()),
+            // Pruned by the fullnode, later days may still be there
+            Ok(None) => continue,
+            Err(error) => Err(error.to_string()),
+        };
+        match value {
+            Ok(value) => changes.push(ProjectAttributeChange {
+                project_id: project.id,
+                key: KEY.to_string(),
+                old_value: changes.last().and_then(|change| change.new_value),
+                new_value: Some(value),
+                changed_by: BACKFILL_AUTHOR.to_string(),
+                changed_at: date,
+                ..Default::default()
+            }),
+            Err(error) => {
+                warn!(project = project.id, %date, %error, "Could not compute a past total value locked");
+                failed += 1;
+                last_error = Some(error);
+            }
+        }
+    }
+
+    state
+        .db
+        .create_attribute_changes(&changes)
+        .await
+        .map_err(|error| failure(&error.to_string()))?;
+    match last_error {
+        Some(error) => Err(failure(&format!(
+            "{failed} days of project {} failed, {} written, last error: {error}",
+            project.id,
+            changes.len()
+        ))),
+        None => Ok(changes.len()),
+    }
+}
+
+/// Tasks [`run_task`] can run once, the others run on events
+pub const RUNNABLE_TASKS: [&str; 11] = [
+    BALANCE_SNAPSHOTS_TASK,
+    COINGECKO_MARKET_DATA_TASK,
+    PRICE_SNAPSHOTS_TASK,
+    TREASURY_FLOW_TASK,
+    ALL_TIME_VOLUME_TASK,
+    DAILY_SWAP_SUMMARY_TASK,
+    ALL_TIME_FEES_TASK,
+    TWICK_TASK,
+    PAIR_DISCOVERY_TASK,
+    CONTRACT_HEALTH_TASK,
+    HOLDER_SNAPSHOTS_TASK,
+];
+
+/// Runs one pass of the background task named `task`, over the project `project_id` alone
+/// when set, for cron jobs and debugging. Failures aren't notified, the caller reports them.
+pub async fn run_task(
+    state: &AppState,
+    task: &str,
+    project_id: Option<i32>,
+) -> Result<(), TaskFailure> {
+    match task {
+        BALANCE_SNAPSHOTS_TASK if project_id.is_none() => snapshot_watched_accounts(state).await,
+        BALANCE_SNAPSHOTS_TASK => Err(TaskFailure::new(
+            task,
+            "Snapshots accounts, it can't run on a project",
+            project_id,
+        )),
+        COINGECKO_MARKET_DATA_TASK => refresh_coingecko_market_data(state, project_id).await,
+        PRICE_SNAPSHOTS_TASK => snapshot_prices(state, project_id).await,
+        TREASURY_FLOW_TASK => refresh_treasury_flows(state, project_id).await,
+        ALL_TIME_VOLUME_TASK => refresh_all_time_volumes(state, project_id).await,
+        DAILY_SWAP_SUMMARY_TASK => summarize_daily_swaps(state, project_id).await,
+        ALL_TIME_FEES_TASK => refresh_all_time_fees(state, project_id).await,
+        TWICK_TASK => refresh_twicks(state, project_id).await,
+        PAIR_DISCOVERY_TASK => discover_pairs(state, project_id).await,
+        CONTRACT_HEALTH_TASK => check_contract_health(state, project_id).await,
+        HOLDER_SNAPSHOTS_TASK => snapshot_holders(state, project_id).await,
+        _ => Err(TaskFailure::new(
+            task,
+            &format!(
+                "Unknown task, expected one of {}",
+                RUNNABLE_TASKS.join(", ")
+            ),
+            project_id,
+        )),
+    }
+}
+
+/// Spawns the task `task` running `run` once per `period`, the first time right away, until
+/// `shutdown` is cancelled. The first of consecutive failed runs is notified, the others are
+/// only logged until a run succeeds again. The next run still comes on time.
+fn spawn_periodic<F, Fut>(
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+    task: &'static str,
+    period: Duration,
+    run: F,
+) -> JoinHandle<()>
+where
+    F: Fn(Arc<AppState>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), TaskFailure>> + Send,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut failing = false;
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            match run(state.clone()).await {
+                Err(failure) => {
+                    warn!(task, error = %failure.error, "Background task failed");
+                    if !failing {
+                        notify(&state, &failure).await;
+                    }
+                    failing = true;
+                }
+                Ok(()) if failing => {
+                    info!(task, "Background task succeeded again");
+                    failing = false;
+                }
+                Ok(()) => {}
+            }
+        }
+    })
+}
+
+/// Projects of `listed` a pass of `task` runs on, the project `only` alone when set. An error
+/// when the task doesn't run on it.
+fn projects_to_run(
+    task: &str,
+    listed: Result<Vec<Project>, sqlx::Error>,
+    only: Option<i32>,
+) -> Result<Vec<Project>, TaskFailure> {
+    let mut projects = listed.map_err(|error| TaskFailure::new(task, &error.to_string(), None))?;
+    if let Some(id) = only {
+        projects.retain(|project| project.id == id);
+        if projects.is_empty() {
+            return Err(TaskFailure::new(
+                task,
+                &format!("Project {id} is not one the task runs on"),
+                None,
+            ));
+        }
+    }
+    Ok(projects)
+}
+
+/// Runs `run` on `projects` one at a time until the app shuts down, a project failing doesn't
+/// keep the others from being run on. The failures are reported together, with the last error.
+async fn run_per_project<'a, F, Fut>(
+    state: &AppState,
+    task: &str,
+    projects: &'a [Project],
+    mut run: F,
+) -> Result<(), TaskFailure>
+where
+    F: FnMut(&'a Project) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let mut failed = 0;
+    let mut last_error = None;
+    for project in projects {
+        if state.shutdown.is_cancelled() {
+            break;
+        }
+        if let Err(error) = run(project).await {
+            warn!(task, project = project.id, %error, "Background task failed on a project");
+            failed += 1;
+            last_error = Some(error);
+        }
+    }
+
+    info!(
+        task,
+        projects = projects.len(),
+        failed,
+        "Ran a background task on projects"
+    );
+    match last_error {
+        Some(error) => Err(TaskFailure::new(
+            task,
+            &format!(
+                "{failed} of {} projects failed, last error: {error}",
+                projects.len()
+            ),
+            None,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Times a task tries to write a project edited by someone else in between, before leaving it
+const PROJECT_WRITE_ATTEMPTS: usize = 3;
+
+/// Applies `change` to the latest version of the project and writes it, unless the project
+/// was updated since it was read, then `change` is applied again to its new version. So the
+/// fields a task sets never overwrite an edit made meanwhile. `change` returns false to leave
+/// the project as it is. Returns whether the project was written, it isn't once deleted.
+async fn update_project_with(
+    state: &AppState,
+    project_id: i32,
+    change: impl Fn(&mut Project) -> bool,
+) -> Result<bool, sqlx::Error> {
+    for _ in 0..PROJECT_WRITE_ATTEMPTS {
+        let Some(mut project) = state.db.get_project_by_id(project_id).await? else {
+            return Ok(false);
+        };
+        if !change(&mut project) {
+            return Ok(false);
+        }
+        let read_at = project.updated_at;
+        let written = state
+            .db
+            .update_project_if_unchanged(&project, SCHEDULER_AUTHOR, Some(read_at))
+            .await?;
+        if written.is_some() {
+            return Ok(true);
+        }
+    }
+    warn!(
+        project = project_id,
+        "Project kept changing while a task wrote it"
+    );
+    Ok(false)
+}
+
+async fn notify(state: &AppState, failure: &TaskFailure) {
+    if let Some(slack) = SlackNotifier::from_config(&state.config) {
+        if let Err(error) = slack.notify_error(failure).await {
+            warn!(%error, "Could not notify Slack");
+        }
+    }
+    if let Some(telegram) = TelegramNotifier::from_config(&state.config) {
+        if let Err(error) = telegram.notify_error(failure).await {
+            warn!(%error, "Could not notify Telegram");
+        }
+    }
+}
+
+#[test]
+fn test_days_to_summarize() {
+    let day = |day| NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+    let yesterday = day(13);
+    assert_eq!(
+        days_to_summarize(Some(day(10)), yesterday),
+        [day(11), day(12), day(13)]
+    );
+    // The summary of yesterday is written again when asked for
+    assert_eq!(days_to_summarize(Some(yesterday), yesterday), [yesterday]);
+    // A project never summarized, or long behind, catches up on the last days only
+    let last_days: Vec<NaiveDate> = (7..=13).map(day).collect();
+    assert_eq!(days_to_summarize(None, yesterday), last_days);
+    assert_eq!(days_to_summarize(Some(day(1)), yesterday), last_days);
+}
+
+#[tokio::test]
+async fn test_deliver_swap_alert() {
+    use axum::{http::StatusCode, routing::post, Router};
+
+    let app = Router::new()
+        .route("/ok", post(|| async { StatusCode::NO_CONTENT }))
+        .route("/refused", post(|| async { StatusCode::BAD_REQUEST }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let webhooks = Webhooks {
+        client: Client::new(),
+        allow_private: true,
+        permits: Arc::new(Semaphore::new(WEBHOOK_CONCURRENCY)),
+    };
+    let swap = SwapTransaction {
+        version: 42,
+        ..Default::default()
+    };
+    let rule = |path: &str| AlertRule {
+        id: 1,
+        webhook_url: format!("http://{address}{path}"),
+        ..Default::default()
+    };
+
+    let delivery = deliver(&webhooks, &rule("/ok"), &swap, 60_000.0).await;
+    assert!(delivery.delivered);
+    assert_eq!(delivery.attempts, 1);
+    assert_eq!(delivery.status_code, Some(204));
+    assert_eq!(delivery.transaction_version, 42);
+
+    // A client error isn't retried
+    let delivery = deliver(&webhooks, &rule("/refused"), &swap, 60_000.0).await;
+    assert!(!delivery.delivered);
+    assert_eq!(delivery.attempts, 1);
+    assert_eq!(delivery.status_code, Some(400));
+    assert_eq!(delivery.error.as_deref(), Some("The webhook answered 400"));
+
+    // Webhooks on private addresses are refused without being called
+    let public_only = Webhooks {
+        allow_private: false,
+        ..webhooks
+    };
+    let delivery = deliver(&public_only, &rule("/ok"), &swap, 60_000.0).await;
+    assert!(!delivery.delivered);
+    assert_eq!(delivery.attempts, 0);
+    assert_eq!(
+        delivery.error.as_deref(),
+        Some("The webhook is not on a public address")
+    );
+}
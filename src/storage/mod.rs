@@ -0,0 +1,312 @@
+pub mod s3;
+
+use std::{io, io::Cursor, path::PathBuf};
+
+use chrono::Utc;
+use image::{ImageReader, Limits};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::Config;
+use s3::{S3Bucket, S3Config};
+
+/// Path the API serves the stored avatars under
+pub const AVATAR_ROUTE: &str = "/api/v1/avatars";
+
+/// Largest avatar accepted
+pub const MAX_AVATAR_BYTES: usize = 2 * 1024 * 1024;
+
+/// Largest avatar upload body, the avatar and the rest of its form data. Avatar uploads may
+/// exceed `MAX_REQUEST_BODY_BYTES` up to it.
+pub const MAX_AVATAR_REQUEST_BYTES: usize = MAX_AVATAR_BYTES + 64 * 1024;
+
+/// Fewest pixels across and down an avatar can have
+pub const MIN_AVATAR_SIDE: u32 = 100;
+
+/// Most memory decoding an avatar may take, enough for a 4096 by 4096 RGBA image. A small
+/// file claiming huge dimensions is refused rather than decoded.
+pub const MAX_AVATAR_DECODE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Image formats avatars can be uploaded in, told apart by their leading bytes rather than the
+/// content type the client claims
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    /// Format of a file stored under `file_name`, from its extension
+    pub fn of_file(file_name: &str) -> Option<Self> {
+        match file_name.rsplit_once('.')?.1 {
+            "jpg" => Some(ImageFormat::Jpeg),
+            "png" => Some(ImageFormat::Png),
+            "webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+
+    /// Width and height of the image in `bytes`, found by decoding it whole so a file with a
+    /// valid header but broken image data is refused too. `None` when it isn't an image in
+    /// this format or can't be decoded within `MAX_AVATAR_DECODE_BYTES`. Decoding takes a
+    /// while, the caller runs it off the async workers.
+    pub fn dimensions(self, bytes: &[u8]) -> Option<(u32, u32)> {
+        let mut reader = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .ok()?;
+        if reader.format()? != self.decoder_format() {
+            return None;
+        }
+        let mut limits = Limits::default();
+        limits.max_alloc = Some(MAX_AVATAR_DECODE_BYTES);
+        reader.limits(limits);
+        let image = reader.decode().ok()?;
+        Some((image.width(), image.height()))
+    }
+
+    fn decoder_format(self) -> image::ImageFormat {
+        match self {
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Webp => image::ImageFormat::WebP,
+        }
+    }
+}
+
+/// Where the uploaded avatars are kept, `STORAGE_BACKEND`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageBackend {
+    /// Directory of the server, `AVATAR_DIR`
+    Local { dir: String },
+    /// Bucket of an S3-compatible object store, `S3_*`
+    S3(S3Config),
+}
+
+/// Storage of the uploaded project avatars, a directory or a bucket as the
+/// [`StorageBackend`] says. They are served under `AVATAR_BASE_URL`, by the API itself
+/// unless a CDN serves the directory or the bucket.
+pub struct AvatarStore {
+    backend: Backend,
+    base_url: String,
+}
+
+enum Backend {
+    Local(PathBuf),
+    S3(S3Bucket),
+}
+
+impl AvatarStore {
+    /// Avatars written to `dir`
+    pub fn new(dir: impl Into<PathBuf>, base_url: &str) -> Self {
+        Self::with_backend(Backend::Local(dir.into()), base_url)
+    }
+
+    /// Avatars written to a bucket
+    pub fn s3(bucket: S3Bucket, base_url: &str) -> Self {
+        Self::with_backend(Backend::S3(bucket), base_url)
+    }
+
+    fn with_backend(backend: Backend, base_url: &str) -> Self {
+        AvatarStore {
+            backend,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> io::Result<Self> {
+        Ok(match &config.storage_backend {
+            StorageBackend::Local { dir } => Self::new(dir, &config.avatar_base_url),
+            StorageBackend::S3(s3) => Self::s3(
+                S3Bucket::new(s3.clone(), config.upstream_timeout)?,
+                &config.avatar_base_url,
+            ),
+        })
+    }
+
+    /// Writes the avatar of a project and returns the URL it is served at. Each upload gets a
+    /// new file, so clients caching the previous avatar see the new one, named after the
+    /// project, the time and a random suffix so two uploads at once get their own.
+    pub async fn save(
+        &self,
+        project_id: i32,
+        format: ImageFormat,
+        bytes: &[u8],
+    ) -> io::Result<String> {
+        let mut suffix = [0u8; 4];
+        SystemRandom::new()
+            .fill(&mut suffix)
+            .expect("System random generator must be available");
+        let file_name = format!(
+            "{project_id}-{}-{}.{}",
+            Utc::now().timestamp_millis(),
+            u32::from_be_bytes(suffix),
+            format.extension()
+        );
+        match &self.backend {
+            Backend::Local(dir) => {
+                tokio::fs::create_dir_all(dir).await?;
+                tokio::fs::write(dir.join(&file_name), bytes).await?;
+            }
+            Backend::S3(bucket) => bucket.put(&file_name, bytes, format.content_type()).await?,
+        }
+        Ok(format!("{}/{file_name}", self.base_url))
+    }
+
+    /// Stored avatar `file_name`, `None` when there is none by that name
+    pub async fn read(&self, file_name: &str) -> io::Result<Option<Vec<u8>>> {
+        if !is_file_name(file_name) {
+            return Ok(None);
+        }
+        let dir = match &self.backend {
+            Backend::Local(dir) => dir,
+            Backend::S3(bucket) => return bucket.get(file_name).await,
+        };
+        match tokio::fs::read(dir.join(file_name)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes the file behind `avatar_url` when it was stored here for the project
+    /// `project_id`, leaving the avatars hosted elsewhere or uploaded for another project alone
+    pub async fn delete(&self, project_id: i32, avatar_url: &str) -> io::Result<()> {
+        let Some(file_name) = self
+            .file_name(avatar_url)
+            .filter(|file_name| file_name.starts_with(&format!("{project_id}-")))
+        else {
+            return Ok(());
+        };
+        let dir = match &self.backend {
+            Backend::Local(dir) => dir,
+            Backend::S3(bucket) => return bucket.delete(file_name).await,
+        };
+        match tokio::fs::remove_file(dir.join(file_name)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `avatar_url` is where a stored avatar is served, only uploads give such URLs
+    pub fn is_stored(&self, avatar_url: &str) -> bool {
+        self.file_name(avatar_url).is_some()
+    }
+
+    /// Name of the stored file `avatar_url` is served from
+    fn file_name<'a>(&self, avatar_url: &'a str) -> Option<&'a str> {
+        let file_name = avatar_url
+            .strip_prefix(self.base_url.as_str())?
+            .strip_prefix('/')?;
+        is_file_name(file_name).then_some(file_name)
+    }
+}
+
+/// Whether `name` can only be a file of the avatar directory, not a path out of it
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[test]
+fn test_sniff_image_format() {
+    assert_eq!(
+        ImageFormat::sniff(&[0xff, 0xd8, 0xff, 0xe0]),
+        Some(ImageFormat::Jpeg)
+    );
+    assert_eq!(
+        ImageFormat::sniff(b"\x89PNG\r\n\x1a\n\0\0"),
+        Some(ImageFormat::Png)
+    );
+    assert_eq!(
+        ImageFormat::sniff(b"RIFF\x10\0\0\0WEBPVP8 "),
+        Some(ImageFormat::Webp)
+    );
+    assert_eq!(ImageFormat::sniff(b"GIF89a"), None);
+    assert_eq!(ImageFormat::sniff(b"RIFF\x10\0\0\0WAVE"), None);
+    assert_eq!(ImageFormat::of_file("4-17.webp"), Some(ImageFormat::Webp));
+    assert_eq!(ImageFormat::of_file("4-17.gif"), None);
+}
+
+#[test]
+fn test_image_dimensions() {
+    let encode = |format| {
+        let mut bytes = Cursor::new(Vec::new());
+        image::RgbImage::new(160, 120)
+            .write_to(&mut bytes, format)
+            .unwrap();
+        bytes.into_inner()
+    };
+
+    let png = encode(image::ImageFormat::Png);
+    assert_eq!(ImageFormat::Png.dimensions(&png), Some((160, 120)));
+    // A whole header, but the image data cut short
+    assert_eq!(ImageFormat::Png.dimensions(&png[..40]), None);
+    assert_eq!(ImageFormat::Jpeg.dimensions(&png), None);
+
+    let jpeg = encode(image::ImageFormat::Jpeg);
+    assert_eq!(ImageFormat::Jpeg.dimensions(&jpeg), Some((160, 120)));
+    assert_eq!(ImageFormat::Jpeg.dimensions(&jpeg[..jpeg.len() / 2]), None);
+
+    let webp = encode(image::ImageFormat::WebP);
+    assert_eq!(ImageFormat::Webp.dimensions(&webp), Some((160, 120)));
+    assert_eq!(ImageFormat::Webp.dimensions(&webp[..30]), None);
+}
+
+#[tokio::test]
+async fn test_avatar_store() {
+    let dir = std::env::temp_dir().join(format!("avatars_{}", std::process::id()));
+    let store = AvatarStore::new(&dir, "/api/v1/avatars/");
+
+    let url = store.save(4, ImageFormat::Png, b"png").await.unwrap();
+    let file_name = url.strip_prefix("/api/v1/avatars/").unwrap();
+    assert!(file_name.starts_with("4-") && file_name.ends_with(".png"));
+    assert_eq!(store.read(file_name).await.unwrap().unwrap(), b"png");
+    assert_eq!(store.read("../avatars/x.png").await.unwrap(), None);
+
+    let other = store.save(4, ImageFormat::Png, b"png").await.unwrap();
+    assert_ne!(other, url);
+    assert!(store.is_stored(&url));
+
+    // Avatars hosted elsewhere or of another project aren't touched
+    store
+        .delete(4, "https://cdn.example.com/4.png")
+        .await
+        .unwrap();
+    assert!(!store.is_stored("https://cdn.example.com/4.png"));
+    store.delete(5, &url).await.unwrap();
+    assert!(store.read(file_name).await.unwrap().is_some());
+    store.delete(4, &url).await.unwrap();
+    assert_eq!(store.read(file_name).await.unwrap(), None);
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,157 @@
+use std::{io, time::Duration};
+
+use object_store::{
+    aws::{AmazonS3, AmazonS3Builder},
+    path::Path,
+    Attribute, Attributes, BackoffConfig, ClientOptions, ObjectStore, PutOptions, PutPayload,
+    RetryConfig,
+};
+
+/// Bucket of an S3-compatible object store, addressed path-style as `{endpoint}/{bucket}/{key}`
+/// so stores without virtual-hosted buckets work too
+#[derive(Debug, Clone, PartialEq)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+/// Objects of an [`S3Config`] bucket, through the S3 client of `object_store`
+pub struct S3Bucket {
+    store: AmazonS3,
+}
+
+impl S3Bucket {
+    /// Client of the bucket of `config`, each request given up after `timeout` and retried
+    /// within it once when the store fails
+    pub fn new(config: S3Config, timeout: Duration) -> io::Result<Self> {
+        let client = ClientOptions::new()
+            .with_allow_http(config.endpoint.starts_with("http://"))
+            .with_timeout(timeout);
+        let store = AmazonS3Builder::new()
+            .with_endpoint(&config.endpoint)
+            .with_virtual_hosted_style_request(false)
+            .with_bucket_name(config.bucket)
+            .with_region(config.region)
+            .with_access_key_id(config.access_key_id)
+            .with_secret_access_key(config.secret_access_key)
+            .with_client_options(client)
+            .with_retry(RetryConfig {
+                backoff: BackoffConfig::default(),
+                max_retries: 1,
+                retry_timeout: timeout,
+            })
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(S3Bucket { store })
+    }
+
+    pub async fn put(&self, key: &str, bytes: &[u8], content_type: &str) -> io::Result<()> {
+        let options = PutOptions {
+            attributes: Attributes::from_iter([(Attribute::ContentType, content_type.to_string())]),
+            ..Default::default()
+        };
+        self.store
+            .put_opts(&Path::from(key), PutPayload::from(bytes.to_vec()), options)
+            .await
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    /// Object `key`, `None` when there is none by that name
+    pub async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let object = match self.store.get(&Path::from(key)).await {
+            Ok(object) => object,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(io::Error::other(e)),
+        };
+        let bytes = object.bytes().await.map_err(io::Error::other)?;
+        Ok(Some(bytes.to_vec()))
+    }
+
+    /// Removes object `key`, which the store doesn't mind being gone already
+    pub async fn delete(&self, key: &str) -> io::Result<()> {
+        match self.store.delete(&Path::from(key)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_s3_bucket() {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, Method as HttpMethod, StatusCode as HttpStatus, Uri},
+        response::IntoResponse,
+        Router,
+    };
+
+    type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+    // Object store keeping the objects in memory, refusing unsigned requests
+    async fn store(
+        State(objects): State<Objects>,
+        method: HttpMethod,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> axum::response::Response {
+        let signed = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("AWS4-HMAC-SHA256 Credential=key/"));
+        if !signed {
+            return HttpStatus::FORBIDDEN.into_response();
+        }
+        let mut objects = objects.lock().unwrap();
+        let key = uri.path().to_string();
+        match method {
+            HttpMethod::PUT => {
+                objects.insert(key, body.to_vec());
+                (HttpStatus::OK, [("etag", "\"1\"")]).into_response()
+            }
+            HttpMethod::GET => match objects.get(&key) {
+                Some(object) => object.clone().into_response(),
+                None => HttpStatus::NOT_FOUND.into_response(),
+            },
+            HttpMethod::DELETE => {
+                objects.remove(&key);
+                HttpStatus::NO_CONTENT.into_response()
+            }
+            _ => HttpStatus::METHOD_NOT_ALLOWED.into_response(),
+        }
+    }
+
+    let objects = Objects::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let app = Router::new().fallback(store).with_state(objects.clone());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let bucket = S3Bucket::new(
+        S3Config {
+            endpoint,
+            bucket: "avatars".to_string(),
+            region: "auto".to_string(),
+            access_key_id: "key".to_string(),
+            secret_access_key: "secret".to_string(),
+        },
+        Duration::from_secs(5),
+    )
+    .unwrap();
+    bucket.put("4-1.png", b"png", "image/png").await.unwrap();
+    assert!(objects.lock().unwrap().contains_key("/avatars/4-1.png"));
+    assert_eq!(bucket.get("4-1.png").await.unwrap().unwrap(), b"png");
+    assert_eq!(bucket.get("4-2.png").await.unwrap(), None);
+    bucket.delete("4-1.png").await.unwrap();
+    bucket.delete("4-1.png").await.unwrap();
+    assert_eq!(bucket.get("4-1.png").await.unwrap(), None);
+}
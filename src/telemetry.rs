@@ -0,0 +1,138 @@
+use std::future::Future;
+
+use axum::{
+    extract::Request as AxumRequest,
+    http::{HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::{global, propagation::Extractor, trace::TraceError, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+use tracing::{info_span, level_filters::LevelFilter, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::config::{Config, LogFormat};
+
+/// Header carrying the id of a request, generated when the client doesn't send one
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Header carrying the id of the request being served on calls to third-party APIs
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Installs the global subscriber. Spans are only exported when `OTLP_ENDPOINT` is set,
+/// otherwise the OpenTelemetry layer isn't registered at all.
+pub fn init(config: &Config) -> Result<(), TraceError> {
+    let otlp = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            global::set_text_map_propagator(TraceContextPropagator::new());
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(trace::config().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", env!("CARGO_PKG_NAME")),
+                ])))
+                .install_batch(runtime::Tokio)?;
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    let (json, pretty) = match config.log_format {
+        LogFormat::Json => (Some(tracing_subscriber::fmt::layer().json()), None),
+        LogFormat::Pretty => (None, Some(tracing_subscriber::fmt::layer())),
+    };
+
+    tracing_subscriber::registry()
+        .with(LevelFilter::DEBUG)
+        .with(json)
+        .with(pretty)
+        .with(otlp)
+        .init();
+    Ok(())
+}
+
+/// Flushes the spans that haven't been exported yet
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Opens the span every log line of a request is emitted in, tagged with its request id.
+/// An incoming `traceparent` header makes it a child of the caller's trace.
+pub fn make_request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+
+    let span = info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = %request_id,
+    );
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+    span
+}
+
+/// Makes the request id readable with [`current_request_id`] while the request is served.
+/// Must run inside the layer setting [`REQUEST_ID_HEADER`].
+pub async fn scope_request_id(request: AxumRequest, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string);
+    with_request_id(request_id, next.run(request)).await
+}
+
+/// Id of the request the current task is serving, `None` outside of a request
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Runs `future` with `request_id` as its [`current_request_id`]. Spawned tasks don't inherit
+/// it, so they have to be wrapped with the id of the task spawning them.
+pub async fn with_request_id<F: Future>(request_id: Option<String>, future: F) -> F::Output {
+    match request_id {
+        Some(id) => REQUEST_ID.scope(id, future).await,
+        None => future.await,
+    }
+}
+
+#[tokio::test]
+async fn test_request_id_scope() {
+    assert_eq!(current_request_id(), None);
+
+    let id = with_request_id(Some("abc".to_string()), async {
+        let spawned = tokio::spawn(async { current_request_id() }).await.unwrap();
+        assert_eq!(spawned, None);
+        current_request_id()
+    })
+    .await;
+    assert_eq!(id.as_deref(), Some("abc"));
+}